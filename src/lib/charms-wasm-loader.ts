@@ -22,7 +22,7 @@ export interface CharmsWasmExports {
   check_spell(app_json: string, tx_json: string, x_json: string, w_json: string): unknown;
   check_token(app_json: string, tx_json: string, x_json: string): unknown;
   check_nft(app_json: string, tx_json: string, x_json: string): unknown;
-  check_escrow(app_json: string, tx_json: string): unknown;
+  check_escrow(app_json: string, tx_json: string, x_json: string): unknown;
   
   // Spell verification
   verify_spell(spell_json: string): unknown;
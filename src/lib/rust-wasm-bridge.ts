@@ -418,7 +418,7 @@ export interface RustWasmModule {
   check_spell(app_json: string, tx_json: string, x_json: string, w_json: string): unknown;
   check_token(app_json: string, tx_json: string, x_json: string): unknown;
   check_nft(app_json: string, tx_json: string, x_json: string): unknown;
-  check_escrow(app_json: string, tx_json: string): unknown;
+  check_escrow(app_json: string, tx_json: string, x_json: string): unknown;
   verify_spell(spell_json: string): unknown;
   build_token_tx(
     app_tag: string,
@@ -537,7 +537,7 @@ function createFallbackModule(): RustWasmModule {
       } else if (app.tag.startsWith('nft:')) {
         return this.check_nft!(app_json, tx_json, x_json);
       } else if (app.tag.startsWith('escrow:')) {
-        return this.check_escrow(app_json, tx_json);
+        return this.check_escrow(app_json, tx_json, x_json);
       } else if (app.tag.startsWith('bounty:')) {
         return this.check_bounty!(app_json, tx_json, x_json);
       } else if (app.tag.startsWith('bollar:')) {
@@ -636,7 +636,7 @@ function createFallbackModule(): RustWasmModule {
       };
     },
     
-    check_escrow(app_json: string, tx_json: string): unknown {
+    check_escrow(app_json: string, tx_json: string, _x_json: string): unknown {
       const app: RustApp = JSON.parse(app_json);
       const tx: RustTransaction = JSON.parse(tx_json);
       
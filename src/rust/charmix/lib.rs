@@ -26,50 +26,400 @@ macro_rules! main {
     };
 }
 
+/// Reject transactions with more app outputs than `App::max_outputs` allows
+fn check_output_limit(
+    app: &charms_sdk::data::App,
+    tx: &charms_sdk::data::Transaction,
+) -> Result<(), charms_sdk::data::CheckError> {
+    let Some(max) = app.max_outputs() else {
+        return Ok(());
+    };
+    let count = tx.app_output_count(&app.tag);
+    if count > max {
+        return Err(charms_sdk::data::CheckError::TooManyOutputs { count, max });
+    }
+    Ok(())
+}
+
+/// Log why a checker rejected a spell, when built with the `tracing` feature
+/// 
+/// The native counterpart to the `errors` the WASM bindings report; `result`
+/// is passed through unchanged.
+fn log_rejection(
+    checker: &'static str,
+    app: &charms_sdk::data::App,
+    result: Result<(), charms_sdk::data::CheckError>,
+) -> Result<(), charms_sdk::data::CheckError> {
+    #[cfg(feature = "tracing")]
+    if let Err(err) = &result {
+        tracing::debug!(checker, app = %app.tag, "spell rejected: {}", err);
+    }
+    #[cfg(not(feature = "tracing"))]
+    let _ = (checker, app);
+    result
+}
+
+/// Registry of canonical burn scripts, whose outputs can never be spent
+pub mod burn {
+    /// `OP_RETURN`: any script starting with it is provably unspendable
+    pub const OP_RETURN: u8 = 0x6a;
+    
+    /// Known eater addresses: P2PKH scripts to hashes with no known key
+    pub const EATER_SCRIPTS: &[&[u8]] = &[
+        // 1BitcoinEaterAddressDontSendf59kuE
+        &[
+            0x76, 0xa9, 0x14,
+            0x75, 0x9d, 0x66, 0x77, 0x09, 0x1e, 0x97, 0x3b, 0x9e, 0x9d,
+            0x99, 0xf1, 0x9c, 0x68, 0xfb, 0xf4, 0x3e, 0x3f, 0x05, 0xf9,
+            0x88, 0xac,
+        ],
+    ];
+    
+    /// Whether coins sent to `script` are provably burned
+    pub fn is_burn_script(script: &[u8]) -> bool {
+        script.first() == Some(&OP_RETURN) || EATER_SCRIPTS.contains(&script)
+    }
+}
+
 /// Token spell checker - validates token transfer rules
 pub mod token {
-    use charms_sdk::data::{App, Data, Transaction, CharmState};
+    use charms_sdk::data::{App, CharmState, CheckError, Data, Transaction};
     
     /// Validate a token transfer spell
     /// 
     /// Rules:
-    /// - Total input amount must equal total output amount (conservation)
-    /// - All inputs must be authorized (signature verification)
+    /// - Total input amount must equal total output amount (conservation),
+    ///   or may exceed it if `app.params` sets `"burn_allowed": true`
+    /// - A mint (see `is_mint`) is only allowed if `app.params` sets a
+    ///   `"max_supply"`, and may create at most that many tokens. The cap
+    ///   applies per mint transaction, as the checker sees no prior supply
+    /// - All inputs must be authorized (signature verification). If
+    ///   `app.params` lists `"signers"` (SEC1 public keys) and a
+    ///   `"threshold"`, `x` must hold signatures over the computed txid (see
+    ///   `Transaction::compute_txid`) from at least `threshold` distinct
+    ///   signers, as a `Data::List` or a single `Data::Bytes`
     /// - Token app tag must match across all UTXOs
-    pub fn check(app: &App, tx: &Transaction, x: &Data, _w: &Data) -> bool {
+    /// - An output claiming to burn its tokens (see `is_claimed_burn`) must
+    ///   pay a canonical burn script
+    pub fn check(app: &App, tx: &Transaction, x: &Data, w: &Data) -> bool {
+        try_check(app, tx, x, w).is_ok()
+    }
+    
+    /// Validate a token transfer spell, reporting why it was rejected
+    pub fn try_check(app: &App, tx: &Transaction, x: &Data, w: &Data) -> Result<(), CheckError> {
+        crate::log_rejection("token", app, check_token(app, tx, x, w))
+    }
+    
+    fn check_token(app: &App, tx: &Transaction, x: &Data, _w: &Data) -> Result<(), CheckError> {
+        crate::check_output_limit(app, tx)?;
+        
+        let input = input_sum(&app.tag, tx).ok_or(CheckError::Overflow)?;
+        let output = output_sum(&app.tag, tx).ok_or(CheckError::Overflow)?;
+        
+        let max_supply = app.param_u64("max_supply");
+        let capped_mint = match max_supply {
+            Some(max_supply) if is_mint(app, tx) => {
+                if output > max_supply {
+                    return Err(CheckError::SupplyCapExceeded { minted: output, max_supply });
+                }
+                true
+            }
+            _ => false,
+        };
+        
+        // Check conservation rule, letting the difference be burned if allowed
+        let conserved = capped_mint || input == output || (burn_allowed(app) && output < input);
+        if !conserved {
+            return Err(CheckError::ConservationViolation { input, output });
+        }
+        
+        check_burns_directed(app, tx)?;
+        check_authorization(app, tx, x)
+    }
+    
+    /// Check every output claiming to burn its tokens pays a burn script
+    fn check_burns_directed(app: &App, tx: &Transaction) -> Result<(), CheckError> {
+        let misdirected_burn = tx.outputs.iter().any(|output| {
+            output.charm_state.as_ref()
+                .and_then(|state| state.get(&app.tag))
+                .is_some_and(is_claimed_burn)
+                && !crate::burn::is_burn_script(&output.script_pubkey)
+        });
+        if misdirected_burn {
+            return Err(CheckError::InvalidBurn);
+        }
+        Ok(())
+    }
+    
+    /// Check `x` authorizes the transfer, by threshold signatures if
+    /// `app.params` sets them up
+    pub(crate) fn check_authorization(app: &App, tx: &Transaction, x: &Data) -> Result<(), CheckError> {
+        match threshold_signers(app)? {
+            Some((threshold, signers)) => check_threshold(tx, x, threshold, &signers),
+            None if !is_authorized(x) => Err(CheckError::MissingAuthorization),
+            None => Ok(()),
+        }
+    }
+    
+    /// Required signature count and signer public keys
+    type ThresholdSigners<'a> = (usize, Vec<&'a [u8]>);
+    
+    /// The `"threshold"` and `"signers"` from `app.params`, if set
+    fn threshold_signers(app: &App) -> Result<Option<ThresholdSigners<'_>>, CheckError> {
+        let (Some(threshold), Some(signers)) = (app.param("threshold"), app.param("signers")) else {
+            return Ok(None);
+        };
+        let threshold = threshold.as_u64()
+            .and_then(|threshold| usize::try_from(threshold).ok())
+            .filter(|threshold| *threshold > 0)
+            .ok_or(CheckError::MissingField("threshold"))?;
+        let signers = signers.as_list()
+            .and_then(|signers| signers.iter().map(Data::as_bytes).collect::<Option<Vec<_>>>())
+            .ok_or(CheckError::MissingField("signers"))?;
+        Ok(Some((threshold, signers)))
+    }
+    
+    /// Check at least `threshold` distinct signers signed the txid
+    /// 
+    /// The txid is computed from the transaction itself, not read from
+    /// `tx.txid`. Each signer counts once, however many of the signatures
+    /// verify for it.
+    fn check_threshold(tx: &Transaction, x: &Data, threshold: usize, signers: &[&[u8]]) -> Result<(), CheckError> {
+        let signatures: Vec<&[u8]> = match x {
+            Data::List(items) => items.iter().filter_map(Data::as_bytes).collect(),
+            Data::Bytes(sig) => vec![sig],
+            _ => return Err(CheckError::MissingAuthorization),
+        };
+        let txid = tx.compute_txid();
+        let mut signed: Vec<&[u8]> = Vec::new();
+        for signer in signers {
+            if signed.contains(signer) {
+                continue;
+            }
+            if signatures.iter().any(|sig| crate::signature::verify(signer, &txid, sig)) {
+                signed.push(signer);
+            }
+        }
+        
+        if signed.len() < threshold {
+            return Err(CheckError::MissingAuthorization);
+        }
+        Ok(())
+    }
+    
+    /// Whether a token output's state claims its balance is burned
+    /// 
+    /// The claim is a `Map` state with `"burn": true`. The tokens still count
+    /// towards conservation, but `burn_amount` treats them as destroyed.
+    pub fn is_claimed_burn(state: &Data) -> bool {
+        match state {
+            Data::Map(map) => matches!(map.get("burn"), Some(Data::Bool(true))),
+            _ => false,
+        }
+    }
+    
+    /// Validate a token transfer that pays a protocol fee to a treasury
+    /// 
+    /// Outputs whose charm state carries `treasury_tag` are treasury outputs;
+    /// their token balance is the fee. The fee may be at most `fee_bps` basis
+    /// points of the input amount, and the remaining outputs must account for
+    /// the rest: `output_sum + treasury_amount == input_sum`.
+    /// 
+    /// Burn claims and authorization, including threshold signatures, are
+    /// checked as in `check`.
+    pub fn check_with_fee(
+        app: &App,
+        tx: &Transaction,
+        x: &Data,
+        _w: &Data,
+        fee_bps: u16,
+        treasury_tag: &str,
+    ) -> bool {
+        if crate::check_output_limit(app, tx).is_err() {
+            return false;
+        }
+        
         let app_tag = &app.tag;
+        let Some(input_sum) = input_sum(app_tag, tx) else {
+            return false;
+        };
         
-        // Sum input token amounts
-        let input_sum: u64 = tx.inputs.iter()
-            .filter_map(|input| {
-                input.charm_state.as_ref()
-                    .and_then(|state| state.get(app_tag))
-                    .and_then(|data| data.as_u64())
-            })
-            .sum();
+        let mut output_sum = 0u64;
+        let mut treasury_amount = 0u64;
+        for state in tx.outputs.iter().filter_map(|output| output.charm_state.as_ref()) {
+            let Some(amount) = state.get(app_tag).map(effective_balance) else {
+                continue;
+            };
+            let total = if state.get(treasury_tag).is_some() {
+                &mut treasury_amount
+            } else {
+                &mut output_sum
+            };
+            *total = match amount.and_then(|amount| total.checked_add(amount)) {
+                Some(sum) => sum,
+                None => return false,
+            };
+        }
         
-        // Sum output token amounts
-        let output_sum: u64 = tx.outputs.iter()
-            .filter_map(|output| {
-                output.charm_state.as_ref()
-                    .and_then(|state| state.get(app_tag))
-                    .and_then(|data| data.as_u64())
-            })
-            .sum();
+        // Fee cap, computed in u128 so large balances can't overflow
+        let max_fee = input_sum as u128 * fee_bps as u128 / 10_000;
+        if treasury_amount as u128 > max_fee {
+            return false;
+        }
         
-        // Check conservation rule
-        if input_sum != output_sum {
+        if output_sum.checked_add(treasury_amount) != Some(input_sum) {
             return false;
         }
         
-        // Check authorization (simplified - real impl would verify signatures)
-        if let Some(auth_data) = x.as_bytes() {
-            if auth_data.is_empty() {
+        check_burns_directed(app, tx).is_ok() && check_authorization(app, tx, x).is_ok()
+    }
+    
+    /// Validate an atomic swap of two token types in one transaction
+    /// 
+    /// Each asset must be conserved on its own, and no input or output may
+    /// carry both, since it would be unclear which side it belongs to. `x`
+    /// must be a `Data::List` of two `Data::Map`s, one per party, each with a
+    /// `"pubkey"` (SEC1) and a `"sig"` over `swap_commitment`. The first
+    /// party must own every input holding `asset_a` and the second every input
+    /// holding `asset_b` (see `signature::is_owner`). The two parties must be
+    /// distinct.
+    pub fn check_atomic_swap(tx: &Transaction, asset_a: &App, asset_b: &App, x: &Data) -> bool {
+        if asset_a.tag == asset_b.tag {
+            return false;
+        }
+        for app in [asset_a, asset_b] {
+            if crate::check_output_limit(app, tx).is_err() {
                 return false;
             }
+            match (input_sum(&app.tag, tx), output_sum(&app.tag, tx)) {
+                (Some(input), Some(output)) if input == output => {}
+                _ => return false,
+            }
+        }
+        
+        let states = tx.inputs.iter().filter_map(|input| input.charm_state.as_ref())
+            .chain(tx.outputs.iter().filter_map(|output| output.charm_state.as_ref()));
+        for state in states {
+            if state.get(&asset_a.tag).is_some() && state.get(&asset_b.tag).is_some() {
+                return false; // Ambiguous UTXO
+            }
+        }
+        
+        let Some([party_a, party_b]) = x.as_list() else {
+            return false;
+        };
+        let commitment = swap_commitment(tx, asset_a, asset_b);
+        let signed = |party: &Data, asset: &App| -> Option<Vec<u8>> {
+            let Data::Map(party) = party else {
+                return None;
+            };
+            let pubkey = party.get("pubkey")?.as_bytes()?;
+            let sig = party.get("sig")?.as_bytes()?;
+            let owns_inputs = tx.app_inputs(&asset.tag)
+                .all(|(input, _)| crate::signature::is_owner(pubkey, &input.script_pubkey));
+            (owns_inputs && crate::signature::verify(pubkey, &commitment, sig)).then(|| pubkey.to_vec())
+        };
+        match (signed(party_a, asset_a), signed(party_b, asset_b)) {
+            (Some(a), Some(b)) => a != b,
+            _ => false,
         }
+    }
+    
+    /// Message both parties sign to agree to swap `asset_a` for `asset_b` in `tx`
+    /// 
+    /// sha256 of the computed txid, then for each asset its tag followed by
+    /// the index, script pubkey and CBOR-encoded state of every output
+    /// carrying it. Tags, scripts and states are prefixed with their length
+    /// and output lists with their count, as little-endian `u64`s; indices
+    /// are little-endian `u32`s.
+    pub fn swap_commitment(tx: &Transaction, asset_a: &App, asset_b: &App) -> [u8; 32] {
+        use sha2::{Digest, Sha256};
         
-        true
+        let mut hasher = Sha256::new();
+        hasher.update(tx.compute_txid());
+        for tag in [&asset_a.tag, &asset_b.tag] {
+            hasher.update((tag.len() as u64).to_le_bytes());
+            hasher.update(tag.as_bytes());
+            hasher.update((tx.app_outputs(tag).count() as u64).to_le_bytes());
+            for (output, data) in tx.app_outputs(tag) {
+                let state = data.to_cbor();
+                hasher.update(output.index.to_le_bytes());
+                hasher.update((output.script_pubkey.len() as u64).to_le_bytes());
+                hasher.update(&output.script_pubkey);
+                hasher.update((state.len() as u64).to_le_bytes());
+                hasher.update(&state);
+            }
+        }
+        hasher.finalize().into()
+    }
+    
+    /// Whether `app.params` opts into burns with `"burn_allowed": true`
+    fn burn_allowed(app: &App) -> bool {
+        app.param("burn_allowed") == Some(&Data::Bool(true))
+    }
+    
+    /// Check authorization (simplified - real impl would verify signatures)
+    fn is_authorized(x: &Data) -> bool {
+        !matches!(x.as_bytes(), Some(auth_data) if auth_data.is_empty())
+    }
+    
+    /// Sum of the app's token balances across the transaction inputs, or
+    /// `None` on overflow
+    pub(crate) fn input_sum(app_tag: &str, tx: &Transaction) -> Option<u64> {
+        tx.app_inputs(app_tag)
+            .try_fold(0u64, |acc, (_, data)| acc.checked_add(effective_balance(data)?))
+    }
+    
+    /// Sum of the app's token balances across the transaction outputs, or
+    /// `None` on overflow
+    pub(crate) fn output_sum(app_tag: &str, tx: &Transaction) -> Option<u64> {
+        tx.app_outputs(app_tag)
+            .try_fold(0u64, |acc, (_, data)| acc.checked_add(effective_balance(data)?))
+    }
+    
+    /// Like `output_sum`, leaving out outputs that claim a burn
+    fn live_output_sum(app_tag: &str, tx: &Transaction) -> Option<u64> {
+        tx.app_outputs(app_tag)
+            .filter(|(_, data)| !is_claimed_burn(data))
+            .try_fold(0u64, |acc, (_, data)| acc.checked_add(effective_balance(data)?))
+    }
+    
+    /// Total token balance carried by a charm state
+    /// 
+    /// A plain `U64` is the balance itself. A `Map` may carry a top-level
+    /// `"amount"` plus a `"subaccounts"` map of `U64` balances; all of them
+    /// count towards the total. Other shapes carry no balance.
+    /// 
+    /// Returns `None` if the total overflows a `u64`.
+    pub fn effective_balance(state: &Data) -> Option<u64> {
+        match state {
+            Data::U64(amount) => Some(*amount),
+            Data::Map(map) => {
+                let top_level = map.get("amount").and_then(|data| data.as_u64()).unwrap_or(0);
+                let subaccounts = match map.get("subaccounts") {
+                    Some(Data::Map(subs)) => subs.values()
+                        .filter_map(|data| data.as_u64())
+                        .try_fold(0u64, |acc, amount| acc.checked_add(amount))?,
+                    _ => 0,
+                };
+                top_level.checked_add(subaccounts)
+            }
+            _ => Some(0),
+        }
+    }
+    
+    /// Net supply change of token `tag` between two UTXO-set snapshots
+    /// 
+    /// Positive means tokens were minted in between, negative that they were
+    /// burned. `None` if either snapshot holds a state whose balance
+    /// overflows a `u64`.
+    pub fn snapshot_diff(before: &[&CharmState], after: &[&CharmState], tag: &str) -> Option<i128> {
+        let supply = |snapshot: &[&CharmState]| -> Option<i128> {
+            snapshot.iter()
+                .filter_map(|state| state.get(tag))
+                .try_fold(0i128, |acc, data| Some(acc + effective_balance(data)? as i128))
+        };
+        Some(supply(after)? - supply(before)?)
     }
     
     /// Check if this is a mint operation (creating new tokens)
@@ -77,55 +427,372 @@ pub mod token {
         let app_tag = &app.tag;
         
         // Mint if no inputs have this token but outputs do
-        let has_input_tokens = tx.inputs.iter().any(|input| {
-            input.charm_state.as_ref()
-                .map(|state| state.get(app_tag).is_some())
-                .unwrap_or(false)
-        });
-        
-        let has_output_tokens = tx.outputs.iter().any(|output| {
-            output.charm_state.as_ref()
-                .map(|state| state.get(app_tag).is_some())
-                .unwrap_or(false)
-        });
-        
-        !has_input_tokens && has_output_tokens
+        tx.app_inputs(app_tag).next().is_none() && tx.app_outputs(app_tag).next().is_some()
+    }
+    
+    /// Tokens created by the transaction: `output_sum - input_sum`
+    /// 
+    /// `None` if the outputs hold fewer tokens than the inputs, or on overflow.
+    pub fn minted_amount(app: &App, tx: &Transaction) -> Option<u64> {
+        let input = input_sum(&app.tag, tx)?;
+        let output = output_sum(&app.tag, tx)?;
+        output.checked_sub(input)
+    }
+    
+    /// Whether minting keeps the supply within `app.params`' `"max_supply"`
+    /// 
+    /// `current_supply` is the supply before this transaction. Apps without
+    /// a `"max_supply"` are uncapped; a `"max_supply"` that isn't a `U64`
+    /// allows no mint at all.
+    pub fn is_valid_mint(app: &App, tx: &Transaction, current_supply: u64) -> bool {
+        let Some(minted) = minted_amount(app, tx) else {
+            return false;
+        };
+        match app.param("max_supply").map(Data::as_u64) {
+            Some(Some(max_supply)) => current_supply.checked_add(minted).is_some_and(|supply| supply <= max_supply),
+            Some(None) => false,
+            None => true,
+        }
     }
     
     /// Check if this is a burn operation (destroying tokens)
     pub fn is_burn(app: &App, tx: &Transaction) -> bool {
-        let app_tag = &app.tag;
+        burn_amount(app, tx).is_some()
+    }
+    
+    /// Amount of tokens destroyed by a burn, or `None` if this is not a burn
+    /// 
+    /// Tokens in outputs claiming a burn count as destroyed. A mint is never
+    /// a burn, so `is_mint` and `is_burn` never both hold.
+    pub fn burn_amount(app: &App, tx: &Transaction) -> Option<u64> {
+        if is_mint(app, tx) {
+            return None;
+        }
+        let input = input_sum(&app.tag, tx)?;
+        let output = live_output_sum(&app.tag, tx)?;
+        input.checked_sub(output).filter(|&burned| burned > 0)
+    }
+    
+    /// Time-locked release of a token allocation by block height
+    pub mod vesting {
+        use charms_sdk::data::{App, Data, Transaction};
+        use std::collections::BTreeMap;
         
-        let input_sum: u64 = tx.inputs.iter()
-            .filter_map(|input| {
-                input.charm_state.as_ref()
-                    .and_then(|state| state.get(app_tag))
-                    .and_then(|data| data.as_u64())
-            })
-            .sum();
+        /// Linear vesting terms, stored as a `Data::Map` in `App::params`
+        /// 
+        /// Nothing is vested before `start_block + cliff_blocks`. From the
+        /// cliff on, the vested amount is `total_amount` scaled by the share of
+        /// `start_block..end_block` elapsed, reaching the full amount at
+        /// `end_block`.
+        #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+        pub struct VestingSchedule {
+            pub start_block: u32,
+            pub end_block: u32,
+            pub total_amount: u64,
+            pub cliff_blocks: u32,
+        }
         
-        let output_sum: u64 = tx.outputs.iter()
-            .filter_map(|output| {
-                output.charm_state.as_ref()
-                    .and_then(|state| state.get(app_tag))
-                    .and_then(|data| data.as_u64())
+        impl VestingSchedule {
+            /// Encode as the `Data::Map` to use for `App::params`
+            pub fn to_params(&self) -> Data {
+                let mut map = BTreeMap::new();
+                map.insert("start_block".to_string(), Data::U64(self.start_block as u64));
+                map.insert("end_block".to_string(), Data::U64(self.end_block as u64));
+                map.insert("total_amount".to_string(), Data::U64(self.total_amount));
+                map.insert("cliff_blocks".to_string(), Data::U64(self.cliff_blocks as u64));
+                Data::Map(map)
+            }
+            
+            /// Read the schedule from `app.params`
+            /// 
+            /// `None` if a field is missing or out of range, `end_block` is
+            /// before `start_block`, or the cliff ends after `end_block`.
+            pub fn from_app(app: &App) -> Option<Self> {
+                let block = |key| u32::try_from(app.param_u64(key)?).ok();
+                let schedule = Self {
+                    start_block: block("start_block")?,
+                    end_block: block("end_block")?,
+                    total_amount: app.param_u64("total_amount")?,
+                    cliff_blocks: block("cliff_blocks")?,
+                };
+                let duration = schedule.end_block.checked_sub(schedule.start_block)?;
+                (schedule.cliff_blocks <= duration).then_some(schedule)
+            }
+            
+            /// Amount vested at `current_block`
+            pub fn vested_amount(&self, current_block: u32) -> u64 {
+                let cliff = self.start_block as u64 + self.cliff_blocks as u64;
+                if (current_block as u64) < cliff {
+                    return 0;
+                }
+                if current_block >= self.end_block {
+                    return self.total_amount;
+                }
+                let elapsed = (current_block - self.start_block) as u128;
+                let duration = (self.end_block - self.start_block) as u128;
+                (self.total_amount as u128 * elapsed / duration) as u64
+            }
+        }
+        
+        /// Validate a release from a vesting allocation
+        /// 
+        /// `x` must be `Data::U64(current_block)`, the height the spell claims
+        /// to be released at. The app's outputs may hold at most the amount
+        /// vested by then under the `VestingSchedule` in `app.params`.
+        pub fn check(app: &App, tx: &Transaction, x: &Data, _w: &Data, current_block: u32) -> bool {
+            let Some(schedule) = VestingSchedule::from_app(app) else {
+                return false;
+            };
+            if x.as_u64() != Some(current_block as u64) {
+                return false;
+            }
+            super::output_sum(&app.tag, tx).is_some_and(|released| released <= schedule.vested_amount(current_block))
+        }
+    }
+}
+
+/// Bollar spell checker - a token backed by collateral
+pub mod bollar {
+    use charms_sdk::data::{App, CheckError, Data, Transaction};
+    use std::collections::BTreeMap;
+    
+    /// Collateral terms, stored as a `Data::Map` in `App::params`
+    #[derive(Debug, Clone, PartialEq, Eq)]
+    pub struct BollarParams {
+        /// Minimum collateral per bollar, in basis points (15_000 is 150%)
+        pub collateral_ratio_bps: u32,
+        /// App tag whose `U64` state in the outputs is the collateral, in sats
+        pub oracle_tag: String,
+    }
+    
+    impl BollarParams {
+        /// Encode as the `Data::Map` to use for `App::params`
+        pub fn to_params(&self) -> Data {
+            let mut map = BTreeMap::new();
+            map.insert("collateral_ratio_bps".to_string(), Data::U64(self.collateral_ratio_bps as u64));
+            map.insert("oracle_tag".to_string(), Data::String(self.oracle_tag.clone()));
+            Data::Map(map)
+        }
+        
+        /// Read the terms from `app.params`
+        pub fn from_app(app: &App) -> Option<Self> {
+            Some(Self {
+                collateral_ratio_bps: u32::try_from(app.param_u64("collateral_ratio_bps")?).ok()?,
+                oracle_tag: app.param("oracle_tag")?.as_str()?.to_string(),
             })
-            .sum();
+        }
+    }
+    
+    /// Validate a bollar spell
+    /// 
+    /// Rules:
+    /// - Transfers follow the token rules of `token::check` and need no
+    ///   collateral
+    /// - A mint (see `token::is_mint`) skips conservation but is still
+    ///   authorized as `token::check` does and kept within any
+    ///   `"max_supply"`. Outputs carrying `oracle_tag` must hold, as `U64`,
+    ///   collateral worth at least `collateral_ratio_bps` of the bollar minted:
+    ///   `collateral_sats * 10_000 >= minted * collateral_ratio_bps`
+    pub fn check(app: &App, tx: &Transaction, x: &Data, w: &Data) -> bool {
+        try_check(app, tx, x, w).is_ok()
+    }
+    
+    /// Validate a bollar spell, reporting why it was rejected
+    pub fn try_check(app: &App, tx: &Transaction, x: &Data, w: &Data) -> Result<(), CheckError> {
+        crate::log_rejection("bollar", app, check_bollar(app, tx, x, w))
+    }
+    
+    fn check_bollar(app: &App, tx: &Transaction, x: &Data, w: &Data) -> Result<(), CheckError> {
+        let params = BollarParams::from_app(app).ok_or(CheckError::MissingField("collateral_ratio_bps"))?;
+        if !crate::token::is_mint(app, tx) {
+            return crate::token::try_check(app, tx, x, w);
+        }
+        
+        crate::check_output_limit(app, tx)?;
+        let (amount, collateral) = position(tx, &app.tag, &params)?;
+        if let Some(max_supply) = app.param_u64("max_supply") {
+            if amount > max_supply {
+                return Err(CheckError::SupplyCapExceeded { minted: amount, max_supply });
+            }
+        }
+        crate::token::check_authorization(app, tx, x)?;
+        
+        let required = amount as u128 * params.collateral_ratio_bps as u128;
+        if (collateral as u128) * 10_000 < required {
+            return Err(CheckError::Undercollateralized {
+                required: u64::try_from(required.div_ceil(10_000)).unwrap_or(u64::MAX),
+                collateral,
+            });
+        }
+        Ok(())
+    }
+    
+    /// Collateral per bollar minted, or `None` if the transaction is not a
+    /// mint or the app has no `BollarParams`
+    pub fn collateral_ratio(app: &App, tx: &Transaction) -> Option<f64> {
+        if !crate::token::is_mint(app, tx) {
+            return None;
+        }
+        let params = BollarParams::from_app(app)?;
+        let (amount, collateral) = position(tx, &app.tag, &params).ok()?;
+        (amount > 0).then(|| collateral as f64 / amount as f64)
+    }
+    
+    /// The bollar amount and the collateral backing it, across the outputs
+    /// 
+    /// For a mint the bollar in the outputs is exactly the amount minted.
+    fn position(tx: &Transaction, tag: &str, params: &BollarParams) -> Result<(u64, u64), CheckError> {
+        Ok((state_sum(tx, tag)?, state_sum(tx, &params.oracle_tag)?))
+    }
+    
+    /// Sum of the `U64` states `tag` holds across the outputs
+    fn state_sum(tx: &Transaction, tag: &str) -> Result<u64, CheckError> {
+        tx.app_outputs(tag)
+            .filter_map(|(_, data)| data.as_u64())
+            .try_fold(0u64, |acc, amount| acc.checked_add(amount))
+            .ok_or(CheckError::Overflow)
+    }
+}
+
+/// Signature verification used by the checkers
+pub mod signature {
+    use k256::ecdsa::{signature::hazmat::PrehashVerifier, Signature, VerifyingKey};
+    
+    /// Verify a secp256k1 ECDSA signature over a 32-byte message hash
+    /// 
+    /// `pubkey` is a SEC1-encoded public key (compressed or uncompressed) and
+    /// `sig` is either a 64-byte compact or a DER-encoded signature. The hash
+    /// is signed as-is, without hashing it again.
+    pub fn verify(pubkey: &[u8], msg_hash: &[u8; 32], sig: &[u8]) -> bool {
+        let Ok(key) = VerifyingKey::from_sec1_bytes(pubkey) else {
+            return false;
+        };
+        let Ok(sig) = Signature::from_slice(sig).or_else(|_| Signature::from_der(sig)) else {
+            return false;
+        };
+        key.verify_prehash(msg_hash, &sig).is_ok()
+    }
+    
+    /// Signature check a checker delegates to, so the zkVM and native builds
+    /// can plug in different implementations
+    pub trait SignatureVerifier {
+        /// Whether `sig` by `pubkey` signs `msg_hash`
+        fn verify(&self, pubkey: &[u8], msg_hash: &[u8; 32], sig: &[u8]) -> bool;
+    }
+    
+    /// Whether `script_pubkey` can only be spent with `pubkey`'s signature
+    /// 
+    /// Recognizes a bare pay-to-pubkey script (`<pubkey> OP_CHECKSIG`) and
+    /// the P2WSH output wrapping one.
+    pub fn is_owner(pubkey: &[u8], script_pubkey: &[u8]) -> bool {
+        use sha2::{Digest, Sha256};
         
-        input_sum > output_sum
+        const OP_0: u8 = 0x00;
+        const OP_CHECKSIG: u8 = 0xac;
+        
+        let Ok(push) = u8::try_from(pubkey.len()) else {
+            return false;
+        };
+        if !matches!(push, 33 | 65) {
+            return false;
+        }
+        let p2pk = [&[push], pubkey, &[OP_CHECKSIG]].concat();
+        let p2wsh = [&[OP_0, 32][..], &Sha256::digest(&p2pk)[..]].concat();
+        script_pubkey == p2pk || script_pubkey == p2wsh
+    }
+    
+    /// `SignatureVerifier` backed by `verify`
+    #[derive(Debug, Clone, Copy, Default)]
+    pub struct Secp256k1Verifier;
+    
+    impl SignatureVerifier for Secp256k1Verifier {
+        fn verify(&self, pubkey: &[u8], msg_hash: &[u8; 32], sig: &[u8]) -> bool {
+            verify(pubkey, msg_hash, sig)
+        }
     }
 }
 
 /// NFT spell checker - validates non-fungible token rules
 pub mod nft {
-    use charms_sdk::data::{App, Data, Transaction};
+    use charms_sdk::data::{App, CheckError, Data, Transaction, UtxoRef};
     
     /// NFT data structure
+    /// 
+    /// Carried in charm state either as a bare `Data::Bytes(id)` or as a
+    /// `Data::Map` with `"id"`, `"metadata_hash"` and `"creator"` entries.
+    /// The map may also carry `"royalty_bps"` and `"royalty_recipient"`;
+    /// without them the NFT pays no royalty.
     #[derive(Debug, Clone)]
     pub struct NftData {
         pub id: [u8; 32],
         pub metadata_hash: [u8; 32],
         pub creator: Vec<u8>,
+        /// Royalty on each sale, in basis points of the sale price
+        pub royalty_bps: u16,
+        /// Script pubkey the royalty must be paid to
+        pub royalty_recipient: Vec<u8>,
+    }
+    
+    impl NftData {
+        /// Parse the map form of an NFT charm state
+        pub fn from_data(data: &Data) -> Option<Self> {
+            let Data::Map(map) = data else {
+                return None;
+            };
+            let royalty_bps = match map.get("royalty_bps") {
+                Some(bps) => u16::try_from(bps.as_u64()?).ok().filter(|bps| *bps <= 10_000)?,
+                None => 0,
+            };
+            let royalty_recipient = match map.get("royalty_recipient") {
+                Some(recipient) => recipient.as_bytes()?.to_vec(),
+                None => Vec::new(),
+            };
+            Some(Self {
+                id: map.get("id")?.as_array32()?,
+                metadata_hash: map.get("metadata_hash")?.as_array32()?,
+                creator: map.get("creator")?.as_bytes()?.to_vec(),
+                royalty_bps,
+                royalty_recipient,
+            })
+        }
+        
+        /// Hash the creator signs to mint this NFT: `sha256(metadata_hash || id)`
+        /// 
+        /// Covering the id keeps a signature over some metadata from minting
+        /// it again under another id.
+        pub fn mint_message(&self) -> [u8; 32] {
+            use sha2::{Digest, Sha256};
+            
+            Sha256::digest([&self.metadata_hash[..], &self.id[..]].concat()).into()
+        }
+        
+        /// Royalty owed on a sale at `sale_price_sats`
+        pub fn royalty_due(&self, sale_price_sats: u64) -> u64 {
+            (sale_price_sats as u128 * self.royalty_bps as u128 / 10_000) as u64
+        }
+    }
+    
+    /// Get the NFT id from either charm state form
+    pub(crate) fn nft_id(data: &Data) -> Option<&[u8]> {
+        match data {
+            Data::Bytes(id) => Some(id),
+            Data::Map(map) => map.get("id")?.as_bytes(),
+            _ => None,
+        }
+    }
+    
+    /// Derive the id of an NFT minted by spending `utxo_ref`
+    /// 
+    /// The id is `sha256(txid || vout_le)`. Minted NFTs must carry the id
+    /// derived from the transaction's first input, which can only ever be
+    /// spent once, so no two mints can produce the same id.
+    pub fn derive_id(utxo_ref: &UtxoRef) -> [u8; 32] {
+        utxo_ref.genesis_id()
+    }
+    
+    /// Creator pubkey required by the app, if `app.params` declares one
+    fn required_creator(app: &App) -> Option<&[u8]> {
+        app.param_bytes("creator")
     }
     
     /// Validate an NFT transfer
@@ -133,8 +800,28 @@ pub mod nft {
     /// Rules:
     /// - NFT ID must be unique and not duplicated
     /// - Only one output can contain each NFT
-    /// - Creator signature required for initial mint
-    pub fn check(app: &App, tx: &Transaction, x: &Data, _w: &Data) -> bool {
+    /// - A minted NFT must carry its `NftData`, and `x` must be the creator's
+    ///   signature over its `NftData::mint_message`
+    /// - A minted NFT's ID must be `derive_id` of the first input UTXO
+    /// - If `app.params` declares a `"creator"`, minted NFTs must name it
+    /// - Transferring an NFT with a royalty needs its sale price in `w` as a
+    ///   `Data::U64` (the same price for every NFT moved), and outputs locked
+    ///   to `royalty_recipient` holding at least the royalty due in sats. NFTs
+    ///   sharing a recipient must be paid for together: the recipient's
+    ///   outputs must cover the sum of their royalties
+    /// - A transferred NFT keeps its royalty terms unchanged
+    pub fn check(app: &App, tx: &Transaction, x: &Data, w: &Data) -> bool {
+        try_check(app, tx, x, w).is_ok()
+    }
+    
+    /// Validate an NFT transfer, reporting why it was rejected
+    pub fn try_check(app: &App, tx: &Transaction, x: &Data, w: &Data) -> Result<(), CheckError> {
+        crate::log_rejection("nft", app, check_nft(app, tx, x, w))
+    }
+    
+    fn check_nft(app: &App, tx: &Transaction, x: &Data, w: &Data) -> Result<(), CheckError> {
+        crate::check_output_limit(app, tx)?;
+        
         let app_tag = &app.tag;
         
         // Collect all input NFT IDs
@@ -142,142 +829,3932 @@ pub mod nft {
             .filter_map(|input| {
                 input.charm_state.as_ref()
                     .and_then(|state| state.get(app_tag))
-                    .and_then(|data| data.as_bytes())
+                    .and_then(nft_id)
             })
             .collect();
         
-        // Collect all output NFT IDs
-        let output_nfts: Vec<&[u8]> = tx.outputs.iter()
+        // Collect all output NFT states
+        let output_nfts: Vec<&Data> = tx.outputs.iter()
             .filter_map(|output| {
                 output.charm_state.as_ref()
                     .and_then(|state| state.get(app_tag))
-                    .and_then(|data| data.as_bytes())
             })
+            .filter(|data| nft_id(data).is_some())
             .collect();
         
         // Check no duplicates in outputs
         let mut seen: Vec<&[u8]> = Vec::new();
-        for nft in &output_nfts {
-            if seen.contains(nft) {
-                return false; // Duplicate NFT
+        for nft in output_nfts.iter().filter_map(|data| nft_id(data)) {
+            if seen.contains(&nft) {
+                return Err(CheckError::DuplicateNft(nft.to_vec()));
             }
             seen.push(nft);
         }
         
+        // Royalty due per recipient, across every NFT transferred
+        let mut royalties: std::collections::BTreeMap<Vec<u8>, u64> = std::collections::BTreeMap::new();
+        
         // All output NFTs must come from inputs (no creation without proper mint)
-        for nft in &output_nfts {
-            if !input_nfts.contains(nft) {
-                // This is a mint - verify creator signature in x
-                if x.is_empty() {
-                    return false;
+        for data in &output_nfts {
+            let Some(nft) = nft_id(data) else {
+                continue;
+            };
+            if input_nfts.contains(&nft) {
+                // Transfer of an existing NFT
+                if let Some((recipient, due)) = transfer_royalty(app, tx, data, w)? {
+                    let total = royalties.entry(recipient).or_insert(0);
+                    *total = total.checked_add(due).ok_or(CheckError::Overflow)?;
+                }
+                continue;
+            }
+            
+            // This is a mint - verify creator signature in x
+            let nft_data = NftData::from_data(data).ok_or(CheckError::InvalidMint)?;
+            let sig = x.as_bytes().ok_or(CheckError::MissingAuthorization)?;
+            if !crate::signature::verify(&nft_data.creator, &nft_data.mint_message(), sig) {
+                return Err(CheckError::MissingAuthorization);
+            }
+            
+            // Bind the ID to the (unique) first input being spent
+            let first_input = tx.inputs.first().ok_or(CheckError::InvalidMint)?;
+            if nft != derive_id(&first_input.utxo_ref) {
+                return Err(CheckError::InvalidMint);
+            }
+            
+            // Bind the NFT to the app's declared creator
+            if let Some(creator) = required_creator(app) {
+                if nft_data.creator != creator {
+                    return Err(CheckError::MissingAuthorization);
                 }
             }
         }
         
-        true
-    }
-}
-
-/// Escrow spell checker - validates escrow contract rules
-pub mod escrow {
-    use charms_sdk::data::{App, Data, Transaction};
-    
-    /// Escrow states
-    #[derive(Debug, Clone, PartialEq, Eq)]
-    pub enum EscrowState {
-        Created,
-        Funded,
-        MilestoneCompleted(u32),
-        Released,
-        Disputed,
-        Refunded,
+        for (recipient, required) in royalties {
+            let paid = tx.outputs.iter()
+                .filter(|output| output.script_pubkey == recipient)
+                .try_fold(0u64, |sum, output| sum.checked_add(output.value))
+                .ok_or(CheckError::Overflow)?;
+            if paid < required {
+                return Err(CheckError::RoyaltyUnpaid { required, paid });
+            }
+        }
+        
+        Ok(())
     }
     
-    /// Validate escrow state transitions
-    pub fn check(app: &App, tx: &Transaction, x: &Data, _w: &Data) -> bool {
-        let app_tag = &app.tag;
-        
-        // Get current escrow state from inputs
-        let current_state = tx.inputs.iter()
-            .find_map(|input| {
-                input.charm_state.as_ref()
-                    .and_then(|state| state.get(app_tag))
-                    .and_then(|data| parse_escrow_state(data))
-            });
-        
-        // Get next state from outputs
-        let next_state = tx.outputs.iter()
-            .find_map(|output| {
-                output.charm_state.as_ref()
-                    .and_then(|state| state.get(app_tag))
-                    .and_then(|data| parse_escrow_state(data))
-            });
+    /// IDs of the NFTs minted by the transaction: those in outputs but in no input
+    pub fn minted_ids(app: &App, tx: &Transaction) -> Vec<Vec<u8>> {
+        let input_ids: Vec<&[u8]> = tx.inputs.iter()
+            .filter_map(|input| input.charm_state.as_ref())
+            .filter_map(|state| state.get(&app.tag))
+            .filter_map(nft_id)
+            .collect();
+        let output_ids = tx.outputs.iter()
+            .filter_map(|output| output.charm_state.as_ref())
+            .filter_map(|state| state.get(&app.tag))
+            .filter_map(nft_id);
         
-        // Validate state transition
-        match (current_state, next_state) {
-            (None, Some(EscrowState::Created)) => true, // Initial creation
-            (Some(EscrowState::Created), Some(EscrowState::Funded)) => true,
-            (Some(EscrowState::Funded), Some(EscrowState::MilestoneCompleted(_))) => true,
-            (Some(EscrowState::MilestoneCompleted(_)), Some(EscrowState::Released)) => true,
-            (Some(EscrowState::Funded), Some(EscrowState::Disputed)) => true,
-            (Some(EscrowState::Disputed), Some(EscrowState::Refunded)) => true,
-            (Some(EscrowState::Disputed), Some(EscrowState::Released)) => true,
-            _ => false, // Invalid transition
-        }
-    }
-    
-    fn parse_escrow_state(data: &Data) -> Option<EscrowState> {
-        match data.as_u64()? {
-            0 => Some(EscrowState::Created),
-            1 => Some(EscrowState::Funded),
-            2 => Some(EscrowState::Released),
-            3 => Some(EscrowState::Disputed),
-            4 => Some(EscrowState::Refunded),
-            n if n >= 100 => Some(EscrowState::MilestoneCompleted((n - 100) as u32)),
-            _ => None,
+        let mut minted: Vec<Vec<u8>> = Vec::new();
+        for id in output_ids {
+            if !input_ids.contains(&id) && !minted.iter().any(|m| m == id) {
+                minted.push(id.to_vec());
+            }
         }
+        minted
     }
-}
-
+    
+    /// Validate an NFT transfer as `check` does, and keep the collection within
+    /// the `"collection_max_size"` set in `app.params`
+    /// 
+    /// `current_collection_size` counts the NFTs minted before this
+    /// transaction. Apps without a `"collection_max_size"` are uncapped.
+    pub fn check_collection(
+        app: &App,
+        tx: &Transaction,
+        x: &Data,
+        w: &Data,
+        current_collection_size: u32,
+    ) -> bool {
+        if !check(app, tx, x, w) {
+            return false;
+        }
+        let max_size = match app.param("collection_max_size") {
+            Some(max) => match max.as_u64().and_then(|max| u32::try_from(max).ok()) {
+                Some(max) => max,
+                None => return false,
+            },
+            None => return true,
+        };
+        
+        let new_mints = minted_ids(app, tx).len() as u64;
+        current_collection_size as u64 + new_mints <= max_size as u64
+    }
+    
+    /// Validate an NFT spell as `check` does, keeping soulbound NFTs with
+    /// their owner
+    /// 
+    /// With `"soulbound": true` in `app.params`, an NFT that is already
+    /// minted may only be burned (spent without reappearing in an output),
+    /// held (re-locked to the script of the input it was spent from) or sent
+    /// to the burn address. The burn address is the `"burn_address"` script
+    /// in `app.params`, or any provably unspendable script if unset.
+    pub fn check_soulbound(app: &App, tx: &Transaction, x: &Data, w: &Data) -> bool {
+        if !check(app, tx, x, w) {
+            return false;
+        }
+        if app.param("soulbound") != Some(&Data::Bool(true)) {
+            return true;
+        }
+        
+        let is_burn_address = |script: &[u8]| match app.param_bytes("burn_address") {
+            Some(burn_address) => script == burn_address,
+            None => crate::burn::is_burn_script(script),
+        };
+        tx.app_outputs(&app.tag).all(|(output, data)| {
+            let Some(id) = nft_id(data) else {
+                return true;
+            };
+            let spent_from = tx.app_inputs(&app.tag)
+                .find(|(_, input_data)| nft_id(input_data) == Some(id))
+                .map(|(input, _)| &input.script_pubkey);
+            match spent_from {
+                Some(owner) => output.script_pubkey == *owner || is_burn_address(&output.script_pubkey),
+                None => true, // Minted, checked by `check`
+            }
+        })
+    }
+    
+    /// Recipient and amount of the royalty on a transferred NFT, using the
+    /// terms from its input, or `None` if it pays no royalty
+    /// 
+    /// `output` is the NFT's state in the transaction outputs, which must
+    /// carry the same royalty terms as the input.
+    fn transfer_royalty(app: &App, tx: &Transaction, output: &Data, w: &Data) -> Result<Option<(Vec<u8>, u64)>, CheckError> {
+        let nft = nft_id(output);
+        let terms = tx.inputs.iter()
+            .filter_map(|input| input.charm_state.as_ref()?.get(&app.tag))
+            .filter(|data| nft_id(data) == nft)
+            .find_map(NftData::from_data);
+        let Some(terms) = terms.filter(|terms| terms.royalty_bps > 0) else {
+            return Ok(None);
+        };
+        
+        let carried_over = NftData::from_data(output).is_some_and(|next| {
+            next.royalty_bps == terms.royalty_bps && next.royalty_recipient == terms.royalty_recipient
+        });
+        if !carried_over {
+            return Err(CheckError::InvalidTransition);
+        }
+        if terms.royalty_recipient.is_empty() {
+            return Err(CheckError::MissingField("royalty_recipient"));
+        }
+        
+        let sale_price = w.as_u64().ok_or(CheckError::MissingField("sale_price"))?;
+        let due = terms.royalty_due(sale_price);
+        Ok(Some((terms.royalty_recipient, due)))
+    }
+    
+    /// NFT auction lifecycle
+    pub mod auction {
+        use charms_sdk::data::{App, CheckError, Data, Transaction};
+        
+        /// Smallest raise over the previous bid, in basis points
+        pub const MIN_RAISE_BPS: u64 = 500;
+        
+        /// Auction states, carried as `"auction_state"` codes `0..=4`
+        #[derive(Debug, Clone, PartialEq, Eq)]
+        pub enum AuctionState {
+            Open,
+            BidPlaced { bidder: Vec<u8>, amount: u64 },
+            Closed,
+            Settled,
+            Cancelled,
+        }
+        
+        impl AuctionState {
+            /// Parse the auction state of an NFT charm state
+            /// 
+            /// The state is a `Data::Map` with an `"auction_state"` code. A
+            /// `BidPlaced` state also needs `"highest_bidder"` and `"highest_bid"`.
+            pub fn from_data(data: &Data) -> Option<Self> {
+                let Data::Map(map) = data else {
+                    return None;
+                };
+                match map.get("auction_state")?.as_u64()? {
+                    0 => Some(AuctionState::Open),
+                    1 => {
+                        let (bidder, amount) = highest_bid(data)?;
+                        Some(AuctionState::BidPlaced { bidder, amount })
+                    }
+                    2 => Some(AuctionState::Closed),
+                    3 => Some(AuctionState::Settled),
+                    4 => Some(AuctionState::Cancelled),
+                    _ => None,
+                }
+            }
+            
+            /// Encode as a state code
+            pub fn code(&self) -> u64 {
+                match self {
+                    AuctionState::Open => 0,
+                    AuctionState::BidPlaced { .. } => 1,
+                    AuctionState::Closed => 2,
+                    AuctionState::Settled => 3,
+                    AuctionState::Cancelled => 4,
+                }
+            }
+        }
+        
+        /// Smallest bid allowed to outbid `amount`
+        pub fn min_next_bid(amount: u64) -> u64 {
+            let raise = (amount as u128 * MIN_RAISE_BPS as u128).div_ceil(10_000) as u64;
+            amount.saturating_add(raise.max(1))
+        }
+        
+        /// The `"highest_bidder"` and `"highest_bid"` of an auction state
+        fn highest_bid(data: &Data) -> Option<(Vec<u8>, u64)> {
+            let Data::Map(map) = data else {
+                return None;
+            };
+            let bidder = map.get("highest_bidder")?.as_bytes()?.to_vec();
+            let amount = map.get("highest_bid")?.as_u64()?;
+            Some((bidder, amount))
+        }
+        
+        /// The `"end_block"` of an auction state
+        fn end_block(data: &Data) -> Option<u32> {
+            match data {
+                Data::Map(map) => map.get("end_block")?.as_u64()?.try_into().ok(),
+                _ => None,
+            }
+        }
+        
+        /// Validate an auction transition
+        /// 
+        /// Rules:
+        /// - `None -> Open`, `Open -> BidPlaced`, `BidPlaced -> BidPlaced`,
+        ///   `Open | BidPlaced -> Closed`, `Closed -> Settled` and
+        ///   `Open | Closed -> Cancelled` are the allowed transitions
+        /// - Every state carries the same `"end_block"`
+        /// - Bids must beat the last by `MIN_RAISE_BPS`, and can only be placed
+        ///   through `check_at_height`, before `end_block`
+        /// - Closing waits for `end_block`, read from the transaction's
+        ///   locktime, and keeps the winning bid
+        /// - Settling delivers the NFT to an output paying the highest bidder
+        pub fn check(app: &App, tx: &Transaction, x: &Data, w: &Data) -> bool {
+            try_check(app, tx, x, w).is_ok()
+        }
+        
+        /// Validate an auction transition, reporting why it was rejected
+        pub fn try_check(app: &App, tx: &Transaction, _x: &Data, _w: &Data) -> Result<(), CheckError> {
+            crate::log_rejection("auction", app, check_auction(app, tx, None))
+        }
+        
+        /// Like `check`, also allowing bids while `current_height` is before
+        /// `end_block`
+        /// 
+        /// `current_height` must be a height the caller vouches for. A
+        /// locktime only bounds the mining height from below, so it cannot
+        /// show a bid was mined before the auction ended. Closing is checked
+        /// against `current_height` too.
+        pub fn check_at_height(app: &App, tx: &Transaction, x: &Data, w: &Data, current_height: u32) -> bool {
+            try_check_at_height(app, tx, x, w, current_height).is_ok()
+        }
+        
+        /// `check_at_height`, reporting why the transition was rejected
+        pub fn try_check_at_height(
+            app: &App,
+            tx: &Transaction,
+            _x: &Data,
+            _w: &Data,
+            current_height: u32,
+        ) -> Result<(), CheckError> {
+            crate::log_rejection("auction", app, check_auction(app, tx, Some(current_height)))
+        }
+        
+        fn check_auction(app: &App, tx: &Transaction, current_height: Option<u32>) -> Result<(), CheckError> {
+            use AuctionState::*;
+            
+            let current_data = tx.inputs.iter()
+                .filter_map(|input| input.charm_state.as_ref()?.get(&app.tag))
+                .find(|data| AuctionState::from_data(data).is_some());
+            let (next_output, next_data) = tx.outputs.iter()
+                .filter_map(|output| Some((output, output.charm_state.as_ref()?.get(&app.tag)?)))
+                .find(|(_, data)| AuctionState::from_data(data).is_some())
+                .ok_or(CheckError::InvalidTransition)?;
+            let current = current_data.and_then(AuctionState::from_data);
+            let next = AuctionState::from_data(next_data).ok_or(CheckError::InvalidTransition)?;
+            
+            let end = end_block(next_data).ok_or(CheckError::MissingField("end_block"))?;
+            if current_data.is_some_and(|data| end_block(data) != Some(end)) {
+                return Err(CheckError::InvalidTransition);
+            }
+            let height = || current_height.or(tx.height()).ok_or(CheckError::MissingField("locktime"));
+            
+            match (&current, &next) {
+                (None, Open) | (Some(Open | Closed), Cancelled) => Ok(()),
+                (Some(Open | BidPlaced { .. }), BidPlaced { amount, .. }) => {
+                    let min = match &current {
+                        Some(BidPlaced { amount: previous, .. }) => min_next_bid(*previous),
+                        _ => 1,
+                    };
+                    if *amount < min {
+                        return Err(CheckError::AmountMismatch { expected: min, actual: *amount });
+                    }
+                    let height = current_height.ok_or(CheckError::InvalidTransition)?;
+                    if height >= end {
+                        return Err(CheckError::InvalidTransition);
+                    }
+                    Ok(())
+                }
+                (Some(Open | BidPlaced { .. }), Closed) => {
+                    let height = height()?;
+                    if height < end {
+                        return Err(CheckError::TimeoutNotReached { height, timeout: end });
+                    }
+                    let winner = match &current {
+                        Some(BidPlaced { bidder, amount }) => Some((bidder.clone(), *amount)),
+                        _ => None,
+                    };
+                    if highest_bid(next_data) != winner {
+                        return Err(CheckError::InvalidTransition);
+                    }
+                    Ok(())
+                }
+                (Some(Closed), Settled) => {
+                    let (bidder, _) = current_data.and_then(highest_bid)
+                        .ok_or(CheckError::MissingField("highest_bidder"))?;
+                    if next_output.script_pubkey != bidder {
+                        return Err(CheckError::UndeliveredAsset(app.tag.clone()));
+                    }
+                    Ok(())
+                }
+                _ => Err(CheckError::InvalidTransition),
+            }
+        }
+    }
+}
+
+/// Escrow spell checker - validates escrow contract rules
+pub mod escrow {
+    use charms_sdk::data::{App, CheckError, Data, Transaction};
+    use charms_sdk::data::escrow::{
+        check_amount_preserved, is_valid_transition, parse_state, recorded_remaining, state_outputs_value,
+    };
+    use crate::signature::{Secp256k1Verifier, SignatureVerifier};
+    use std::collections::BTreeMap;
+    
+    pub use charms_sdk::data::escrow::EscrowState;
+    
+    /// Financial terms of an escrow, stored CBOR-encoded in `App::params`
+    #[derive(Debug, Clone, PartialEq, Eq)]
+    pub struct EscrowParams {
+        /// Script pubkey refunds are paid to
+        pub buyer: Vec<u8>,
+        /// Script pubkey releases are paid to
+        pub seller: Vec<u8>,
+        /// SEC1 public key of the party settling disputes, if any; it must
+        /// sign releasing or refunding a `Disputed` escrow
+        pub arbiter: Option<Vec<u8>>,
+        /// Amount held in escrow
+        pub amount_sats: u64,
+        /// Blocks after funding before the buyer may be refunded; 0 disables
+        /// the requirement
+        pub timeout_blocks: u32,
+        /// SEC1 public keys of the parties who co-sign releases and disputes,
+        /// each listed once
+        pub co_signers: Vec<Vec<u8>>,
+        /// How many `co_signers` must sign, at most their number; 0 disables
+        /// the requirement
+        pub required_sigs: u8,
+        /// Partial release schedule, if the seller is paid in stages
+        pub milestones: Option<MilestoneEscrow>,
+    }
+    
+    /// Partial release schedule: milestone `n` pays `milestones[n]` to the seller
+    #[derive(Debug, Clone, PartialEq, Eq)]
+    pub struct MilestoneEscrow {
+        pub milestones: Vec<MilestoneAmount>,
+    }
+    
+    /// One stage of a `MilestoneEscrow`
+    #[derive(Debug, Clone, PartialEq, Eq)]
+    pub struct MilestoneAmount {
+        /// First block height at which the milestone can be paid
+        pub block_height: u32,
+        /// Sats paid to the seller on completing the milestone
+        pub amount_sats: u64,
+    }
+    
+    impl MilestoneEscrow {
+        /// Sats still held in escrow once milestone `n` has been paid
+        /// 
+        /// `None` if there is no milestone `n`, or the milestones up to it
+        /// pay out more than `total`.
+        pub fn remaining_after(&self, total: u64, n: u32) -> Option<u64> {
+            let paid = self.milestones.get(..=usize::try_from(n).ok()?)?
+                .iter()
+                .try_fold(0u64, |sum, milestone| sum.checked_add(milestone.amount_sats))?;
+            total.checked_sub(paid)
+        }
+        
+        fn to_data(&self) -> Data {
+            Data::List(self.milestones.iter()
+                .map(|milestone| {
+                    let mut map = BTreeMap::new();
+                    map.insert("block_height".to_string(), Data::U64(milestone.block_height as u64));
+                    map.insert("amount_sats".to_string(), Data::U64(milestone.amount_sats));
+                    Data::Map(map)
+                })
+                .collect())
+        }
+        
+        fn from_data(data: &Data) -> Option<Self> {
+            let milestones = data.as_list()?.iter()
+                .map(|milestone| {
+                    let map = milestone.as_map()?;
+                    Some(MilestoneAmount {
+                        block_height: u32::try_from(map.get("block_height")?.as_u64()?).ok()?,
+                        amount_sats: map.get("amount_sats")?.as_u64()?,
+                    })
+                })
+                .collect::<Option<_>>()?;
+            Some(Self { milestones })
+        }
+    }
+    
+    impl EscrowParams {
+        /// Encode as the `Data::Bytes` to use for `App::params`
+        pub fn to_params(&self) -> Data {
+            let mut map = BTreeMap::new();
+            map.insert("buyer".to_string(), Data::Bytes(self.buyer.clone()));
+            map.insert("seller".to_string(), Data::Bytes(self.seller.clone()));
+            if let Some(arbiter) = &self.arbiter {
+                map.insert("arbiter".to_string(), Data::Bytes(arbiter.clone()));
+            }
+            map.insert("amount_sats".to_string(), Data::U64(self.amount_sats));
+            map.insert("timeout_blocks".to_string(), Data::U64(self.timeout_blocks as u64));
+            if self.required_sigs > 0 || !self.co_signers.is_empty() {
+                let co_signers = self.co_signers.iter().cloned().map(Data::Bytes).collect();
+                map.insert("co_signers".to_string(), Data::List(co_signers));
+                map.insert("required_sigs".to_string(), Data::U64(self.required_sigs as u64));
+            }
+            if let Some(milestones) = &self.milestones {
+                map.insert("milestones".to_string(), milestones.to_data());
+            }
+            Data::Bytes(Data::Map(map).to_cbor())
+        }
+        
+        /// Decode from `App::params`
+        /// 
+        /// Fails for a key listed twice in `co_signers`, which would let one
+        /// signature count twice, or `required_sigs` above the number of
+        /// co-signers, which could never be met.
+        pub fn from_params(params: &Data) -> Option<Self> {
+            let Data::Map(map) = Data::from_cbor(params.as_bytes()?).ok()? else {
+                return None;
+            };
+            let arbiter = match map.get("arbiter") {
+                Some(arbiter) => Some(arbiter.as_bytes()?.to_vec()),
+                None => None,
+            };
+            let co_signers = match map.get("co_signers") {
+                Some(co_signers) => co_signers.as_list()?.iter()
+                    .map(|key| key.as_bytes().map(<[u8]>::to_vec))
+                    .collect::<Option<_>>()?,
+                None => Vec::new(),
+            };
+            let required_sigs = match map.get("required_sigs") {
+                Some(required) => u8::try_from(required.as_u64()?).ok()?,
+                None => 0,
+            };
+            let mut keys = std::collections::BTreeSet::new();
+            if !co_signers.iter().all(|key| keys.insert(key)) {
+                return None; // Listed twice
+            }
+            if usize::from(required_sigs) > co_signers.len() {
+                return None; // Unreachable threshold
+            }
+            let milestones = match map.get("milestones") {
+                Some(milestones) => Some(MilestoneEscrow::from_data(milestones)?),
+                None => None,
+            };
+            Some(Self {
+                buyer: map.get("buyer")?.as_bytes()?.to_vec(),
+                seller: map.get("seller")?.as_bytes()?.to_vec(),
+                arbiter,
+                amount_sats: map.get("amount_sats")?.as_u64()?,
+                timeout_blocks: u32::try_from(map.get("timeout_blocks")?.as_u64()?).ok()?,
+                co_signers,
+                required_sigs,
+                milestones,
+            })
+        }
+    }
+    
+    /// Validate escrow state transitions
+    /// 
+    /// The transition table is shared with the WASM bindings through
+    /// `charms_data::escrow::is_valid_transition`.
+    /// 
+    /// The escrow state is a `Data::Map` with a `"state"` code. Only the
+    /// `Created`, `Released` and `Refunded` states may instead be a bare
+    /// `Data::U64` code: the states in between must record the funded
+    /// `"amount"` (see below), which needs the map. The map may hold a bundle
+    /// of assets: a `"recipient"` script and a `"bundle"` map from app tag to
+    /// a token amount (`U64`) or an NFT id (`Bytes`). Releasing the escrow
+    /// must deliver every bundled asset to outputs paying the recipient.
+    /// 
+    /// The map form may also set a `"timeout_height"`, before which the escrow
+    /// cannot be refunded. The current height is read from the transaction's
+    /// locktime, which must then claim a height with at least one non-final
+    /// input.
+    /// 
+    /// If `app.params` holds `EscrowParams`, the `Funded` output must carry
+    /// exactly `amount_sats`, and releasing or refunding must pay at least
+    /// `amount_sats` to the seller or buyer respectively.
+    /// 
+    /// With `EscrowParams::milestones` set, completing milestone `n` must pay
+    /// exactly its `amount_sats` to the seller, at or after its
+    /// `block_height` (read from the locktime), and leave exactly the unpaid
+    /// rest in the escrow output. Releasing after a milestone pays out that
+    /// rest rather than the full `amount_sats`. Disputing after a milestone
+    /// must record the rest as `"remaining"` in the `Disputed` map, and the
+    /// dispute is then released or refunded with exactly that rest.
+    /// 
+    /// The `Funded` state must be in map form and record the funded
+    /// `"amount"`. It must match the sats locked in the `Funded` outputs, be
+    /// carried unchanged through later states, and be paid out exactly by the
+    /// `Released` or `Refunded` outputs (after milestones, only the rest).
+    /// 
+    /// A `Created` escrow in map form may name its `"payer"` (a SEC1 public
+    /// key). Funding it then needs `x` to be a `Data::List` with one entry per
+    /// transaction input: the payer's signature over the txid for inputs the
+    /// payer spends, `Data::Empty` otherwise. Each signed input must be owned
+    /// by the payer (see `signature::is_owner`), and together they must have
+    /// known values holding at least the sats locked in the `Funded` output.
+    /// 
+    /// With `EscrowParams::timeout_blocks` set, funding must happen at a
+    /// height (read from the locktime) and set a `"timeout_height"` at least
+    /// that many blocks later, which every later state keeps. Refunds then
+    /// wait for it as above.
+    /// 
+    /// With `EscrowParams::arbiter` set, releasing or refunding a `Disputed`
+    /// escrow needs `x` to be a `Data::List` holding the arbiter's signature
+    /// over the txid.
+    /// 
+    /// With `EscrowParams::required_sigs` set, moving from `Funded` or a
+    /// completed milestone to `Released` or `Disputed` needs `x` to be a
+    /// `Data::List` of signatures over the txid from at least that many
+    /// distinct `co_signers`.
+    /// 
+    /// Signatures are always over `Transaction::compute_txid`, never the
+    /// caller-supplied `tx.txid`.
+    /// 
+    /// A `Funded` escrow can only be refunded directly through
+    /// `check_at_height`, once its `"timeout_height"` has passed.
+    pub fn check(app: &App, tx: &Transaction, x: &Data, w: &Data) -> bool {
+        try_check(app, tx, x, w).is_ok()
+    }
+    
+    /// Validate escrow state transitions, reporting why they were rejected
+    pub fn try_check(app: &App, tx: &Transaction, x: &Data, w: &Data) -> Result<(), CheckError> {
+        try_check_with(&Secp256k1Verifier, app, tx, x, w)
+    }
+    
+    /// Like `check`, also allowing a timed-out `Funded` escrow to be refunded
+    /// 
+    /// `Funded -> Refunded` is valid once `current_height` is at or past the
+    /// `"timeout_height"` in the funded escrow's map state.
+    pub fn check_at_height(app: &App, tx: &Transaction, x: &Data, w: &Data, current_height: u32) -> bool {
+        try_check_at_height(app, tx, x, w, current_height).is_ok()
+    }
+    
+    /// `check_at_height`, reporting why the spell was rejected
+    pub fn try_check_at_height(
+        app: &App,
+        tx: &Transaction,
+        x: &Data,
+        _w: &Data,
+        current_height: u32,
+    ) -> Result<(), CheckError> {
+        crate::log_rejection("escrow", app, check_escrow(&Secp256k1Verifier, app, tx, x, Some(current_height)))
+    }
+    
+    /// `try_check` with a custom signature verifier
+    pub fn try_check_with<V: SignatureVerifier>(
+        verifier: &V,
+        app: &App,
+        tx: &Transaction,
+        x: &Data,
+        _w: &Data,
+    ) -> Result<(), CheckError> {
+        crate::log_rejection("escrow", app, check_escrow(verifier, app, tx, x, None))
+    }
+    
+    fn check_escrow<V: SignatureVerifier>(
+        verifier: &V,
+        app: &App,
+        tx: &Transaction,
+        x: &Data,
+        current_height: Option<u32>,
+    ) -> Result<(), CheckError> {
+        crate::check_output_limit(app, tx)?;
+        
+        let app_tag = &app.tag;
+        
+        // Get current escrow state from inputs
+        let current_data = tx.inputs.iter()
+            .find_map(|input| {
+                input.charm_state.as_ref()
+                    .and_then(|state| state.get(app_tag))
+                    .filter(|data| parse_state(data).is_some())
+            });
+        let (current_state, next_state) = transition(app, tx);
+        
+        let timeout_refund = current_state == Some(EscrowState::Funded)
+            && next_state == Some(EscrowState::Refunded);
+        if timeout_refund {
+            let height = current_height.ok_or(CheckError::InvalidTransition)?;
+            check_refund_deadline(current_data, height)?;
+        } else if !is_valid_transition(current_state, next_state) {
+            return Err(CheckError::InvalidTransition);
+        }
+        
+        if let Some(next_state) = next_state {
+            check_terms(app, tx, current_data, next_state)?;
+            check_timeout_scheduled(app, tx, current_data, next_state)?;
+            let remaining = match current_data {
+                Some(data) => milestone_remainder(app, data)?,
+                None => None,
+            };
+            check_amount_preserved(app_tag, tx, current_data, next_state, remaining)?;
+        }
+        
+        if next_state == Some(EscrowState::Funded) {
+            if let Some(Data::Map(escrow)) = current_data {
+                check_payer_funded(verifier, app, escrow, tx, x)?;
+            }
+        }
+        
+        let co_signed = matches!(next_state, Some(EscrowState::Released | EscrowState::Disputed));
+        let releasable = matches!(
+            current_state,
+            Some(EscrowState::Funded | EscrowState::MilestoneCompleted(_))
+        );
+        if releasable && co_signed {
+            check_co_signers(verifier, app, tx, x)?;
+        }
+        
+        let settled = matches!(next_state, Some(EscrowState::Released | EscrowState::Refunded));
+        if current_state == Some(EscrowState::Disputed) && settled {
+            check_arbiter(verifier, app, tx, x)?;
+        }
+        
+        if next_state == Some(EscrowState::Released) {
+            if let Some(Data::Map(escrow)) = current_data {
+                check_bundle_delivered(escrow, tx)?;
+            }
+        }
+        
+        if next_state == Some(EscrowState::Refunded) && !timeout_refund {
+            if let Some(Data::Map(escrow)) = current_data {
+                check_timeout_reached(escrow, tx)?;
+            }
+        }
+        
+        Ok(())
+    }
+    
+    /// Current and next escrow state of a transaction
+    pub fn transition(app: &App, tx: &Transaction) -> (Option<EscrowState>, Option<EscrowState>) {
+        let current = tx.inputs.iter()
+            .find_map(|input| input.charm_state.as_ref()?.get(&app.tag).and_then(parse_state));
+        let next = tx.outputs.iter()
+            .find_map(|output| output.charm_state.as_ref()?.get(&app.tag).and_then(parse_state));
+        (current, next)
+    }
+    
+    /// Check the transaction moves the amounts set out in the escrow's params
+    fn check_terms(
+        app: &App,
+        tx: &Transaction,
+        current_data: Option<&Data>,
+        next_state: EscrowState,
+    ) -> Result<(), CheckError> {
+        if !matches!(app.params, Data::Bytes(_)) {
+            return Ok(()); // No financial terms
+        }
+        let params = EscrowParams::from_params(&app.params)
+            .ok_or(CheckError::MissingField("params"))?;
+        
+        let remaining = match current_data {
+            Some(data) => milestone_remainder(app, data)?,
+            None => None,
+        };
+        let expected = remaining.unwrap_or(params.amount_sats);
+        let actual = match next_state {
+            EscrowState::Funded => {
+                let output = tx.outputs.iter()
+                    .find(|output| {
+                        output.charm_state.as_ref()
+                            .and_then(|state| state.get(&app.tag))
+                            .and_then(parse_state) == Some(EscrowState::Funded)
+                    })
+                    .ok_or(CheckError::InvalidTransition)?;
+                if output.value != expected {
+                    return Err(CheckError::AmountMismatch { expected, actual: output.value });
+                }
+                return Ok(());
+            }
+            EscrowState::MilestoneCompleted(n) => match &params.milestones {
+                Some(milestones) => return check_milestone_paid(app, tx, &params, milestones, n),
+                None => return Ok(()),
+            },
+            EscrowState::Released => paid_to(tx, &params.seller)?,
+            EscrowState::Refunded => paid_to(tx, &params.buyer)?,
+            _ => return Ok(()),
+        };
+        
+        if actual < expected {
+            return Err(CheckError::AmountMismatch { expected, actual });
+        }
+        Ok(())
+    }
+    
+    /// Check milestone `n` is due, paid in full to the seller, and the rest
+    /// stays in escrow
+    fn check_milestone_paid(
+        app: &App,
+        tx: &Transaction,
+        params: &EscrowParams,
+        milestones: &MilestoneEscrow,
+        n: u32,
+    ) -> Result<(), CheckError> {
+        let milestone = usize::try_from(n).ok()
+            .and_then(|n| milestones.milestones.get(n))
+            .ok_or(CheckError::InvalidTransition)?;
+        let remaining = milestones.remaining_after(params.amount_sats, n)
+            .ok_or(CheckError::MissingField("milestones"))?;
+        
+        let height = tx.height().ok_or(CheckError::MissingField("locktime"))?;
+        if height < milestone.block_height {
+            return Err(CheckError::TimeoutNotReached { height, timeout: milestone.block_height });
+        }
+        
+        let paid = paid_to(tx, &params.seller)?;
+        if paid != milestone.amount_sats {
+            return Err(CheckError::AmountMismatch { expected: milestone.amount_sats, actual: paid });
+        }
+        
+        let held = state_outputs_value(&app.tag, tx, EscrowState::MilestoneCompleted(n))?;
+        if held != remaining {
+            return Err(CheckError::AmountMismatch { expected: remaining, actual: held });
+        }
+        Ok(())
+    }
+    
+    /// Total sats paid to `script_pubkey`
+    fn paid_to(tx: &Transaction, script_pubkey: &[u8]) -> Result<u64, CheckError> {
+        tx.outputs.iter()
+            .filter(|output| output.script_pubkey == script_pubkey)
+            .try_fold(0u64, |sum, output| sum.checked_add(output.value))
+            .ok_or(CheckError::Overflow)
+    }
+    
+    /// Check enough co-signers signed the txid, if the params require any
+    fn check_co_signers<V: SignatureVerifier>(
+        verifier: &V,
+        app: &App,
+        tx: &Transaction,
+        x: &Data,
+    ) -> Result<(), CheckError> {
+        let Some(params) = EscrowParams::from_params(&app.params) else {
+            return Ok(()); // No terms, or already rejected by `check_terms`
+        };
+        if params.required_sigs == 0 {
+            return Ok(());
+        }
+        
+        let signatures = x.as_list().ok_or(CheckError::MissingAuthorization)?;
+        let txid = tx.compute_txid();
+        let signed = params.co_signers.iter()
+            .filter(|co_signer| {
+                signatures.iter()
+                    .filter_map(Data::as_bytes)
+                    .any(|sig| verifier.verify(co_signer, &txid, sig))
+            })
+            .count();
+        
+        if signed < params.required_sigs as usize {
+            return Err(CheckError::MissingAuthorization);
+        }
+        Ok(())
+    }
+    
+    /// Check the arbiter, if the params name one, signed the txid
+    fn check_arbiter<V: SignatureVerifier>(
+        verifier: &V,
+        app: &App,
+        tx: &Transaction,
+        x: &Data,
+    ) -> Result<(), CheckError> {
+        let Some(arbiter) = EscrowParams::from_params(&app.params).and_then(|params| params.arbiter) else {
+            return Ok(()); // Either party may settle
+        };
+        
+        let signatures = x.as_list().ok_or(CheckError::MissingAuthorization)?;
+        let txid = tx.compute_txid();
+        let signed = signatures.iter()
+            .filter_map(Data::as_bytes)
+            .any(|sig| verifier.verify(&arbiter, &txid, sig));
+        if !signed {
+            return Err(CheckError::MissingAuthorization);
+        }
+        Ok(())
+    }
+    
+    /// Check funding sets a `"timeout_height"` at least
+    /// `EscrowParams::timeout_blocks` past its height, and later states keep it
+    fn check_timeout_scheduled(
+        app: &App,
+        tx: &Transaction,
+        current_data: Option<&Data>,
+        next_state: EscrowState,
+    ) -> Result<(), CheckError> {
+        let Some(params) = EscrowParams::from_params(&app.params) else {
+            return Ok(()); // No terms, or already rejected by `check_terms`
+        };
+        if params.timeout_blocks == 0 {
+            return Ok(());
+        }
+        
+        let timeout_height = |data: &Data| match data {
+            Data::Map(map) => map.get("timeout_height")
+                .and_then(Data::as_u64)
+                .and_then(|timeout| u32::try_from(timeout).ok()),
+            _ => None,
+        };
+        let timeout = tx.outputs.iter()
+            .filter_map(|output| output.charm_state.as_ref()?.get(&app.tag))
+            .find(|data| parse_state(data) == Some(next_state))
+            .and_then(timeout_height);
+        
+        match next_state {
+            EscrowState::Funded => {
+                let timeout = timeout.ok_or(CheckError::MissingField("timeout_height"))?;
+                let height = tx.height().ok_or(CheckError::MissingField("locktime"))?;
+                let earliest = height.checked_add(params.timeout_blocks).ok_or(CheckError::Overflow)?;
+                if timeout < earliest {
+                    return Err(CheckError::TimeoutNotReached { height: timeout, timeout: earliest });
+                }
+            }
+            EscrowState::MilestoneCompleted(_) | EscrowState::Disputed => {
+                // Dropping or moving the deadline would let a refund skip it
+                if timeout.is_none() || timeout != current_data.and_then(timeout_height) {
+                    return Err(CheckError::MissingField("timeout_height"));
+                }
+            }
+            EscrowState::Created | EscrowState::Released | EscrowState::Refunded => {}
+        }
+        Ok(())
+    }
+    
+    /// Check the `Funded` output's sats come from inputs the payer owns and
+    /// signed for
+    fn check_payer_funded<V: SignatureVerifier>(
+        verifier: &V,
+        app: &App,
+        escrow: &BTreeMap<String, Data>,
+        tx: &Transaction,
+        x: &Data,
+    ) -> Result<(), CheckError> {
+        let Some(payer) = escrow.get("payer") else {
+            return Ok(()); // Anyone may fund
+        };
+        let payer = payer.as_bytes().ok_or(CheckError::MissingField("payer"))?;
+        
+        let Data::List(signatures) = x else {
+            return Err(CheckError::MissingAuthorization);
+        };
+        if signatures.len() != tx.inputs.len() {
+            return Err(CheckError::MissingAuthorization);
+        }
+        
+        let txid = tx.compute_txid();
+        let mut payer_funded = 0u64;
+        for (input, signature) in tx.inputs.iter().zip(signatures) {
+            let Some(sig) = signature.as_bytes() else {
+                continue; // Not spent by the payer
+            };
+            // The signature alone is not tied to the input, so it could be
+            // copied into the slot of an input someone else spends
+            if !crate::signature::is_owner(payer, &input.script_pubkey) || !verifier.verify(payer, &txid, sig) {
+                return Err(CheckError::MissingAuthorization);
+            }
+            let value = input.value.ok_or(CheckError::MissingField("value"))?;
+            payer_funded = payer_funded.checked_add(value).ok_or(CheckError::Overflow)?;
+        }
+        
+        let funded = state_outputs_value(&app.tag, tx, EscrowState::Funded)?;
+        if payer_funded < funded {
+            return Err(CheckError::AmountMismatch { expected: funded, actual: payer_funded });
+        }
+        Ok(())
+    }
+    
+    /// Sats still held by an escrow after its milestones paid out part of its
+    /// `amount_sats`, `None` if none were paid
+    /// 
+    /// A `Disputed` escrow carries the remainder it was disputed with as
+    /// `"remaining"`.
+    fn milestone_remainder(app: &App, data: &Data) -> Result<Option<u64>, CheckError> {
+        match (parse_state(data), data) {
+            (Some(EscrowState::MilestoneCompleted(n)), _) => {
+                let Some(params) = EscrowParams::from_params(&app.params) else {
+                    return Ok(None);
+                };
+                let Some(milestones) = &params.milestones else {
+                    return Ok(None);
+                };
+                milestones.remaining_after(params.amount_sats, n)
+                    .map(Some)
+                    .ok_or(CheckError::MissingField("milestones"))
+            }
+            (Some(EscrowState::Disputed), _) => recorded_remaining(data),
+            _ => Ok(None),
+        }
+    }
+    
+    /// Check the transaction's height is at or past the escrow's timeout, if any
+    fn check_timeout_reached(
+        escrow: &BTreeMap<String, Data>,
+        tx: &Transaction,
+    ) -> Result<(), CheckError> {
+        let Some(timeout) = escrow.get("timeout_height") else {
+            return Ok(());
+        };
+        let timeout = timeout.as_u64()
+            .and_then(|timeout| u32::try_from(timeout).ok())
+            .ok_or(CheckError::MissingField("timeout_height"))?;
+        let height = tx.height().ok_or(CheckError::MissingField("locktime"))?;
+        
+        if height < timeout {
+            return Err(CheckError::TimeoutNotReached { height, timeout });
+        }
+        Ok(())
+    }
+    
+    /// Check a funded escrow's `"timeout_height"` has passed at `height`
+    fn check_refund_deadline(funded: Option<&Data>, height: u32) -> Result<(), CheckError> {
+        let timeout = match funded {
+            Some(Data::Map(escrow)) => escrow.get("timeout_height"),
+            _ => None,
+        };
+        let timeout = timeout
+            .and_then(Data::as_u64)
+            .and_then(|timeout| u32::try_from(timeout).ok())
+            .ok_or(CheckError::MissingField("timeout_height"))?;
+        
+        if height < timeout {
+            return Err(CheckError::TimeoutNotReached { height, timeout });
+        }
+        Ok(())
+    }
+    
+    /// Check every bundled asset reaches outputs paying the recipient
+    fn check_bundle_delivered(
+        escrow: &BTreeMap<String, Data>,
+        tx: &Transaction,
+    ) -> Result<(), CheckError> {
+        let Some(bundle) = escrow.get("bundle") else {
+            return Ok(()); // Nothing held besides the escrow itself
+        };
+        let Data::Map(bundle) = bundle else {
+            return Err(CheckError::MissingField("bundle"));
+        };
+        let recipient = escrow.get("recipient")
+            .and_then(|data| data.as_bytes())
+            .ok_or(CheckError::MissingField("recipient"))?;
+        
+        for (tag, asset) in bundle {
+            let ok = match asset {
+                Data::U64(amount) => {
+                    let received = delivered(tx, recipient, tag)
+                        .try_fold(0u64, |acc, data| acc.checked_add(crate::token::effective_balance(data)?))
+                        .ok_or(CheckError::Overflow)?;
+                    received >= *amount
+                }
+                Data::Bytes(id) => delivered(tx, recipient, tag)
+                    .any(|data| crate::nft::nft_id(data) == Some(id.as_slice())),
+                _ => return Err(CheckError::MissingField("bundle")),
+            };
+            if !ok {
+                return Err(CheckError::UndeliveredAsset(tag.clone()));
+            }
+        }
+        
+        Ok(())
+    }
+    
+    /// The `tag` states in outputs paying `recipient`
+    fn delivered<'a>(
+        tx: &'a Transaction,
+        recipient: &'a [u8],
+        tag: &'a str,
+    ) -> impl Iterator<Item = &'a Data> + 'a {
+        tx.outputs.iter()
+            .filter(move |output| output.script_pubkey == recipient)
+            .filter_map(move |output| output.charm_state.as_ref()?.get(tag))
+    }
+}
+
+/// Validate every app carried by a transaction in a single pass
+/// 
+/// Each `(app, x, w)` is checked against the same transaction, with the
+/// built-in checkers of `CheckerRegistry::new`, and all of them must pass. An
+/// app may only be listed once, and every app state in the transaction's
+/// inputs and outputs must belong to a listed app, so nothing unchecked can
+/// ride along with the apps that are.
+pub fn check_all(
+    tx: &charms_sdk::data::Transaction,
+    apps: &[(charms_sdk::data::App, charms_sdk::data::Data, charms_sdk::data::Data)],
+) -> bool {
+    let mut tags = std::collections::BTreeSet::new();
+    for (app, _, _) in apps {
+        if !tags.insert(app.tag.as_str()) {
+            return false; // Listed twice
+        }
+    }
+    
+    let states = tx.inputs.iter().filter_map(|input| input.charm_state.as_ref())
+        .chain(tx.outputs.iter().filter_map(|output| output.charm_state.as_ref()));
+    for state in states {
+        if state.apps.keys().any(|tag| !tags.contains(tag.as_str())) {
+            return false; // App state nobody checks
+        }
+    }
+    
+    let registry = CheckerRegistry::new();
+    apps.iter().all(|(app, x, w)| registry.dispatch(app, tx, x, w))
+}
+
+/// Why an `AppRegistry` could not check a spell
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AppError {
+    /// No app is registered under the tag
+    UnknownApp(String),
+    /// The app's category has no native checker
+    UnsupportedType(String),
+    /// The transaction carries no charm state for the app
+    NotInTransaction(String),
+}
+
+impl std::fmt::Display for AppError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AppError::UnknownApp(tag) => write!(f, "unknown app {}", tag),
+            AppError::UnsupportedType(tag) => write!(f, "no checker for app {}", tag),
+            AppError::NotInTransaction(tag) => write!(f, "app {} not in transaction", tag),
+        }
+    }
+}
+
+impl std::error::Error for AppError {}
+
+/// Known apps by tag, each checked with the built-in checker for its tag prefix
+#[derive(Debug, Clone, Default)]
+pub struct AppRegistry(std::collections::HashMap<String, charms_sdk::data::App>);
+
+impl AppRegistry {
+    /// Create an empty registry
+    pub fn new() -> Self {
+        Self::default()
+    }
+    
+    /// Register an app under its tag, returning any app it replaces
+    /// 
+    /// The tag must pass `App::validate_tag`.
+    pub fn register(
+        &mut self,
+        app: charms_sdk::data::App,
+    ) -> Result<Option<charms_sdk::data::App>, charms_sdk::data::TagValidationError> {
+        charms_sdk::data::App::validate_tag(&app.tag)?;
+        Ok(self.0.insert(app.tag.clone(), app))
+    }
+    
+    /// Look up a registered app
+    pub fn get(&self, tag: &str) -> Option<&charms_sdk::data::App> {
+        self.0.get(tag)
+    }
+    
+    /// Validate a spell for the app registered under `tag`, which the
+    /// transaction must carry charm state for
+    /// 
+    /// The checker is the one `CheckerRegistry::new` holds for the tag.
+    pub fn check_spell(
+        &self,
+        tag: &str,
+        tx: &charms_sdk::data::Transaction,
+        x: &charms_sdk::data::Data,
+        w: &charms_sdk::data::Data,
+    ) -> Result<bool, AppError> {
+        let app = self.get(tag).ok_or_else(|| AppError::UnknownApp(tag.to_string()))?;
+        if !tx.has_charm(tag) {
+            return Err(AppError::NotInTransaction(tag.to_string()));
+        }
+        let checker = CheckerRegistry::new().get(tag)
+            .ok_or_else(|| AppError::UnsupportedType(tag.to_string()))?;
+        Ok(checker(app, tx, x, w))
+    }
+}
+
+/// A spell checker callable through a `CheckerRegistry`
+pub type Checker = fn(
+    &charms_sdk::data::App,
+    &charms_sdk::data::Transaction,
+    &charms_sdk::data::Data,
+    &charms_sdk::data::Data,
+) -> bool;
+
+/// Spell checkers keyed by tag prefix
+/// 
+/// `new` starts with the built-in `token:`, `nft:`, `escrow:`, `bounty:` and
+/// `bollar:` checkers; more can be registered for custom app types. When several
+/// prefixes match a tag the longest one wins.
+#[derive(Debug, Clone)]
+pub struct CheckerRegistry(std::collections::BTreeMap<String, Checker>);
+
+impl CheckerRegistry {
+    /// Create a registry with the built-in checkers
+    pub fn new() -> Self {
+        let mut registry = Self::empty();
+        registry.register("token:", token::check);
+        registry.register("nft:", nft::check);
+        registry.register("escrow:", escrow::check);
+        registry.register("bounty:", bounty::check);
+        registry.register("bollar:", bollar::check);
+        registry
+    }
+    
+    /// Create a registry with no checkers
+    pub fn empty() -> Self {
+        Self(std::collections::BTreeMap::new())
+    }
+    
+    /// Register a checker for tags starting with `prefix`, returning any it replaces
+    pub fn register(&mut self, prefix: &str, checker: Checker) -> Option<Checker> {
+        self.0.insert(prefix.to_string(), checker)
+    }
+    
+    /// The checker for a tag, if any prefix matches
+    pub fn get(&self, tag: &str) -> Option<Checker> {
+        self.0.iter()
+            .filter(|(prefix, _)| tag.starts_with(prefix.as_str()))
+            .max_by_key(|(prefix, _)| prefix.len())
+            .map(|(_, checker)| *checker)
+    }
+    
+    /// Validate a spell with the checker for the app's tag
+    /// 
+    /// Apps with no matching checker are rejected.
+    pub fn dispatch(
+        &self,
+        app: &charms_sdk::data::App,
+        tx: &charms_sdk::data::Transaction,
+        x: &charms_sdk::data::Data,
+        w: &charms_sdk::data::Data,
+    ) -> bool {
+        self.get(&app.tag).is_some_and(|checker| checker(app, tx, x, w))
+    }
+}
+
+impl Default for CheckerRegistry {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Bounty spell checker - validates the bounty lifecycle
+pub mod bounty {
+    use charms_sdk::data::{App, CheckError, Data, Transaction};
+    use std::fmt;
+    
+    /// Bounty states, carried as `Data::U64` codes `0..=4`
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub enum BountyState {
+        Open,
+        InProgress,
+        Completed,
+        Cancelled,
+        Disputed,
+    }
+    
+    impl BountyState {
+        /// Decode a state code
+        pub fn from_code(code: u64) -> Option<Self> {
+            match code {
+                0 => Some(BountyState::Open),
+                1 => Some(BountyState::InProgress),
+                2 => Some(BountyState::Completed),
+                3 => Some(BountyState::Cancelled),
+                4 => Some(BountyState::Disputed),
+                _ => None,
+            }
+        }
+        
+        /// Encode as a state code
+        pub fn code(&self) -> u64 {
+            match self {
+                BountyState::Open => 0,
+                BountyState::InProgress => 1,
+                BountyState::Completed => 2,
+                BountyState::Cancelled => 3,
+                BountyState::Disputed => 4,
+            }
+        }
+    }
+    
+    impl fmt::Display for BountyState {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            let name = match self {
+                BountyState::Open => "Open",
+                BountyState::InProgress => "InProgress",
+                BountyState::Completed => "Completed",
+                BountyState::Cancelled => "Cancelled",
+                BountyState::Disputed => "Disputed",
+            };
+            f.write_str(name)
+        }
+    }
+    
+    /// Whether a bounty may move from `current` to `next`
+    pub fn is_valid_transition(current: Option<BountyState>, next: Option<BountyState>) -> bool {
+        use BountyState::*;
+        
+        matches!(
+            (current, next),
+            (None, Some(Open))
+                | (Some(Open), Some(InProgress))
+                | (Some(InProgress), Some(Completed))
+                | (Some(Open), Some(Cancelled))
+                | (Some(InProgress), Some(Disputed))
+                | (Some(Disputed), Some(Completed))
+                | (Some(Disputed), Some(Cancelled))
+        )
+    }
+    
+    /// Bounty payload
+    /// 
+    /// Carried in charm state as a `Data::Map` with `"state"`, `"reward_amount"`,
+    /// `"hunter"` and `"deadline_block"` entries. A bare `Data::U64` state code
+    /// is also accepted and carries no payload.
+    #[derive(Debug, Clone, PartialEq, Eq)]
+    pub struct BountyData {
+        pub reward_amount: u64,
+        pub hunter: Vec<u8>,
+        pub deadline_block: u32,
+    }
+    
+    impl BountyData {
+        /// Parse the payload from the map form of a bounty charm state
+        pub fn from_data(data: &Data) -> Option<Self> {
+            let Data::Map(map) = data else {
+                return None;
+            };
+            Some(Self {
+                reward_amount: map.get("reward_amount")?.as_u64()?,
+                hunter: map.get("hunter")?.as_bytes()?.to_vec(),
+                deadline_block: map.get("deadline_block")?.as_u64()?.try_into().ok()?,
+            })
+        }
+    }
+    
+    /// Bounty state of either charm state form
+    fn parse_state(data: &Data) -> Option<BountyState> {
+        let code = match data {
+            Data::Map(map) => map.get("state")?.as_u64()?,
+            _ => data.as_u64()?,
+        };
+        BountyState::from_code(code)
+    }
+    
+    /// The app's bounty state in the inputs and outputs
+    fn bounty_states<'a>(app: &App, tx: &'a Transaction) -> (Option<&'a Data>, Option<&'a Data>) {
+        let app_tag = &app.tag;
+        let current = tx.inputs.iter().find_map(|input| {
+            input.charm_state.as_ref()
+                .and_then(|state| state.get(app_tag))
+                .filter(|data| parse_state(data).is_some())
+        });
+        let next = tx.outputs.iter().find_map(|output| {
+            output.charm_state.as_ref()
+                .and_then(|state| state.get(app_tag))
+                .filter(|data| parse_state(data).is_some())
+        });
+        (current, next)
+    }
+    
+    /// Current and next bounty state of a transaction
+    pub fn transition(app: &App, tx: &Transaction) -> (Option<BountyState>, Option<BountyState>) {
+        let (current, next) = bounty_states(app, tx);
+        (current.and_then(parse_state), next.and_then(parse_state))
+    }
+    
+    /// Validate a bounty transition
+    /// 
+    /// Rules:
+    /// - The state transition must be allowed by `is_valid_transition`
+    /// - Once a bounty carries `BountyData`, every later state must carry it
+    ///   with the same `reward_amount`
+    /// - Moving to `InProgress` must set a non-empty `hunter`
+    pub fn check(app: &App, tx: &Transaction, x: &Data, w: &Data) -> bool {
+        try_check(app, tx, x, w).is_ok()
+    }
+    
+    /// Validate a bounty transition, reporting why it was rejected
+    pub fn try_check(app: &App, tx: &Transaction, x: &Data, w: &Data) -> Result<(), CheckError> {
+        crate::log_rejection("bounty", app, check_bounty(app, tx, x, w))
+    }
+    
+    fn check_bounty(app: &App, tx: &Transaction, _x: &Data, _w: &Data) -> Result<(), CheckError> {
+        crate::check_output_limit(app, tx)?;
+        
+        let (current_data, next_data) = bounty_states(app, tx);
+        let current = current_data.and_then(parse_state);
+        let next = next_data.and_then(parse_state);
+        if !is_valid_transition(current, next) {
+            return Err(CheckError::InvalidTransition);
+        }
+        
+        let current_bounty = current_data.and_then(BountyData::from_data);
+        let next_bounty = next_data.and_then(BountyData::from_data);
+        
+        // Reward is conserved through the lifecycle
+        if let Some(before) = &current_bounty {
+            let Some(after) = &next_bounty else {
+                return Err(CheckError::MissingField("reward_amount"));
+            };
+            if after.reward_amount != before.reward_amount {
+                return Err(CheckError::ConservationViolation {
+                    input: before.reward_amount,
+                    output: after.reward_amount,
+                });
+            }
+        }
+        
+        // Taking on the bounty names the hunter
+        if next == Some(BountyState::InProgress)
+            && next_bounty.as_ref().is_none_or(|bounty| bounty.hunter.is_empty())
+        {
+            return Err(CheckError::MissingField("hunter"));
+        }
+        
+        Ok(())
+    }
+}
+
+/// Helpers for building checked transaction chains in tests
+#[cfg(any(test, feature = "testutil"))]
+pub mod testutil {
+    use charms_sdk::data::{App, CharmState, Data, Transaction, TxInput, TxOutput, UtxoRef};
+    use crate::escrow::{self, EscrowState};
+    
+    /// Build a chain of escrow transactions, one per state
+    /// 
+    /// Transaction `i` spends output 0 of transaction `i - 1` and carries
+    /// `states[i]` in its escrow output. Nothing is checked.
+    pub fn escrow_chain(app: &App, states: &[EscrowState]) -> Vec<Transaction> {
+        let mut txs: Vec<Transaction> = Vec::with_capacity(states.len());
+        
+        for (i, state) in states.iter().enumerate() {
+            let mut tx = Transaction::new([i as u8 + 1; 32]);
+            
+            if let Some(prev) = txs.last() {
+                let prev_output = &prev.outputs[0];
+                tx.add_input(TxInput {
+                    value: Some(prev_output.value),
+                    script_pubkey: prev_output.script_pubkey.clone(),
+                    ..TxInput::new(
+                        UtxoRef { txid: prev.txid, vout: prev_output.index },
+                        prev_output.charm_state.clone(),
+                    )
+                });
+            }
+            
+            let mut escrow = std::collections::BTreeMap::new();
+            escrow.insert("state".to_string(), Data::U64(state.code()));
+            escrow.insert("amount".to_string(), Data::U64(100_000));
+            tx.add_output(TxOutput {
+                index: 0,
+                value: 100_000,
+                script_pubkey: vec![],
+                charm_state: Some(CharmState::new().with_app(app.tag.as_str(), Data::Map(escrow))),
+            });
+            
+            txs.push(tx);
+        }
+        
+        txs
+    }
+    
+    /// Index of the first transaction in the chain rejected by `escrow::check`
+    pub fn first_invalid(app: &App, txs: &[Transaction]) -> Option<usize> {
+        txs.iter().position(|tx| !escrow::check(app, tx, &Data::Empty, &Data::Empty))
+    }
+    
+    /// Build the `Created -> Funded -> Released` lifecycle, asserting every step is valid
+    pub fn escrow_lifecycle(app: &App) -> Vec<Transaction> {
+        let txs = escrow_chain(
+            app,
+            &[EscrowState::Created, EscrowState::Funded, EscrowState::Released],
+        );
+        
+        if let Some(i) = first_invalid(app, &txs) {
+            panic!("escrow lifecycle step {} rejected for app {}", i, app.tag);
+        }
+        
+        txs
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use super::escrow;
     use charms_sdk::data::*;
     
     #[test]
-    fn test_token_conservation() {
+    fn test_token_conservation() {
+        let app = App::new("test-token", [0u8; 32]);
+        
+        let mut tx = Transaction::new([0u8; 32]);
+        
+        // Add input with 1000 tokens
+        tx.inputs.push(TxInput::new(
+            UtxoRef { txid: [0u8; 32], vout: 0 },
+            Some(CharmState::new().with_app("test-token", Data::U64(1000))),
+        ));
+        
+        // Add outputs totaling 1000 tokens
+        tx.outputs.push(TxOutput {
+            index: 0,
+            value: 546,
+            script_pubkey: vec![],
+            charm_state: Some(
+                CharmState::new().with_app("test-token", Data::U64(600))
+            ),
+        });
+        tx.outputs.push(TxOutput {
+            index: 1,
+            value: 546,
+            script_pubkey: vec![],
+            charm_state: Some(
+                CharmState::new().with_app("test-token", Data::U64(400))
+            ),
+        });
+        
+        let auth = Data::Bytes(vec![1, 2, 3]); // Mock authorization
+        
+        assert!(token::check(&app, &tx, &auth, &Data::Empty));
+    }
+    
+    /// Build a token transfer of `input` tokens into `outputs`, each given as
+    /// `(amount, is_treasury)`
+    fn fee_tx(tag: &str, input: u64, outputs: &[(u64, bool)]) -> Transaction {
+        let mut tx = Transaction::new([0u8; 32]);
+        tx.inputs.push(TxInput::new(
+            UtxoRef { txid: [0u8; 32], vout: 0 },
+            Some(CharmState::new().with_app(tag, Data::U64(input))),
+        ));
+        for (index, &(amount, is_treasury)) in outputs.iter().enumerate() {
+            let mut state = CharmState::new().with_app(tag, Data::U64(amount));
+            if is_treasury {
+                state = state.with_app("treasury", Data::Empty);
+            }
+            tx.outputs.push(TxOutput {
+                index: index as u32,
+                value: 546,
+                script_pubkey: vec![],
+                charm_state: Some(state),
+            });
+        }
+        tx
+    }
+    
+    #[test]
+    fn test_token_fee_within_cap() {
+        let app = App::new("test-token", [0u8; 32]);
+        let auth = Data::Bytes(vec![1, 2, 3]);
+        
+        // 30 bps of 10_000 is 30
+        let tx = fee_tx("test-token", 10_000, &[(6_000, false), (3_970, false), (30, true)]);
+        assert!(token::check_with_fee(&app, &tx, &auth, &Data::Empty, 30, "treasury"));
+        
+        // The same fee exceeds a 29 bps cap
+        assert!(!token::check_with_fee(&app, &tx, &auth, &Data::Empty, 29, "treasury"));
+    }
+    
+    #[test]
+    fn test_token_fee_over_cap_rejected() {
+        let app = App::new("test-token", [0u8; 32]);
+        let auth = Data::Bytes(vec![1, 2, 3]);
+        
+        let tx = fee_tx("test-token", 10_000, &[(9_969, false), (31, true)]);
+        assert!(!token::check_with_fee(&app, &tx, &auth, &Data::Empty, 30, "treasury"));
+        
+        // Fee within the cap, but tokens missing from the outputs
+        let tx = fee_tx("test-token", 10_000, &[(9_960, false), (30, true)]);
+        assert!(!token::check_with_fee(&app, &tx, &auth, &Data::Empty, 30, "treasury"));
+    }
+    
+    /// Pay-to-pubkey script for `key`
+    fn p2pk(key: &k256::ecdsa::SigningKey) -> Vec<u8> {
+        [&[33u8][..], &pubkey(key), &[0xac]].concat()
+    }
+    
+    /// Build a swap of `a_amount` of `token:A`, held by `signing_key(1)`, for
+    /// `b_amount` of `token:B`, held by `signing_key(2)`, with `b_paid` of B
+    /// actually reaching the A holder
+    fn swap_tx(a_amount: u64, b_amount: u64, b_paid: u64) -> Transaction {
+        let mut tx = Transaction::new([6u8; 32]);
+        for (vout, (tag, amount)) in [("token:A", a_amount), ("token:B", b_amount)].into_iter().enumerate() {
+            tx.inputs.push(TxInput {
+                script_pubkey: p2pk(&signing_key(vout as u8 + 1)),
+                ..TxInput::new(
+                    UtxoRef { txid: [1u8; 32], vout: vout as u32 },
+                    Some(CharmState::new().with_app(tag, Data::U64(amount))),
+                )
+            });
+        }
+        for (index, (tag, amount)) in [("token:B", b_paid), ("token:A", a_amount)].into_iter().enumerate() {
+            tx.outputs.push(TxOutput {
+                index: index as u32,
+                value: 546,
+                script_pubkey: vec![],
+                charm_state: Some(CharmState::new().with_app(tag, Data::U64(amount))),
+            });
+        }
+        tx
+    }
+    
+    fn swap_party(key: &k256::ecdsa::SigningKey, commitment: &[u8; 32]) -> Data {
+        let mut party = std::collections::BTreeMap::new();
+        party.insert("pubkey".to_string(), Data::Bytes(pubkey(key)));
+        party.insert("sig".to_string(), sign(key, commitment));
+        Data::Map(party)
+    }
+    
+    #[test]
+    fn test_token_atomic_swap() {
+        let (a, b) = (App::new("token:A", [0u8; 32]), App::new("token:B", [0u8; 32]));
+        let (alice, bob) = (signing_key(1), signing_key(2));
+        
+        let tx = swap_tx(1_000, 50, 50);
+        let commitment = token::swap_commitment(&tx, &a, &b);
+        let x = Data::List(vec![swap_party(&alice, &commitment), swap_party(&bob, &commitment)]);
+        assert!(token::check_atomic_swap(&tx, &a, &b, &x));
+        
+        // Commitments are bound to the asset pair
+        assert!(!token::check_atomic_swap(&tx, &b, &a, &x));
+        
+        // One party alone, or signing twice, is not a swap
+        let alone = Data::List(vec![swap_party(&alice, &commitment), swap_party(&alice, &commitment)]);
+        assert!(!token::check_atomic_swap(&tx, &a, &b, &alone));
+        assert!(!token::check_atomic_swap(&tx, &a, &b, &Data::List(vec![swap_party(&alice, &commitment)])));
+        assert!(!token::check_atomic_swap(&tx, &a, &b, &Data::Empty));
+    }
+    
+    #[test]
+    fn test_token_atomic_swap_rejected() {
+        let (a, b) = (App::new("token:A", [0u8; 32]), App::new("token:B", [0u8; 32]));
+        let (alice, bob) = (signing_key(1), signing_key(2));
+        let parties = |tx: &Transaction| {
+            let commitment = token::swap_commitment(tx, &a, &b);
+            Data::List(vec![swap_party(&alice, &commitment), swap_party(&bob, &commitment)])
+        };
+        
+        // One side short
+        let tx = swap_tx(1_000, 50, 49);
+        assert!(!token::check_atomic_swap(&tx, &a, &b, &parties(&tx)));
+        
+        // An output carrying both assets
+        let mut tx = swap_tx(1_000, 50, 50);
+        let both = CharmState::new().with_app("token:A", Data::U64(0)).with_app("token:B", Data::U64(50));
+        tx.outputs[0].charm_state = Some(both);
+        assert!(!token::check_atomic_swap(&tx, &a, &b, &parties(&tx)));
+        
+        // The same asset on both sides
+        let tx = swap_tx(1_000, 50, 50);
+        assert!(!token::check_atomic_swap(&tx, &a, &a, &parties(&tx)));
+        
+        // Signers who don't hold the swapped inputs
+        let (carol, dave) = (signing_key(3), signing_key(4));
+        let commitment = token::swap_commitment(&tx, &a, &b);
+        let strangers = Data::List(vec![swap_party(&carol, &commitment), swap_party(&dave, &commitment)]);
+        assert!(!token::check_atomic_swap(&tx, &a, &b, &strangers));
+        let crossed = Data::List(vec![swap_party(&bob, &commitment), swap_party(&alice, &commitment)]);
+        assert!(!token::check_atomic_swap(&tx, &a, &b, &crossed));
+        
+        // Redirecting an output after signing breaks the commitment
+        let x = parties(&tx);
+        let mut redirected = tx.clone();
+        redirected.outputs[0].script_pubkey = p2pk(&carol);
+        assert!(!token::check_atomic_swap(&redirected, &a, &b, &x));
+    }
+    
+    #[test]
+    fn test_signature_is_owner() {
+        use sha2::{Digest, Sha256};
+        
+        let key = signing_key(1);
+        let p2pk = p2pk(&key);
+        assert!(signature::is_owner(&pubkey(&key), &p2pk));
+        
+        let p2wsh = [&[0x00, 0x20][..], &Sha256::digest(&p2pk)[..]].concat();
+        assert!(signature::is_owner(&pubkey(&key), &p2wsh));
+        
+        assert!(!signature::is_owner(&pubkey(&signing_key(2)), &p2pk));
+        assert!(!signature::is_owner(&pubkey(&key), &[]));
+    }
+    
+    /// Build an NFT mint spending `funding` and creating `nft` in output 0
+    fn nft_mint_tx(tag: &str, funding: UtxoRef, nft: Data) -> Transaction {
+        let mut tx = Transaction::new([5u8; 32]);
+        tx.inputs.push(TxInput::new(funding, None));
+        tx.outputs.push(TxOutput {
+            index: 0,
+            value: 546,
+            script_pubkey: vec![],
+            charm_state: Some(CharmState::new().with_app(tag, nft)),
+        });
+        tx
+    }
+    
+    fn expected_mint_id(utxo_ref: &UtxoRef) -> Vec<u8> {
+        use sha2::{Digest, Sha256};
+        
+        let mut preimage = utxo_ref.txid.to_vec();
+        preimage.extend_from_slice(&utxo_ref.vout.to_le_bytes());
+        Sha256::digest(&preimage).to_vec()
+    }
+    
+    #[test]
+    fn test_nft_derive_id() {
+        let utxo = UtxoRef { txid: [0u8; 32], vout: 0 };
+        assert_eq!(
+            hex::encode(&nft::derive_id(&utxo)),
+            "6db65fd59fd356f6729140571b5bcd6bb3b83492a16e1bf0a3884442fc3c8a0e"
+        );
+        
+        let funding = UtxoRef { txid: [7u8; 32], vout: 1 };
+        assert_eq!(nft::derive_id(&funding), nft::derive_id(&funding.clone()));
+        assert_eq!(nft::derive_id(&funding).to_vec(), expected_mint_id(&funding));
+        
+        let sibling = UtxoRef { txid: [7u8; 32], vout: 2 };
+        assert_ne!(nft::derive_id(&funding), nft::derive_id(&sibling));
+    }
+    
+    fn signing_key(seed: u8) -> k256::ecdsa::SigningKey {
+        k256::ecdsa::SigningKey::from_slice(&[seed; 32]).unwrap()
+    }
+    
+    fn pubkey(key: &k256::ecdsa::SigningKey) -> Vec<u8> {
+        key.verifying_key().to_sec1_bytes().to_vec()
+    }
+    
+    fn sign(key: &k256::ecdsa::SigningKey, msg_hash: &[u8; 32]) -> Data {
+        use k256::ecdsa::{signature::hazmat::PrehashSigner, Signature};
+        
+        let sig: Signature = key.sign_prehash(msg_hash).unwrap();
+        Data::Bytes(sig.to_bytes().to_vec())
+    }
+    
+    const METADATA_HASH: [u8; 32] = [0x11; 32];
+    
+    /// Hash a creator signs to mint the NFT `id` with `METADATA_HASH`
+    fn mint_hash(id: &[u8]) -> [u8; 32] {
+        use sha2::{Digest, Sha256};
+        
+        Sha256::digest([&METADATA_HASH[..], id].concat()).into()
+    }
+    
+    fn nft_state(id: Vec<u8>, creator: Vec<u8>) -> Data {
+        let mut map = std::collections::BTreeMap::new();
+        map.insert("id".to_string(), Data::Bytes(id));
+        map.insert("metadata_hash".to_string(), Data::Bytes(METADATA_HASH.to_vec()));
+        map.insert("creator".to_string(), Data::Bytes(creator));
+        Data::Map(map)
+    }
+    
+    #[test]
+    fn test_nft_mint_with_derived_id() {
+        let app = App::new("nft:ART", [0u8; 32]);
+        let creator = signing_key(1);
+        let funding = UtxoRef { txid: [7u8; 32], vout: 1 };
+        let id = expected_mint_id(&funding);
+        
+        let tx = nft_mint_tx(&app.tag, funding, nft_state(id.clone(), pubkey(&creator)));
+        assert!(nft::check(&app, &tx, &sign(&creator, &mint_hash(&id)), &Data::Empty));
+    }
+    
+    #[test]
+    fn test_nft_mint_with_mismatched_id_rejected() {
+        let app = App::new("nft:ART", [0u8; 32]);
+        let creator = signing_key(1);
+        let funding = UtxoRef { txid: [7u8; 32], vout: 1 };
+        
+        let tx = nft_mint_tx(&app.tag, funding, nft_state(vec![0xaa; 32], pubkey(&creator)));
+        assert!(!nft::check(&app, &tx, &sign(&creator, &mint_hash(&[0xaa; 32])), &Data::Empty));
+    }
+    
+    #[test]
+    fn test_nft_mint_creator_binding() {
+        let creator = signing_key(1);
+        let other = signing_key(2);
+        let mut params = std::collections::BTreeMap::new();
+        params.insert("creator".to_string(), Data::Bytes(pubkey(&creator)));
+        let app = App::with_params("nft:ART", [0u8; 32], Data::Map(params));
+        
+        let funding = UtxoRef { txid: [7u8; 32], vout: 1 };
+        let id = expected_mint_id(&funding);
+        
+        let tx = nft_mint_tx(&app.tag, funding.clone(), nft_state(id.clone(), pubkey(&creator)));
+        assert!(nft::check(&app, &tx, &sign(&creator, &mint_hash(&id)), &Data::Empty));
+        
+        // Validly self-signed, but not the creator the app declares
+        let tx = nft_mint_tx(&app.tag, funding.clone(), nft_state(id.clone(), pubkey(&other)));
+        assert!(!nft::check(&app, &tx, &sign(&other, &mint_hash(&id)), &Data::Empty));
+        
+        // A bare id carries no creator or metadata to sign
+        let tx = nft_mint_tx(&app.tag, funding, Data::Bytes(id.clone()));
+        assert!(!nft::check(&app, &tx, &sign(&creator, &mint_hash(&id)), &Data::Empty));
+    }
+    
+    #[test]
+    fn test_nft_mint_creator_signature() {
+        let app = App::new("nft:ART", [0u8; 32]);
+        let creator = signing_key(1);
+        let funding = UtxoRef { txid: [7u8; 32], vout: 1 };
+        let id = expected_mint_id(&funding);
+        let tx = nft_mint_tx(&app.tag, funding, nft_state(id.clone(), pubkey(&creator)));
+        
+        // Correctly signed
+        assert!(nft::check(&app, &tx, &sign(&creator, &mint_hash(&id)), &Data::Empty));
+        
+        // Signed by the wrong key
+        assert!(!nft::check(&app, &tx, &sign(&signing_key(2), &mint_hash(&id)), &Data::Empty));
+        
+        // Signature over different metadata
+        assert!(!nft::check(&app, &tx, &sign(&creator, &[0x22; 32]), &Data::Empty));
+        
+        // Signature over the metadata alone, or for another id
+        assert!(!nft::check(&app, &tx, &sign(&creator, &METADATA_HASH), &Data::Empty));
+        assert!(!nft::check(&app, &tx, &sign(&creator, &mint_hash(&[0xaa; 32])), &Data::Empty));
+        
+        // Missing signature
+        assert!(!nft::check(&app, &tx, &Data::Empty, &Data::Empty));
+    }
+    
+    fn vesting_release(amount: u64) -> (App, Transaction) {
+        let schedule = token::vesting::VestingSchedule {
+            start_block: 1_000,
+            end_block: 2_000,
+            total_amount: 100_000,
+            cliff_blocks: 250,
+        };
+        let app = App::with_params("token:VEST", [0u8; 32], schedule.to_params());
+        let mut tx = Transaction::new([1u8; 32]);
+        tx.outputs.push(TxOutput {
+            index: 0,
+            value: 546,
+            script_pubkey: vec![0x00, 0x14],
+            charm_state: Some(CharmState::new().with_app("token:VEST", Data::U64(amount))),
+        });
+        (app, tx)
+    }
+    
+    #[test]
+    fn test_vesting_schedule() {
+        use token::vesting::check;
+        
+        let release = |amount: u64, block: u32| {
+            let (app, tx) = vesting_release(amount);
+            check(&app, &tx, &Data::U64(block as u64), &Data::Empty, block)
+        };
+        
+        // Before the cliff nothing is vested
+        assert!(release(0, 1_249));
+        assert!(!release(1, 1_249));
+        // At the cliff the first quarter unlocks at once
+        assert!(release(25_000, 1_250));
+        assert!(!release(25_001, 1_250));
+        // Mid-vesting
+        assert!(release(60_000, 1_600));
+        assert!(!release(60_001, 1_600));
+        // Fully vested, and no more than the total ever
+        assert!(release(100_000, 2_000));
+        assert!(release(100_000, 5_000));
+        assert!(!release(100_001, 5_000));
+    }
+    
+    #[test]
+    fn test_vesting_requires_claimed_height_and_schedule() {
+        let (app, tx) = vesting_release(60_000);
+        assert!(!token::vesting::check(&app, &tx, &Data::U64(1_700), &Data::Empty, 1_600));
+        assert!(!token::vesting::check(&app, &tx, &Data::Empty, &Data::Empty, 1_600));
+        
+        let unscheduled = App::new("token:VEST", [0u8; 32]);
+        assert!(!token::vesting::check(&unscheduled, &tx, &Data::U64(1_600), &Data::Empty, 1_600));
+        
+        // A cliff past the end of the schedule is rejected
+        let bad = token::vesting::VestingSchedule {
+            start_block: 10,
+            end_block: 20,
+            total_amount: 1,
+            cliff_blocks: 11,
+        };
+        let app = App::with_params("token:VEST", [0u8; 32], bad.to_params());
+        assert_eq!(token::vesting::VestingSchedule::from_app(&app), None);
+    }
+    
+    const AUCTION_END: u64 = 900_000;
+    
+    fn auction_state(code: u64, bid: Option<(&[u8], u64)>) -> Data {
+        let mut map = std::collections::BTreeMap::new();
+        map.insert("id".to_string(), Data::Bytes(vec![0x42; 32]));
+        map.insert("auction_state".to_string(), Data::U64(code));
+        map.insert("end_block".to_string(), Data::U64(AUCTION_END));
+        if let Some((bidder, amount)) = bid {
+            map.insert("highest_bidder".to_string(), Data::Bytes(bidder.to_vec()));
+            map.insert("highest_bid".to_string(), Data::U64(amount));
+        }
+        Data::Map(map)
+    }
+    
+    fn auction_tx(current: Option<Data>, next: Data, height: u64, recipient: &[u8]) -> Transaction {
+        let mut tx = Transaction::new([1u8; 32]);
+        tx.locktime = Some(height as u32);
+        if let Some(current) = current {
+            tx.inputs.push(TxInput::new(
+                UtxoRef { txid: [0u8; 32], vout: 0 },
+                Some(CharmState::new().with_app("nft:AUCTION", current)),
+            ));
+            tx.inputs[0].sequence = MAX_SEQUENCE_NONFINAL;
+        }
+        tx.outputs.push(TxOutput {
+            index: 0,
+            value: 546,
+            script_pubkey: recipient.to_vec(),
+            charm_state: Some(CharmState::new().with_app("nft:AUCTION", next)),
+        });
+        tx
+    }
+    
+    #[test]
+    fn test_nft_auction_outbid_and_settle() {
+        use nft::auction::{try_check, try_check_at_height};
+        
+        let app = App::new("nft:AUCTION", [0u8; 32]);
+        let (alice, bob) = (b"alice".as_slice(), b"bob".as_slice());
+        let before_end = AUCTION_END - 1;
+        let steps = [
+            (None, auction_state(0, None), before_end, b"seller".as_slice()),
+            (Some(auction_state(0, None)), auction_state(1, Some((alice, 1_000))), before_end, b"seller"),
+            // Exactly 5% more outbids
+            (Some(auction_state(1, Some((alice, 1_000)))), auction_state(1, Some((bob, 1_050))), before_end, b"seller"),
+            (Some(auction_state(1, Some((bob, 1_050)))), auction_state(2, Some((bob, 1_050))), AUCTION_END, b"seller"),
+            (Some(auction_state(2, Some((bob, 1_050)))), auction_state(3, None), AUCTION_END, bob),
+        ];
+        for (current, next, height, recipient) in steps {
+            let tx = auction_tx(current, next, height, recipient);
+            assert_eq!(try_check_at_height(&app, &tx, &Data::Empty, &Data::Empty, height as u32), Ok(()));
+        }
+        
+        // Settling must deliver the NFT to the winner
+        let tx = auction_tx(Some(auction_state(2, Some((bob, 1_050)))), auction_state(3, None), AUCTION_END, alice);
+        assert_eq!(
+            try_check(&app, &tx, &Data::Empty, &Data::Empty),
+            Err(CheckError::UndeliveredAsset("nft:AUCTION".to_string()))
+        );
+        
+        // Closing keeps the winning bid and waits for the end block
+        let closing = Some(auction_state(1, Some((bob, 1_050))));
+        let tx = auction_tx(closing.clone(), auction_state(2, Some((alice, 1_050))), AUCTION_END, b"seller");
+        assert_eq!(try_check(&app, &tx, &Data::Empty, &Data::Empty), Err(CheckError::InvalidTransition));
+        let tx = auction_tx(closing, auction_state(2, Some((bob, 1_050))), before_end, b"seller");
+        assert_eq!(
+            try_check(&app, &tx, &Data::Empty, &Data::Empty),
+            Err(CheckError::TimeoutNotReached { height: before_end as u32, timeout: AUCTION_END as u32 })
+        );
+    }
+    
+    #[test]
+    fn test_nft_auction_rejects_lowball_and_late_bids() {
+        use nft::auction::{min_next_bid, try_check, try_check_at_height};
+        
+        let app = App::new("nft:AUCTION", [0u8; 32]);
+        let leading = auction_state(1, Some((b"alice", 1_000)));
+        let before_end = AUCTION_END as u32 - 1;
+        
+        let lowball = auction_state(1, Some((b"bob", 1_049)));
+        let lowball = auction_tx(Some(leading.clone()), lowball, AUCTION_END - 1, b"seller");
+        assert_eq!(
+            try_check_at_height(&app, &lowball, &Data::Empty, &Data::Empty, before_end),
+            Err(CheckError::AmountMismatch { expected: 1_050, actual: 1_049 })
+        );
+        
+        let bid = auction_state(1, Some((b"bob", 2_000)));
+        let late = auction_tx(Some(leading.clone()), bid.clone(), AUCTION_END, b"seller");
+        assert_eq!(
+            try_check_at_height(&app, &late, &Data::Empty, &Data::Empty, AUCTION_END as u32),
+            Err(CheckError::InvalidTransition)
+        );
+        
+        // A low locktime doesn't get a bid mined after the end accepted
+        let backdated = auction_tx(Some(leading), bid, AUCTION_END - 1, b"seller");
+        assert_eq!(
+            try_check_at_height(&app, &backdated, &Data::Empty, &Data::Empty, AUCTION_END as u32 + 10),
+            Err(CheckError::InvalidTransition)
+        );
+        // Without an attested height, no bid is accepted at all
+        assert_eq!(try_check(&app, &backdated, &Data::Empty, &Data::Empty), Err(CheckError::InvalidTransition));
+        
+        // Tiny bids still have to rise
+        assert_eq!(min_next_bid(1), 2);
+        assert_eq!(min_next_bid(u64::MAX), u64::MAX);
+        
+        // No settling an auction nobody bid on
+        let unsold = auction_tx(Some(auction_state(2, None)), auction_state(3, None), AUCTION_END, b"seller");
+        assert_eq!(
+            try_check(&app, &unsold, &Data::Empty, &Data::Empty),
+            Err(CheckError::MissingField("highest_bidder"))
+        );
+    }
+    
+    fn collection_app(max_size: u64) -> App {
+        let mut params = std::collections::BTreeMap::new();
+        params.insert("collection_max_size".to_string(), Data::U64(max_size));
+        App::with_params("nft:ART", [0u8; 32], Data::Map(params))
+    }
+    
+    #[test]
+    fn test_nft_collection_cap() {
+        let app = collection_app(10);
+        let creator = signing_key(1);
+        let funding = UtxoRef { txid: [7u8; 32], vout: 1 };
+        let id = expected_mint_id(&funding);
+        let tx = nft_mint_tx(&app.tag, funding, nft_state(id.clone(), pubkey(&creator)));
+        let sig = sign(&creator, &mint_hash(&id));
+        
+        assert_eq!(nft::minted_ids(&app, &tx), vec![id]);
+        
+        // Minting the last slot
+        assert!(nft::check_collection(&app, &tx, &sig, &Data::Empty, 9));
+        // Beyond the cap
+        assert!(!nft::check_collection(&app, &tx, &sig, &Data::Empty, 10));
+        
+        // An invalid mint fails regardless of room in the collection
+        assert!(!nft::check_collection(&app, &tx, &Data::Empty, &Data::Empty, 0));
+        
+        // No cap
+        let uncapped = App::new("nft:ART", [0u8; 32]);
+        assert!(nft::check_collection(&uncapped, &tx, &sig, &Data::Empty, u32::MAX));
+    }
+    
+    #[test]
+    fn test_nft_transfers_do_not_grow_collection() {
+        let app = collection_app(10);
+        let nft = nft_state(vec![0x99; 32], pubkey(&signing_key(1)));
+        let tx = nft_sale_tx(&app.tag, nft, None);
+        
+        assert!(nft::minted_ids(&app, &tx).is_empty());
+        assert!(nft::check_collection(&app, &tx, &Data::Empty, &Data::Empty, 10));
+    }
+    
+    #[test]
+    fn test_nft_transfer_needs_no_derivation() {
+        let app = App::new("nft:ART", [0u8; 32]);
+        let id = Data::Bytes(vec![0x99; 32]);
+        
+        let mut tx = Transaction::new([6u8; 32]);
+        tx.inputs.push(TxInput::new(
+            UtxoRef { txid: [8u8; 32], vout: 0 },
+            Some(CharmState::new().with_app(app.tag.as_str(), id.clone())),
+        ));
+        tx.outputs.push(TxOutput {
+            index: 0,
+            value: 546,
+            script_pubkey: vec![],
+            charm_state: Some(CharmState::new().with_app(app.tag.as_str(), id)),
+        });
+        
+        assert!(nft::check(&app, &tx, &Data::Empty, &Data::Empty));
+    }
+    
+    fn royalty_nft_state(id: Vec<u8>, creator: Vec<u8>, bps: u64, recipient: &[u8]) -> Data {
+        let Data::Map(mut map) = nft_state(id, creator) else {
+            unreachable!()
+        };
+        map.insert("royalty_bps".to_string(), Data::U64(bps));
+        map.insert("royalty_recipient".to_string(), Data::Bytes(recipient.to_vec()));
+        Data::Map(map)
+    }
+    
+    /// Transfer `nft` to a buyer, paying `royalty` to `recipient` if given
+    fn nft_sale_tx(tag: &str, nft: Data, royalty: Option<(&[u8], u64)>) -> Transaction {
+        let mut tx = Transaction::new([6u8; 32]);
+        tx.inputs.push(TxInput::new(
+            UtxoRef { txid: [8u8; 32], vout: 0 },
+            Some(CharmState::new().with_app(tag, nft.clone())),
+        ));
+        tx.outputs.push(TxOutput {
+            index: 0,
+            value: 546,
+            script_pubkey: b"buyer".to_vec(),
+            charm_state: Some(CharmState::new().with_app(tag, nft.clone())),
+        });
+        if let Some((recipient, amount)) = royalty {
+            tx.outputs.push(TxOutput {
+                index: 1,
+                value: amount,
+                script_pubkey: recipient.to_vec(),
+                charm_state: None,
+            });
+        }
+        tx
+    }
+    
+    #[test]
+    fn test_nft_royalty_enforced_on_transfer() {
+        let app = App::new("nft:ART", [0u8; 32]);
+        let creator = pubkey(&signing_key(1));
+        let nft = royalty_nft_state(vec![0x99; 32], creator, 250, b"artist");
+        let price = Data::U64(1_000_000);
+        
+        // 2.5% of 1,000,000 sats
+        let tx = nft_sale_tx(&app.tag, nft.clone(), Some((b"artist", 25_000)));
+        assert!(nft::check(&app, &tx, &Data::Empty, &price));
+        
+        let tx = nft_sale_tx(&app.tag, nft.clone(), Some((b"artist", 24_999)));
+        assert_eq!(
+            nft::try_check(&app, &tx, &Data::Empty, &price),
+            Err(CheckError::RoyaltyUnpaid { required: 25_000, paid: 24_999 })
+        );
+        
+        // Paid to someone other than the royalty recipient
+        let tx = nft_sale_tx(&app.tag, nft.clone(), Some((b"seller", 25_000)));
+        assert_eq!(
+            nft::try_check(&app, &tx, &Data::Empty, &price),
+            Err(CheckError::RoyaltyUnpaid { required: 25_000, paid: 0 })
+        );
+        
+        let tx = nft_sale_tx(&app.tag, nft.clone(), None);
+        assert!(!nft::check(&app, &tx, &Data::Empty, &price));
+        assert_eq!(
+            nft::try_check(&app, &tx, &Data::Empty, &Data::Empty),
+            Err(CheckError::MissingField("sale_price"))
+        );
+        
+        // A charm state merely claiming the royalty pays nothing
+        let mut tx = nft_sale_tx(&app.tag, nft.clone(), Some((b"artist", 0)));
+        tx.outputs[1].charm_state = Some(CharmState::new().with_app("token:BTC", Data::U64(25_000)));
+        assert_eq!(
+            nft::try_check(&app, &tx, &Data::Empty, &price),
+            Err(CheckError::RoyaltyUnpaid { required: 25_000, paid: 0 })
+        );
+    }
+    
+    #[test]
+    fn test_nft_royalty_two_nfts_one_recipient() {
+        let app = App::new("nft:ART", [0u8; 32]);
+        let creator = pubkey(&signing_key(1));
+        let first = royalty_nft_state(vec![0x99; 32], creator.clone(), 250, b"artist");
+        let second = royalty_nft_state(vec![0x98; 32], creator, 250, b"artist");
+        let price = Data::U64(1_000_000);
+        
+        // Both NFTs sold in one transaction, with a single 25,000 sat royalty
+        let mut tx = nft_sale_tx(&app.tag, first, Some((b"artist", 25_000)));
+        tx.inputs.push(TxInput::new(
+            UtxoRef { txid: [8u8; 32], vout: 1 },
+            Some(CharmState::new().with_app(app.tag.as_str(), second.clone())),
+        ));
+        tx.outputs.push(TxOutput {
+            index: 2,
+            value: 546,
+            script_pubkey: b"buyer".to_vec(),
+            charm_state: Some(CharmState::new().with_app(app.tag.as_str(), second)),
+        });
+        assert_eq!(
+            nft::try_check(&app, &tx, &Data::Empty, &price),
+            Err(CheckError::RoyaltyUnpaid { required: 50_000, paid: 25_000 })
+        );
+        
+        // A second payment covers both
+        tx.outputs.push(TxOutput {
+            index: 3,
+            value: 25_000,
+            script_pubkey: b"artist".to_vec(),
+            charm_state: None,
+        });
+        assert_eq!(nft::try_check(&app, &tx, &Data::Empty, &price), Ok(()));
+    }
+    
+    #[test]
+    fn test_nft_royalty_terms_carry_over() {
+        let app = App::new("nft:ART", [0u8; 32]);
+        let creator = pubkey(&signing_key(1));
+        let nft = royalty_nft_state(vec![0x99; 32], creator.clone(), 250, b"artist");
+        let price = Data::U64(1_000_000);
+        
+        // Dropping the terms, or lowering the royalty, on the way out
+        for next in [
+            Data::Bytes(vec![0x99; 32]),
+            nft_state(vec![0x99; 32], creator.clone()),
+            royalty_nft_state(vec![0x99; 32], creator.clone(), 100, b"artist"),
+            royalty_nft_state(vec![0x99; 32], creator.clone(), 250, b"buyer"),
+        ] {
+            let mut tx = nft_sale_tx(&app.tag, nft.clone(), Some((b"artist", 25_000)));
+            tx.outputs[0].charm_state = Some(CharmState::new().with_app(app.tag.as_str(), next));
+            assert_eq!(
+                nft::try_check(&app, &tx, &Data::Empty, &price),
+                Err(CheckError::InvalidTransition)
+            );
+        }
+    }
+    
+    fn soulbound_app(burn_address: Option<&[u8]>) -> App {
+        let mut params = std::collections::BTreeMap::new();
+        params.insert("soulbound".to_string(), Data::Bool(true));
+        if let Some(burn_address) = burn_address {
+            params.insert("burn_address".to_string(), Data::Bytes(burn_address.to_vec()));
+        }
+        App::with_params("nft:BADGE", [0u8; 32], Data::Map(params))
+    }
+    
+    /// Move NFT `0x99..` from a UTXO locked to `owner` into one locked to `to`
+    fn soulbound_move_tx(tag: &str, owner: &[u8], to: Option<&[u8]>) -> Transaction {
+        let id = Data::Bytes(vec![0x99; 32]);
+        let mut tx = Transaction::new([6u8; 32]);
+        tx.inputs.push(TxInput {
+            script_pubkey: owner.to_vec(),
+            ..TxInput::new(
+                UtxoRef { txid: [8u8; 32], vout: 0 },
+                Some(CharmState::new().with_app(tag, id.clone())),
+            )
+        });
+        if let Some(to) = to {
+            tx.outputs.push(TxOutput {
+                index: 0,
+                value: 546,
+                script_pubkey: to.to_vec(),
+                charm_state: Some(CharmState::new().with_app(tag, id)),
+            });
+        }
+        tx
+    }
+    
+    #[test]
+    fn test_nft_soulbound_transfer_rejected() {
+        let app = soulbound_app(None);
+        let tx = soulbound_move_tx(&app.tag, b"owner", Some(b"buyer"));
+        
+        assert!(nft::check(&app, &tx, &Data::Empty, &Data::Empty));
+        assert!(!nft::check_soulbound(&app, &tx, &Data::Empty, &Data::Empty));
+        
+        // Without the flag the same transfer is fine
+        let transferable = App::new(app.tag.as_str(), [0u8; 32]);
+        assert!(nft::check_soulbound(&transferable, &tx, &Data::Empty, &Data::Empty));
+    }
+    
+    #[test]
+    fn test_nft_soulbound_burn_and_hold() {
+        let app = soulbound_app(None);
+        
+        // Spent without reappearing
+        let tx = soulbound_move_tx(&app.tag, b"owner", None);
+        assert!(nft::check_soulbound(&app, &tx, &Data::Empty, &Data::Empty));
+        // Re-locked to its owner
+        let tx = soulbound_move_tx(&app.tag, b"owner", Some(b"owner"));
+        assert!(nft::check_soulbound(&app, &tx, &Data::Empty, &Data::Empty));
+        // Sent to a provably unspendable script
+        let tx = soulbound_move_tx(&app.tag, b"owner", Some(&[burn::OP_RETURN]));
+        assert!(nft::check_soulbound(&app, &tx, &Data::Empty, &Data::Empty));
+        
+        // A configured burn address replaces the default
+        let app = soulbound_app(Some(b"graveyard"));
+        let tx = soulbound_move_tx(&app.tag, b"owner", Some(b"graveyard"));
+        assert!(nft::check_soulbound(&app, &tx, &Data::Empty, &Data::Empty));
+        let tx = soulbound_move_tx(&app.tag, b"owner", Some(&[burn::OP_RETURN]));
+        assert!(!nft::check_soulbound(&app, &tx, &Data::Empty, &Data::Empty));
+    }
+    
+    #[test]
+    fn test_nft_royalty_not_required_on_mint() {
+        let app = App::new("nft:ART", [0u8; 32]);
+        let creator = signing_key(1);
+        let funding = UtxoRef { txid: [7u8; 32], vout: 1 };
+        let id = expected_mint_id(&funding);
+        let state = royalty_nft_state(id.clone(), pubkey(&creator), 500, b"artist");
+        
+        let tx = nft_mint_tx(&app.tag, funding, state);
+        assert!(nft::check(&app, &tx, &sign(&creator, &mint_hash(&id)), &Data::U64(1_000_000)));
+    }
+    
+    #[test]
+    fn test_nft_without_royalty_transfers_freely() {
+        let app = App::new("nft:ART", [0u8; 32]);
+        let nft = nft_state(vec![0x99; 32], pubkey(&signing_key(1)));
+        let tx = nft_sale_tx(&app.tag, nft, None);
+        assert!(nft::check(&app, &tx, &Data::Empty, &Data::Empty));
+    }
+    
+    #[test]
+    fn test_effective_balance_with_subaccounts() {
+        let mut subaccounts = std::collections::BTreeMap::new();
+        subaccounts.insert("savings".to_string(), Data::U64(250));
+        subaccounts.insert("trading".to_string(), Data::U64(150));
+        
+        let mut state = std::collections::BTreeMap::new();
+        state.insert("amount".to_string(), Data::U64(600));
+        state.insert("subaccounts".to_string(), Data::Map(subaccounts));
+        
+        assert_eq!(token::effective_balance(&Data::Map(state)), Some(1000));
+        assert_eq!(token::effective_balance(&Data::U64(42)), Some(42));
+        assert_eq!(token::effective_balance(&Data::Empty), Some(0));
+    }
+    
+    #[test]
+    fn test_effective_balance_overflow_rejected() {
+        let app = App::new("token:BTC", [0u8; 32]);
+        
+        let mut subaccounts = std::collections::BTreeMap::new();
+        subaccounts.insert("savings".to_string(), Data::U64(u64::MAX));
+        let mut state = std::collections::BTreeMap::new();
+        state.insert("amount".to_string(), Data::U64(1));
+        state.insert("subaccounts".to_string(), Data::Map(subaccounts));
+        let state = Data::Map(state);
+        assert_eq!(token::effective_balance(&state), None);
+        
+        let mut tx = Transaction::new([0u8; 32]);
+        tx.inputs.push(TxInput::new(
+            UtxoRef { txid: [1u8; 32], vout: 0 },
+            Some(CharmState::new().with_app(&app.tag, state)),
+        ));
+        tx.outputs.push(TxOutput {
+            index: 0,
+            value: 546,
+            script_pubkey: vec![],
+            charm_state: Some(CharmState::new().with_app(&app.tag, Data::U64(u64::MAX))),
+        });
+        assert_eq!(token::try_check(&app, &tx, &Data::Bytes(vec![1]), &Data::Empty), Err(CheckError::Overflow));
+    }
+    
+    #[test]
+    fn test_token_conservation_across_subaccounts() {
+        let app = App::new("test-token", [0u8; 32]);
+        let mut tx = Transaction::new([0u8; 32]);
+        
+        let mut subaccounts = std::collections::BTreeMap::new();
+        subaccounts.insert("a".to_string(), Data::U64(300));
+        subaccounts.insert("b".to_string(), Data::U64(200));
+        let mut state = std::collections::BTreeMap::new();
+        state.insert("amount".to_string(), Data::U64(500));
+        state.insert("subaccounts".to_string(), Data::Map(subaccounts));
+        
+        tx.inputs.push(TxInput::new(
+            UtxoRef { txid: [0u8; 32], vout: 0 },
+            Some(CharmState::new().with_app("test-token", Data::Map(state))),
+        ));
+        tx.outputs.push(TxOutput {
+            index: 0,
+            value: 546,
+            script_pubkey: vec![],
+            charm_state: Some(CharmState::new().with_app("test-token", Data::U64(1000))),
+        });
+        
+        assert!(token::check(&app, &tx, &Data::Bytes(vec![1]), &Data::Empty));
+    }
+    
+    #[test]
+    fn test_escrow_lifecycle_helper() {
+        let app = App::new("escrow:LIFECYCLE", [0u8; 32]);
+        
+        let txs = testutil::escrow_lifecycle(&app);
+        assert_eq!(txs.len(), 3);
+        assert!(txs[0].inputs.is_empty());
+        assert_eq!(txs[2].inputs[0].utxo_ref.txid, txs[1].txid);
+    }
+    
+    #[test]
+    fn test_escrow_lifecycle_broken_chain_caught() {
+        use escrow::EscrowState;
+        
+        let app = App::new("escrow:LIFECYCLE", [0u8; 32]);
+        
+        // Skips funding: Created -> Released
+        let txs = testutil::escrow_chain(&app, &[EscrowState::Created, EscrowState::Released]);
+        assert_eq!(testutil::first_invalid(&app, &txs), Some(1));
+    }
+    
+    /// Escrow state with raw state `code`, recording a funded amount of 100,000 sats
+    fn escrow_state(code: u64) -> Data {
+        let mut escrow = std::collections::BTreeMap::new();
+        escrow.insert("state".to_string(), Data::U64(code));
+        escrow.insert("amount".to_string(), Data::U64(100_000));
+        Data::Map(escrow)
+    }
+    
+    /// Build an escrow transaction moving from `current` to `next` (raw state
+    /// codes), holding 100,000 sats throughout
+    fn escrow_tx(tag: &str, current: Option<u64>, next: u64) -> Transaction {
+        let mut tx = Transaction::new([0u8; 32]);
+        
+        if let Some(state) = current {
+            tx.inputs.push(TxInput::new(
+                UtxoRef { txid: [0u8; 32], vout: 0 },
+                Some(CharmState::new().with_app(tag, escrow_state(state))),
+            ));
+        }
+        
+        tx.outputs.push(TxOutput {
+            index: 0,
+            value: 100_000,
+            script_pubkey: vec![],
+            charm_state: Some(CharmState::new().with_app(tag, escrow_state(next))),
+        });
+        
+        tx
+    }
+    
+    /// Build a Disputed -> Refunded transaction for an escrow timing out at `timeout`
+    fn escrow_refund_tx(tag: &str, timeout: u64, locktime: Option<u32>) -> Transaction {
+        let mut escrow = std::collections::BTreeMap::new();
+        escrow.insert("state".to_string(), Data::U64(escrow::EscrowState::Disputed.code()));
+        escrow.insert("amount".to_string(), Data::U64(100_000));
+        escrow.insert("timeout_height".to_string(), Data::U64(timeout));
+        
+        let mut tx = escrow_tx(tag, None, escrow::EscrowState::Refunded.code());
+        tx.inputs.push(TxInput::new(
+            UtxoRef { txid: [0u8; 32], vout: 0 },
+            Some(CharmState::new().with_app(tag, Data::Map(escrow))),
+        ));
+        tx.inputs[0].sequence = MAX_SEQUENCE_NONFINAL;
+        tx.locktime = locktime;
+        tx
+    }
+    
+    #[test]
+    fn test_escrow_refund_timeout() {
+        let app = App::new("escrow:TIMEOUT", [0u8; 32]);
+        
+        let tx = escrow_refund_tx(&app.tag, 850_000, Some(850_000));
+        assert!(escrow::check(&app, &tx, &Data::Empty, &Data::Empty));
+        
+        let tx = escrow_refund_tx(&app.tag, 850_000, Some(849_999));
+        assert_eq!(
+            escrow::try_check(&app, &tx, &Data::Empty, &Data::Empty),
+            Err(CheckError::TimeoutNotReached { height: 849_999, timeout: 850_000 })
+        );
+        
+        // No height, or an implausible one, cannot satisfy a timeout
+        for locktime in [None, Some(0), Some(1_700_000_000)] {
+            let tx = escrow_refund_tx(&app.tag, 850_000, locktime);
+            assert_eq!(
+                escrow::try_check(&app, &tx, &Data::Empty, &Data::Empty),
+                Err(CheckError::MissingField("locktime"))
+            );
+        }
+        
+        // Bitcoin ignores the locktime once every input is final
+        let mut tx = escrow_refund_tx(&app.tag, 850_000, Some(850_000));
+        tx.inputs[0].sequence = SEQUENCE_FINAL;
+        assert_eq!(
+            escrow::try_check(&app, &tx, &Data::Empty, &Data::Empty),
+            Err(CheckError::MissingField("locktime"))
+        );
+        
+        // Without a timeout the refund needs no height
+        let tx = escrow_tx(&app.tag, Some(escrow::EscrowState::Disputed.code()), escrow::EscrowState::Refunded.code());
+        assert!(escrow::check(&app, &tx, &Data::Empty, &Data::Empty));
+    }
+    
+    /// Build a Funded -> Refunded transaction for an escrow timing out at `timeout`
+    fn escrow_timeout_refund_tx(tag: &str, timeout: Option<u64>) -> Transaction {
+        let mut escrow = std::collections::BTreeMap::new();
+        escrow.insert("state".to_string(), Data::U64(escrow::EscrowState::Funded.code()));
+        escrow.insert("amount".to_string(), Data::U64(100_000));
+        if let Some(timeout) = timeout {
+            escrow.insert("timeout_height".to_string(), Data::U64(timeout));
+        }
+        
+        let mut tx = escrow_tx(tag, None, escrow::EscrowState::Refunded.code());
+        tx.inputs.push(TxInput::new(
+            UtxoRef { txid: [0u8; 32], vout: 0 },
+            Some(CharmState::new().with_app(tag, Data::Map(escrow))),
+        ));
+        tx
+    }
+    
+    #[test]
+    fn test_escrow_funded_refund_after_deadline() {
+        let app = App::new("escrow:STUCK", [0u8; 32]);
+        let tx = escrow_timeout_refund_tx(&app.tag, Some(850_000));
+        
+        assert!(escrow::check_at_height(&app, &tx, &Data::Empty, &Data::Empty, 850_000));
+        assert!(escrow::check_at_height(&app, &tx, &Data::Empty, &Data::Empty, 900_000));
+    }
+    
+    #[test]
+    fn test_escrow_funded_refund_before_deadline_rejected() {
+        let app = App::new("escrow:STUCK", [0u8; 32]);
+        let tx = escrow_timeout_refund_tx(&app.tag, Some(850_000));
+        
+        assert_eq!(
+            escrow::try_check_at_height(&app, &tx, &Data::Empty, &Data::Empty, 849_999),
+            Err(CheckError::TimeoutNotReached { height: 849_999, timeout: 850_000 })
+        );
+        
+        // Without a height the direct refund stays forbidden
+        assert_eq!(
+            escrow::try_check(&app, &tx, &Data::Empty, &Data::Empty),
+            Err(CheckError::InvalidTransition)
+        );
+        
+        // As does a refund of an escrow without a deadline
+        let tx = escrow_timeout_refund_tx(&app.tag, None);
+        assert_eq!(
+            escrow::try_check_at_height(&app, &tx, &Data::Empty, &Data::Empty, 900_000),
+            Err(CheckError::MissingField("timeout_height"))
+        );
+    }
+    
+    /// Escrow state in map form recording the funded amount
+    fn escrow_with_amount(state: escrow::EscrowState, amount: u64) -> Data {
+        let mut escrow = std::collections::BTreeMap::new();
+        escrow.insert("state".to_string(), Data::U64(state.code()));
+        escrow.insert("amount".to_string(), Data::U64(amount));
+        Data::Map(escrow)
+    }
+    
+    /// Build a transaction moving an escrow from `current` to `next`, with
+    /// the next state in an output holding `value` sats
+    fn escrow_amount_tx(tag: &str, current: Data, next: Data, value: u64) -> Transaction {
+        let mut tx = Transaction::new([5u8; 32]);
+        tx.inputs.push(TxInput::new(
+            UtxoRef { txid: [0u8; 32], vout: 0 },
+            Some(CharmState::new().with_app(tag, current)),
+        ));
+        tx.outputs.push(TxOutput {
+            index: 0,
+            value,
+            script_pubkey: vec![],
+            charm_state: Some(CharmState::new().with_app(tag, next)),
+        });
+        tx
+    }
+    
+    #[test]
+    fn test_escrow_amount_release() {
+        use escrow::EscrowState;
+        
+        let app = App::new("escrow:AMOUNT", [0u8; 32]);
+        let funded = escrow_with_amount(EscrowState::Funded, 100_000);
+        
+        // Funding records the locked amount
+        let tx = escrow_amount_tx(&app.tag, Data::U64(EscrowState::Created.code()), funded.clone(), 100_000);
+        assert_eq!(escrow::try_check(&app, &tx, &Data::Empty, &Data::Empty), Ok(()));
+        let tx = escrow_amount_tx(&app.tag, Data::U64(EscrowState::Created.code()), funded.clone(), 99_999);
+        assert_eq!(
+            escrow::try_check(&app, &tx, &Data::Empty, &Data::Empty),
+            Err(CheckError::AmountMismatch { expected: 100_000, actual: 99_999 })
+        );
+        
+        let released = Data::U64(EscrowState::Released.code());
+        let tx = escrow_amount_tx(&app.tag, funded.clone(), released.clone(), 100_000);
+        assert_eq!(escrow::try_check(&app, &tx, &Data::Empty, &Data::Empty), Ok(()));
+        
+        // Releasing more than was funded
+        let tx = escrow_amount_tx(&app.tag, funded, released, 150_000);
+        assert_eq!(
+            escrow::try_check(&app, &tx, &Data::Empty, &Data::Empty),
+            Err(CheckError::AmountMismatch { expected: 100_000, actual: 150_000 })
+        );
+    }
+    
+    #[test]
+    fn test_escrow_amount_through_dispute() {
+        use escrow::EscrowState;
+        
+        let app = App::new("escrow:AMOUNT", [0u8; 32]);
+        let funded = escrow_with_amount(EscrowState::Funded, 100_000);
+        
+        let disputed = escrow_with_amount(EscrowState::Disputed, 100_000);
+        let tx = escrow_amount_tx(&app.tag, funded.clone(), disputed.clone(), 546);
+        assert_eq!(escrow::try_check(&app, &tx, &Data::Empty, &Data::Empty), Ok(()));
+        
+        // The amount cannot be dropped or changed on the way
+        let tx = escrow_amount_tx(&app.tag, funded.clone(), Data::U64(EscrowState::Disputed.code()), 546);
+        assert_eq!(
+            escrow::try_check(&app, &tx, &Data::Empty, &Data::Empty),
+            Err(CheckError::MissingField("amount"))
+        );
+        let inflated = escrow_with_amount(EscrowState::Disputed, 200_000);
+        let tx = escrow_amount_tx(&app.tag, funded, inflated, 546);
+        assert!(!escrow::check(&app, &tx, &Data::Empty, &Data::Empty));
+        
+        let refunded = Data::U64(EscrowState::Refunded.code());
+        let tx = escrow_amount_tx(&app.tag, disputed.clone(), refunded.clone(), 100_000);
+        assert_eq!(escrow::try_check(&app, &tx, &Data::Empty, &Data::Empty), Ok(()));
+        let tx = escrow_amount_tx(&app.tag, disputed.clone(), refunded.clone(), 100_001);
+        assert!(!escrow::check(&app, &tx, &Data::Empty, &Data::Empty));
+        
+        // Summing the payout outputs cannot wrap around to the funded amount
+        let mut tx = escrow_amount_tx(&app.tag, disputed, refunded.clone(), u64::MAX);
+        tx.outputs.push(TxOutput {
+            index: 1,
+            value: 100_001,
+            script_pubkey: vec![],
+            charm_state: Some(CharmState::new().with_app(&app.tag, refunded)),
+        });
+        assert_eq!(
+            escrow::try_check(&app, &tx, &Data::Empty, &Data::Empty),
+            Err(CheckError::Overflow)
+        );
+    }
+    
+    fn escrow_params() -> escrow::EscrowParams {
+        escrow::EscrowParams {
+            buyer: b"buyer".to_vec(),
+            seller: b"seller".to_vec(),
+            arbiter: None,
+            amount_sats: 100_000,
+            timeout_blocks: 0,
+            co_signers: Vec::new(),
+            required_sigs: 0,
+            milestones: None,
+        }
+    }
+    
+    #[test]
+    fn test_escrow_params_round_trip() {
+        let params = escrow_params();
+        assert_eq!(escrow::EscrowParams::from_params(&params.to_params()), Some(params.clone()));
+        
+        let with_arbiter = escrow::EscrowParams { arbiter: Some(b"arbiter".to_vec()), ..params.clone() };
+        assert_eq!(escrow::EscrowParams::from_params(&with_arbiter.to_params()), Some(with_arbiter));
+        
+        let multisig = escrow::EscrowParams {
+            co_signers: vec![vec![2; 33], vec![3; 33]],
+            required_sigs: 2,
+            ..params
+        };
+        assert_eq!(escrow::EscrowParams::from_params(&multisig.to_params()), Some(multisig.clone()));
+        
+        // One key listed twice would sign twice
+        let duplicated = escrow::EscrowParams { co_signers: vec![vec![2; 33], vec![2; 33]], ..multisig.clone() };
+        assert_eq!(escrow::EscrowParams::from_params(&duplicated.to_params()), None);
+        
+        // More signatures than co-signers can never be collected
+        let unreachable = escrow::EscrowParams { required_sigs: 3, ..multisig };
+        assert_eq!(escrow::EscrowParams::from_params(&unreachable.to_params()), None);
+        
+        let staged = escrow::EscrowParams { milestones: Some(three_milestones()), ..escrow_params() };
+        assert_eq!(escrow::EscrowParams::from_params(&staged.to_params()), Some(staged));
+        
+        let timed = escrow::EscrowParams { timeout_blocks: 144, ..escrow_params() };
+        assert_eq!(escrow::EscrowParams::from_params(&timed.to_params()), Some(timed));
+        
+        assert_eq!(escrow::EscrowParams::from_params(&Data::Bytes(vec![0xff])), None);
+    }
+    
+    #[test]
+    fn test_escrow_funded_amount() {
+        use escrow::EscrowState;
+        
+        let app = App::with_params("escrow:TERMS", [0u8; 32], escrow_params().to_params());
+        
+        let mut tx = escrow_tx(&app.tag, Some(EscrowState::Created.code()), EscrowState::Funded.code());
+        assert!(escrow::check(&app, &tx, &Data::Empty, &Data::Empty));
+        
+        tx.outputs[0].value = 90_000;
+        assert_eq!(
+            escrow::try_check(&app, &tx, &Data::Empty, &Data::Empty),
+            Err(CheckError::AmountMismatch { expected: 100_000, actual: 90_000 })
+        );
+        
+        // Overfunding is just as wrong
+        tx.outputs[0].value = 100_001;
+        assert!(!escrow::check(&app, &tx, &Data::Empty, &Data::Empty));
+    }
+    
+    #[test]
+    fn test_escrow_funded_requires_amount() {
+        use escrow::EscrowState;
+        
+        let app = App::new("escrow:UNRECORDED", [0u8; 32]);
+        
+        // Funding without recording the amount
+        let tx = escrow_amount_tx(&app.tag, escrow_state(EscrowState::Created.code()), Data::U64(EscrowState::Funded.code()), 100_000);
+        assert_eq!(
+            escrow::try_check(&app, &tx, &Data::Empty, &Data::Empty),
+            Err(CheckError::MissingField("amount"))
+        );
+        
+        // A funded escrow without an amount can't shrink unnoticed
+        let tx = escrow_amount_tx(&app.tag, Data::U64(EscrowState::Funded.code()), escrow_state(EscrowState::Disputed.code()), 1);
+        assert_eq!(
+            escrow::try_check(&app, &tx, &Data::Empty, &Data::Empty),
+            Err(CheckError::MissingField("amount"))
+        );
+    }
+    
+    #[test]
+    fn test_escrow_payout_parties() {
+        use escrow::EscrowState;
+        
+        let app = App::with_params("escrow:TERMS", [0u8; 32], escrow_params().to_params());
+        
+        let mut release = escrow_tx(&app.tag, Some(EscrowState::Funded.code()), EscrowState::Released.code());
+        release.outputs[0].script_pubkey = b"seller".to_vec();
+        assert!(escrow::check(&app, &release, &Data::Empty, &Data::Empty));
+        
+        // Released to the buyer instead of the seller
+        release.outputs[0].script_pubkey = b"buyer".to_vec();
+        assert_eq!(
+            escrow::try_check(&app, &release, &Data::Empty, &Data::Empty),
+            Err(CheckError::AmountMismatch { expected: 100_000, actual: 0 })
+        );
+        
+        let mut refund = escrow_tx(&app.tag, Some(EscrowState::Disputed.code()), EscrowState::Refunded.code());
+        refund.outputs[0].script_pubkey = b"buyer".to_vec();
+        assert!(escrow::check(&app, &refund, &Data::Empty, &Data::Empty));
+        
+        refund.outputs[0].value = 50_000;
+        assert!(!escrow::check(&app, &refund, &Data::Empty, &Data::Empty));
+        
+        // Undecodable params are rejected rather than ignored
+        let app = App::with_params("escrow:TERMS", [0u8; 32], Data::Bytes(vec![0xff]));
+        assert_eq!(
+            escrow::try_check(&app, &refund, &Data::Empty, &Data::Empty),
+            Err(CheckError::MissingField("params"))
+        );
+    }
+    
+    /// Escrow app whose releases need 2 of the 3 given co-signers
+    fn multisig_escrow(co_signers: &[&k256::ecdsa::SigningKey]) -> App {
+        let params = escrow::EscrowParams {
+            co_signers: co_signers.iter().map(|key| pubkey(key)).collect(),
+            required_sigs: 2,
+            ..escrow_params()
+        };
+        App::with_params("escrow:MULTISIG", [0u8; 32], params.to_params())
+    }
+    
+    #[test]
+    fn test_escrow_two_of_three_release() {
+        use escrow::EscrowState;
+        
+        let keys = [signing_key(1), signing_key(2), signing_key(3)];
+        let app = multisig_escrow(&[&keys[0], &keys[1], &keys[2]]);
+        let mut tx = escrow_tx(&app.tag, Some(EscrowState::Funded.code()), EscrowState::Released.code());
+        tx.outputs[0].script_pubkey = b"seller".to_vec();
+        tx.outputs[0].value = 100_000;
+        
+        let sigs = |signers: &[usize]| {
+            Data::List(signers.iter().map(|&i| sign(&keys[i], &tx.compute_txid())).collect())
+        };
+        
+        assert_eq!(escrow::try_check(&app, &tx, &sigs(&[0, 2]), &Data::Empty), Ok(()));
+        assert_eq!(escrow::try_check(&app, &tx, &sigs(&[2, 1, 0]), &Data::Empty), Ok(()));
+        
+        // One signer short, or the same signer twice
+        for x in [sigs(&[1]), sigs(&[1, 1]), Data::Empty] {
+            assert_eq!(
+                escrow::try_check(&app, &tx, &x, &Data::Empty),
+                Err(CheckError::MissingAuthorization)
+            );
+        }
+        
+        // A stranger's signature does not count
+        let outsider = Data::List(vec![sign(&keys[0], &tx.compute_txid()), sign(&signing_key(9), &tx.compute_txid())]);
+        assert!(!escrow::check(&app, &tx, &outsider, &Data::Empty));
+    }
+    
+    /// Escrow state in `state`, holding 100,000 sats until `timeout` if set
+    fn timed_escrow_state(state: escrow::EscrowState, timeout: Option<u64>) -> Data {
+        let mut escrow = std::collections::BTreeMap::new();
+        escrow.insert("state".to_string(), Data::U64(state.code()));
+        escrow.insert("amount".to_string(), Data::U64(100_000));
+        if let Some(timeout) = timeout {
+            escrow.insert("timeout_height".to_string(), Data::U64(timeout));
+        }
+        Data::Map(escrow)
+    }
+    
+    #[test]
+    fn test_escrow_timeout_blocks_set_refund_deadline() {
+        use escrow::EscrowState::{Created, Disputed, Funded, Refunded};
+        
+        let params = escrow::EscrowParams { timeout_blocks: 144, ..escrow_params() };
+        let app = App::with_params("escrow:TIMED", [0u8; 32], params.to_params());
+        
+        // Funded at 840,000, the escrow must hold until at least 840,144
+        let funding = |timeout| {
+            let mut tx = escrow_amount_tx(
+                &app.tag,
+                timed_escrow_state(Created, None),
+                timed_escrow_state(Funded, timeout),
+                100_000,
+            )
+            .with_locktime(840_000);
+            tx.inputs[0].sequence = MAX_SEQUENCE_NONFINAL;
+            tx
+        };
+        assert_eq!(escrow::try_check(&app, &funding(Some(840_144)), &Data::Empty, &Data::Empty), Ok(()));
+        assert_eq!(
+            escrow::try_check(&app, &funding(Some(840_143)), &Data::Empty, &Data::Empty),
+            Err(CheckError::TimeoutNotReached { height: 840_143, timeout: 840_144 })
+        );
+        assert_eq!(
+            escrow::try_check(&app, &funding(None), &Data::Empty, &Data::Empty),
+            Err(CheckError::MissingField("timeout_height"))
+        );
+        let mut unlocked = funding(Some(840_144));
+        unlocked.inputs[0].sequence = SEQUENCE_FINAL;
+        assert_eq!(
+            escrow::try_check(&app, &unlocked, &Data::Empty, &Data::Empty),
+            Err(CheckError::MissingField("locktime"))
+        );
+        
+        // A dispute keeps the deadline
+        let funded = timed_escrow_state(Funded, Some(840_144));
+        let dispute = |timeout| escrow_amount_tx(&app.tag, funded.clone(), timed_escrow_state(Disputed, timeout), 100_000);
+        assert_eq!(escrow::try_check(&app, &dispute(Some(840_144)), &Data::Empty, &Data::Empty), Ok(()));
+        for timeout in [None, Some(840_000)] {
+            assert_eq!(
+                escrow::try_check(&app, &dispute(timeout), &Data::Empty, &Data::Empty),
+                Err(CheckError::MissingField("timeout_height"))
+            );
+        }
+        
+        // The buyer is refunded once it passes
+        let mut refund = escrow_amount_tx(&app.tag, funded, timed_escrow_state(Refunded, None), 100_000);
+        refund.outputs[0].script_pubkey = b"buyer".to_vec();
+        assert_eq!(
+            escrow::try_check_at_height(&app, &refund, &Data::Empty, &Data::Empty, 840_143),
+            Err(CheckError::TimeoutNotReached { height: 840_143, timeout: 840_144 })
+        );
+        assert_eq!(escrow::try_check_at_height(&app, &refund, &Data::Empty, &Data::Empty, 840_144), Ok(()));
+    }
+    
+    #[test]
+    fn test_escrow_arbiter_settles_dispute() {
+        use escrow::EscrowState::{Disputed, Refunded, Released};
+        
+        let arbiter = signing_key(7);
+        let params = escrow::EscrowParams { arbiter: Some(pubkey(&arbiter)), ..escrow_params() };
+        let app = App::with_params("escrow:ARBITRATED", [0u8; 32], params.to_params());
+        
+        for (next, recipient) in [(Released, &b"seller"[..]), (Refunded, &b"buyer"[..])] {
+            let mut tx = escrow_tx(&app.tag, Some(Disputed.code()), next.code());
+            tx.outputs[0].script_pubkey = recipient.to_vec();
+            
+            let x = Data::List(vec![sign(&arbiter, &tx.compute_txid())]);
+            assert_eq!(escrow::try_check(&app, &tx, &x, &Data::Empty), Ok(()), "{}", next);
+            
+            // Neither party may settle alone
+            let stranger = Data::List(vec![sign(&signing_key(8), &tx.compute_txid())]);
+            for x in [Data::Empty, stranger] {
+                assert_eq!(
+                    escrow::try_check(&app, &tx, &x, &Data::Empty),
+                    Err(CheckError::MissingAuthorization),
+                    "{}", next
+                );
+            }
+        }
+    }
+    
+    #[test]
+    fn test_escrow_dispute_needs_co_signers() {
+        use escrow::EscrowState;
+        
+        let keys = [signing_key(1), signing_key(2), signing_key(3)];
+        let app = multisig_escrow(&[&keys[0], &keys[1], &keys[2]]);
+        let tx = escrow_tx(&app.tag, Some(EscrowState::Funded.code()), EscrowState::Disputed.code());
+        
+        let x = Data::List(vec![sign(&keys[1], &tx.compute_txid())]);
+        assert!(!escrow::check(&app, &tx, &x, &Data::Empty));
+        let x = Data::List(vec![sign(&keys[1], &tx.compute_txid()), sign(&keys[2], &tx.compute_txid())]);
+        assert!(escrow::check(&app, &tx, &x, &Data::Empty));
+    }
+    
+    #[test]
+    fn test_escrow_custom_signature_verifier() {
+        use escrow::EscrowState;
+        use signature::SignatureVerifier;
+        
+        /// Accepts a signature equal to the signer's key
+        struct EchoVerifier;
+        
+        impl SignatureVerifier for EchoVerifier {
+            fn verify(&self, pubkey: &[u8], _msg_hash: &[u8; 32], sig: &[u8]) -> bool {
+                pubkey == sig
+            }
+        }
+        
+        let keys = [signing_key(1), signing_key(2), signing_key(3)];
+        let app = multisig_escrow(&[&keys[0], &keys[1], &keys[2]]);
+        let tx = escrow_tx(&app.tag, Some(EscrowState::Funded.code()), EscrowState::Disputed.code());
+        let x = Data::List(vec![Data::Bytes(pubkey(&keys[0])), Data::Bytes(pubkey(&keys[2]))]);
+        
+        assert_eq!(escrow::try_check_with(&EchoVerifier, &app, &tx, &x, &Data::Empty), Ok(()));
+        assert!(!escrow::check(&app, &tx, &x, &Data::Empty));
+    }
+    
+    /// Fund a `Created` escrow naming `payer` from two 60,000 sat inputs
+    fn escrow_funding_tx(tag: &str, payer: Vec<u8>) -> Transaction {
+        use escrow::EscrowState;
+        
+        let script_pubkey = [&[33u8][..], &payer, &[0xac]].concat();
+        let mut created = std::collections::BTreeMap::new();
+        created.insert("state".to_string(), Data::U64(EscrowState::Created.code()));
+        created.insert("payer".to_string(), Data::Bytes(payer));
+        
+        let mut tx = escrow_tx(tag, None, EscrowState::Funded.code());
+        tx.inputs.push(TxInput {
+            value: Some(60_000),
+            script_pubkey: script_pubkey.clone(),
+            ..TxInput::new(
+                UtxoRef { txid: [1u8; 32], vout: 0 },
+                Some(CharmState::new().with_app(tag, Data::Map(created))),
+            )
+        });
+        tx.inputs.push(TxInput {
+            value: Some(60_000),
+            script_pubkey,
+            ..TxInput::new(UtxoRef { txid: [2u8; 32], vout: 0 }, None)
+        });
+        tx
+    }
+    
+    #[test]
+    fn test_escrow_funded_by_payer() {
+        let app = App::new("escrow:PAYER", [0u8; 32]);
+        let payer = signing_key(1);
+        let tx = escrow_funding_tx(&app.tag, pubkey(&payer));
+        
+        let x = Data::List(vec![sign(&payer, &tx.compute_txid()), sign(&payer, &tx.compute_txid())]);
+        assert!(escrow::check(&app, &tx, &x, &Data::Empty));
+        
+        // One payer input doesn't cover the 100,000 sats locked
+        let x = Data::List(vec![sign(&payer, &tx.compute_txid()), Data::Empty]);
+        assert_eq!(
+            escrow::try_check(&app, &tx, &x, &Data::Empty),
+            Err(CheckError::AmountMismatch { expected: 100_000, actual: 60_000 })
+        );
+        
+        // Signing the caller-supplied txid instead of the computed one
+        assert_ne!(tx.txid, tx.compute_txid());
+        let x = Data::List(vec![sign(&payer, &tx.txid), sign(&payer, &tx.txid)]);
+        assert_eq!(
+            escrow::try_check(&app, &tx, &x, &Data::Empty),
+            Err(CheckError::MissingAuthorization)
+        );
+        
+        // A payer input of unknown value is not counted as zero
+        let mut unknown = tx.clone();
+        unknown.inputs[1].value = None;
+        let x = Data::List(vec![sign(&payer, &tx.compute_txid()), sign(&payer, &tx.compute_txid())]);
+        assert_eq!(
+            escrow::try_check(&app, &unknown, &x, &Data::Empty),
+            Err(CheckError::MissingField("value"))
+        );
+    }
+    
+    #[test]
+    fn test_escrow_funded_by_third_party_rejected() {
+        let app = App::new("escrow:PAYER", [0u8; 32]);
+        let payer = signing_key(1);
+        let third_party = signing_key(2);
+        let tx = escrow_funding_tx(&app.tag, pubkey(&payer));
+        
+        let x = Data::List(vec![sign(&third_party, &tx.compute_txid()), sign(&third_party, &tx.compute_txid())]);
+        assert_eq!(
+            escrow::try_check(&app, &tx, &x, &Data::Empty),
+            Err(CheckError::MissingAuthorization)
+        );
+        
+        // No signatures at all
+        assert_eq!(
+            escrow::try_check(&app, &tx, &Data::Empty, &Data::Empty),
+            Err(CheckError::MissingAuthorization)
+        );
+    }
+    
+    #[test]
+    fn test_escrow_payer_signature_copied_to_third_party_input() {
+        let app = App::new("escrow:PAYER", [0u8; 32]);
+        let payer = signing_key(1);
+        let mut tx = escrow_funding_tx(&app.tag, pubkey(&payer));
+        tx.inputs[1].script_pubkey = p2pk(&signing_key(2));
+        
+        // The payer's one signature, pasted into the third party's slot
+        let sig = sign(&payer, &tx.compute_txid());
+        let x = Data::List(vec![sig.clone(), sig]);
+        assert_eq!(
+            escrow::try_check(&app, &tx, &x, &Data::Empty),
+            Err(CheckError::MissingAuthorization)
+        );
+        
+        // Leaving the third party's input unclaimed doesn't cover the escrow
+        let x = Data::List(vec![sign(&payer, &tx.compute_txid()), Data::Empty]);
+        assert_eq!(
+            escrow::try_check(&app, &tx, &x, &Data::Empty),
+            Err(CheckError::AmountMismatch { expected: 100_000, actual: 60_000 })
+        );
+    }
+    
+    #[test]
+    fn test_escrow_three_milestones() {
+        let app = App::new("escrow:MILESTONES", [0u8; 32]);
+        
+        // Funded -> M0 -> M1 -> M2 -> Released
+        let steps = [(1, 100), (100, 101), (101, 102), (102, 2)];
+        for (current, next) in steps {
+            let tx = escrow_tx(&app.tag, Some(current), next);
+            assert!(
+                escrow::check(&app, &tx, &Data::Empty, &Data::Empty),
+                "transition {} -> {} should be valid", current, next
+            );
+        }
+    }
+    
+    /// 25,000 sats at each of heights 100 and 200, then 30,000 at 300
+    fn three_milestones() -> escrow::MilestoneEscrow {
+        let milestone = |block_height, amount_sats| escrow::MilestoneAmount { block_height, amount_sats };
+        escrow::MilestoneEscrow {
+            milestones: vec![milestone(100, 25_000), milestone(200, 25_000), milestone(300, 30_000)],
+        }
+    }
+    
+    /// Move an escrow from `current` to `next`, keeping `held` sats in escrow
+    /// and paying `paid` to the seller at height `height`
+    fn milestone_tx(tag: &str, current: u64, next: u64, held: u64, paid: u64, height: u32) -> Transaction {
+        let mut tx = escrow_tx(tag, Some(current), next).with_locktime(height);
+        tx.inputs[0].sequence = MAX_SEQUENCE_NONFINAL;
+        tx.outputs[0].value = held;
+        tx.outputs.push(TxOutput {
+            index: 1,
+            value: paid,
+            script_pubkey: b"seller".to_vec(),
+            charm_state: None,
+        });
+        tx
+    }
+    
+    #[test]
+    fn test_escrow_milestone_payments() {
+        use escrow::EscrowState::{Funded, MilestoneCompleted, Released};
+        
+        let params = escrow::EscrowParams { milestones: Some(three_milestones()), ..escrow_params() };
+        let app = App::with_params("escrow:STAGED", [0u8; 32], params.to_params());
+        
+        // Funded -> M0 -> M1 -> M2, leaving 75,000, 50,000 and 20,000 held
+        let steps = [
+            (Funded, MilestoneCompleted(0), 75_000, 25_000, 100),
+            (MilestoneCompleted(0), MilestoneCompleted(1), 50_000, 25_000, 200),
+            (MilestoneCompleted(1), MilestoneCompleted(2), 20_000, 30_000, 300),
+        ];
+        for (current, next, held, paid, height) in steps {
+            let tx = milestone_tx(&app.tag, current.code(), next.code(), held, paid, height);
+            assert_eq!(escrow::try_check(&app, &tx, &Data::Empty, &Data::Empty), Ok(()), "{} -> {}", current, next);
+            
+            let underpaid = milestone_tx(&app.tag, current.code(), next.code(), held + 1, paid - 1, height);
+            assert_eq!(
+                escrow::try_check(&app, &underpaid, &Data::Empty, &Data::Empty),
+                Err(CheckError::AmountMismatch { expected: paid, actual: paid - 1 })
+            );
+            
+            let overpaid = milestone_tx(&app.tag, current.code(), next.code(), held - 1, paid + 1, height);
+            assert!(!escrow::check(&app, &overpaid, &Data::Empty, &Data::Empty));
+            
+            let drained = milestone_tx(&app.tag, current.code(), next.code(), held - 1, paid, height);
+            assert_eq!(
+                escrow::try_check(&app, &drained, &Data::Empty, &Data::Empty),
+                Err(CheckError::AmountMismatch { expected: held, actual: held - 1 })
+            );
+            
+            let early = milestone_tx(&app.tag, current.code(), next.code(), held, paid, height - 1);
+            assert_eq!(
+                escrow::try_check(&app, &early, &Data::Empty, &Data::Empty),
+                Err(CheckError::TimeoutNotReached { height: height - 1, timeout: height })
+            );
+        }
+        
+        // Releasing after the last milestone pays out the remaining 20,000
+        let mut release = escrow_tx(&app.tag, Some(MilestoneCompleted(2).code()), Released.code());
+        release.outputs[0].script_pubkey = b"seller".to_vec();
+        release.outputs[0].value = 20_000;
+        assert!(escrow::check(&app, &release, &Data::Empty, &Data::Empty));
+        release.outputs[0].value = 19_999;
+        assert!(!escrow::check(&app, &release, &Data::Empty, &Data::Empty));
+        
+        // There is no fourth milestone
+        let tx = milestone_tx(&app.tag, MilestoneCompleted(2).code(), MilestoneCompleted(3).code(), 0, 20_000, 400);
+        assert_eq!(
+            escrow::try_check(&app, &tx, &Data::Empty, &Data::Empty),
+            Err(CheckError::InvalidTransition)
+        );
+    }
+    
+    /// An escrow disputed after milestone 0, recording `remaining` if any
+    fn disputed_state(remaining: Option<u64>) -> Data {
+        let mut escrow = std::collections::BTreeMap::new();
+        escrow.insert("state".to_string(), Data::U64(escrow::EscrowState::Disputed.code()));
+        escrow.insert("amount".to_string(), Data::U64(100_000));
+        if let Some(remaining) = remaining {
+            escrow.insert("remaining".to_string(), Data::U64(remaining));
+        }
+        Data::Map(escrow)
+    }
+    
+    #[test]
+    fn test_escrow_milestone_dispute_settles_remainder() {
+        use escrow::EscrowState::{Disputed, MilestoneCompleted, Refunded, Released};
+        
+        let params = escrow::EscrowParams { milestones: Some(three_milestones()), ..escrow_params() };
+        let app = App::with_params("escrow:STAGED", [0u8; 32], params.to_params());
+        
+        // Milestone 0 paid 25,000, so the dispute holds the other 75,000
+        let mut dispute = escrow_tx(&app.tag, Some(MilestoneCompleted(0).code()), Disputed.code());
+        dispute.outputs[0].value = 75_000;
+        dispute.outputs[0].charm_state = Some(CharmState::new().with_app(&app.tag, disputed_state(Some(75_000))));
+        assert_eq!(escrow::try_check(&app, &dispute, &Data::Empty, &Data::Empty), Ok(()));
+        
+        for remaining in [None, Some(100_000), Some(74_999)] {
+            dispute.outputs[0].charm_state = Some(CharmState::new().with_app(&app.tag, disputed_state(remaining)));
+            assert_eq!(
+                escrow::try_check(&app, &dispute, &Data::Empty, &Data::Empty),
+                Err(CheckError::AmountMismatch { expected: 75_000, actual: remaining.unwrap_or(100_000) })
+            );
+        }
+        
+        // Either way, the dispute settles exactly the remaining 75,000
+        for (next, recipient) in [(Released, &b"seller"[..]), (Refunded, &b"buyer"[..])] {
+            let mut settle = escrow_tx(&app.tag, None, next.code());
+            settle.inputs.push(TxInput::new(
+                UtxoRef { txid: [0u8; 32], vout: 0 },
+                Some(CharmState::new().with_app(&app.tag, disputed_state(Some(75_000)))),
+            ));
+            settle.outputs[0].script_pubkey = recipient.to_vec();
+            settle.outputs[0].value = 75_000;
+            assert_eq!(escrow::try_check(&app, &settle, &Data::Empty, &Data::Empty), Ok(()), "{}", next);
+            
+            settle.outputs[0].value = 100_000;
+            assert_eq!(
+                escrow::try_check(&app, &settle, &Data::Empty, &Data::Empty),
+                Err(CheckError::AmountMismatch { expected: 75_000, actual: 100_000 }),
+                "{}", next
+            );
+        }
+    }
+    
+    #[test]
+    fn test_escrow_milestone_skip_rejected() {
+        let app = App::new("escrow:MILESTONES", [0u8; 32]);
+        
+        // M0 -> M2 skips milestone 1
+        let tx = escrow_tx(&app.tag, Some(100), 102);
+        assert!(!escrow::check(&app, &tx, &Data::Empty, &Data::Empty));
+        
+        // Funded must start at milestone 0
+        let tx = escrow_tx(&app.tag, Some(1), 101);
+        assert!(!escrow::check(&app, &tx, &Data::Empty, &Data::Empty));
+    }
+    
+    #[test]
+    fn test_escrow_milestone_backwards_rejected() {
+        let app = App::new("escrow:MILESTONES", [0u8; 32]);
+        
+        let tx = escrow_tx(&app.tag, Some(102), 101);
+        assert!(!escrow::check(&app, &tx, &Data::Empty, &Data::Empty));
+        
+        let tx = escrow_tx(&app.tag, Some(101), 101);
+        assert!(!escrow::check(&app, &tx, &Data::Empty, &Data::Empty));
+    }
+    
+    #[test]
+    fn test_escrow_milestone_dispute() {
+        let app = App::new("escrow:MILESTONES", [0u8; 32]);
+        
+        let tx = escrow_tx(&app.tag, Some(101), 3);
+        assert!(escrow::check(&app, &tx, &Data::Empty, &Data::Empty));
+    }
+    
+    #[cfg(feature = "tracing")]
+    #[test]
+    fn test_rejection_is_traced() {
+        use std::sync::{Arc, Mutex};
+        
+        /// Collects everything the subscriber writes
+        #[derive(Clone, Default)]
+        struct Capture(Arc<Mutex<Vec<u8>>>);
+        
+        impl std::io::Write for Capture {
+            fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+                self.0.lock().unwrap().extend_from_slice(buf);
+                Ok(buf.len())
+            }
+            
+            fn flush(&mut self) -> std::io::Result<()> {
+                Ok(())
+            }
+        }
+        
+        let capture = Capture::default();
+        let writer = capture.clone();
+        let subscriber = tracing_subscriber::fmt()
+            .with_max_level(tracing::Level::DEBUG)
+            .with_ansi(false)
+            .with_writer(move || writer.clone())
+            .finish();
+        
+        let app = App::new("token:TRACED", [0u8; 32]);
+        let auth = Data::Bytes(vec![1, 2, 3]);
+        let balanced = fee_tx(&app.tag, 1_000, &[(1_000, false)]);
+        let unbalanced = fee_tx(&app.tag, 1_000, &[(900, false)]);
+        
+        let (ok, rejected) = tracing::subscriber::with_default(subscriber, || {
+            let ok = token::try_check(&app, &balanced, &auth, &Data::Empty);
+            let logged_ok = capture.0.lock().unwrap().len();
+            (ok.map(|_| logged_ok), token::try_check(&app, &unbalanced, &auth, &Data::Empty))
+        });
+        // Accepted spells log nothing
+        assert_eq!(ok, Ok(0));
+        
+        let err = CheckError::ConservationViolation { input: 1_000, output: 900 };
+        assert_eq!(rejected, Err(err.clone()));
+        let logged = String::from_utf8(capture.0.lock().unwrap().clone()).unwrap();
+        assert!(logged.contains("DEBUG"), "{}", logged);
+        assert!(logged.contains("token:TRACED"), "{}", logged);
+        assert!(logged.contains(&format!("spell rejected: {}", err)), "{}", logged);
+    }
+    
+    #[test]
+    fn test_token_try_check_errors() {
         let app = App::new("test-token", [0u8; 32]);
+        let auth = Data::Bytes(vec![1, 2, 3]);
+        
+        let tx = fee_tx("test-token", 1_000, &[(900, false)]);
+        assert_eq!(
+            token::try_check(&app, &tx, &auth, &Data::Empty),
+            Err(CheckError::ConservationViolation { input: 1_000, output: 900 })
+        );
+        
+        let tx = fee_tx("test-token", u64::MAX, &[(u64::MAX, false), (1, false)]);
+        assert_eq!(token::try_check(&app, &tx, &auth, &Data::Empty), Err(CheckError::Overflow));
+        
+        let tx = fee_tx("test-token", 1_000, &[(1_000, false)]);
+        assert_eq!(
+            token::try_check(&app, &tx, &Data::Bytes(vec![]), &Data::Empty),
+            Err(CheckError::MissingAuthorization)
+        );
+        assert_eq!(token::try_check(&app, &tx, &auth, &Data::Empty), Ok(()));
+    }
+    
+    #[test]
+    fn test_nft_try_check_errors() {
+        let app = App::new("nft:ART", [0u8; 32]);
+        let creator = signing_key(1);
+        let funding = UtxoRef { txid: [7u8; 32], vout: 1 };
+        let id = expected_mint_id(&funding);
+        let sig = sign(&creator, &mint_hash(&id));
+        
+        // Same NFT in two outputs
+        let mut tx = nft_mint_tx(&app.tag, funding.clone(), nft_state(id.clone(), pubkey(&creator)));
+        tx.outputs.push(tx.outputs[0].clone());
+        tx.outputs[1].index = 1;
+        assert_eq!(nft::try_check(&app, &tx, &sig, &Data::Empty), Err(CheckError::DuplicateNft(id.clone())));
+        
+        let tx = nft_mint_tx(&app.tag, funding.clone(), nft_state(id.clone(), pubkey(&creator)));
+        assert_eq!(nft::try_check(&app, &tx, &Data::Empty, &Data::Empty), Err(CheckError::MissingAuthorization));
+        
+        // Signed for its own id, which is not the derived one
+        let tx = nft_mint_tx(&app.tag, funding.clone(), nft_state(vec![0xaa; 32], pubkey(&creator)));
+        let misderived_sig = sign(&creator, &mint_hash(&[0xaa; 32]));
+        assert_eq!(nft::try_check(&app, &tx, &misderived_sig, &Data::Empty), Err(CheckError::InvalidMint));
+        
+        let tx = nft_mint_tx(&app.tag, funding, Data::Bytes(id));
+        assert_eq!(nft::try_check(&app, &tx, &sig, &Data::Empty), Err(CheckError::InvalidMint));
+    }
+    
+    #[test]
+    fn test_escrow_try_check_errors() {
+        let app = App::new("escrow:MILESTONES", [0u8; 32]);
+        
+        let tx = escrow_tx(&app.tag, Some(100), 102);
+        assert_eq!(
+            escrow::try_check(&app, &tx, &Data::Empty, &Data::Empty),
+            Err(CheckError::InvalidTransition)
+        );
+        
+        let tx = escrow_tx(&app.tag, Some(100), 101);
+        assert_eq!(escrow::try_check(&app, &tx, &Data::Empty, &Data::Empty), Ok(()));
+    }
+    
+    #[test]
+    fn test_escrow_output_limit_exceeded() {
+        let app = App::new("escrow:DEAL1", [0u8; 32]);
+        let mut tx = escrow_tx(&app.tag, Some(1), 2);
+        let output = tx.outputs[0].clone();
+        for index in 1..50 {
+            tx.outputs.push(TxOutput { index, ..output.clone() });
+        }
+        
+        assert_eq!(
+            escrow::try_check(&app, &tx, &Data::Empty, &Data::Empty),
+            Err(CheckError::TooManyOutputs { count: 50, max: 2 })
+        );
+    }
+    
+    #[test]
+    fn test_token_output_limit_respected() {
+        let app = App::new("token:BTC", [0u8; 32]);
+        let outputs = vec![(100, false); 50];
+        let tx = fee_tx(&app.tag, 5_000, &outputs);
+        
+        assert!(token::check(&app, &tx, &Data::Bytes(vec![1]), &Data::Empty));
+    }
+    
+    fn burnable_token() -> App {
+        let mut params = std::collections::BTreeMap::new();
+        params.insert("burn_allowed".to_string(), Data::Bool(true));
+        App::with_params("token:BTC", [0u8; 32], Data::Map(params))
+    }
+    
+    #[test]
+    fn test_token_full_transfer_burns_nothing() {
+        let app = burnable_token();
+        let tx = fee_tx(&app.tag, 1_000, &[(600, false), (400, false)]);
+        
+        assert!(token::check(&app, &tx, &Data::Bytes(vec![1]), &Data::Empty));
+        assert_eq!(token::burn_amount(&app, &tx), None);
+        assert!(!token::is_burn(&app, &tx));
+        assert!(!token::is_mint(&app, &tx));
+    }
+    
+    #[test]
+    fn test_token_partial_burn_allowed() {
+        let app = burnable_token();
+        let tx = fee_tx(&app.tag, 1_000, &[(600, false)]);
+        
+        assert!(token::check(&app, &tx, &Data::Bytes(vec![1]), &Data::Empty));
+        assert_eq!(token::burn_amount(&app, &tx), Some(400));
+        assert!(token::is_burn(&app, &tx));
+        assert!(!token::is_mint(&app, &tx));
+    }
+    
+    #[test]
+    fn test_token_burn_without_burn_mode_rejected() {
+        let app = App::new("token:BTC", [0u8; 32]);
+        let tx = fee_tx(&app.tag, 1_000, &[(600, false)]);
         
+        assert_eq!(
+            token::try_check(&app, &tx, &Data::Bytes(vec![1]), &Data::Empty),
+            Err(CheckError::ConservationViolation { input: 1_000, output: 600 })
+        );
+        assert_eq!(token::burn_amount(&app, &tx), Some(400));
+    }
+    
+    /// Transfer 1,000 tokens, claiming 400 of them burned at `burn_script`
+    fn claimed_burn_tx(tag: &str, burn_script: &[u8]) -> Transaction {
+        let mut tx = fee_tx(tag, 1_000, &[(600, false)]);
+        let mut burned = std::collections::BTreeMap::new();
+        burned.insert("amount".to_string(), Data::U64(400));
+        burned.insert("burn".to_string(), Data::Bool(true));
+        tx.outputs.push(TxOutput {
+            index: 1,
+            value: 0,
+            script_pubkey: burn_script.to_vec(),
+            charm_state: Some(CharmState::new().with_app(tag, Data::Map(burned))),
+        });
+        tx
+    }
+    
+    #[test]
+    fn test_burn_script_registry() {
+        assert!(burn::is_burn_script(&[burn::OP_RETURN]));
+        assert!(burn::is_burn_script(&[burn::OP_RETURN, 0x04, b'b', b'u', b'r', b'n']));
+        assert!(burn::is_burn_script(burn::EATER_SCRIPTS[0]));
+        
+        let mut p2pkh = burn::EATER_SCRIPTS[0].to_vec();
+        p2pkh[3] ^= 1;
+        assert!(!burn::is_burn_script(&p2pkh));
+        assert!(!burn::is_burn_script(&[]));
+    }
+    
+    #[test]
+    fn test_token_claimed_burn_to_op_return() {
+        let app = App::new("token:BTC", [0u8; 32]);
+        let tx = claimed_burn_tx(&app.tag, &[burn::OP_RETURN]);
+        
+        assert!(token::check(&app, &tx, &Data::Bytes(vec![1]), &Data::Empty));
+        assert_eq!(token::burn_amount(&app, &tx), Some(400));
+    }
+    
+    #[test]
+    fn test_token_claimed_burn_to_normal_address_rejected() {
+        let app = App::new("token:BTC", [0u8; 32]);
+        let p2wpkh = [vec![0x00, 0x14], vec![0x42; 20]].concat();
+        let tx = claimed_burn_tx(&app.tag, &p2wpkh);
+        
+        assert_eq!(
+            token::try_check(&app, &tx, &Data::Bytes(vec![1]), &Data::Empty),
+            Err(CheckError::InvalidBurn)
+        );
+    }
+    
+    fn capped_token(max_supply: u64) -> App {
+        let mut params = std::collections::BTreeMap::new();
+        params.insert("max_supply".to_string(), Data::U64(max_supply));
+        App::with_params("token:CAP", [0u8; 32], Data::Map(params))
+    }
+    
+    #[test]
+    fn test_token_mint_supply_cap() {
+        let app = capped_token(1_000_000);
         let mut tx = Transaction::new([0u8; 32]);
+        tx.outputs.push(TxOutput {
+            index: 0,
+            value: 546,
+            script_pubkey: vec![],
+            charm_state: Some(CharmState::new().with_app(app.tag.as_str(), Data::U64(250_000))),
+        });
         
-        // Add input with 1000 tokens
-        tx.inputs.push(TxInput {
-            utxo_ref: UtxoRef { txid: [0u8; 32], vout: 0 },
+        assert_eq!(token::minted_amount(&app, &tx), Some(250_000));
+        
+        // Exactly at the cap
+        assert!(token::is_valid_mint(&app, &tx, 750_000));
+        // One over
+        assert!(!token::is_valid_mint(&app, &tx, 750_001));
+        assert!(!token::is_valid_mint(&app, &tx, u64::MAX));
+        
+        // Without a cap any amount may be minted
+        let uncapped = App::new(app.tag.as_str(), [0u8; 32]);
+        assert!(token::is_valid_mint(&uncapped, &tx, u64::MAX - 250_000));
+        
+        // A malformed cap is not mistaken for no cap
+        let mut params = std::collections::BTreeMap::new();
+        params.insert("max_supply".to_string(), Data::String("1000000".to_string()));
+        let malformed = App::with_params(app.tag.as_str(), [0u8; 32], Data::Map(params));
+        assert!(!token::is_valid_mint(&malformed, &tx, 0));
+    }
+    
+    #[test]
+    fn test_token_minted_amount_of_transfer_and_burn() {
+        let app = capped_token(1_000);
+        
+        let transfer = fee_tx(&app.tag, 1_000, &[(600, false), (400, false)]);
+        assert_eq!(token::minted_amount(&app, &transfer), Some(0));
+        assert!(token::is_valid_mint(&app, &transfer, 1_000));
+        
+        let burn = fee_tx(&app.tag, 1_000, &[(600, false)]);
+        assert_eq!(token::minted_amount(&app, &burn), None);
+        assert!(!token::is_valid_mint(&app, &burn, 0));
+    }
+    
+    fn multisig_token(threshold: u64, signers: &[&k256::ecdsa::SigningKey]) -> App {
+        let mut params = std::collections::BTreeMap::new();
+        params.insert("threshold".to_string(), Data::U64(threshold));
+        params.insert(
+            "signers".to_string(),
+            Data::List(signers.iter().map(|key| Data::Bytes(pubkey(key))).collect()),
+        );
+        App::with_params("token:MULTI", [0u8; 32], Data::Map(params))
+    }
+    
+    #[test]
+    fn test_token_threshold_signatures() {
+        let keys = [signing_key(1), signing_key(2), signing_key(3)];
+        let app = multisig_token(2, &[&keys[0], &keys[1], &keys[2]]);
+        let tx = fee_tx(&app.tag, 1_000, &[(1_000, false)]);
+        
+        // 2-of-3, in any order
+        let x = Data::List(vec![sign(&keys[2], &tx.compute_txid()), sign(&keys[0], &tx.compute_txid())]);
+        assert!(token::check(&app, &tx, &x, &Data::Empty));
+        
+        // 1-of-3
+        let x = Data::List(vec![sign(&keys[1], &tx.compute_txid())]);
+        assert_eq!(token::try_check(&app, &tx, &x, &Data::Empty), Err(CheckError::MissingAuthorization));
+        
+        // A signature from outside the set doesn't count
+        let x = Data::List(vec![sign(&keys[1], &tx.compute_txid()), sign(&signing_key(4), &tx.compute_txid())]);
+        assert!(!token::check(&app, &tx, &x, &Data::Empty));
+    }
+    
+    #[test]
+    fn test_token_threshold_counts_each_signer_once() {
+        let keys = [signing_key(1), signing_key(2), signing_key(3)];
+        let app = multisig_token(2, &[&keys[0], &keys[1], &keys[2]]);
+        let tx = fee_tx(&app.tag, 1_000, &[(1_000, false)]);
+        
+        let sig = sign(&keys[0], &tx.compute_txid());
+        let x = Data::List(vec![sig.clone(), sig]);
+        assert_eq!(token::try_check(&app, &tx, &x, &Data::Empty), Err(CheckError::MissingAuthorization));
+        
+        // Listing a signer twice doesn't let it count twice either
+        let app = multisig_token(2, &[&keys[0], &keys[0]]);
+        let x = Data::List(vec![sign(&keys[0], &tx.compute_txid())]);
+        assert!(!token::check(&app, &tx, &x, &Data::Empty));
+    }
+    
+    #[test]
+    fn test_token_fee_threshold_signatures() {
+        let keys = [signing_key(1), signing_key(2), signing_key(3)];
+        let app = multisig_token(2, &[&keys[0], &keys[1], &keys[2]]);
+        let tx = fee_tx(&app.tag, 10_000, &[(9_970, false), (30, true)]);
+        
+        let x = Data::List(vec![sign(&keys[0], &tx.compute_txid()), sign(&keys[2], &tx.compute_txid())]);
+        assert!(token::check_with_fee(&app, &tx, &x, &Data::Empty, 30, "treasury"));
+        
+        let x = Data::List(vec![sign(&keys[1], &tx.compute_txid())]);
+        assert!(!token::check_with_fee(&app, &tx, &x, &Data::Empty, 30, "treasury"));
+        assert!(!token::check_with_fee(&app, &tx, &Data::Empty, &Data::Empty, 30, "treasury"));
+    }
+    
+    #[test]
+    fn test_token_single_signer_threshold() {
+        let key = signing_key(1);
+        let app = multisig_token(1, &[&key]);
+        let tx = fee_tx(&app.tag, 1_000, &[(1_000, false)]);
+        
+        assert!(token::check(&app, &tx, &sign(&key, &tx.compute_txid()), &Data::Empty));
+        assert!(token::check(&app, &tx, &Data::List(vec![sign(&key, &tx.compute_txid())]), &Data::Empty));
+        // Any non-empty blob is no longer enough
+        assert!(!token::check(&app, &tx, &Data::Bytes(vec![1, 2, 3]), &Data::Empty));
+    }
+    
+    #[test]
+    fn test_token_check_enforces_max_supply() {
+        let app = capped_token(1_000);
+        let auth = Data::Bytes(vec![1]);
+        let mint = |amount: u64| {
+            let mut tx = Transaction::new([0u8; 32]);
+            tx.outputs.push(TxOutput {
+                index: 0,
+                value: 546,
+                script_pubkey: vec![],
+                charm_state: Some(CharmState::new().with_app(app.tag.as_str(), Data::U64(amount))),
+            });
+            tx
+        };
+        
+        // Exactly at the cap
+        assert!(token::check(&app, &mint(1_000), &auth, &Data::Empty));
+        // One over
+        assert_eq!(
+            token::try_check(&app, &mint(1_001), &auth, &Data::Empty),
+            Err(CheckError::SupplyCapExceeded { minted: 1_001, max_supply: 1_000 })
+        );
+        // Uncapped tokens still can't be minted
+        let uncapped = App::new(app.tag.as_str(), [0u8; 32]);
+        assert_eq!(
+            token::try_check(&uncapped, &mint(1), &auth, &Data::Empty),
+            Err(CheckError::ConservationViolation { input: 0, output: 1 })
+        );
+    }
+    
+    #[test]
+    fn test_token_transfer_ignores_max_supply() {
+        let app = capped_token(1_000);
+        let auth = Data::Bytes(vec![1]);
+        
+        // A transfer is no mint, so balances above the cap are not checked
+        let transfer = fee_tx(&app.tag, 5_000, &[(3_000, false), (2_000, false)]);
+        assert!(token::check(&app, &transfer, &auth, &Data::Empty));
+        
+        let inflating = fee_tx(&app.tag, 500, &[(600, false)]);
+        assert_eq!(
+            token::try_check(&app, &inflating, &auth, &Data::Empty),
+            Err(CheckError::ConservationViolation { input: 500, output: 600 })
+        );
+    }
+    
+    #[test]
+    fn test_token_snapshot_diff() {
+        let tag = "token:BTC";
+        let a = CharmState::new().with_app(tag, Data::U64(500));
+        let b = CharmState::new().with_app(tag, Data::U64(300)).with_app("nft:X", Data::Bytes(vec![1]));
+        let c = CharmState::new().with_app(tag, Data::U64(u64::MAX));
+        let other = CharmState::new().with_app("token:ETH", Data::U64(1_000));
+        
+        // Net mint: 800 -> u64::MAX + 300
+        let before = [&a, &b, &other];
+        let after = [&b, &c];
+        assert_eq!(token::snapshot_diff(&before, &after, tag), Some(u64::MAX as i128 - 500));
+        
+        // Net burn: 800 -> 300
+        let after = [&b];
+        assert_eq!(token::snapshot_diff(&before, &after, tag), Some(-500));
+        
+        assert_eq!(token::snapshot_diff(&before, &before, tag), Some(0));
+    }
+    
+    /// Token transfer of 1000 plus an escrow moving from `current` to `next`,
+    /// sharing the same inputs and outputs
+    fn token_and_escrow_tx(current: u64, next: u64) -> Transaction {
+        let mut tx = Transaction::new([9u8; 32]);
+        tx.inputs.push(TxInput::new(
+            UtxoRef { txid: [8u8; 32], vout: 0 },
+            Some(
+                CharmState::new()
+                    .with_app("token:BTC", Data::U64(1_000))
+                    .with_app("escrow:DEAL1", escrow_state(current)),
+            ),
+        ));
+        tx.outputs.push(TxOutput {
+            index: 0,
+            value: 100_000,
+            script_pubkey: vec![],
             charm_state: Some(
-                CharmState::new().with_app("test-token", Data::U64(1000))
+                CharmState::new()
+                    .with_app("token:BTC", Data::U64(1_000))
+                    .with_app("escrow:DEAL1", escrow_state(next))
             ),
         });
+        tx
+    }
+    
+    fn token_and_escrow_apps() -> Vec<(App, Data, Data)> {
+        vec![
+            (App::new("token:BTC", [0u8; 32]), Data::Bytes(vec![1]), Data::Empty),
+            (App::new("escrow:DEAL1", [0u8; 32]), Data::Empty, Data::Empty),
+        ]
+    }
+    
+    #[test]
+    fn test_check_all_token_and_escrow() {
+        // Funded -> Released
+        let tx = token_and_escrow_tx(1, 2);
+        assert!(check_all(&tx, &token_and_escrow_apps()));
+    }
+    
+    #[test]
+    fn test_check_all_one_app_invalid() {
+        // Token conserved, but Created -> Released skips funding
+        let tx = token_and_escrow_tx(0, 2);
+        let apps = token_and_escrow_apps();
+        assert!(CheckerRegistry::new().dispatch(&apps[0].0, &tx, &apps[0].1, &apps[0].2));
+        assert!(!check_all(&tx, &apps));
         
-        // Add outputs totaling 1000 tokens
+        // Escrow valid, but the token authorization is empty
+        let tx = token_and_escrow_tx(1, 2);
+        let mut apps = token_and_escrow_apps();
+        apps[0].1 = Data::Bytes(vec![]);
+        assert!(!check_all(&tx, &apps));
+    }
+    
+    #[test]
+    fn test_check_all_rejects_unchecked_or_duplicate_apps() {
+        let tx = token_and_escrow_tx(1, 2);
+        let apps = token_and_escrow_apps();
+        
+        // The escrow state would go unchecked
+        assert!(!check_all(&tx, &apps[..1]));
+        
+        let mut duplicated = apps.clone();
+        duplicated.push(apps[1].clone());
+        assert!(!check_all(&tx, &duplicated));
+    }
+    
+    fn bounty_state(state: u64, reward_amount: u64, hunter: &[u8]) -> Data {
+        let mut map = std::collections::BTreeMap::new();
+        map.insert("state".to_string(), Data::U64(state));
+        map.insert("reward_amount".to_string(), Data::U64(reward_amount));
+        map.insert("hunter".to_string(), Data::Bytes(hunter.to_vec()));
+        map.insert("deadline_block".to_string(), Data::U64(900_000));
+        Data::Map(map)
+    }
+    
+    fn bounty_tx(tag: &str, current: Option<Data>, next: Data) -> Transaction {
+        let mut tx = Transaction::new([6u8; 32]);
+        if let Some(current) = current {
+            tx.inputs.push(TxInput::new(
+                UtxoRef { txid: [5u8; 32], vout: 0 },
+                Some(CharmState::new().with_app(tag, current)),
+            ));
+        }
+        tx.outputs.push(TxOutput {
+            index: 0,
+            value: 10_000,
+            script_pubkey: vec![],
+            charm_state: Some(CharmState::new().with_app(tag, next)),
+        });
+        tx
+    }
+    
+    #[test]
+    fn test_bounty_lifecycle() {
+        let app = App::new("bounty:BUG42", [0u8; 32]);
+        let hunter = [0x02; 33];
+        let steps = [
+            (None, bounty_state(0, 5_000, &[])),
+            (Some(bounty_state(0, 5_000, &[])), bounty_state(1, 5_000, &hunter)),
+            (Some(bounty_state(1, 5_000, &hunter)), bounty_state(2, 5_000, &hunter)),
+        ];
+        for (current, next) in steps {
+            let tx = bounty_tx(&app.tag, current, next);
+            assert_eq!(bounty::try_check(&app, &tx, &Data::Empty, &Data::Empty), Ok(()));
+        }
+        
+        let tx = bounty_tx(&app.tag, Some(bounty_state(0, 5_000, &[])), bounty_state(2, 5_000, &hunter));
+        assert_eq!(
+            bounty::try_check(&app, &tx, &Data::Empty, &Data::Empty),
+            Err(CheckError::InvalidTransition)
+        );
+    }
+    
+    #[test]
+    fn test_app_registry() {
+        let mut registry = AppRegistry::new();
+        assert_eq!(registry.register(App::new("token:ABC", [0u8; 32])), Ok(None));
+        assert_eq!(
+            registry.register(App::new("vote:USD", [0u8; 32])),
+            Err(TagValidationError::UnknownCategory("vote".to_string()))
+        );
+        assert_eq!(registry.get("token:ABC").map(|app| app.tag.as_str()), Some("token:ABC"));
+        assert!(registry.get("token:XYZ").is_none());
+        
+        let mut tx = Transaction::new([1u8; 32]);
+        tx.inputs.push(TxInput::new(
+            UtxoRef { txid: [0u8; 32], vout: 0 },
+            Some(CharmState::new().with_app("token:ABC", Data::U64(100))),
+        ));
         tx.outputs.push(TxOutput {
             index: 0,
             value: 546,
             script_pubkey: vec![],
-            charm_state: Some(
-                CharmState::new().with_app("test-token", Data::U64(600))
+            charm_state: Some(CharmState::new().with_app("token:ABC", Data::U64(100))),
+        });
+        let x = Data::Bytes(vec![0x30, 0x44]);
+        
+        assert_eq!(registry.check_spell("token:ABC", &tx, &x, &Data::Empty), Ok(true));
+        assert_eq!(registry.check_spell("token:ABC", &tx, &Data::Bytes(vec![]), &Data::Empty), Ok(false));
+        assert_eq!(
+            registry.check_spell("token:XYZ", &tx, &x, &Data::Empty),
+            Err(AppError::UnknownApp("token:XYZ".to_string()))
+        );
+        assert_eq!(
+            registry.check_spell("vote:USD", &tx, &x, &Data::Empty),
+            Err(AppError::UnknownApp("vote:USD".to_string()))
+        );
+        
+        registry.register(App::new("nft:ART", [0u8; 32])).unwrap();
+        assert_eq!(
+            registry.check_spell("nft:ART", &tx, &x, &Data::Empty),
+            Err(AppError::NotInTransaction("nft:ART".to_string()))
+        );
+    }
+    
+    #[test]
+    fn test_app_registry_rejects_malformed_tags() {
+        let mut registry = AppRegistry::new();
+        let register = |registry: &mut AppRegistry, tag: &str| {
+            registry.register(App::new(tag, [0u8; 32])).map(|_| ())
+        };
+        
+        assert_eq!(register(&mut registry, "token:USD-2"), Ok(()));
+        assert_eq!(register(&mut registry, "token:US D"), Err(TagValidationError::InvalidName("US D".to_string())));
+        assert_eq!(register(&mut registry, "token::USD"), Err(TagValidationError::InvalidName(":USD".to_string())));
+        assert_eq!(register(&mut registry, "token:"), Err(TagValidationError::InvalidName(String::new())));
+        assert_eq!(register(&mut registry, "tokenUSD"), Err(TagValidationError::MissingColon));
+        assert_eq!(register(&mut registry, &format!("nft:{}", "a".repeat(61))), Err(TagValidationError::TooLong));
+        assert!(registry.get("token:US D").is_none());
+    }
+    
+    #[test]
+    fn test_checker_registry() {
+        fn vote(app: &App, tx: &Transaction, _x: &Data, _w: &Data) -> bool {
+            // A ballot output must exist; its value is the choice
+            tx.outputs.iter().any(|output| {
+                matches!(output.charm_state.as_ref().and_then(|s| s.get(&app.tag)), Some(Data::U64(_)))
+            })
+        }
+        
+        let vote_app = App::new("vote:ELECTION", [0u8; 32]);
+        let mut tx = Transaction::new([1u8; 32]);
+        tx.outputs.push(TxOutput {
+            index: 0,
+            value: 546,
+            script_pubkey: vec![],
+            charm_state: Some(CharmState::new().with_app("vote:ELECTION", Data::U64(1))),
+        });
+        
+        let mut registry = CheckerRegistry::new();
+        assert!(registry.get("token:ABC").is_some());
+        assert!(!registry.dispatch(&vote_app, &tx, &Data::Empty, &Data::Empty));
+        
+        assert!(registry.register("vote:", vote).is_none());
+        assert!(registry.dispatch(&vote_app, &tx, &Data::Empty, &Data::Empty));
+        assert!(!registry.dispatch(&vote_app, &Transaction::new([1u8; 32]), &Data::Empty, &Data::Empty));
+        
+        // The longest matching prefix wins
+        registry.register("vote:ELECTION", |_, _, _, _| false);
+        assert!(!registry.dispatch(&vote_app, &tx, &Data::Empty, &Data::Empty));
+        assert!(CheckerRegistry::empty().get("token:ABC").is_none());
+    }
+    
+    #[test]
+    fn test_bounty_reward_conserved() {
+        let app = App::new("bounty:BUG42", [0u8; 32]);
+        let hunter = [0x02; 33];
+        
+        let tx = bounty_tx(&app.tag, Some(bounty_state(1, 5_000, &hunter)), bounty_state(2, 9_000, &hunter));
+        assert_eq!(
+            bounty::try_check(&app, &tx, &Data::Empty, &Data::Empty),
+            Err(CheckError::ConservationViolation { input: 5_000, output: 9_000 })
+        );
+        
+        // The payload can't be dropped to escape the check
+        let tx = bounty_tx(&app.tag, Some(bounty_state(1, 5_000, &hunter)), Data::U64(2));
+        assert_eq!(
+            bounty::try_check(&app, &tx, &Data::Empty, &Data::Empty),
+            Err(CheckError::MissingField("reward_amount"))
+        );
+    }
+    
+    #[test]
+    fn test_bounty_in_progress_requires_hunter() {
+        let app = App::new("bounty:BUG42", [0u8; 32]);
+        
+        let tx = bounty_tx(&app.tag, Some(bounty_state(0, 5_000, &[])), bounty_state(1, 5_000, &[]));
+        assert_eq!(
+            bounty::try_check(&app, &tx, &Data::Empty, &Data::Empty),
+            Err(CheckError::MissingField("hunter"))
+        );
+        
+        let tx = bounty_tx(&app.tag, Some(Data::U64(0)), Data::U64(1));
+        assert!(!bounty::check(&app, &tx, &Data::Empty, &Data::Empty));
+    }
+    
+    /// Release of an escrow bundling 500 `token:BTC` and NFT `[9; 32]` for
+    /// `recipient`, with the outputs given as `(script_pubkey, token amount,
+    /// carries the NFT)`
+    fn bundle_release_tx(recipient: &[u8], outputs: &[(&[u8], u64, bool)]) -> Transaction {
+        let mut bundle = std::collections::BTreeMap::new();
+        bundle.insert("token:BTC".to_string(), Data::U64(500));
+        bundle.insert("nft:ART".to_string(), Data::Bytes(vec![9; 32]));
+        let mut escrow = std::collections::BTreeMap::new();
+        escrow.insert("state".to_string(), Data::U64(escrow::EscrowState::Funded.code()));
+        escrow.insert("amount".to_string(), Data::U64(546));
+        escrow.insert("recipient".to_string(), Data::Bytes(recipient.to_vec()));
+        escrow.insert("bundle".to_string(), Data::Map(bundle));
+        
+        let mut tx = Transaction::new([4u8; 32]);
+        tx.inputs.push(TxInput::new(
+            UtxoRef { txid: [3u8; 32], vout: 0 },
+            Some(
+                CharmState::new()
+                    .with_app("escrow:DEAL1", Data::Map(escrow))
+                    .with_app("token:BTC", Data::U64(500))
+                    .with_app("nft:ART", Data::Bytes(vec![9; 32])),
             ),
+        ));
+        tx.outputs.push(TxOutput {
+            index: 0,
+            value: 546,
+            script_pubkey: vec![],
+            charm_state: Some(CharmState::new().with_app("escrow:DEAL1", Data::U64(escrow::EscrowState::Released.code()))),
         });
+        for (i, &(script, amount, nft)) in outputs.iter().enumerate() {
+            let mut state = CharmState::new().with_app("token:BTC", Data::U64(amount));
+            if nft {
+                state = state.with_app("nft:ART", Data::Bytes(vec![9; 32]));
+            }
+            tx.outputs.push(TxOutput {
+                index: i as u32 + 1,
+                value: 546,
+                script_pubkey: script.to_vec(),
+                charm_state: Some(state),
+            });
+        }
+        tx
+    }
+    
+    #[test]
+    fn test_escrow_bundle_release() {
+        let app = App::new("escrow:DEAL1", [0u8; 32]);
+        let recipient = [0x00, 0x14, 0xaa];
+        
+        let tx = bundle_release_tx(&recipient, &[(&recipient, 500, true)]);
+        assert_eq!(escrow::try_check(&app, &tx, &Data::Empty, &Data::Empty), Ok(()));
+        
+        // Tokens may arrive split across several recipient outputs
+        let tx = bundle_release_tx(&recipient, &[(&recipient, 200, false), (&recipient, 300, true)]);
+        assert_eq!(escrow::try_check(&app, &tx, &Data::Empty, &Data::Empty), Ok(()));
+    }
+    
+    #[test]
+    fn test_escrow_bundle_nft_withheld_rejected() {
+        let app = App::new("escrow:DEAL1", [0u8; 32]);
+        let recipient = [0x00, 0x14, 0xaa];
+        let other = [0x00, 0x14, 0xbb];
+        
+        let tx = bundle_release_tx(&recipient, &[(&recipient, 500, false), (&other, 0, true)]);
+        assert_eq!(
+            escrow::try_check(&app, &tx, &Data::Empty, &Data::Empty),
+            Err(CheckError::UndeliveredAsset("nft:ART".to_string()))
+        );
+        
+        let tx = bundle_release_tx(&recipient, &[(&recipient, 499, true)]);
+        assert_eq!(
+            escrow::try_check(&app, &tx, &Data::Empty, &Data::Empty),
+            Err(CheckError::UndeliveredAsset("token:BTC".to_string()))
+        );
+    }
+    
+    /// Build a mint of 1000 bollar with `collateral` sats of `oracle:BTC`
+    /// locked in its outputs
+    fn bollar_tx(collateral: u64) -> Transaction {
+        let mut tx = Transaction::new([0u8; 32]);
+        tx.inputs.push(TxInput::new(UtxoRef { txid: [0u8; 32], vout: 0 }, None));
         tx.outputs.push(TxOutput {
-            index: 1,
+            index: 0,
             value: 546,
             script_pubkey: vec![],
             charm_state: Some(
-                CharmState::new().with_app("test-token", Data::U64(400))
+                CharmState::new()
+                    .with_app("bollar:USD", Data::U64(1000))
+                    .with_app("oracle:BTC", Data::U64(collateral))
             ),
         });
+        tx
+    }
+    
+    fn bollar_app() -> App {
+        let params = bollar::BollarParams {
+            collateral_ratio_bps: 15_000,
+            oracle_tag: "oracle:BTC".to_string(),
+        };
+        App::with_params("bollar:USD", [0u8; 32], params.to_params())
+    }
+    
+    #[test]
+    fn test_bollar_params_roundtrip() {
+        let app = bollar_app();
+        let params = bollar::BollarParams::from_app(&app).unwrap();
+        assert_eq!(params.collateral_ratio_bps, 15_000);
+        assert_eq!(params.oracle_tag, "oracle:BTC");
+        assert_eq!(bollar::BollarParams::from_app(&App::new("bollar:USD", [0u8; 32])), None);
+    }
+    
+    #[test]
+    fn test_bollar_overcollateralized() {
+        let app = bollar_app();
+        let auth = Data::Bytes(vec![1]);
         
-        let auth = Data::Bytes(vec![1, 2, 3]); // Mock authorization
+        assert!(bollar::check(&app, &bollar_tx(1500), &auth, &Data::Empty));
+        assert!(bollar::check(&app, &bollar_tx(2000), &auth, &Data::Empty));
+        assert!(CheckerRegistry::new().dispatch(&app, &bollar_tx(2000), &auth, &Data::Empty));
+    }
+    
+    #[test]
+    fn test_bollar_undercollateralized() {
+        let app = bollar_app();
+        let auth = Data::Bytes(vec![1]);
         
-        assert!(token::check(&app, &tx, &auth, &Data::Empty));
+        assert_eq!(
+            bollar::try_check(&app, &bollar_tx(1499), &auth, &Data::Empty),
+            Err(CheckError::Undercollateralized { required: 1500, collateral: 1499 })
+        );
+        assert!(!CheckerRegistry::new().dispatch(&app, &bollar_tx(0), &auth, &Data::Empty));
+    }
+    
+    #[test]
+    fn test_bollar_requires_conservation() {
+        let app = bollar_app();
+        let auth = Data::Bytes(vec![1]);
+        let mut tx = bollar_tx(10_000);
+        tx.inputs[0].charm_state = Some(CharmState::new().with_app("bollar:USD", Data::U64(1000)));
+        tx.outputs[0].charm_state = Some(
+            CharmState::new()
+                .with_app("bollar:USD", Data::U64(1001))
+                .with_app("oracle:BTC", Data::U64(10_000))
+        );
+        
+        assert_eq!(
+            bollar::try_check(&app, &tx, &auth, &Data::Empty),
+            Err(CheckError::ConservationViolation { input: 1000, output: 1001 })
+        );
+    }
+    
+    #[test]
+    fn test_bollar_mint_needs_authorization() {
+        let app = bollar_app();
+        assert_eq!(
+            bollar::try_check(&app, &bollar_tx(1500), &Data::Bytes(vec![]), &Data::Empty),
+            Err(CheckError::MissingAuthorization)
+        );
     }
 }
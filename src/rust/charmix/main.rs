@@ -3,6 +3,7 @@
 //! This binary reads spell data from stdin and validates it using the
 //! appropriate spell checker based on the app configuration.
 
+use charmix::AppRegistry;
 use charms_sdk::data::{App, Data, Transaction};
 
 /// Main entry point using the charmix macro
@@ -15,27 +16,34 @@ fn main() {
         charms_sdk::data::util::read(std::io::stdin())
             .expect("should deserialize (app, tx, x, w): (App, Transaction, Data, Data)");
     
-    // Route to appropriate checker based on app tag
-    let result = match app.tag.as_str() {
-        tag if tag.starts_with("token:") => charmix::token::check(&app, &tx, &x, &w),
-        tag if tag.starts_with("nft:") => charmix::nft::check(&app, &tx, &x, &w),
-        tag if tag.starts_with("escrow:") => charmix::escrow::check(&app, &tx, &x, &w),
-        _ => {
-            eprintln!("Unknown app type: {}", app.tag);
-            false
-        }
-    };
+    let tag = app.tag.clone();
+    let result = verify(app, &tx, &x, &w);
     
-    assert!(result, "Spell verification failed for app: {}", app.tag);
+    assert!(result, "Spell verification failed for app: {}", tag);
     
     println!("✓ Spell verified successfully");
 }
 
-/// Alternative main using macro pattern (commented for reference)
-/// 
-/// ```rust
-/// charmix::main!(charmix::token::check);
-/// ```
+/// Register the app and route the spell to its checker through an `AppRegistry`
+fn verify(app: App, tx: &Transaction, x: &Data, w: &Data) -> bool {
+    let tag = app.tag.clone();
+    let mut registry = AppRegistry::new();
+    if let Err(e) = registry.register(app) {
+        eprintln!("invalid app tag {}: {}", tag, e);
+        return false;
+    }
+    
+    registry.check_spell(&tag, tx, x, w).unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        false
+    })
+}
+
+// Alternative main using macro pattern (commented for reference)
+//
+// ```rust
+// charmix::main!(charmix::token::check);
+// ```
 
 #[cfg(test)]
 mod tests {
@@ -48,12 +56,10 @@ mod tests {
         let mut tx = Transaction::new([1u8; 32]);
         
         // Input: 1000 tokens
-        tx.inputs.push(TxInput {
-            utxo_ref: UtxoRef { txid: [0u8; 32], vout: 0 },
-            charm_state: Some(
-                CharmState::new().with_app("token:TEST", Data::U64(1000))
-            ),
-        });
+        tx.inputs.push(TxInput::new(
+            UtxoRef { txid: [0u8; 32], vout: 0 },
+            Some(CharmState::new().with_app("token:TEST", Data::U64(1000))),
+        ));
         
         // Output: 1000 tokens (split)
         tx.outputs.push(TxOutput {
@@ -85,16 +91,24 @@ mod tests {
         assert!(charmix::token::check(&app, &tx, &x, &w));
     }
     
+    #[test]
+    fn test_verify_routes_through_registry() {
+        let (app, tx, x, w) = create_test_token_tx();
+        assert!(verify(app.clone(), &tx, &x, &w));
+        assert!(!verify(app.clone(), &tx, &Data::Bytes(vec![]), &w));
+        
+        // Neither an app the transaction doesn't carry nor a malformed tag passes
+        assert!(!verify(App::new("nft:ART", [0u8; 32]), &tx, &x, &w));
+        assert!(!verify(App::new("vote:USD", [0u8; 32]), &tx, &x, &w));
+    }
+    
     #[test]
     fn test_token_mint_detection() {
         let app = App::new("token:MINT", [0u8; 32]);
         let mut tx = Transaction::new([2u8; 32]);
         
         // No inputs with tokens
-        tx.inputs.push(TxInput {
-            utxo_ref: UtxoRef { txid: [0u8; 32], vout: 0 },
-            charm_state: None,
-        });
+        tx.inputs.push(TxInput::new(UtxoRef { txid: [0u8; 32], vout: 0 }, None));
         
         // Output with new tokens
         tx.outputs.push(TxOutput {
@@ -115,20 +129,21 @@ mod tests {
         let mut tx = Transaction::new([3u8; 32]);
         
         // Input: Created state (0)
-        tx.inputs.push(TxInput {
-            utxo_ref: UtxoRef { txid: [0u8; 32], vout: 0 },
-            charm_state: Some(
-                CharmState::new().with_app("escrow:CONTRACT1", Data::U64(0))
-            ),
-        });
+        tx.inputs.push(TxInput::new(
+            UtxoRef { txid: [0u8; 32], vout: 0 },
+            Some(CharmState::new().with_app("escrow:CONTRACT1", Data::U64(0))),
+        ));
         
-        // Output: Funded state (1)
+        // Output: Funded state (1), recording the 100,000 sats it locks
+        let mut funded = std::collections::BTreeMap::new();
+        funded.insert("state".to_string(), Data::U64(1));
+        funded.insert("amount".to_string(), Data::U64(100_000));
         tx.outputs.push(TxOutput {
             index: 0,
             value: 100_000,
             script_pubkey: vec![],
             charm_state: Some(
-                CharmState::new().with_app("escrow:CONTRACT1", Data::U64(1))
+                CharmState::new().with_app("escrow:CONTRACT1", Data::Map(funded))
             ),
         });
         
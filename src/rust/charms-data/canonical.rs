@@ -0,0 +1,400 @@
+//! Canonical byte encoding for content-addressed hashes
+//! 
+//! Struct fields are written in declaration order and maps and sets in their
+//! (sorted) iteration order. Integers are fixed-width little-endian, lengths
+//! are `u64`, an `Option` is a `0`/`1` byte followed by its value, and a
+//! `Data` value is a one-byte variant index followed by its contents; an
+//! `F64` is the exception, written as its IEEE 754 big-endian bytes. There
+//! is no version header. Only `NormalizedSpell` is ever decoded, and decoding
+//! rejects anything `encode` would not have produced.
+
+use super::*;
+use sha2::{Digest, Sha256};
+
+/// Types with a single canonical encoding
+pub(crate) trait Canonical {
+    fn encode(&self, out: &mut Vec<u8>);
+}
+
+/// Types that can be read back from their canonical encoding
+pub(crate) trait CanonicalDecode: Sized {
+    fn decode(reader: &mut Reader<'_>) -> Result<Self, CanonicalDecodeError>;
+}
+
+/// The canonical encoding of `value`
+pub(crate) fn encode<T: Canonical>(value: &T) -> Vec<u8> {
+    let mut bytes = Vec::new();
+    value.encode(&mut bytes);
+    bytes
+}
+
+/// sha256 of the canonical encoding of `value`
+pub(crate) fn hash<T: Canonical>(value: &T) -> [u8; 32] {
+    Sha256::digest(encode(value)).into()
+}
+
+/// Decode a value that must span all of `bytes`
+pub(crate) fn decode<T: CanonicalDecode>(bytes: &[u8]) -> Result<T, CanonicalDecodeError> {
+    let mut reader = Reader { bytes };
+    let value = T::decode(&mut reader)?;
+    if !reader.bytes.is_empty() {
+        return Err(CanonicalDecodeError::TrailingBytes);
+    }
+    Ok(value)
+}
+
+/// Cursor over the bytes being decoded
+pub(crate) struct Reader<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, n: usize) -> Result<&'a [u8], CanonicalDecodeError> {
+        if n > self.bytes.len() {
+            return Err(CanonicalDecodeError::UnexpectedEnd);
+        }
+        let (head, rest) = self.bytes.split_at(n);
+        self.bytes = rest;
+        Ok(head)
+    }
+    
+    fn byte(&mut self) -> Result<u8, CanonicalDecodeError> {
+        Ok(self.take(1)?[0])
+    }
+    
+    fn array<const N: usize>(&mut self) -> Result<[u8; N], CanonicalDecodeError> {
+        self.take(N)?.try_into().map_err(|_| CanonicalDecodeError::UnexpectedEnd)
+    }
+    
+    /// A length prefix; lengths past the end of the input fail on use
+    fn len(&mut self) -> Result<usize, CanonicalDecodeError> {
+        usize::try_from(u64::decode(self)?).map_err(|_| CanonicalDecodeError::UnexpectedEnd)
+    }
+    
+    fn bytes(&mut self) -> Result<Vec<u8>, CanonicalDecodeError> {
+        let len = self.len()?;
+        Ok(self.take(len)?.to_vec())
+    }
+    
+    fn string(&mut self) -> Result<String, CanonicalDecodeError> {
+        String::from_utf8(self.bytes()?).map_err(|_| CanonicalDecodeError::InvalidUtf8)
+    }
+    
+    /// `len` items, without trusting `len` to pre-allocate
+    fn seq<T>(
+        &mut self,
+        mut item: impl FnMut(&mut Self) -> Result<T, CanonicalDecodeError>,
+    ) -> Result<Vec<T>, CanonicalDecodeError> {
+        let len = self.len()?;
+        let mut items = Vec::new();
+        for _ in 0..len {
+            items.push(item(self)?);
+        }
+        Ok(items)
+    }
+    
+    /// A map whose keys must be strictly increasing
+    fn map<V>(
+        &mut self,
+        mut value: impl FnMut(&mut Self) -> Result<V, CanonicalDecodeError>,
+    ) -> Result<BTreeMap<String, V>, CanonicalDecodeError> {
+        let entries = self.seq(|reader| Ok((reader.string()?, value(reader)?)))?;
+        if !entries.windows(2).all(|pair| pair[0].0 < pair[1].0) {
+            return Err(CanonicalDecodeError::NonCanonical);
+        }
+        Ok(entries.into_iter().collect())
+    }
+}
+
+impl Canonical for u32 {
+    fn encode(&self, out: &mut Vec<u8>) {
+        out.extend_from_slice(&self.to_le_bytes());
+    }
+}
+
+impl Canonical for u64 {
+    fn encode(&self, out: &mut Vec<u8>) {
+        out.extend_from_slice(&self.to_le_bytes());
+    }
+}
+
+impl Canonical for [u8] {
+    fn encode(&self, out: &mut Vec<u8>) {
+        (self.len() as u64).encode(out);
+        out.extend_from_slice(self);
+    }
+}
+
+impl Canonical for str {
+    fn encode(&self, out: &mut Vec<u8>) {
+        self.as_bytes().encode(out);
+    }
+}
+
+impl Canonical for String {
+    fn encode(&self, out: &mut Vec<u8>) {
+        self.as_str().encode(out);
+    }
+}
+
+impl<T: Canonical> Canonical for Option<T> {
+    fn encode(&self, out: &mut Vec<u8>) {
+        match self {
+            None => out.push(0),
+            Some(value) => {
+                out.push(1);
+                value.encode(out);
+            }
+        }
+    }
+}
+
+impl<T: Canonical> Canonical for Vec<T> {
+    fn encode(&self, out: &mut Vec<u8>) {
+        (self.len() as u64).encode(out);
+        for item in self {
+            item.encode(out);
+        }
+    }
+}
+
+impl<V: Canonical> Canonical for BTreeMap<String, V> {
+    fn encode(&self, out: &mut Vec<u8>) {
+        (self.len() as u64).encode(out);
+        for (key, value) in self {
+            key.encode(out);
+            value.encode(out);
+        }
+    }
+}
+
+impl Canonical for BTreeSet<String> {
+    fn encode(&self, out: &mut Vec<u8>) {
+        (self.len() as u64).encode(out);
+        for item in self {
+            item.encode(out);
+        }
+    }
+}
+
+impl Canonical for Data {
+    fn encode(&self, out: &mut Vec<u8>) {
+        match self {
+            Data::Empty => out.push(0),
+            Data::Bool(b) => {
+                out.push(1);
+                out.push(*b as u8);
+            }
+            Data::U64(n) => {
+                out.push(2);
+                n.encode(out);
+            }
+            Data::I64(n) => {
+                out.push(3);
+                out.extend_from_slice(&n.to_le_bytes());
+            }
+            Data::Bytes(bytes) => {
+                out.push(4);
+                bytes.as_slice().encode(out);
+            }
+            Data::String(s) => {
+                out.push(5);
+                s.encode(out);
+            }
+            Data::List(items) => {
+                out.push(6);
+                items.encode(out);
+            }
+            Data::Map(map) => {
+                out.push(7);
+                map.encode(out);
+            }
+            Data::F64(n) => {
+                out.push(8);
+                out.extend_from_slice(&n.to_be_bytes());
+            }
+        }
+    }
+}
+
+impl Canonical for UtxoRef {
+    fn encode(&self, out: &mut Vec<u8>) {
+        out.extend_from_slice(&self.txid);
+        self.vout.encode(out);
+    }
+}
+
+impl Canonical for CharmState {
+    fn encode(&self, out: &mut Vec<u8>) {
+        self.apps.encode(out);
+    }
+}
+
+impl Canonical for TxInput {
+    fn encode(&self, out: &mut Vec<u8>) {
+        self.utxo_ref.encode(out);
+        self.value.encode(out);
+        self.script_pubkey.as_slice().encode(out);
+        self.script_sig.as_slice().encode(out);
+        self.sequence.encode(out);
+        self.charm_state.encode(out);
+    }
+}
+
+impl Canonical for TxOutput {
+    fn encode(&self, out: &mut Vec<u8>) {
+        self.index.encode(out);
+        self.value.encode(out);
+        self.script_pubkey.as_slice().encode(out);
+        self.charm_state.encode(out);
+    }
+}
+
+impl Canonical for SpellInput {
+    fn encode(&self, out: &mut Vec<u8>) {
+        self.utxo_ref.encode(out);
+        self.charms.encode(out);
+    }
+}
+
+impl Canonical for SpellOutput {
+    fn encode(&self, out: &mut Vec<u8>) {
+        self.index.encode(out);
+        self.charms.encode(out);
+    }
+}
+
+impl Canonical for NormalizedSpell {
+    fn encode(&self, out: &mut Vec<u8>) {
+        self.version.encode(out);
+        self.ins.encode(out);
+        self.outs.encode(out);
+        self.nonces.encode(out);
+        self.genesis.encode(out);
+        self.valid_before.encode(out);
+    }
+}
+
+impl Canonical for Transaction {
+    fn encode(&self, out: &mut Vec<u8>) {
+        out.extend_from_slice(&self.txid);
+        self.inputs.encode(out);
+        self.outputs.encode(out);
+        self.spell.encode(out);
+        self.locktime.encode(out);
+        self.version.encode(out);
+    }
+}
+
+impl CanonicalDecode for u32 {
+    fn decode(reader: &mut Reader<'_>) -> Result<Self, CanonicalDecodeError> {
+        Ok(u32::from_le_bytes(reader.array()?))
+    }
+}
+
+impl CanonicalDecode for u64 {
+    fn decode(reader: &mut Reader<'_>) -> Result<Self, CanonicalDecodeError> {
+        Ok(u64::from_le_bytes(reader.array()?))
+    }
+}
+
+impl<T: CanonicalDecode> CanonicalDecode for Option<T> {
+    fn decode(reader: &mut Reader<'_>) -> Result<Self, CanonicalDecodeError> {
+        match reader.byte()? {
+            0 => Ok(None),
+            1 => T::decode(reader).map(Some),
+            tag => Err(CanonicalDecodeError::InvalidTag(tag)),
+        }
+    }
+}
+
+impl CanonicalDecode for Data {
+    fn decode(reader: &mut Reader<'_>) -> Result<Self, CanonicalDecodeError> {
+        decode_data(reader, MAX_DATA_DEPTH)
+    }
+}
+
+/// Decode `Data`, allowing `remaining` more levels of nesting
+fn decode_data(reader: &mut Reader<'_>, remaining: usize) -> Result<Data, CanonicalDecodeError> {
+    let nested = |reader: &mut Reader<'_>| match remaining.checked_sub(1) {
+        Some(remaining) => decode_data(reader, remaining),
+        None => Err(CanonicalDecodeError::TooDeep),
+    };
+    
+    match reader.byte()? {
+        0 => Ok(Data::Empty),
+        1 => match reader.byte()? {
+            0 => Ok(Data::Bool(false)),
+            1 => Ok(Data::Bool(true)),
+            b => Err(CanonicalDecodeError::InvalidTag(b)),
+        },
+        2 => u64::decode(reader).map(Data::U64),
+        3 => Ok(Data::I64(i64::from_le_bytes(reader.array()?))),
+        4 => reader.bytes().map(Data::Bytes),
+        5 => reader.string().map(Data::String),
+        6 => reader.seq(nested).map(Data::List),
+        7 => reader.map(nested).map(Data::Map),
+        8 => Ok(Data::F64(f64::from_be_bytes(reader.array()?))),
+        tag => Err(CanonicalDecodeError::InvalidTag(tag)),
+    }
+}
+
+impl CanonicalDecode for UtxoRef {
+    fn decode(reader: &mut Reader<'_>) -> Result<Self, CanonicalDecodeError> {
+        Ok(UtxoRef {
+            txid: reader.array()?,
+            vout: u32::decode(reader)?,
+        })
+    }
+}
+
+impl CanonicalDecode for CharmState {
+    fn decode(reader: &mut Reader<'_>) -> Result<Self, CanonicalDecodeError> {
+        Ok(CharmState { apps: reader.map(Data::decode)? })
+    }
+}
+
+impl CanonicalDecode for SpellInput {
+    fn decode(reader: &mut Reader<'_>) -> Result<Self, CanonicalDecodeError> {
+        Ok(SpellInput {
+            utxo_ref: UtxoRef::decode(reader)?,
+            charms: Option::decode(reader)?,
+        })
+    }
+}
+
+impl CanonicalDecode for SpellOutput {
+    fn decode(reader: &mut Reader<'_>) -> Result<Self, CanonicalDecodeError> {
+        Ok(SpellOutput {
+            index: u32::decode(reader)?,
+            charms: Option::decode(reader)?,
+        })
+    }
+}
+
+/// Decodes `NormalizedSpell::to_canonical_bytes` output, so inputs and
+/// outputs must already be sorted
+impl CanonicalDecode for NormalizedSpell {
+    fn decode(reader: &mut Reader<'_>) -> Result<Self, CanonicalDecodeError> {
+        let version = u32::decode(reader)?;
+        let ins = reader.seq(SpellInput::decode)?;
+        let outs = reader.seq(SpellOutput::decode)?;
+        let nonces = reader.map(u64::decode)?;
+        // A set is encoded like a map without values
+        let genesis = reader.map(|_| Ok(()))?.into_keys().collect();
+        let valid_before = Option::decode(reader)?;
+        
+        let sorted = ins.windows(2).all(|pair| input_order(&pair[0]) <= input_order(&pair[1]))
+            && outs.windows(2).all(|pair| pair[0].index <= pair[1].index);
+        if !sorted {
+            return Err(CanonicalDecodeError::NonCanonical);
+        }
+        
+        Ok(NormalizedSpell { version, ins, outs, nonces, genesis, valid_before })
+    }
+}
+
+/// Canonical position of a spell input: by txid, then vout
+pub(crate) fn input_order(input: &SpellInput) -> ([u8; 32], u32) {
+    (input.utxo_ref.txid, input.utxo_ref.vout)
+}
@@ -0,0 +1,241 @@
+//! Deterministic CBOR encoding for `Data`
+//! 
+//! Every `Data` value has exactly one encoding: map keys are written in
+//! lexicographic order (the `BTreeMap` order), all lengths are definite and
+//! integers use their shortest form. Signed integers are wrapped in the
+//! `I64_TAG` tag so they decode back to `Data::I64` rather than `Data::U64`.
+//! Floats are written in the shortest width that holds them exactly.
+//! Decoding rejects nesting deeper than `MAX_DATA_DEPTH`.
+
+use super::{Data, MAX_DATA_DEPTH};
+use ciborium::value::{Integer, Value};
+use alloc::collections::BTreeMap;
+use core::fmt;
+
+/// CBOR tag marking a `Data::I64`
+pub const I64_TAG: u64 = 65_600;
+
+/// Errors decoding `Data` from CBOR
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CborError {
+    /// The bytes are not well-formed CBOR
+    Malformed(String),
+    /// Well-formed CBOR with no `Data` equivalent
+    Unsupported(&'static str),
+    /// A map has the same key twice
+    DuplicateKey(String),
+    /// Bytes left over after the encoded value
+    TrailingBytes(usize),
+    /// Lists and maps nested deeper than `MAX_DATA_DEPTH`
+    TooDeep,
+}
+
+impl fmt::Display for CborError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CborError::Malformed(msg) => write!(f, "malformed CBOR: {}", msg),
+            CborError::Unsupported(what) => write!(f, "unsupported CBOR item: {}", what),
+            CborError::DuplicateKey(key) => write!(f, "duplicate map key {:?}", key),
+            CborError::TrailingBytes(len) => write!(f, "{} trailing bytes after CBOR value", len),
+            CborError::TooDeep => write!(f, "nesting exceeds maximum depth {}", MAX_DATA_DEPTH),
+        }
+    }
+}
+
+impl core::error::Error for CborError {}
+
+impl Data {
+    /// Encode as deterministic CBOR
+    pub fn to_cbor(&self) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        ciborium::ser::into_writer(&to_value(self), &mut bytes)
+            .expect("writing CBOR to a Vec cannot fail");
+        bytes
+    }
+    
+    /// Decode from CBOR, rejecting anything after the first value
+    pub fn from_cbor(bytes: &[u8]) -> Result<Data, CborError> {
+        let mut reader = bytes;
+        let value: Value = ciborium::de::from_reader(&mut reader)
+            .map_err(|e| CborError::Malformed(e.to_string()))?;
+        if !reader.is_empty() {
+            return Err(CborError::TrailingBytes(reader.len()));
+        }
+        from_value(value, MAX_DATA_DEPTH)
+    }
+}
+
+fn to_value(data: &Data) -> Value {
+    match data {
+        Data::Empty => Value::Null,
+        Data::Bool(b) => Value::Bool(*b),
+        Data::U64(n) => Value::Integer(Integer::from(*n)),
+        Data::I64(n) => Value::Tag(I64_TAG, Box::new(Value::Integer(Integer::from(*n)))),
+        Data::Bytes(bytes) => Value::Bytes(bytes.clone()),
+        Data::String(s) => Value::Text(s.clone()),
+        Data::List(items) => Value::Array(items.iter().map(to_value).collect()),
+        Data::Map(map) => Value::Map(
+            map.iter()
+                .map(|(key, value)| (Value::Text(key.clone()), to_value(value)))
+                .collect(),
+        ),
+        Data::F64(n) => Value::Float(*n),
+    }
+}
+
+/// Convert a decoded value, allowing `remaining` more levels of nesting
+fn from_value(value: Value, remaining: usize) -> Result<Data, CborError> {
+    let nested = || remaining.checked_sub(1).ok_or(CborError::TooDeep);
+    
+    match value {
+        Value::Null => Ok(Data::Empty),
+        Value::Bool(b) => Ok(Data::Bool(b)),
+        Value::Integer(n) => u64::try_from(n)
+            .map(Data::U64)
+            .map_err(|_| CborError::Unsupported("negative integer without i64 tag")),
+        Value::Tag(I64_TAG, inner) => match *inner {
+            Value::Integer(n) => i64::try_from(n)
+                .map(Data::I64)
+                .map_err(|_| CborError::Unsupported("integer out of i64 range")),
+            _ => Err(CborError::Unsupported("i64 tag on a non-integer")),
+        },
+        Value::Bytes(bytes) => Ok(Data::Bytes(bytes)),
+        Value::Text(s) => Ok(Data::String(s)),
+        Value::Array(items) => {
+            let remaining = nested()?;
+            items.into_iter()
+                .map(|item| from_value(item, remaining))
+                .collect::<Result<_, _>>()
+                .map(Data::List)
+        }
+        Value::Map(entries) => {
+            let remaining = nested()?;
+            let mut map = BTreeMap::new();
+            for (key, value) in entries {
+                let Value::Text(key) = key else {
+                    return Err(CborError::Unsupported("non-string map key"));
+                };
+                if map.contains_key(&key) {
+                    return Err(CborError::DuplicateKey(key));
+                }
+                map.insert(key, from_value(value, remaining)?);
+            }
+            Ok(Data::Map(map))
+        }
+        Value::Float(n) => Ok(Data::F64(n)),
+        Value::Tag(..) => Err(CborError::Unsupported("tag")),
+        _ => Err(CborError::Unsupported("unknown item")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    fn round_trip(data: Data) {
+        let bytes = data.to_cbor();
+        assert_eq!(Data::from_cbor(&bytes), Ok(data));
+    }
+    
+    #[test]
+    fn test_round_trip_all_variants() {
+        round_trip(Data::Empty);
+        round_trip(Data::Bool(true));
+        round_trip(Data::Bool(false));
+        round_trip(Data::U64(0));
+        round_trip(Data::U64(u64::MAX));
+        round_trip(Data::I64(5));
+        round_trip(Data::I64(i64::MIN));
+        round_trip(Data::Bytes(vec![]));
+        round_trip(Data::Bytes(vec![0xde, 0xad, 0xbe, 0xef]));
+        round_trip(Data::String("charms".to_string()));
+        round_trip(Data::List(vec![Data::U64(1), Data::String("a".to_string()), Data::Empty]));
+        
+        let mut map = BTreeMap::new();
+        map.insert("amount".to_string(), Data::U64(1000));
+        map.insert("owner".to_string(), Data::Bytes(vec![1; 33]));
+        round_trip(Data::Map(map));
+    }
+    
+    #[test]
+    fn test_known_encodings() {
+        assert_eq!(Data::U64(1000).to_cbor(), vec![0x19, 0x03, 0xe8]);
+        assert_eq!(Data::Empty.to_cbor(), vec![0xf6]);
+        assert_eq!(Data::List(vec![]).to_cbor(), vec![0x80]);
+    }
+    
+    #[test]
+    fn test_map_encoding_independent_of_insertion_order() {
+        let mut a = BTreeMap::new();
+        a.insert("zeta".to_string(), Data::U64(1));
+        a.insert("alpha".to_string(), Data::U64(2));
+        a.insert("mid".to_string(), Data::Bool(true));
+        
+        let mut b = BTreeMap::new();
+        b.insert("mid".to_string(), Data::Bool(true));
+        b.insert("alpha".to_string(), Data::U64(2));
+        b.insert("zeta".to_string(), Data::U64(1));
+        
+        assert_eq!(Data::Map(a).to_cbor(), Data::Map(b).to_cbor());
+    }
+    
+    #[test]
+    fn test_deeply_nested_round_trip() {
+        let mut data = Data::U64(42);
+        for depth in 0..MAX_DATA_DEPTH {
+            data = if depth % 2 == 0 {
+                Data::List(vec![data])
+            } else {
+                let mut map = BTreeMap::new();
+                map.insert(format!("level{}", depth), data);
+                Data::Map(map)
+            };
+        }
+        round_trip(data.clone());
+        
+        let too_deep = Data::List(vec![data]);
+        assert_eq!(Data::from_cbor(&too_deep.to_cbor()), Err(CborError::TooDeep));
+    }
+    
+    #[test]
+    fn test_size_estimate_tracks_cbor() {
+        let mut owner = BTreeMap::new();
+        owner.insert("pubkey".to_string(), Data::Bytes(vec![2; 33]));
+        owner.insert("name".to_string(), Data::String("satoshi".repeat(10)));
+        
+        let mut map = BTreeMap::new();
+        map.insert("owner".to_string(), Data::Map(owner));
+        map.insert("image".to_string(), Data::Bytes(vec![0xab; 1_000]));
+        map.insert("amounts".to_string(), Data::List(vec![Data::U64(u64::MAX); 20]));
+        
+        let samples = [
+            Data::Bytes(vec![7; 300]),
+            Data::String("charm".repeat(100)),
+            Data::List(vec![Data::Bytes(vec![1; 64]); 16]),
+            Data::Map(map),
+        ];
+        for data in samples {
+            let estimate = data.size_estimate();
+            let actual = data.to_cbor().len();
+            assert!(estimate.abs_diff(actual) * 10 <= actual, "{} vs {} for {:?}", estimate, actual, data);
+        }
+    }
+    
+    #[test]
+    fn test_rejects_trailing_and_unsupported() {
+        let mut bytes = Data::U64(1).to_cbor();
+        bytes.push(0x00);
+        assert_eq!(Data::from_cbor(&bytes), Err(CborError::TrailingBytes(1)));
+        
+        // Half-precision float 1.0
+        assert_eq!(Data::from_cbor(&[0xf9, 0x3c, 0x00]), Ok(Data::F64(1.0)));
+        
+        // Tag 1 (epoch time)
+        assert!(matches!(Data::from_cbor(&[0xc1, 0x00]), Err(CborError::Unsupported(_))));
+        
+        // Untagged negative integer -1
+        assert!(matches!(Data::from_cbor(&[0x20]), Err(CborError::Unsupported(_))));
+        
+        assert!(matches!(Data::from_cbor(&[0x19, 0x03]), Err(CborError::Malformed(_))));
+    }
+}
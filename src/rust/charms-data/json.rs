@@ -0,0 +1,174 @@
+//! Tagged JSON encoding for `Data`
+//! 
+//! Values are written as `{"type": <variant>, "value": <contents>}`, the same
+//! shape `WasmData` uses, so native and WASM code exchange identical JSON.
+//! `Empty` has no `"value"`, `Bytes` are lowercase hex and `U64`/`I64`/`F64`
+//! are JSON numbers; a NaN or infinite `F64` is written as `null` and can't
+//! be read back. Decoding rejects nesting deeper than `MAX_DATA_DEPTH`.
+
+use super::{hex, CheckError, Data, MAX_DATA_DEPTH};
+use serde_json::{json, Map, Value};
+use alloc::collections::BTreeMap;
+
+impl Data {
+    /// Encode as tagged JSON
+    pub fn to_json_value(&self) -> Value {
+        match self {
+            Data::Empty => json!({ "type": "Empty" }),
+            Data::Bool(b) => json!({ "type": "Bool", "value": b }),
+            Data::U64(n) => json!({ "type": "U64", "value": n }),
+            Data::I64(n) => json!({ "type": "I64", "value": n }),
+            Data::F64(n) => json!({ "type": "F64", "value": n }),
+            Data::Bytes(bytes) => json!({ "type": "Bytes", "value": hex::encode(bytes) }),
+            Data::String(s) => json!({ "type": "String", "value": s }),
+            Data::List(items) => json!({
+                "type": "List",
+                "value": items.iter().map(Data::to_json_value).collect::<Vec<_>>(),
+            }),
+            Data::Map(map) => json!({
+                "type": "Map",
+                "value": map.iter()
+                    .map(|(key, value)| (key.clone(), value.to_json_value()))
+                    .collect::<Map<_, _>>(),
+            }),
+        }
+    }
+    
+    /// Decode from tagged JSON
+    pub fn from_json_value(v: &Value) -> Result<Data, CheckError> {
+        from_value(v, MAX_DATA_DEPTH)
+    }
+}
+
+fn invalid(msg: impl Into<String>) -> CheckError {
+    CheckError::InvalidJson(msg.into())
+}
+
+fn from_value(v: &Value, remaining: usize) -> Result<Data, CheckError> {
+    let object = v.as_object().ok_or_else(|| invalid("expected an object"))?;
+    let tag = object.get("type")
+        .and_then(Value::as_str)
+        .ok_or_else(|| invalid("missing \"type\""))?;
+    let value = object.get("value");
+    let contents = || value.ok_or_else(|| invalid(format!("missing \"value\" for {}", tag)));
+    let mismatch = || invalid(format!("invalid \"value\" for {}", tag));
+    
+    let nested = |v: &Value| match remaining.checked_sub(1) {
+        Some(remaining) => from_value(v, remaining),
+        None => Err(invalid("nesting exceeds maximum depth")),
+    };
+    
+    match tag {
+        "Empty" => Ok(Data::Empty),
+        "Bool" => contents()?.as_bool().map(Data::Bool).ok_or_else(mismatch),
+        "U64" => contents()?.as_u64().map(Data::U64).ok_or_else(mismatch),
+        "I64" => contents()?.as_i64().map(Data::I64).ok_or_else(mismatch),
+        "F64" => contents()?.as_f64().map(Data::F64).ok_or_else(mismatch),
+        "Bytes" => contents()?.as_str()
+            .and_then(hex::decode)
+            .map(Data::Bytes)
+            .ok_or_else(mismatch),
+        "String" => contents()?.as_str()
+            .map(|s| Data::String(s.to_string()))
+            .ok_or_else(mismatch),
+        "List" => contents()?.as_array()
+            .ok_or_else(mismatch)?
+            .iter()
+            .map(nested)
+            .collect::<Result<_, _>>()
+            .map(Data::List),
+        "Map" => contents()?.as_object()
+            .ok_or_else(mismatch)?
+            .iter()
+            .map(|(key, value)| Ok((key.clone(), nested(value)?)))
+            .collect::<Result<BTreeMap<_, _>, _>>()
+            .map(Data::Map),
+        _ => Err(invalid(format!("unknown type {:?}", tag))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    fn round_trip(data: Data) {
+        assert_eq!(Data::from_json_value(&data.to_json_value()), Ok(data));
+    }
+    
+    #[test]
+    fn test_round_trip_every_variant() {
+        round_trip(Data::Empty);
+        round_trip(Data::Bool(true));
+        round_trip(Data::U64(u64::MAX));
+        round_trip(Data::I64(i64::MIN));
+        round_trip(Data::Bytes(vec![]));
+        round_trip(Data::Bytes(vec![0x00, 0xab, 0xff]));
+        round_trip(Data::String("spell".to_string()));
+        round_trip(Data::List(vec![Data::U64(1), Data::Empty]));
+        
+        let mut inner = BTreeMap::new();
+        inner.insert("id".to_string(), Data::Bytes(vec![0x42; 32]));
+        let mut outer = BTreeMap::new();
+        outer.insert("nft".to_string(), Data::Map(inner));
+        outer.insert("tags".to_string(), Data::List(vec![Data::String("a".to_string())]));
+        round_trip(Data::Map(outer));
+    }
+    
+    #[test]
+    fn test_tagged_shape() {
+        assert_eq!(Data::Empty.to_json_value(), json!({ "type": "Empty" }));
+        assert_eq!(
+            Data::Bytes(vec![0xde, 0xad]).to_json_value(),
+            json!({ "type": "Bytes", "value": "dead" })
+        );
+        
+        // Upper-case hex is accepted
+        assert_eq!(
+            Data::from_json_value(&json!({ "type": "Bytes", "value": "DEAD" })),
+            Ok(Data::Bytes(vec![0xde, 0xad]))
+        );
+    }
+    
+    #[test]
+    fn test_rejects_malformed() {
+        let rejected = [
+            json!(1),
+            json!({ "value": 1 }),
+            json!({ "type": "Float", "value": 1.5 }),
+            json!({ "type": "U64" }),
+            json!({ "type": "U64", "value": -1 }),
+            json!({ "type": "Bytes", "value": "abc" }),
+            json!({ "type": "List", "value": [1] }),
+        ];
+        for v in rejected {
+            assert!(matches!(Data::from_json_value(&v), Err(CheckError::InvalidJson(_))), "{}", v);
+        }
+    }
+    
+    #[test]
+    fn test_rejects_too_deep() {
+        let mut data = Data::U64(1);
+        for _ in 0..MAX_DATA_DEPTH {
+            data = Data::List(vec![data]);
+        }
+        round_trip(data.clone());
+        
+        let too_deep = Data::List(vec![data]).to_json_value();
+        assert!(Data::from_json_value(&too_deep).is_err());
+    }
+    
+    #[cfg(feature = "wasm")]
+    #[test]
+    fn test_matches_wasm_data() {
+        use crate::wasm_bindings::WasmData;
+        
+        let mut map = BTreeMap::new();
+        map.insert("amount".to_string(), Data::U64(7));
+        map.insert("owner".to_string(), Data::Bytes(vec![1, 2, 3]));
+        let data = Data::List(vec![Data::Empty, Data::I64(-1), Data::Map(map)]);
+        
+        let wasm: WasmData = serde_json::from_value(data.to_json_value()).unwrap();
+        assert_eq!(wasm.to_data(), Ok(data.clone()));
+        assert_eq!(serde_json::to_value(&wasm).unwrap(), data.to_json_value());
+    }
+}
@@ -4,14 +4,40 @@
 //! the Charms ecosystem for spell verification and transaction processing.
 //! 
 //! Build with WASM support: `wasm-pack build --target web --features wasm`
+//! 
+//! Enable the `cbor` feature for deterministic binary encoding of `Data`.
+//! 
+//! The crate is `no_std` (with `alloc`) when built without the default `std`
+//! feature, for zkVM guests. `util`, `serde`, `cbor`, `json` and `wasm` need
+//! `std` and enable it. Check with
+//! `cargo rustc --no-default-features --lib --crate-type rlib`, since the
+//! `cdylib` target links `std` for its allocator and panic handler.
+
+#![cfg_attr(not(feature = "std"), no_std)]
+
+extern crate alloc;
 
 #[cfg(feature = "wasm")]
 pub mod wasm_bindings;
 
-use std::collections::BTreeMap;
+#[cfg(feature = "cbor")]
+pub mod cbor;
+
+#[cfg(feature = "json")]
+mod json;
+
+mod canonical;
+
+#[cfg(not(feature = "std"))]
+use alloc::{boxed::Box, format, string::{String, ToString}, vec::Vec};
+use alloc::collections::{BTreeMap, BTreeSet};
+use core::fmt;
+use core::str::FromStr;
+use sha2::{Digest, Sha256};
 
 /// Represents a Charms application definition
 #[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct App {
     /// The unique identifier/tag for this app
     pub tag: String,
@@ -19,6 +45,8 @@ pub struct App {
     pub vk_hash: [u8; 32],
     /// Application-specific parameters
     pub params: Data,
+    /// Expected shape of this app's charm state, if enforced
+    pub schema: Option<DataSchema>,
 }
 
 impl App {
@@ -28,6 +56,7 @@ impl App {
             tag: tag.into(),
             vk_hash,
             params: Data::Empty,
+            schema: None,
         }
     }
     
@@ -37,12 +66,193 @@ impl App {
             tag: tag.into(),
             vk_hash,
             params,
+            schema: None,
+        }
+    }
+    
+    /// Require this app's charm state to match `schema`
+    pub fn with_schema(mut self, schema: DataSchema) -> Self {
+        self.schema = Some(schema);
+        self
+    }
+    
+    /// Get the app type encoded in the tag prefix (e.g. `token:` -> `Token`)
+    pub fn app_type(&self) -> AppType {
+        AppType::from_tag(&self.tag)
+    }
+    
+    /// Get the app category named by the tag prefix (e.g. `vote:` -> `Custom("vote")`)
+    pub fn category(&self) -> AppCategory {
+        AppCategory::from_tag(&self.tag)
+    }
+    
+    /// Get the part of the tag after the type prefix, if the tag has one
+    pub fn app_id(&self) -> Option<&str> {
+        self.tag.split_once(':').map(|(_, id)| id)
+    }
+    
+    /// Check that `tag` follows the `type:name` convention, returning both parts
+    /// 
+    /// The category must be a built-in `AppCategory` rather than `Custom`,
+    /// and the name non-empty ASCII letters, digits and hyphens. The whole tag
+    /// is at most `MAX_TAG_LEN` characters.
+    pub fn validate_tag(tag: &str) -> Result<(AppCategory, &str), TagValidationError> {
+        if tag.len() > MAX_TAG_LEN {
+            return Err(TagValidationError::TooLong);
+        }
+        let (_, name) = tag.split_once(':').ok_or(TagValidationError::MissingColon)?;
+        let category = AppCategory::from_tag(tag);
+        if let AppCategory::Custom(category) = category {
+            return Err(TagValidationError::UnknownCategory(category));
+        }
+        let valid_name = !name.is_empty()
+            && name.bytes().all(|b| b.is_ascii_alphanumeric() || b == b'-');
+        if !valid_name {
+            return Err(TagValidationError::InvalidName(name.to_string()));
+        }
+        Ok((category, name))
+    }
+    
+    /// Look up `key` in `params`, if `params` is a `Data::Map`
+    pub fn param(&self, key: &str) -> Option<&Data> {
+        match &self.params {
+            Data::Map(map) => map.get(key),
+            _ => None,
+        }
+    }
+    
+    /// Look up a `U64` param by key
+    pub fn param_u64(&self, key: &str) -> Option<u64> {
+        self.param(key)?.as_u64()
+    }
+    
+    /// Look up a `Bytes` param by key
+    pub fn param_bytes(&self, key: &str) -> Option<&[u8]> {
+        self.param(key)?.as_bytes()
+    }
+    
+    /// Maximum number of outputs that may carry this app's state
+    /// 
+    /// A `"max_outputs"` entry in `params` overrides the app type's default.
+    /// `None` means unlimited.
+    pub fn max_outputs(&self) -> Option<usize> {
+        match self.param_u64("max_outputs") {
+            Some(max) => Some(usize::try_from(max).unwrap_or(usize::MAX)),
+            None => self.app_type().default_max_outputs(),
         }
     }
 }
 
+/// Formats as the tag and vk hash, followed by the params unless empty
+impl fmt::Display for App {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} (vk {})", self.tag, hex::encode(&self.vk_hash))?;
+        if !self.params.is_empty() {
+            write!(f, " {}", self.params)?;
+        }
+        Ok(())
+    }
+}
+
+/// Kind of app, parsed from the `type:id` prefix of an app tag
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AppType {
+    Token,
+    Nft,
+    Escrow,
+    Bounty,
+    Bollar,
+    Unknown,
+}
+
+impl AppType {
+    /// Parse the app type from a tag such as `"token:BTC"`
+    pub fn from_tag(tag: &str) -> Self {
+        match tag.split_once(':') {
+            Some(("token", _)) => AppType::Token,
+            Some(("nft", _)) => AppType::Nft,
+            Some(("escrow", _)) => AppType::Escrow,
+            Some(("bounty", _)) => AppType::Bounty,
+            Some(("bollar", _)) => AppType::Bollar,
+            _ => AppType::Unknown,
+        }
+    }
+    
+    /// Default cap on outputs carrying this app type's state
+    pub fn default_max_outputs(&self) -> Option<usize> {
+        match self {
+            AppType::Token | AppType::Bollar => Some(64),
+            AppType::Nft => Some(16),
+            AppType::Escrow => Some(2),
+            AppType::Bounty => Some(8),
+            AppType::Unknown => None,
+        }
+    }
+}
+
+/// Category of app, named by the prefix before the first `:` of its tag
+/// 
+/// Prefixes with no built-in checker are kept as `Custom`; a tag with no `:`
+/// is a `Custom` category of the whole tag.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum AppCategory {
+    Token,
+    Nft,
+    Escrow,
+    Bounty,
+    Bollar,
+    Custom(String),
+}
+
+impl AppCategory {
+    /// Parse the app category from a tag such as `"token:BTC"`
+    pub fn from_tag(tag: &str) -> Self {
+        let Some((prefix, _)) = tag.split_once(':') else {
+            return AppCategory::Custom(tag.to_string());
+        };
+        match prefix {
+            "token" => AppCategory::Token,
+            "nft" => AppCategory::Nft,
+            "escrow" => AppCategory::Escrow,
+            "bounty" => AppCategory::Bounty,
+            "bollar" => AppCategory::Bollar,
+            custom => AppCategory::Custom(custom.to_string()),
+        }
+    }
+}
+
+/// Longest app tag accepted by `App::validate_tag`
+pub const MAX_TAG_LEN: usize = 64;
+
+/// Why an app tag does not follow the `type:name` convention
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TagValidationError {
+    /// The tag has no `:` separating type and name
+    MissingColon,
+    /// The type prefix is a `Custom` rather than built-in `AppCategory`
+    UnknownCategory(String),
+    /// The name is empty or has characters other than letters, digits and `-`
+    InvalidName(String),
+    /// The tag is longer than `MAX_TAG_LEN`
+    TooLong,
+}
+
+impl fmt::Display for TagValidationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TagValidationError::MissingColon => write!(f, "app tag has no type prefix"),
+            TagValidationError::UnknownCategory(category) => write!(f, "unknown app type {}", category),
+            TagValidationError::InvalidName(name) => write!(f, "invalid app name {:?}", name),
+            TagValidationError::TooLong => write!(f, "app tag longer than {} characters", MAX_TAG_LEN),
+        }
+    }
+}
+
+impl core::error::Error for TagValidationError {}
+
 /// Represents a Bitcoin transaction in the Charms context
 #[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Transaction {
     /// Transaction ID (32-byte hash)
     pub txid: [u8; 32],
@@ -52,6 +262,37 @@ pub struct Transaction {
     pub outputs: Vec<TxOutput>,
     /// The normalized spell being executed
     pub spell: Option<NormalizedSpell>,
+    /// Bitcoin `nLockTime`, which `txid` commits to
+    pub locktime: Option<u32>,
+    /// Bitcoin transaction version, which `txid` commits to
+    /// 
+    /// Defaults to `DEFAULT_TX_VERSION` when absent from JSON written before
+    /// the field existed.
+    #[cfg_attr(feature = "serde", serde(default = "default_tx_version"))]
+    pub version: u32,
+}
+
+/// `nLockTime` values from here up are Unix timestamps, not block heights
+pub const LOCKTIME_THRESHOLD: u32 = 500_000_000;
+
+/// Version `Transaction::new` starts with, the one Bitcoin Core creates
+pub const DEFAULT_TX_VERSION: u32 = 2;
+
+/// `nSequence` of an input that opts out of replacement and relative locktime
+pub const SEQUENCE_FINAL: u32 = 0xffff_ffff;
+
+/// Highest `nSequence` that still has Bitcoin enforce the locktime, without
+/// opting into replacement
+pub const MAX_SEQUENCE_NONFINAL: u32 = SEQUENCE_FINAL - 1;
+
+#[cfg(feature = "serde")]
+fn default_tx_version() -> u32 {
+    DEFAULT_TX_VERSION
+}
+
+#[cfg(feature = "serde")]
+fn sequence_final() -> u32 {
+    SEQUENCE_FINAL
 }
 
 impl Transaction {
@@ -62,7 +303,34 @@ impl Transaction {
             inputs: Vec::new(),
             outputs: Vec::new(),
             spell: None,
+            locktime: None,
+            version: DEFAULT_TX_VERSION,
+        }
+    }
+    
+    /// Set the transaction's `nLockTime`
+    pub fn with_locktime(mut self, locktime: u32) -> Self {
+        self.locktime = Some(locktime);
+        self
+    }
+    
+    /// Set the transaction's version
+    pub fn with_version(mut self, version: u32) -> Self {
+        self.version = version;
+        self
+    }
+    
+    /// Block height the transaction claims through its locktime
+    /// 
+    /// Height-based checks should read the current height from here. A
+    /// missing or zero locktime, or one holding a timestamp, claims no height.
+    /// Neither does a transaction whose inputs all have `SEQUENCE_FINAL`
+    /// sequences, since Bitcoin then ignores its locktime.
+    pub fn height(&self) -> Option<u32> {
+        if self.inputs.iter().all(|input| input.sequence == SEQUENCE_FINAL) {
+            return None;
         }
+        self.locktime.filter(|locktime| (1..LOCKTIME_THRESHOLD).contains(locktime))
     }
     
     /// Add an input to the transaction
@@ -75,27 +343,338 @@ impl Transaction {
         self.outputs.push(output);
     }
     
-    /// Verify the transaction spell is valid
+    /// First input spending `utxo_ref`
+    pub fn input_by_utxo_ref(&self, utxo_ref: &UtxoRef) -> Option<&TxInput> {
+        self.inputs.iter().find(|input| &input.utxo_ref == utxo_ref)
+    }
+    
+    /// First input spending `utxo_ref`, mutably
+    pub fn input_by_utxo_ref_mut(&mut self, utxo_ref: &UtxoRef) -> Option<&mut TxInput> {
+        self.inputs.iter_mut().find(|input| &input.utxo_ref == utxo_ref)
+    }
+    
+    /// First output with the given `index`
+    pub fn output_by_index(&self, index: u32) -> Option<&TxOutput> {
+        self.outputs.iter().find(|output| output.index == index)
+    }
+    
+    /// First output with the given `index`, mutably
+    pub fn output_by_index_mut(&mut self, index: u32) -> Option<&mut TxOutput> {
+        self.outputs.iter_mut().find(|output| output.index == index)
+    }
+    
+    /// The UTXOs spent by the inputs, in order
+    pub fn input_utxo_refs(&self) -> impl Iterator<Item = &UtxoRef> {
+        self.inputs.iter().map(|input| &input.utxo_ref)
+    }
+    
+    /// Inputs carrying charm state for `tag`, each with that state
+    pub fn app_inputs<'a>(&'a self, tag: &'a str) -> impl Iterator<Item = (&'a TxInput, &'a Data)> {
+        self.inputs.iter()
+            .filter_map(move |input| Some((input, input.charm_state.as_ref()?.get(tag)?)))
+    }
+    
+    /// Outputs carrying charm state for `tag`, each with that state
+    pub fn app_outputs<'a>(&'a self, tag: &'a str) -> impl Iterator<Item = (&'a TxOutput, &'a Data)> {
+        self.outputs.iter()
+            .filter_map(move |output| Some((output, output.charm_state.as_ref()?.get(tag)?)))
+    }
+    
+    /// Every app tag in any input or output charm state
+    pub fn charm_apps(&self) -> BTreeSet<String> {
+        let input_states = self.inputs.iter().filter_map(|input| input.charm_state.as_ref());
+        let output_states = self.outputs.iter().filter_map(|output| output.charm_state.as_ref());
+        input_states.chain(output_states)
+            .flat_map(|state| state.keys().map(str::to_string))
+            .collect()
+    }
+    
+    /// Whether any input or output charm state holds state for `tag`
+    pub fn has_charm(&self, tag: &str) -> bool {
+        self.app_inputs(tag).next().is_some() || self.app_outputs(tag).next().is_some()
+    }
+    
+    /// Count the outputs carrying charm state for `app_tag`
+    pub fn app_output_count(&self, app_tag: &str) -> usize {
+        self.app_outputs(app_tag).count()
+    }
+    
+    /// Compute the miner fee implied by input and output values
+    /// 
+    /// Returns `None` if any input value is unknown or if outputs spend
+    /// more than the inputs provide.
+    pub fn fee(&self) -> Option<u64> {
+        let input_sum = self.inputs.iter()
+            .try_fold(0u64, |acc, input| acc.checked_add(input.value?))?;
+        let output_sum = self.outputs.iter()
+            .try_fold(0u64, |acc, output| acc.checked_add(output.value))?;
+        
+        input_sum.checked_sub(output_sum)
+    }
+    
+    /// Check that no output is dust, see `TxOutput::is_dust`
+    pub fn validate_dust(&self, dust_limit: u64) -> bool {
+        !self.outputs.iter().any(|output| output.is_dust(dust_limit))
+    }
+    
+    /// Check that the inputs cover the outputs
+    /// 
+    /// Fails if any input value is unknown, as the fee cannot be computed.
+    pub fn validate_fee(&self) -> bool {
+        self.fee().is_some()
+    }
+    
+    /// Verify the transaction spell is well-formed and describes this
+    /// transaction, see `spell_matches_tx`
+    /// 
+    /// The spell's expiry is not checked: the locktime cannot prove the
+    /// transaction is mined before it. Use `NormalizedSpell::verify_at` with
+    /// an attested height for that.
     pub fn verify_spell(&self) -> bool {
         if let Some(ref spell) = self.spell {
-            spell.verify()
+            self.spell_matches_tx() && spell.verify()
         } else {
             true // No spell means no charm constraints
         }
     }
+    
+    /// Check the spell's inputs and outputs line up with the transaction's
+    /// 
+    /// Spell inputs must spend exactly the transaction's inputs, in order,
+    /// and every spell output index must exist in the transaction. A
+    /// transaction without a spell trivially matches.
+    pub fn spell_matches_tx(&self) -> bool {
+        let Some(ref spell) = self.spell else {
+            return true;
+        };
+        
+        spell.ins.len() == self.inputs.len()
+            && spell.ins.iter().zip(&self.inputs).all(|(spell_in, tx_in)| spell_in.utxo_ref == tx_in.utxo_ref)
+            && spell.outs.iter().all(|spell_out| self.output_by_index(spell_out.index).is_some())
+    }
+    
+    /// Content hash: sha256 of a canonical encoding of every field
+    /// 
+    /// Equal transactions always hash the same, so this can serve as a
+    /// cache key independent of how the transaction was built.
+    pub fn hash(&self) -> [u8; 32] {
+        canonical::hash(self)
+    }
+    
+    /// Bitcoin txid: double sha256 of the legacy (non-witness) serialization
+    /// 
+    /// Covers version, inputs, outputs in order and locktime, a missing
+    /// locktime counting as 0, so `UtxoRef`s to the transaction's outputs can
+    /// be built before it is broadcast. Like `txid`, the result is in the
+    /// byte order txids are displayed in.
+    pub fn compute_txid(&self) -> [u8; 32] {
+        let first = Sha256::digest(self.to_bitcoin_bytes());
+        let mut txid: [u8; 32] = Sha256::digest(first).into();
+        txid.reverse();
+        txid
+    }
+    
+    /// Bitcoin's standard serialization, without witness data
+    fn to_bitcoin_bytes(&self) -> Vec<u8> {
+        let mut out = Vec::new();
+        out.extend_from_slice(&self.version.to_le_bytes());
+        
+        write_compact_size(&mut out, self.inputs.len());
+        for input in &self.inputs {
+            out.extend(input.utxo_ref.txid.iter().rev());
+            out.extend_from_slice(&input.utxo_ref.vout.to_le_bytes());
+            write_compact_size(&mut out, input.script_sig.len());
+            out.extend_from_slice(&input.script_sig);
+            out.extend_from_slice(&input.sequence.to_le_bytes());
+        }
+        
+        write_compact_size(&mut out, self.outputs.len());
+        for output in &self.outputs {
+            out.extend_from_slice(&output.value.to_le_bytes());
+            write_compact_size(&mut out, output.script_pubkey.len());
+            out.extend_from_slice(&output.script_pubkey);
+        }
+        
+        out.extend_from_slice(&self.locktime.unwrap_or(0).to_le_bytes());
+        out
+    }
+    
+    /// Run every structural check, reporting all violations found
+    /// 
+    /// Errors are ordered by rule, then by position in the transaction.
+    pub fn full_validate(&self, config: &VerificationConfig) -> Result<(), Vec<TransactionError>> {
+        let mut errors = Vec::new();
+        
+        for (position, input) in self.inputs.iter().enumerate() {
+            if let Some(first) = self.inputs[..position].iter().position(|i| i.utxo_ref == input.utxo_ref) {
+                errors.push(TransactionError::DuplicateInput { first, duplicate: position });
+            }
+        }
+        
+        for (position, output) in self.outputs.iter().enumerate() {
+            if let Some(first) = self.outputs[..position].iter().position(|o| o.index == output.index) {
+                errors.push(TransactionError::DuplicateOutputIndex {
+                    first,
+                    duplicate: position,
+                    index: output.index,
+                });
+            }
+        }
+        
+        for (position, input) in self.inputs.iter().enumerate() {
+            if input.utxo_ref.txid == self.txid {
+                errors.push(TransactionError::SelfReference { input: position });
+            }
+        }
+        
+        for (position, output) in self.outputs.iter().enumerate() {
+            let script = &output.script_pubkey;
+            if script.is_empty() {
+                errors.push(TransactionError::InvalidScript { output: position, reason: "empty script" });
+            } else if script.len() > config.max_script_len {
+                errors.push(TransactionError::InvalidScript { output: position, reason: "script too long" });
+            }
+        }
+        
+        for (position, output) in self.outputs.iter().enumerate() {
+            if output.is_dust(config.dust_limit) {
+                errors.push(TransactionError::DustOutput {
+                    output: position,
+                    value: output.value,
+                    dust_limit: config.dust_limit,
+                });
+            }
+        }
+        
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+}
+
+/// Limits applied by `Transaction::full_validate`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VerificationConfig {
+    /// Smallest value an output carrying charm state may hold
+    pub dust_limit: u64,
+    /// Longest script pubkey accepted
+    pub max_script_len: usize,
+}
+
+impl Default for VerificationConfig {
+    fn default() -> Self {
+        Self {
+            dust_limit: DUST_LIMIT,
+            max_script_len: 10_000,
+        }
+    }
+}
+
+/// A structural problem found by `Transaction::full_validate`
+/// 
+/// Locations are positions in `inputs` or `outputs`, not output `index`es.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TransactionError {
+    /// The same UTXO is spent by two inputs
+    DuplicateInput { first: usize, duplicate: usize },
+    /// Two outputs claim the same `index`
+    DuplicateOutputIndex { first: usize, duplicate: usize, index: u32 },
+    /// An input spends an output of this very transaction
+    SelfReference { input: usize },
+    /// An output's script pubkey is empty or too long
+    InvalidScript { output: usize, reason: &'static str },
+    /// An output is dust, see `TxOutput::is_dust`
+    DustOutput { output: usize, value: u64, dust_limit: u64 },
+}
+
+impl fmt::Display for TransactionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TransactionError::DuplicateInput { first, duplicate } => {
+                write!(f, "inputs[{}] spends the same UTXO as inputs[{}]", duplicate, first)
+            }
+            TransactionError::DuplicateOutputIndex { first, duplicate, index } => {
+                write!(f, "outputs[{}] reuses index {} of outputs[{}]", duplicate, index, first)
+            }
+            TransactionError::SelfReference { input } => {
+                write!(f, "inputs[{}] spends the transaction itself", input)
+            }
+            TransactionError::InvalidScript { output, reason } => {
+                write!(f, "outputs[{}]: {}", output, reason)
+            }
+            TransactionError::DustOutput { output, value, dust_limit } => {
+                write!(f, "outputs[{}] value {} is below dust limit {}", output, value, dust_limit)
+            }
+        }
+    }
 }
 
+impl core::error::Error for TransactionError {}
+
 /// Transaction input with optional charm state
+/// 
+/// The `serde(default)` fields only default in self-describing formats
+/// such as JSON; bincode needs every field, see `util::WIRE_VERSION`.
 #[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct TxInput {
     /// Reference to the UTXO being spent
     pub utxo_ref: UtxoRef,
+    /// Satoshi value of the spent output, if known
+    pub value: Option<u64>,
+    /// Locking script of the spent output, empty if unknown
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub script_pubkey: Vec<u8>,
+    /// Unlocking script, empty for segwit spends
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub script_sig: Vec<u8>,
+    /// Bitcoin `nSequence`
+    #[cfg_attr(feature = "serde", serde(default = "sequence_final"))]
+    pub sequence: u32,
     /// Charm state attached to this input (if any)
     pub charm_state: Option<CharmState>,
 }
 
+impl TxInput {
+    /// Input spending `utxo_ref` with an unknown value and script, an empty
+    /// `script_sig` and a final sequence
+    pub fn new(utxo_ref: UtxoRef, charm_state: Option<CharmState>) -> Self {
+        Self {
+            utxo_ref,
+            value: None,
+            script_pubkey: Vec::new(),
+            script_sig: Vec::new(),
+            sequence: SEQUENCE_FINAL,
+            charm_state,
+        }
+    }
+}
+
+/// Append Bitcoin's variable-length integer encoding of `n`
+fn write_compact_size(out: &mut Vec<u8>, n: usize) {
+    let n = n as u64;
+    match n {
+        0..=0xfc => out.push(n as u8),
+        0xfd..=0xffff => {
+            out.push(0xfd);
+            out.extend_from_slice(&(n as u16).to_le_bytes());
+        }
+        0x1_0000..=0xffff_ffff => {
+            out.push(0xfe);
+            out.extend_from_slice(&(n as u32).to_le_bytes());
+        }
+        _ => {
+            out.push(0xff);
+            out.extend_from_slice(&n.to_le_bytes());
+        }
+    }
+}
+
 /// Transaction output with optional charm state
 #[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct TxOutput {
     /// Output index
     pub index: u32,
@@ -107,8 +686,22 @@ pub struct TxOutput {
     pub charm_state: Option<CharmState>,
 }
 
+/// Default dust limit in sats, Bitcoin Core's for a P2PKH output
+pub const DUST_LIMIT: u64 = 546;
+
+impl TxOutput {
+    /// Whether this output carries charm state on less than `dust_limit` sats
+    /// 
+    /// Outputs without charm state are not charms' concern. This is the
+    /// only dust rule, shared by `validate_dust` and `full_validate`.
+    pub fn is_dust(&self, dust_limit: u64) -> bool {
+        self.charm_state.is_some() && self.value < dust_limit
+    }
+}
+
 /// Reference to a UTXO
 #[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct UtxoRef {
     /// Transaction ID containing this UTXO
     pub txid: [u8; 32],
@@ -116,8 +709,149 @@ pub struct UtxoRef {
     pub vout: u32,
 }
 
+impl UtxoRef {
+    /// Id of an app created by spending this UTXO: `sha256(txid || vout_le)`
+    /// 
+    /// A UTXO can only be spent once, so no two creations share an id.
+    pub fn genesis_id(&self) -> [u8; 32] {
+        let mut hasher = Sha256::new();
+        hasher.update(self.txid);
+        hasher.update(self.vout.to_le_bytes());
+        hasher.finalize().into()
+    }
+    
+    /// App tag `{prefix}:{genesis_id_hex}` for an app created by spending this UTXO
+    pub fn genesis_tag(&self, prefix: &str) -> String {
+        format!("{}:{}", prefix, hex::encode(&self.genesis_id()))
+    }
+}
+
+/// Formats as `{txid_hex}:{vout}`, the usual Bitcoin outpoint notation
+impl fmt::Display for UtxoRef {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}", hex::encode(&self.txid), self.vout)
+    }
+}
+
+/// Parses the `{txid_hex}:{vout}` form produced by `Display`
+impl FromStr for UtxoRef {
+    type Err = UtxoRefParseError;
+    
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (txid_hex, vout) = s.rsplit_once(':')
+            .ok_or(UtxoRefParseError::MissingSeparator)?;
+        
+        let txid = hex::decode_32(txid_hex).map_err(|e| match e {
+            hex::HexError::InvalidLength { actual, .. } | hex::HexError::OddLength(actual) => {
+                UtxoRefParseError::InvalidTxidLength(actual)
+            }
+            hex::HexError::InvalidCharacter => UtxoRefParseError::InvalidTxidHex,
+        })?;
+        
+        let vout = vout.parse::<u32>()
+            .map_err(|_| UtxoRefParseError::InvalidVout(vout.to_string()))?;
+        
+        Ok(UtxoRef { txid, vout })
+    }
+}
+
+/// Error returned when parsing a `UtxoRef` from a string
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum UtxoRefParseError {
+    /// No `:` between txid and vout
+    MissingSeparator,
+    /// The txid is not 64 hex characters long
+    InvalidTxidLength(usize),
+    /// The txid contains non-hex characters
+    InvalidTxidHex,
+    /// The vout is not a valid `u32`
+    InvalidVout(String),
+}
+
+impl fmt::Display for UtxoRefParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            UtxoRefParseError::MissingSeparator => write!(f, "expected txid:vout"),
+            UtxoRefParseError::InvalidTxidLength(len) => {
+                write!(f, "txid must be 64 hex characters, got {}", len)
+            }
+            UtxoRefParseError::InvalidTxidHex => write!(f, "txid is not valid hex"),
+            UtxoRefParseError::InvalidVout(vout) => write!(f, "invalid vout: {}", vout),
+        }
+    }
+}
+
+impl core::error::Error for UtxoRefParseError {}
+
+/// Hex encoding helpers shared by the native types and the WASM bindings
+pub mod hex {
+    #[cfg(not(feature = "std"))]
+    use alloc::{format, string::String, vec::Vec};
+    use core::fmt;
+    
+    /// Error decoding a hex value
+    #[derive(Debug, Clone, PartialEq, Eq)]
+    pub enum HexError {
+        /// Wrong number of hex characters
+        InvalidLength { expected: usize, actual: usize },
+        /// An odd number of hex characters, which can't form whole bytes
+        OddLength(usize),
+        /// A character outside `0-9a-fA-F`
+        InvalidCharacter,
+    }
+    
+    impl fmt::Display for HexError {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            match self {
+                HexError::InvalidLength { expected, actual } => {
+                    write!(f, "expected {} hex characters, got {}", expected, actual)
+                }
+                HexError::OddLength(len) => write!(f, "odd number of hex characters: {}", len),
+                HexError::InvalidCharacter => write!(f, "invalid hex character"),
+            }
+        }
+    }
+    
+    impl core::error::Error for HexError {}
+    
+    /// Lowercase hex encoding
+    pub fn encode(bytes: &[u8]) -> String {
+        bytes.iter().map(|b| format!("{:02x}", b)).collect()
+    }
+    
+    /// Decode a hex string (either case), returning `None` on odd length or invalid digits
+    pub fn decode(hex: &str) -> Option<Vec<u8>> {
+        try_decode(hex).ok()
+    }
+    
+    /// Like `decode`, reporting why the string is not valid hex
+    pub fn try_decode(hex: &str) -> Result<Vec<u8>, HexError> {
+        if !hex.len().is_multiple_of(2) {
+            return Err(HexError::OddLength(hex.len()));
+        }
+        if !hex.bytes().all(|b| b.is_ascii_hexdigit()) {
+            return Err(HexError::InvalidCharacter);
+        }
+        (0..hex.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&hex[i..i + 2], 16).map_err(|_| HexError::InvalidCharacter))
+            .collect()
+    }
+    
+    /// Decode a 32-byte value (hash, txid, vk) from exactly 64 hex characters
+    pub fn decode_32(hex: &str) -> Result<[u8; 32], HexError> {
+        if hex.len() != 64 {
+            return Err(HexError::InvalidLength { expected: 64, actual: hex.len() });
+        }
+        decode(hex)
+            .and_then(|bytes| <[u8; 32]>::try_from(bytes).ok())
+            .ok_or(HexError::InvalidCharacter)
+    }
+}
+
 /// Charm state attached to a UTXO
 #[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct CharmState {
     /// Map of app tags to their state data
     pub apps: BTreeMap<String, Data>,
@@ -141,6 +875,96 @@ impl CharmState {
     pub fn get(&self, tag: &str) -> Option<&Data> {
         self.apps.get(tag)
     }
+    
+    /// Remove an app's state, returning it if present
+    pub fn remove(&mut self, tag: &str) -> Option<Data> {
+        self.apps.remove(tag)
+    }
+    
+    /// Whether the state holds an entry for `tag`
+    pub fn contains_key(&self, tag: &str) -> bool {
+        self.apps.contains_key(tag)
+    }
+    
+    /// App tags, in order
+    pub fn keys(&self) -> impl Iterator<Item = &str> {
+        self.apps.keys().map(String::as_str)
+    }
+    
+    /// App states, in tag order
+    pub fn values(&self) -> impl Iterator<Item = &Data> {
+        self.apps.values()
+    }
+    
+    /// Check this state's entry for `app` against the app's schema, if it has one
+    pub fn validate_for_app(&self, app: &App) -> Result<(), SchemaViolation> {
+        match (&app.schema, self.get(&app.tag)) {
+            (Some(schema), Some(data)) => schema.validate(data),
+            _ => Ok(()),
+        }
+    }
+    
+    /// Approximate serialized size: the sum of every app state's
+    /// `Data::size_estimate`
+    pub fn size_estimate(&self) -> usize {
+        self.apps.values().map(Data::size_estimate).sum()
+    }
+    
+    /// Compare states, treating an app mapped to `Data::Empty` as absent
+    pub fn semantic_eq(&self, other: &CharmState) -> bool {
+        let is_present = |(_, data): &(&String, &Data)| !matches!(data, Data::Empty);
+        self.apps.iter().filter(is_present).eq(other.apps.iter().filter(is_present))
+    }
+    
+    /// Compute which app states were added, removed or changed going from
+    /// `before` to `after`
+    pub fn diff<'a>(before: &'a CharmState, after: &'a CharmState) -> CharmStateDiff<'a> {
+        let mut diff = CharmStateDiff::default();
+        
+        for (tag, old) in &before.apps {
+            match after.apps.get(tag) {
+                None => {
+                    diff.removed.insert(tag.clone(), old);
+                }
+                Some(new) if new != old => {
+                    diff.changed.insert(tag.clone(), (old, new));
+                }
+                Some(_) => {}
+            }
+        }
+        for (tag, new) in &after.apps {
+            if !before.apps.contains_key(tag) {
+                diff.added.insert(tag.clone(), new);
+            }
+        }
+        
+        diff
+    }
+}
+
+/// Formats like a `Data::Map` from app tag to state
+impl fmt::Display for CharmState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt_map(f, &self.apps)
+    }
+}
+
+/// App state changes between two charm states, see `CharmState::diff`
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct CharmStateDiff<'a> {
+    /// Apps only present after
+    pub added: BTreeMap<String, &'a Data>,
+    /// Apps only present before
+    pub removed: BTreeMap<String, &'a Data>,
+    /// Apps present in both with different state, as `(before, after)`
+    pub changed: BTreeMap<String, (&'a Data, &'a Data)>,
+}
+
+impl CharmStateDiff<'_> {
+    /// Whether the two states were identical
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.changed.is_empty()
+    }
 }
 
 impl Default for CharmState {
@@ -151,6 +975,7 @@ impl Default for CharmState {
 
 /// A normalized spell structure for ZK verification
 #[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct NormalizedSpell {
     /// Protocol version
     pub version: u32,
@@ -158,6 +983,18 @@ pub struct NormalizedSpell {
     pub ins: Vec<SpellInput>,
     /// Spell outputs
     pub outs: Vec<SpellOutput>,
+    /// Replay nonces, one monotonic counter per app tag
+    pub nonces: BTreeMap<String, u64>,
+    /// Apps this spell creates without any input state
+    /// 
+    /// Declaring a tag here authorizes nothing by itself: see
+    /// `is_genesis_authorized`.
+    pub genesis: BTreeSet<String>,
+    /// First block height at which the spell has expired, if it expires
+    /// 
+    /// Only checked by `verify_at`, against an attested height.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub valid_before: Option<u32>,
 }
 
 impl NormalizedSpell {
@@ -167,27 +1004,264 @@ impl NormalizedSpell {
             version,
             ins: Vec::new(),
             outs: Vec::new(),
+            nonces: BTreeMap::new(),
+            genesis: BTreeSet::new(),
+            valid_before: None,
+        }
+    }
+    
+    /// Start building a spell input by input and output by output
+    pub fn builder(version: u32) -> SpellBuilder {
+        SpellBuilder {
+            spell: Self::new(version),
         }
     }
     
-    /// Verify the spell is well-formed
+    /// Verify the spell is well-formed: exactly when `diagnostics` finds no problem
     pub fn verify(&self) -> bool {
-        // Basic validation
-        self.version > 0 && !self.ins.is_empty() && !self.outs.is_empty()
+        self.diagnostics().is_valid()
     }
-}
-
-/// Spell input reference
-#[derive(Debug, Clone, PartialEq, Eq)]
-pub struct SpellInput {
-    /// UTXO reference
-    pub utxo_ref: UtxoRef,
-    /// Input charm state
-    pub charms: Option<CharmState>,
-}
+    
+    /// Verify the spell is well-formed and not expired at `attested_height`
+    /// 
+    /// `attested_height` must be a height the caller vouches for, such as
+    /// the chain tip the prover checked the spell's inputs against. A
+    /// transaction's locktime only bounds its mining height from below, so
+    /// it cannot show that a spell is still valid.
+    pub fn verify_at(&self, attested_height: u32) -> bool {
+        let expired = self.valid_before.is_some_and(|valid_before| attested_height >= valid_before);
+        self.verify() && !expired
+    }
+    
+    /// Describe every structural problem with the spell
+    /// 
+    /// A zero version, no inputs or outputs, duplicate inputs and duplicate
+    /// output indices. `verify` passes exactly when this finds none.
+    pub fn diagnostics(&self) -> SpellDiagnostics {
+        let mut problems = Vec::new();
+        
+        if self.version == 0 {
+            problems.push("version 0".to_string());
+        }
+        if self.ins.is_empty() {
+            problems.push("empty inputs".to_string());
+        }
+        if self.outs.is_empty() {
+            problems.push("empty outputs".to_string());
+        }
+        for (position, input) in self.ins.iter().enumerate() {
+            if self.ins[..position].iter().any(|i| i.utxo_ref == input.utxo_ref) {
+                problems.push(format!("duplicate input {}", input.utxo_ref));
+            }
+        }
+        for (position, output) in self.outs.iter().enumerate() {
+            if self.outs[..position].iter().any(|o| o.index == output.index) {
+                problems.push(format!("duplicate output index {}", output.index));
+            }
+        }
+        
+        SpellDiagnostics {
+            version: self.version,
+            input_count: self.ins.len(),
+            output_count: self.outs.len(),
+            problems,
+        }
+    }
+    
+    /// Make the spell expire at block height `height`
+    pub fn with_valid_before(mut self, height: u32) -> Self {
+        self.valid_before = Some(height);
+        self
+    }
+    
+    /// Set the replay nonce for an app
+    pub fn with_nonce(mut self, tag: impl Into<String>, nonce: u64) -> Self {
+        self.nonces.insert(tag.into(), nonce);
+        self
+    }
+    
+    /// Declare that this spell creates the first state for an app
+    pub fn with_genesis(mut self, tag: impl Into<String>) -> Self {
+        self.genesis.insert(tag.into());
+        self
+    }
+    
+    /// Every app tag in any input or output charm state
+    pub fn apps(&self) -> BTreeSet<String> {
+        let input_states = self.ins.iter().filter_map(|input| input.charms.as_ref());
+        let output_states = self.outs.iter().filter_map(|output| output.charms.as_ref());
+        input_states.chain(output_states)
+            .flat_map(|state| state.keys().map(str::to_string))
+            .collect()
+    }
+    
+    /// Whether any input or output charm state holds state for `tag`
+    pub fn contains_app(&self, tag: &str) -> bool {
+        let input_states = self.ins.iter().filter_map(|input| input.charms.as_ref());
+        let output_states = self.outs.iter().filter_map(|output| output.charms.as_ref());
+        input_states.chain(output_states).any(|state| state.contains_key(tag))
+    }
+    
+    /// Whether this spell may create the first state for app `tag`
+    /// 
+    /// The tag must be declared in `genesis` and be the
+    /// `UtxoRef::genesis_tag` of one of the spell's inputs, so only the
+    /// spender of that UTXO can ever create the app.
+    pub fn is_genesis_authorized(&self, tag: &str) -> bool {
+        let Some((prefix, _)) = tag.split_once(':') else {
+            return false;
+        };
+        self.genesis.contains(tag)
+            && self.ins.iter().any(|input| input.utxo_ref.genesis_tag(prefix) == tag)
+    }
+    
+    /// App tags that appear only in outputs without an authorized genesis
+    /// 
+    /// Such an app's state would be created out of thin air, so a correct
+    /// spell must return an empty list.
+    pub fn unauthorized_new_apps(&self) -> Vec<&str> {
+        let input_apps: BTreeSet<&str> = self.ins.iter()
+            .filter_map(|input| input.charms.as_ref())
+            .flat_map(|state| state.apps.keys().map(String::as_str))
+            .collect();
+        let output_apps: BTreeSet<&str> = self.outs.iter()
+            .filter_map(|output| output.charms.as_ref())
+            .flat_map(|state| state.apps.keys().map(String::as_str))
+            .collect();
+        
+        output_apps.into_iter()
+            .filter(|tag| !input_apps.contains(tag) && !self.is_genesis_authorized(tag))
+            .collect()
+    }
+    
+    /// Verify every app nonce strictly advances past the last one seen for that app
+    /// 
+    /// Nonces are namespaced by app tag, so two apps never collide: each app's
+    /// counter is only compared against its own entry in `last_seen`. Apps with
+    /// no entry in `last_seen` accept any nonce.
+    pub fn verify_nonces(&self, last_seen: &BTreeMap<String, u64>) -> bool {
+        self.nonces.iter().all(|(tag, nonce)| {
+            last_seen.get(tag).is_none_or(|last| nonce > last)
+        })
+    }
+    
+    /// Content hash: sha256 of `to_canonical_bytes`
+    /// 
+    /// Logically identical spells hash the same whatever the order of their
+    /// inputs and outputs.
+    pub fn hash(&self) -> [u8; 32] {
+        Sha256::digest(self.to_canonical_bytes()).into()
+    }
+    
+    /// Warning naming the apps whose state holds a `Data::F64`, if any
+    /// 
+    /// Floats are not deterministic enough to prove (see `Data`), so hosts
+    /// should surface this before submitting such a spell.
+    pub fn f64_warning(&self) -> Option<String> {
+        let input_states = self.ins.iter().filter_map(|input| input.charms.as_ref());
+        let output_states = self.outs.iter().filter_map(|output| output.charms.as_ref());
+        let tags: BTreeSet<&str> = input_states.chain(output_states)
+            .flat_map(|state| state.apps.iter())
+            .filter(|(_, data)| data.contains_f64())
+            .map(|(tag, _)| tag.as_str())
+            .collect();
+        if tags.is_empty() {
+            return None;
+        }
+        
+        let tags: Vec<&str> = tags.into_iter().collect();
+        Some(format!(
+            "spell state for {} holds Data::F64, which is not deterministic in ZK proofs",
+            tags.join(", ")
+        ))
+    }
+    
+    /// Approximate serialized size, see `Data::size_estimate`
+    /// 
+    /// Counts 36 bytes per input's UTXO, 4 per output index, the charm
+    /// states on both, and the nonces and genesis tags.
+    pub fn size_estimate(&self) -> usize {
+        let charms = |charms: &Option<CharmState>| charms.as_ref().map_or(1, CharmState::size_estimate);
+        
+        4 + self.ins.iter().map(|input| 36 + charms(&input.charms)).sum::<usize>()
+            + self.outs.iter().map(|output| 4 + charms(&output.charms)).sum::<usize>()
+            + self.nonces.keys().map(|tag| tag.len() + 4 + 8).sum::<usize>()
+            + self.genesis.iter().map(|tag| tag.len() + 4).sum::<usize>()
+            + self.valid_before.map_or(1, |_| 4)
+    }
+    
+    /// Check the spell's `size_estimate` fits within `max_bytes`
+    pub fn validate_size_budget(&self, max_bytes: usize) -> Result<(), SizeBudgetExceeded> {
+        let estimate = self.size_estimate();
+        if estimate > max_bytes {
+            return Err(SizeBudgetExceeded { estimate, max_bytes });
+        }
+        Ok(())
+    }
+    
+    /// Byte encoding shared by every logically identical spell
+    /// 
+    /// Inputs are sorted by `(txid, vout)` and outputs by index before every
+    /// field is written, length-prefixed, in the crate's canonical encoding;
+    /// maps and sets are in key order already.
+    pub fn to_canonical_bytes(&self) -> Vec<u8> {
+        let mut spell = self.clone();
+        spell.ins.sort_by_key(canonical::input_order);
+        spell.outs.sort_by_key(|output| output.index);
+        canonical::encode(&spell)
+    }
+    
+    /// Decode `to_canonical_bytes` output, rejecting any other encoding
+    pub fn from_canonical_bytes(bytes: &[u8]) -> Result<Self, CanonicalDecodeError> {
+        canonical::decode(bytes)
+    }
+}
+
+/// Why `NormalizedSpell::from_canonical_bytes` rejected its input
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CanonicalDecodeError {
+    /// The input ended in the middle of a value
+    UnexpectedEnd,
+    /// A variant, option or boolean tag byte is out of range
+    InvalidTag(u8),
+    /// A string is not UTF-8
+    InvalidUtf8,
+    /// Keys, inputs or outputs are out of canonical order
+    NonCanonical,
+    /// `Data` is nested deeper than `MAX_DATA_DEPTH`
+    TooDeep,
+    /// Bytes are left over after the spell
+    TrailingBytes,
+}
+
+impl fmt::Display for CanonicalDecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CanonicalDecodeError::UnexpectedEnd => write!(f, "unexpected end of input"),
+            CanonicalDecodeError::InvalidTag(tag) => write!(f, "invalid tag byte {}", tag),
+            CanonicalDecodeError::InvalidUtf8 => write!(f, "string is not valid UTF-8"),
+            CanonicalDecodeError::NonCanonical => write!(f, "entries are not in canonical order"),
+            CanonicalDecodeError::TooDeep => write!(f, "data nested deeper than {}", MAX_DATA_DEPTH),
+            CanonicalDecodeError::TrailingBytes => write!(f, "trailing bytes after spell"),
+        }
+    }
+}
+
+impl core::error::Error for CanonicalDecodeError {}
+
+/// Spell input reference
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SpellInput {
+    /// UTXO reference
+    pub utxo_ref: UtxoRef,
+    /// Input charm state
+    pub charms: Option<CharmState>,
+}
 
 /// Spell output definition
 #[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct SpellOutput {
     /// Output index
     pub index: u32,
@@ -195,10 +1269,116 @@ pub struct SpellOutput {
     pub charms: Option<CharmState>,
 }
 
+/// A spell too large for its budget, see `NormalizedSpell::validate_size_budget`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SizeBudgetExceeded {
+    /// The spell's `size_estimate`
+    pub estimate: usize,
+    /// The budget it exceeds
+    pub max_bytes: usize,
+}
+
+impl fmt::Display for SizeBudgetExceeded {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "spell size estimate {} exceeds budget of {} bytes", self.estimate, self.max_bytes)
+    }
+}
+
+impl core::error::Error for SizeBudgetExceeded {}
+
+/// Summary of a spell's shape and problems, see `NormalizedSpell::diagnostics`
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct SpellDiagnostics {
+    /// Protocol version
+    pub version: u32,
+    /// Number of spell inputs
+    pub input_count: usize,
+    /// Number of spell outputs
+    pub output_count: usize,
+    /// Human-readable description of each problem found
+    pub problems: Vec<String>,
+}
+
+impl SpellDiagnostics {
+    /// Whether no problems were found
+    pub fn is_valid(&self) -> bool {
+        self.problems.is_empty()
+    }
+}
+
+/// Chainable constructor for `NormalizedSpell`, see `NormalizedSpell::builder`
+#[derive(Debug, Clone)]
+pub struct SpellBuilder {
+    spell: NormalizedSpell,
+}
+
+impl SpellBuilder {
+    /// Add an input spending `utxo_ref`
+    pub fn input(mut self, utxo_ref: UtxoRef, charms: Option<CharmState>) -> Self {
+        self.spell.ins.push(SpellInput { utxo_ref, charms });
+        self
+    }
+    
+    /// Add an output at `index`
+    pub fn output(mut self, index: u32, charms: Option<CharmState>) -> Self {
+        self.spell.outs.push(SpellOutput { index, charms });
+        self
+    }
+    
+    /// Finish the spell
+    pub fn build(self) -> NormalizedSpell {
+        self.spell
+    }
+}
+
+/// Deepest `List`/`Map` nesting accepted when deserializing `Data`
+pub const MAX_DATA_DEPTH: usize = 32;
+
+/// Per-value byte counts behind `Data::size_hint` and `Data::size_estimate`
+struct SizeModel {
+    /// Variant tag written before every value
+    tag: usize,
+    /// Length prefix of bytes, strings, lists, maps and map keys
+    len: usize,
+    /// Payload of `Data::Empty`
+    empty: usize,
+}
+
+/// Bincode as configured in `util`
+const BINCODE_SIZES: SizeModel = SizeModel { tag: 4, len: 8, empty: 0 };
+
+/// Tag-free, 4-byte lengths, close to CBOR for byte- and text-heavy data
+const COMPACT_SIZES: SizeModel = SizeModel { tag: 0, len: 4, empty: 1 };
+
+/// Tolerance within which `Data::approx_eq` treats two `Data::F64` values as equal
+pub const F64_EPSILON: f64 = f64::EPSILON * 4.0;
+
 /// Flexible data type for app state
-#[derive(Debug, Clone, PartialEq, Eq)]
+/// 
+/// `Deserialize` is implemented by hand so that nesting deeper than
+/// `MAX_DATA_DEPTH` is rejected before it can exhaust the stack.
+/// 
+/// Values are ordered by variant first, in declaration order
+/// (`Empty < Bool < U64 < I64 < Bytes < String < List < Map < F64`), then by
+/// their contents. `Bytes`, `String` and `List` compare lexicographically,
+/// a prefix sorting first; `Map` compares its `(key, value)` entries
+/// lexicographically in key order. Sorting a `Vec<Data>` before hashing it
+/// therefore gives a canonical order.
+/// 
+/// `F64` values are ordered by `f64::total_cmp`, so they are equal exactly
+/// when their bits are: `0.0 != -0.0`, and NaNs are equal only to the same
+/// NaN. This keeps `Eq`, `Ord` and `Hash` consistent with each other and
+/// with the canonical encoding. Use `Data::approx_eq` to compare floats
+/// within `F64_EPSILON`.
+/// 
+/// `F64` must not be used in ZK contexts where determinism is required:
+/// floating-point arithmetic can differ between the prover and other
+/// platforms. See `NormalizedSpell::f64_warning`.
+#[derive(Debug, Clone, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub enum Data {
     /// No data
+    #[default]
     Empty,
     /// Boolean value
     Bool(bool),
@@ -212,14 +1392,195 @@ pub enum Data {
     String(String),
     /// List of data values
     List(Vec<Data>),
-    /// Map of string keys to data values
+    /// Map of string keys to data values, always iterated in key order
     Map(BTreeMap<String, Data>),
+    /// IEEE 754 binary64 float, compared bit for bit
+    F64(f64),
+}
+
+impl PartialEq for Data {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other).is_eq()
+    }
+}
+
+impl Eq for Data {}
+
+impl PartialOrd for Data {
+    fn partial_cmp(&self, other: &Self) -> Option<core::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Data {
+    fn cmp(&self, other: &Self) -> core::cmp::Ordering {
+        use core::cmp::Ordering;
+        
+        match (self, other) {
+            (Data::Empty, Data::Empty) => Ordering::Equal,
+            (Data::Bool(a), Data::Bool(b)) => a.cmp(b),
+            (Data::U64(a), Data::U64(b)) => a.cmp(b),
+            (Data::I64(a), Data::I64(b)) => a.cmp(b),
+            (Data::Bytes(a), Data::Bytes(b)) => a.cmp(b),
+            (Data::String(a), Data::String(b)) => a.cmp(b),
+            (Data::List(a), Data::List(b)) => a.cmp(b),
+            (Data::Map(a), Data::Map(b)) => a.cmp(b),
+            (Data::F64(a), Data::F64(b)) => a.total_cmp(b),
+            _ => self.variant_rank().cmp(&other.variant_rank()),
+        }
+    }
+}
+
+impl core::hash::Hash for Data {
+    fn hash<H: core::hash::Hasher>(&self, state: &mut H) {
+        self.variant_rank().hash(state);
+        match self {
+            Data::Empty => {}
+            Data::Bool(b) => b.hash(state),
+            Data::U64(n) => n.hash(state),
+            Data::I64(n) => n.hash(state),
+            Data::Bytes(bytes) => bytes.hash(state),
+            Data::String(s) => s.hash(state),
+            Data::List(items) => items.hash(state),
+            Data::Map(map) => map.hash(state),
+            Data::F64(n) => n.to_bits().hash(state),
+        }
+    }
 }
 
 impl Data {
-    /// Check if data is empty
+    /// Equality that treats `F64` values within `F64_EPSILON` (or both NaN)
+    /// as equal, at any depth; every other variant compares exactly
+    /// 
+    /// Not transitive, so not usable as `Eq`.
+    pub fn approx_eq(&self, other: &Data) -> bool {
+        match (self, other) {
+            (Data::F64(a), Data::F64(b)) => {
+                a == b || (a - b).abs() <= F64_EPSILON || (a.is_nan() && b.is_nan())
+            }
+            (Data::List(a), Data::List(b)) => {
+                a.len() == b.len() && a.iter().zip(b).all(|(a, b)| a.approx_eq(b))
+            }
+            (Data::Map(a), Data::Map(b)) => {
+                a.len() == b.len()
+                    && a.iter().zip(b).all(|((ka, va), (kb, vb))| ka == kb && va.approx_eq(vb))
+            }
+            _ => self == other,
+        }
+    }
+    
+    /// Name of this value's variant
+    pub(crate) fn variant_name(&self) -> &'static str {
+        match self {
+            Data::Empty => "Empty",
+            Data::Bool(_) => "Bool",
+            Data::U64(_) => "U64",
+            Data::I64(_) => "I64",
+            Data::Bytes(_) => "Bytes",
+            Data::String(_) => "String",
+            Data::List(_) => "List",
+            Data::Map(_) => "Map",
+            Data::F64(_) => "F64",
+        }
+    }
+    
+    /// Position of this value's variant in declaration order
+    fn variant_rank(&self) -> u8 {
+        match self {
+            Data::Empty => 0,
+            Data::Bool(_) => 1,
+            Data::U64(_) => 2,
+            Data::I64(_) => 3,
+            Data::Bytes(_) => 4,
+            Data::String(_) => 5,
+            Data::List(_) => 6,
+            Data::Map(_) => 7,
+            Data::F64(_) => 8,
+        }
+    }
+    
+    /// Check if data is `Empty`, an empty list or an empty map
     pub fn is_empty(&self) -> bool {
-        matches!(self, Data::Empty)
+        match self {
+            Data::Empty => true,
+            Data::List(items) => items.is_empty(),
+            Data::Map(map) => map.is_empty(),
+            _ => false,
+        }
+    }
+    
+    /// Number of bytes, characters, elements or entries; `None` for scalars
+    pub fn len(&self) -> Option<usize> {
+        match self {
+            Data::Bytes(bytes) => Some(bytes.len()),
+            Data::String(s) => Some(s.chars().count()),
+            Data::List(items) => Some(items.len()),
+            Data::Map(map) => Some(map.len()),
+            Data::Empty | Data::Bool(_) | Data::U64(_) | Data::I64(_) | Data::F64(_) => None,
+        }
+    }
+    
+    /// Whether this is, or nests, a `Data::F64`
+    pub fn contains_f64(&self) -> bool {
+        match self {
+            Data::F64(_) => true,
+            Data::List(items) => items.iter().any(Data::contains_f64),
+            Data::Map(map) => map.values().any(Data::contains_f64),
+            _ => false,
+        }
+    }
+    
+    /// Levels of `List`/`Map` nesting; scalars are depth 0
+    /// 
+    /// Deserialization rejects anything deeper than `MAX_DATA_DEPTH` (32).
+    pub fn depth(&self) -> usize {
+        match self {
+            Data::List(items) => 1 + items.iter().map(Data::depth).max().unwrap_or(0),
+            Data::Map(map) => 1 + map.values().map(Data::depth).max().unwrap_or(0),
+            _ => 0,
+        }
+    }
+    
+    /// Serialized size in bytes, for pre-allocating buffers
+    /// 
+    /// Exact for the bincode encoding used by `util` (a 4-byte variant tag,
+    /// 8-byte lengths); other encodings are usually smaller.
+    pub fn size_hint(&self) -> usize {
+        self.size_with(&BINCODE_SIZES)
+    }
+    
+    /// Approximate serialized size in bytes, for zkVM memory and I/O budgets
+    /// 
+    /// Scalars count 1 or 8 bytes and each length prefix 4, so unlike
+    /// `size_hint` this tracks compact encodings such as CBOR once byte
+    /// strings and text dominate.
+    pub fn size_estimate(&self) -> usize {
+        self.size_with(&COMPACT_SIZES)
+    }
+    
+    fn size_with(&self, sizes: &SizeModel) -> usize {
+        sizes.tag + match self {
+            Data::Empty => sizes.empty,
+            Data::Bool(_) => 1,
+            Data::U64(_) | Data::I64(_) | Data::F64(_) => 8,
+            Data::Bytes(bytes) => sizes.len + bytes.len(),
+            Data::String(s) => sizes.len + s.len(),
+            Data::List(items) => sizes.len + items.iter().map(|item| item.size_with(sizes)).sum::<usize>(),
+            Data::Map(map) => sizes.len + map.iter()
+                .map(|(key, value)| sizes.len + key.len() + value.size_with(sizes))
+                .sum::<usize>(),
+        }
+    }
+    
+    /// Deserialize, rejecting `List`/`Map` nesting deeper than `max_depth`
+    #[cfg(feature = "serde")]
+    pub fn deserialize_with_max_depth<'de, D: serde::Deserializer<'de>>(
+        deserializer: D,
+        max_depth: usize,
+    ) -> Result<Data, D::Error> {
+        use serde::de::DeserializeSeed;
+        
+        data_de::DataSeed { remaining: max_depth }.deserialize(deserializer)
     }
     
     /// Get as u64 if applicable
@@ -238,6 +1599,24 @@ impl Data {
         }
     }
     
+    /// Get as a 32-byte value (txid, vk hash, NFT id), if this is `Bytes` of
+    /// exactly that length
+    pub fn as_array32(&self) -> Option<[u8; 32]> {
+        self.as_bytes()?.try_into().ok()
+    }
+    
+    /// Bytes as the lowercase hex string the WASM bindings use, if applicable
+    pub fn to_wasm_hex(&self) -> Option<String> {
+        self.as_bytes().map(hex::encode)
+    }
+    
+    /// `Data::Bytes` from a WASM hex string
+    /// 
+    /// Either case is accepted; the length must be even.
+    pub fn from_wasm_hex(hex: &str) -> Result<Self, hex::HexError> {
+        hex::try_decode(hex).map(Data::Bytes)
+    }
+    
     /// Get as string if applicable
     pub fn as_str(&self) -> Option<&str> {
         match self {
@@ -245,60 +1624,2489 @@ impl Data {
             _ => None,
         }
     }
+    
+    /// Get as bool if applicable
+    pub fn as_bool(&self) -> Option<bool> {
+        match self {
+            Data::Bool(b) => Some(*b),
+            _ => None,
+        }
+    }
+    
+    /// Get as i64 if applicable
+    pub fn as_i64(&self) -> Option<i64> {
+        match self {
+            Data::I64(v) => Some(*v),
+            _ => None,
+        }
+    }
+    
+    /// Get as f64 if applicable
+    /// 
+    /// Integers are not converted; see the determinism caveats on `Data`.
+    pub fn as_f64(&self) -> Option<f64> {
+        match self {
+            Data::F64(v) => Some(*v),
+            _ => None,
+        }
+    }
+    
+    /// Get as map if applicable
+    pub fn as_map(&self) -> Option<&BTreeMap<String, Data>> {
+        match self {
+            Data::Map(map) => Some(map),
+            _ => None,
+        }
+    }
+    
+    /// Get as mutable map if applicable
+    pub fn as_map_mut(&mut self) -> Option<&mut BTreeMap<String, Data>> {
+        match self {
+            Data::Map(map) => Some(map),
+            _ => None,
+        }
+    }
+    
+    /// Walk nested maps by key, e.g. `&["collateral", "ratio"]`
+    /// 
+    /// `None` if a key is missing or a non-map is reached before the end of
+    /// the path. An empty path returns `self`.
+    pub fn get_path(&self, path: &[&str]) -> Option<&Data> {
+        path.iter().try_fold(self, |data, key| data.as_map()?.get(*key))
+    }
+    
+    /// Get as list if applicable
+    pub fn as_list(&self) -> Option<&[Data]> {
+        match self {
+            Data::List(items) => Some(items),
+            _ => None,
+        }
+    }
+    
+    /// Get as mutable list if applicable
+    pub fn as_list_mut(&mut self) -> Option<&mut Vec<Data>> {
+        match self {
+            Data::List(items) => Some(items),
+            _ => None,
+        }
+    }
+    
+    /// Get as u64, treating any other variant as an error
+    pub fn try_as_u64(&self) -> Result<u64, DataTypeError> {
+        self.as_u64().ok_or(DataTypeError {
+            expected: "U64",
+            actual: self.variant_name(),
+        })
+    }
+    
+    /// Combine two values of the same variant
+    /// 
+    /// `U64`s and `I64`s are added (failing on overflow), `Bytes` and `List`s
+    /// concatenated, and `Map`s merged, failing if both have a key. Any other
+    /// pair of variants is a `TypeMismatch`.
+    pub fn try_merge(self, other: Data) -> Result<Data, DataMergeError> {
+        match (self, other) {
+            (Data::U64(a), Data::U64(b)) => a.checked_add(b).map(Data::U64).ok_or(DataMergeError::Overflow),
+            (Data::I64(a), Data::I64(b)) => a.checked_add(b).map(Data::I64).ok_or(DataMergeError::Overflow),
+            (Data::Bytes(mut a), Data::Bytes(b)) => {
+                a.extend(b);
+                Ok(Data::Bytes(a))
+            }
+            (Data::List(mut a), Data::List(b)) => {
+                a.extend(b);
+                Ok(Data::List(a))
+            }
+            (Data::Map(mut a), Data::Map(b)) => {
+                for (key, value) in b {
+                    if a.contains_key(&key) {
+                        return Err(DataMergeError::KeyCollision(key));
+                    }
+                    a.insert(key, value);
+                }
+                Ok(Data::Map(a))
+            }
+            (a, b) => Err(DataMergeError::TypeMismatch {
+                left: a.variant_name(),
+                right: b.variant_name(),
+            }),
+        }
+    }
+
 }
 
-impl Default for Data {
-    fn default() -> Self {
-        Data::Empty
+/// Why two `Data` values could not be merged with `Data::try_merge`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DataMergeError {
+    /// The values are different variants, or a variant that can't be merged
+    TypeMismatch { left: &'static str, right: &'static str },
+    /// Adding the two numbers overflowed
+    Overflow,
+    /// Both maps have this key
+    KeyCollision(String),
+}
+
+impl fmt::Display for DataMergeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DataMergeError::TypeMismatch { left, right } => write!(f, "cannot merge {} with {}", left, right),
+            DataMergeError::Overflow => write!(f, "merge overflowed"),
+            DataMergeError::KeyCollision(key) => write!(f, "both maps have key {:?}", key),
+        }
     }
 }
 
-/// Utility functions for data handling
-pub mod util {
-    use super::*;
-    use std::io::Read;
-    
-    /// Read and deserialize data from stdin
-    pub fn read<R: Read>(_reader: R) -> Result<(App, Transaction, Data, Data), std::io::Error> {
-        // Placeholder - real implementation would use serde
-        Err(std::io::Error::new(
-            std::io::ErrorKind::Other,
-            "Not implemented"
-        ))
+impl core::error::Error for DataMergeError {}
+
+/// A `Data` value has a different variant than required
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DataTypeError {
+    /// Variant that was required
+    pub expected: &'static str,
+    /// Variant actually found
+    pub actual: &'static str,
+}
+
+/// JSON-like form for debugging: `null`, `true`, `7`, `1.5`, `0x0aff`,
+/// `"text"`, `[a, b]` and `{key: value}`
+impl fmt::Display for Data {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Data::Empty => write!(f, "null"),
+            Data::Bool(b) => write!(f, "{}", b),
+            Data::U64(n) => write!(f, "{}", n),
+            Data::I64(n) => write!(f, "{}", n),
+            // Debug formatting keeps the `.0` of whole numbers
+            Data::F64(n) => write!(f, "{:?}", n),
+            Data::Bytes(bytes) => write!(f, "0x{}", hex::encode(bytes)),
+            Data::String(s) => write!(f, "{:?}", s),
+            Data::List(items) => {
+                write!(f, "[")?;
+                for (i, item) in items.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{}", item)?;
+                }
+                write!(f, "]")
+            }
+            Data::Map(map) => fmt_map(f, map),
+        }
     }
 }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+/// Write a map as `{key: value, ...}`
+fn fmt_map(f: &mut fmt::Formatter<'_>, map: &BTreeMap<String, Data>) -> fmt::Result {
+    write!(f, "{{")?;
+    for (i, (key, value)) in map.iter().enumerate() {
+        if i > 0 {
+            write!(f, ", ")?;
+        }
+        write!(f, "{}: {}", key, value)?;
+    }
+    write!(f, "}}")
+}
+
+impl fmt::Display for DataTypeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "expected {} data, found {}", self.expected, self.actual)
+    }
+}
+
+impl core::error::Error for DataTypeError {}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for Data {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        Data::deserialize_with_max_depth(deserializer, MAX_DATA_DEPTH)
+    }
+}
+
+/// Depth-limited deserialization of `Data`, in the derived enum format
+#[cfg(feature = "serde")]
+mod data_de {
+    use super::Data;
+    use serde::de::{self, DeserializeSeed, EnumAccess, MapAccess, SeqAccess, VariantAccess, Visitor};
+    use alloc::collections::BTreeMap;
+    use core::fmt;
     
-    #[test]
-    fn test_app_creation() {
-        let app = App::new("test-token", [0u8; 32]);
-        assert_eq!(app.tag, "test-token");
+    const VARIANTS: &[&str] = &["Empty", "Bool", "U64", "I64", "Bytes", "String", "List", "Map", "F64"];
+    
+    /// Deserializes a `Data` allowing `remaining` more levels of nesting
+    pub(super) struct DataSeed {
+        pub remaining: usize,
     }
     
-    #[test]
-    fn test_charm_state() {
-        let state = CharmState::new()
-            .with_app("token", Data::U64(1000));
+    /// One level deeper, or an error if no levels remain
+    fn nested<E: de::Error>(remaining: usize) -> Result<usize, E> {
+        remaining.checked_sub(1)
+            .ok_or_else(|| E::custom("Data nested deeper than the maximum depth"))
+    }
+    
+    impl<'de> DeserializeSeed<'de> for DataSeed {
+        type Value = Data;
         
-        assert!(state.get("token").is_some());
-        assert_eq!(state.get("token").unwrap().as_u64(), Some(1000));
+        fn deserialize<D: de::Deserializer<'de>>(self, deserializer: D) -> Result<Data, D::Error> {
+            deserializer.deserialize_enum("Data", VARIANTS, self)
+        }
     }
     
-    #[test]
-    fn test_spell_verification() {
-        let mut spell = NormalizedSpell::new(1);
-        spell.ins.push(SpellInput {
-            utxo_ref: UtxoRef { txid: [0u8; 32], vout: 0 },
-            charms: None,
-        });
-        spell.outs.push(SpellOutput {
-            index: 0,
-            charms: None,
-        });
+    impl<'de> Visitor<'de> for DataSeed {
+        type Value = Data;
         
-        assert!(spell.verify());
+        fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            f.write_str("enum Data")
+        }
+        
+        fn visit_enum<A: EnumAccess<'de>>(self, data: A) -> Result<Data, A::Error> {
+            let (Variant(index), access) = data.variant()?;
+            match VARIANTS[index] {
+                "Empty" => access.unit_variant().map(|_| Data::Empty),
+                "Bool" => access.newtype_variant().map(Data::Bool),
+                "U64" => access.newtype_variant().map(Data::U64),
+                "I64" => access.newtype_variant().map(Data::I64),
+                "Bytes" => access.newtype_variant().map(Data::Bytes),
+                "String" => access.newtype_variant().map(Data::String),
+                "List" => access
+                    .newtype_variant_seed(ListSeed { remaining: nested(self.remaining)? })
+                    .map(Data::List),
+                "Map" => access
+                    .newtype_variant_seed(MapSeed { remaining: nested(self.remaining)? })
+                    .map(Data::Map),
+                _ => access.newtype_variant().map(Data::F64),
+            }
+        }
+    }
+    
+    /// Variant index, read from either its position or its name
+    struct Variant(usize);
+    
+    impl<'de> de::Deserialize<'de> for Variant {
+        fn deserialize<D: de::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+            deserializer.deserialize_identifier(VariantVisitor)
+        }
+    }
+    
+    struct VariantVisitor;
+    
+    impl Visitor<'_> for VariantVisitor {
+        type Value = Variant;
+        
+        fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            f.write_str("Data variant")
+        }
+        
+        fn visit_u64<E: de::Error>(self, index: u64) -> Result<Variant, E> {
+            match usize::try_from(index) {
+                Ok(index) if index < VARIANTS.len() => Ok(Variant(index)),
+                _ => Err(E::invalid_value(de::Unexpected::Unsigned(index), &self)),
+            }
+        }
+        
+        fn visit_str<E: de::Error>(self, name: &str) -> Result<Variant, E> {
+            VARIANTS.iter()
+                .position(|variant| *variant == name)
+                .map(Variant)
+                .ok_or_else(|| E::unknown_variant(name, VARIANTS))
+        }
+    }
+    
+    struct ListSeed {
+        remaining: usize,
+    }
+    
+    impl<'de> DeserializeSeed<'de> for ListSeed {
+        type Value = Vec<Data>;
+        
+        fn deserialize<D: de::Deserializer<'de>>(self, deserializer: D) -> Result<Vec<Data>, D::Error> {
+            deserializer.deserialize_seq(self)
+        }
+    }
+    
+    impl<'de> Visitor<'de> for ListSeed {
+        type Value = Vec<Data>;
+        
+        fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            f.write_str("a list of Data")
+        }
+        
+        fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Vec<Data>, A::Error> {
+            let mut items = Vec::with_capacity(seq.size_hint().unwrap_or(0).min(1024));
+            while let Some(item) = seq.next_element_seed(DataSeed { remaining: self.remaining })? {
+                items.push(item);
+            }
+            Ok(items)
+        }
+    }
+    
+    struct MapSeed {
+        remaining: usize,
+    }
+    
+    impl<'de> DeserializeSeed<'de> for MapSeed {
+        type Value = BTreeMap<String, Data>;
+        
+        fn deserialize<D: de::Deserializer<'de>>(self, deserializer: D) -> Result<Self::Value, D::Error> {
+            deserializer.deserialize_map(self)
+        }
+    }
+    
+    impl<'de> Visitor<'de> for MapSeed {
+        type Value = BTreeMap<String, Data>;
+        
+        fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            f.write_str("a map of strings to Data")
+        }
+        
+        fn visit_map<A: MapAccess<'de>>(self, mut access: A) -> Result<Self::Value, A::Error> {
+            let mut map = BTreeMap::new();
+            while let Some(key) = access.next_key::<String>()? {
+                let value = access.next_value_seed(DataSeed { remaining: self.remaining })?;
+                map.insert(key, value);
+            }
+            Ok(map)
+        }
+    }
+}
+
+/// Expected shape of an app's charm state
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum DataSchema {
+    /// Any value is accepted
+    Any,
+    /// A `Data::U64`
+    U64,
+    /// A `Data::Bytes` of at most `max_len` bytes
+    Bytes { max_len: usize },
+    /// A `Data::String` of at most `max_len` bytes
+    Str { max_len: usize },
+    /// A `Data::Map` with exactly these keys, each matching its schema
+    Map(BTreeMap<String, DataSchema>),
+    /// A `Data::List` of at most `max_len` items, each matching `item`
+    List { item: Box<DataSchema>, max_len: usize },
+}
+
+impl DataSchema {
+    /// Check that `data` matches this schema
+    pub fn validate(&self, data: &Data) -> Result<(), SchemaViolation> {
+        self.validate_at("$", data)
+    }
+    
+    fn validate_at(&self, path: &str, data: &Data) -> Result<(), SchemaViolation> {
+        let mismatch = |expected: &'static str| SchemaViolation::TypeMismatch {
+            path: path.to_string(),
+            expected,
+            found: data.variant_name(),
+        };
+        let check_len = |len: usize, max_len: usize| {
+            if len > max_len {
+                Err(SchemaViolation::TooLong { path: path.to_string(), len, max_len })
+            } else {
+                Ok(())
+            }
+        };
+        
+        match (self, data) {
+            (DataSchema::Any, _) => Ok(()),
+            (DataSchema::U64, Data::U64(_)) => Ok(()),
+            (DataSchema::Bytes { max_len }, Data::Bytes(bytes)) => check_len(bytes.len(), *max_len),
+            (DataSchema::Str { max_len }, Data::String(s)) => check_len(s.len(), *max_len),
+            (DataSchema::Map(fields), Data::Map(map)) => {
+                for (key, schema) in fields {
+                    let value = map.get(key).ok_or_else(|| SchemaViolation::MissingKey {
+                        path: path.to_string(),
+                        key: key.clone(),
+                    })?;
+                    schema.validate_at(&format!("{}.{}", path, key), value)?;
+                }
+                if let Some(key) = map.keys().find(|key| !fields.contains_key(*key)) {
+                    return Err(SchemaViolation::UnexpectedKey {
+                        path: path.to_string(),
+                        key: key.clone(),
+                    });
+                }
+                Ok(())
+            }
+            (DataSchema::List { item, max_len }, Data::List(items)) => {
+                check_len(items.len(), *max_len)?;
+                for (i, value) in items.iter().enumerate() {
+                    item.validate_at(&format!("{}[{}]", path, i), value)?;
+                }
+                Ok(())
+            }
+            (DataSchema::U64, _) => Err(mismatch("U64")),
+            (DataSchema::Bytes { .. }, _) => Err(mismatch("Bytes")),
+            (DataSchema::Str { .. }, _) => Err(mismatch("String")),
+            (DataSchema::Map(_), _) => Err(mismatch("Map")),
+            (DataSchema::List { .. }, _) => Err(mismatch("List")),
+        }
+    }
+}
+
+/// Where and how a value failed to match its `DataSchema`
+/// 
+/// Paths are written `$.key[index]`, with `$` the root value.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SchemaViolation {
+    /// The value has the wrong variant
+    TypeMismatch { path: String, expected: &'static str, found: &'static str },
+    /// Bytes, string or list longer than allowed
+    TooLong { path: String, len: usize, max_len: usize },
+    /// A map is missing a key required by the schema
+    MissingKey { path: String, key: String },
+    /// A map has a key the schema does not declare
+    UnexpectedKey { path: String, key: String },
+}
+
+impl fmt::Display for SchemaViolation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SchemaViolation::TypeMismatch { path, expected, found } => {
+                write!(f, "{}: expected {}, found {}", path, expected, found)
+            }
+            SchemaViolation::TooLong { path, len, max_len } => {
+                write!(f, "{}: length {} exceeds maximum {}", path, len, max_len)
+            }
+            SchemaViolation::MissingKey { path, key } => write!(f, "{}: missing key {:?}", path, key),
+            SchemaViolation::UnexpectedKey { path, key } => write!(f, "{}: unexpected key {:?}", path, key),
+        }
+    }
+}
+
+impl core::error::Error for SchemaViolation {}
+
+/// Why a checker rejected a spell
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CheckError {
+    /// Token amounts going in and out differ
+    ConservationViolation { input: u64, output: u64 },
+    /// The app state transition is not allowed
+    InvalidTransition,
+    /// The same NFT id appears in more than one output
+    DuplicateNft(Vec<u8>),
+    /// Authorization or signature in `x` is missing or invalid
+    MissingAuthorization,
+    /// A minted NFT is malformed or its id is not derived from its inputs
+    InvalidMint,
+    /// Summing amounts overflowed `u64`
+    Overflow,
+    /// More outputs carry the app's state than `App::max_outputs` allows
+    TooManyOutputs { count: usize, max: usize },
+    /// A field the transition requires is missing or empty
+    MissingField(&'static str),
+    /// An asset the transaction must deliver did not reach its recipient
+    UndeliveredAsset(String),
+    /// An NFT changed hands without paying its creator's royalty
+    RoyaltyUnpaid { required: u64, paid: u64 },
+    /// A height-locked transition was attempted before its timeout height
+    TimeoutNotReached { height: u32, timeout: u32 },
+    /// An output carries a different amount than the app's terms require
+    AmountMismatch { expected: u64, actual: u64 },
+    /// Tokens claimed as burned were not sent to a burn script
+    InvalidBurn,
+    /// A mint creates more tokens than the app's supply cap allows
+    SupplyCapExceeded { minted: u64, max_supply: u64 },
+    /// Collateral backing a stablecoin is below the required amount
+    Undercollateralized { required: u64, collateral: u64 },
+    /// JSON does not encode a `Data` value
+    InvalidJson(String),
+}
+
+impl fmt::Display for CheckError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CheckError::ConservationViolation { input, output } => {
+                write!(f, "conservation violated: {} in, {} out", input, output)
+            }
+            CheckError::InvalidTransition => write!(f, "invalid state transition"),
+            CheckError::DuplicateNft(id) => write!(f, "duplicate NFT {}", hex::encode(id)),
+            CheckError::MissingAuthorization => write!(f, "missing or invalid authorization"),
+            CheckError::InvalidMint => write!(f, "invalid NFT mint"),
+            CheckError::Overflow => write!(f, "amount overflow"),
+            CheckError::TooManyOutputs { count, max } => {
+                write!(f, "{} app outputs exceed maximum {}", count, max)
+            }
+            CheckError::MissingField(field) => write!(f, "missing field {:?}", field),
+            CheckError::UndeliveredAsset(tag) => write!(f, "asset {} not delivered to recipient", tag),
+            CheckError::RoyaltyUnpaid { required, paid } => {
+                write!(f, "royalty of {} required, {} paid", required, paid)
+            }
+            CheckError::TimeoutNotReached { height, timeout } => {
+                write!(f, "height {} is before timeout {}", height, timeout)
+            }
+            CheckError::AmountMismatch { expected, actual } => {
+                write!(f, "expected {} sats, got {}", expected, actual)
+            }
+            CheckError::InvalidBurn => write!(f, "burn not sent to a burn script"),
+            CheckError::SupplyCapExceeded { minted, max_supply } => {
+                write!(f, "minted {} exceeds max supply {}", minted, max_supply)
+            }
+            CheckError::Undercollateralized { required, collateral } => {
+                write!(f, "collateral {} below required {}", collateral, required)
+            }
+            CheckError::InvalidJson(msg) => write!(f, "invalid data JSON: {}", msg),
+        }
+    }
+}
+
+impl core::error::Error for CheckError {}
+
+/// Utility functions for data handling
+/// 
+/// With the `serde` feature, spell data is bincode-encoded. Framed messages
+/// are a 4-byte little-endian length followed by that many bytes of payload,
+/// so a host can stream many spells over one pipe. Requires the `std` feature.
+#[cfg(feature = "std")]
+pub mod util {
+    use super::*;
+    use std::io::{self, Read};
+    #[cfg(feature = "serde")]
+    use std::io::Write;
+    #[cfg(feature = "serde")]
+    use bincode::Options;
+    
+    /// Version of the bincode layout of `SpellData`
+    /// 
+    /// Bincode has no field defaults, so any field added to a serialized
+    /// type changes the layout and bumps this. Version 2 added
+    /// `Transaction::version` and `TxInput::script_sig`/`sequence`.
+    pub const WIRE_VERSION: u32 = 2;
+    
+    /// Largest framed payload accepted by `read_framed`
+    pub const MAX_FRAME_LEN: usize = 16 * 1024 * 1024;
+    
+    /// Spell checker input: `(app, tx, x, w)`
+    pub type SpellData = (App, Transaction, Data, Data);
+    
+    /// Bincode with fixed-width integers, rejecting trailing bytes
+    #[cfg(feature = "serde")]
+    fn bincode_options() -> impl Options {
+        bincode::DefaultOptions::new()
+            .with_fixint_encoding()
+            .reject_trailing_bytes()
+    }
+    
+    /// Read and deserialize a value, usually `SpellData` from stdin
+    #[cfg(feature = "serde")]
+    pub fn read<T: serde::de::DeserializeOwned, R: Read>(reader: R) -> Result<T, io::Error> {
+        bincode_options().deserialize_from(reader).map_err(io::Error::other)
+    }
+    
+    /// Read and deserialize a value, usually `SpellData` from stdin
+    #[cfg(not(feature = "serde"))]
+    pub fn read<T, R: Read>(_reader: R) -> Result<T, io::Error> {
+        Err(io::Error::other("Deserialization requires the `serde` feature"))
+    }
+    
+    /// Serialize `value` in the encoding `read` accepts
+    #[cfg(feature = "serde")]
+    pub fn write<T: serde::Serialize>(value: &T) -> Result<Vec<u8>, io::Error> {
+        bincode_options().serialize(value).map_err(io::Error::other)
+    }
+    
+    /// Deserialize spell data from an in-memory buffer
+    #[cfg(feature = "serde")]
+    pub fn read_from_slice(bytes: &[u8]) -> Result<SpellData, io::Error> {
+        bincode_options().deserialize(bytes).map_err(io::Error::other)
+    }
+    
+    /// Read one framed message, or `None` at a clean end of input
+    /// 
+    /// Only the frame itself is read, so the reader is left positioned at the
+    /// start of the next one.
+    #[cfg(feature = "serde")]
+    pub fn read_framed<T: serde::de::DeserializeOwned, R: Read>(reader: &mut R) -> Result<Option<T>, io::Error> {
+        let mut len_bytes = [0u8; 4];
+        let mut filled = 0;
+        while filled < len_bytes.len() {
+            match reader.read(&mut len_bytes[filled..])? {
+                0 if filled == 0 => return Ok(None),
+                0 => return Err(io::ErrorKind::UnexpectedEof.into()),
+                n => filled += n,
+            }
+        }
+        
+        let len = u32::from_le_bytes(len_bytes) as usize;
+        if len > MAX_FRAME_LEN {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("frame of {} bytes exceeds maximum {}", len, MAX_FRAME_LEN),
+            ));
+        }
+        
+        let mut payload = vec![0u8; len];
+        reader.read_exact(&mut payload)?;
+        bincode_options().deserialize(&payload).map(Some).map_err(io::Error::other)
+    }
+    
+    /// Write `value` as one framed message
+    #[cfg(feature = "serde")]
+    pub fn write_framed<T: serde::Serialize, W: Write>(writer: &mut W, value: &T) -> Result<(), io::Error> {
+        let payload = bincode_options().serialize(value).map_err(io::Error::other)?;
+        if payload.len() > MAX_FRAME_LEN {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, "frame too large"));
+        }
+        writer.write_all(&(payload.len() as u32).to_le_bytes())?;
+        writer.write_all(&payload)
+    }
+}
+
+/// Escrow state machine shared by the native and WASM checkers
+/// 
+/// States are carried in charm state as `Data::U64` codes: `0..=4` for the
+/// fixed states and `100 + n` for a completed milestone `n`. From funding
+/// on, the code sits under `"state"` in a `Data::Map` that also records the
+/// funded `"amount"`.
+pub mod escrow {
+    use super::{CheckError, Data, Transaction};
+    use core::fmt;
+    
+    /// Escrow states
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub enum EscrowState {
+        Created,
+        Funded,
+        MilestoneCompleted(u32),
+        Released,
+        Disputed,
+        Refunded,
+    }
+    
+    /// Offset added to a milestone number to form its state code
+    pub const MILESTONE_BASE: u64 = 100;
+    
+    impl EscrowState {
+        /// Parse a state from its numeric code
+        pub fn from_code(code: u64) -> Option<Self> {
+            match code {
+                0 => Some(EscrowState::Created),
+                1 => Some(EscrowState::Funded),
+                2 => Some(EscrowState::Released),
+                3 => Some(EscrowState::Disputed),
+                4 => Some(EscrowState::Refunded),
+                n if n >= MILESTONE_BASE => {
+                    u32::try_from(n - MILESTONE_BASE).ok().map(EscrowState::MilestoneCompleted)
+                }
+                _ => None,
+            }
+        }
+        
+        /// Numeric code for this state
+        pub fn code(&self) -> u64 {
+            match self {
+                EscrowState::Created => 0,
+                EscrowState::Funded => 1,
+                EscrowState::Released => 2,
+                EscrowState::Disputed => 3,
+                EscrowState::Refunded => 4,
+                EscrowState::MilestoneCompleted(n) => MILESTONE_BASE + *n as u64,
+            }
+        }
+    }
+    
+    impl fmt::Display for EscrowState {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            match self {
+                EscrowState::Created => write!(f, "Created"),
+                EscrowState::Funded => write!(f, "Funded"),
+                EscrowState::MilestoneCompleted(n) => write!(f, "MilestoneCompleted({})", n),
+                EscrowState::Released => write!(f, "Released"),
+                EscrowState::Disputed => write!(f, "Disputed"),
+                EscrowState::Refunded => write!(f, "Refunded"),
+            }
+        }
+    }
+    
+    /// Check whether moving from `current` to `next` is a legal escrow transition
+    /// 
+    /// `None` means no escrow state is present on that side of the transaction.
+    /// A `Funded` escrow may be released directly. Milestones are completed
+    /// strictly in order: `Funded` may only advance to milestone 0, and
+    /// milestone `n` only to milestone `n + 1`. Any completed milestone may
+    /// then be released or disputed.
+    pub fn is_valid_transition(current: Option<EscrowState>, next: Option<EscrowState>) -> bool {
+        use EscrowState::*;
+        
+        match (current, next) {
+            (None, Some(Created)) => true, // Initial creation
+            (Some(Created), Some(Funded)) => true,
+            (Some(Funded), Some(Released)) => true,
+            (Some(Funded), Some(MilestoneCompleted(0))) => true,
+            (Some(MilestoneCompleted(n)), Some(MilestoneCompleted(m))) => n.checked_add(1) == Some(m),
+            (Some(MilestoneCompleted(_)), Some(Released)) => true,
+            (Some(MilestoneCompleted(_)), Some(Disputed)) => true,
+            (Some(Funded), Some(Disputed)) => true,
+            (Some(Disputed), Some(Refunded)) => true,
+            (Some(Disputed), Some(Released)) => true,
+            _ => false, // Invalid transition
+        }
+    }
+    
+    /// State of an escrow's charm data: a map's `"state"` code, or a bare code
+    pub fn parse_state(data: &Data) -> Option<EscrowState> {
+        let code = match data {
+            Data::Map(map) => map.get("state")?.as_u64()?,
+            _ => data.as_u64()?,
+        };
+        EscrowState::from_code(code)
+    }
+    
+    /// Check the funded `"amount"` is recorded correctly, carried forward and
+    /// paid out in full
+    /// 
+    /// `milestone_remainder` is what the escrow's milestones left unpaid, if
+    /// the current state completed one; the caller knows the schedule. A
+    /// `Disputed` escrow's recorded `"remaining"` is read here.
+    pub fn check_amount_preserved(
+        tag: &str,
+        tx: &Transaction,
+        current_data: Option<&Data>,
+        next_state: EscrowState,
+        milestone_remainder: Option<u64>,
+    ) -> Result<(), CheckError> {
+        let recorded_amount = |data: &Data| match data {
+            Data::Map(map) => map.get("amount")
+                .map(|amount| amount.as_u64().ok_or(CheckError::MissingField("amount")))
+                .transpose(),
+            _ => Ok(None),
+        };
+        let next_data = tx.outputs.iter()
+            .filter_map(|output| output.charm_state.as_ref()?.get(tag))
+            .find(|data| parse_state(data) == Some(next_state));
+        let next_amount = match next_data {
+            Some(data) => recorded_amount(data)?,
+            None => None,
+        };
+        
+        let current_state = current_data.and_then(parse_state);
+        let funded = match current_data {
+            Some(data) => recorded_amount(data)?,
+            None => None,
+        };
+        let remaining = match (milestone_remainder, current_data) {
+            (Some(remaining), _) => Some(remaining),
+            (None, Some(data)) => recorded_remaining(data)?,
+            (None, None) => None,
+        };
+        let funded = match (funded, current_state) {
+            (_, None | Some(EscrowState::Created)) => {
+                // Funding records the amount, which must be what was locked
+                if next_state == EscrowState::Funded {
+                    let expected = next_amount.ok_or(CheckError::MissingField("amount"))?;
+                    let actual = state_outputs_value(tag, tx, next_state)?;
+                    if actual != expected {
+                        return Err(CheckError::AmountMismatch { expected, actual });
+                    }
+                }
+                return Ok(());
+            }
+            (Some(funded), _) => funded,
+            // Every state after funding carries the amount forward
+            (None, Some(_)) => return Err(CheckError::MissingField("amount")),
+        };
+        
+        match next_state {
+            EscrowState::Released | EscrowState::Refunded => {
+                // After milestones, only their remainder is left to pay out
+                let expected = remaining.unwrap_or(funded);
+                let actual = state_outputs_value(tag, tx, next_state)?;
+                if actual != expected {
+                    return Err(CheckError::AmountMismatch { expected, actual });
+                }
+            }
+            _ => {
+                let actual = next_amount.ok_or(CheckError::MissingField("amount"))?;
+                if actual != funded {
+                    return Err(CheckError::AmountMismatch { expected: funded, actual });
+                }
+            }
+        }
+        
+        if next_state == EscrowState::Disputed {
+            // A dispute settles whatever the milestones left in escrow
+            let expected = remaining.unwrap_or(funded);
+            let actual = match next_data {
+                Some(data) => recorded_remaining(data)?,
+                None => None,
+            };
+            let actual = actual.unwrap_or(funded);
+            if actual != expected {
+                return Err(CheckError::AmountMismatch { expected, actual });
+            }
+        }
+        Ok(())
+    }
+    
+    /// The `"remaining"` sats a `Disputed` escrow recorded from its milestones
+    pub fn recorded_remaining(data: &Data) -> Result<Option<u64>, CheckError> {
+        match (parse_state(data), data) {
+            (Some(EscrowState::Disputed), Data::Map(map)) => map.get("remaining")
+                .map(|remaining| remaining.as_u64().ok_or(CheckError::MissingField("remaining")))
+                .transpose(),
+            _ => Ok(None),
+        }
+    }
+    
+    /// Total sats in outputs carrying `tag`'s escrow in `state`
+    pub fn state_outputs_value(tag: &str, tx: &Transaction, state: EscrowState) -> Result<u64, CheckError> {
+        tx.outputs.iter()
+            .filter(|output| {
+                output.charm_state.as_ref()
+                    .and_then(|charms| charms.get(tag))
+                    .and_then(parse_state) == Some(state)
+            })
+            .try_fold(0u64, |sum, output| sum.checked_add(output.value))
+            .ok_or(CheckError::Overflow)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    #[cfg(feature = "serde")]
+    fn sample_spell_data(tag: &str, amount: u64) -> util::SpellData {
+        let app = App::new(tag, [1u8; 32]);
+        let mut tx = Transaction::new([2u8; 32]);
+        tx.add_output(TxOutput {
+            index: 0,
+            value: 546,
+            script_pubkey: vec![0x00, 0x14],
+            charm_state: Some(CharmState::new().with_app(tag, Data::U64(amount))),
+        });
+        (app, tx, Data::Bytes(vec![1, 2, 3]), Data::Empty)
+    }
+    
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_read_framed_consecutive_messages() {
+        let first = sample_spell_data("token:A", 100);
+        let second = sample_spell_data("nft:B", 7);
+        
+        let mut buf = Vec::new();
+        util::write_framed(&mut buf, &first).unwrap();
+        util::write_framed(&mut buf, &second).unwrap();
+        
+        let mut reader = buf.as_slice();
+        let decoded: Option<util::SpellData> = util::read_framed(&mut reader).unwrap();
+        assert_eq!(decoded, Some(first));
+        let decoded: Option<util::SpellData> = util::read_framed(&mut reader).unwrap();
+        assert_eq!(decoded, Some(second));
+        let decoded: Option<util::SpellData> = util::read_framed(&mut reader).unwrap();
+        assert_eq!(decoded, None);
+    }
+    
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_read_framed_truncated_and_oversized() {
+        let mut buf = Vec::new();
+        util::write_framed(&mut buf, &sample_spell_data("token:A", 100)).unwrap();
+        buf.truncate(buf.len() - 1);
+        assert!(util::read_framed::<util::SpellData, _>(&mut buf.as_slice()).is_err());
+        
+        let oversized = (util::MAX_FRAME_LEN as u32 + 1).to_le_bytes();
+        assert!(util::read_framed::<util::SpellData, _>(&mut oversized.as_slice()).is_err());
+    }
+    
+    /// `depth` lists nested inside each other around a single `U64`
+    fn nested_list(depth: usize) -> Data {
+        (0..depth).fold(Data::U64(1), |data, _| Data::List(vec![data]))
+    }
+    
+    #[test]
+    fn test_transaction_height() {
+        let mut tx = Transaction::new([0u8; 32]);
+        tx.inputs.push(TxInput {
+            sequence: MAX_SEQUENCE_NONFINAL,
+            ..TxInput::new(UtxoRef { txid: [1u8; 32], vout: 0 }, None)
+        });
+        assert_eq!(tx.height(), None);
+        assert_eq!(tx.clone().with_locktime(0).height(), None);
+        assert_eq!(tx.clone().with_locktime(840_000).height(), Some(840_000));
+        assert_eq!(tx.clone().with_locktime(LOCKTIME_THRESHOLD - 1).height(), Some(LOCKTIME_THRESHOLD - 1));
+        
+        // A Unix timestamp, not a height
+        assert_eq!(tx.clone().with_locktime(1_700_000_000).height(), None);
+    }
+    
+    #[test]
+    fn test_transaction_height_ignored_when_inputs_final() {
+        let mut tx = Transaction::new([0u8; 32]).with_locktime(840_000);
+        tx.inputs.push(TxInput::new(UtxoRef { txid: [1u8; 32], vout: 0 }, None));
+        tx.inputs.push(TxInput::new(UtxoRef { txid: [1u8; 32], vout: 1 }, None));
+        assert_eq!(tx.height(), None);
+        
+        // One non-final input is enough for the locktime to be enforced
+        tx.inputs[1].sequence = MAX_SEQUENCE_NONFINAL;
+        assert_eq!(tx.height(), Some(840_000));
+        
+        // No inputs at all
+        assert_eq!(Transaction::new([0u8; 32]).with_locktime(840_000).height(), None);
+    }
+    
+    #[test]
+    fn test_transaction_lookups() {
+        let first = UtxoRef { txid: [1u8; 32], vout: 0 };
+        let second = UtxoRef { txid: [1u8; 32], vout: 1 };
+        let mut tx = Transaction::new([9u8; 32]);
+        for (utxo_ref, value) in [(&first, 1), (&second, 2), (&first, 3)] {
+            tx.add_input(TxInput {
+                value: Some(value),
+                ..TxInput::new(utxo_ref.clone(), None)
+            });
+        }
+        tx.add_output(p2wpkh_output(0, 1_000));
+        tx.add_output(p2wpkh_output(1, 2_000));
+        tx.add_output(p2wpkh_output(0, 3_000));
+        
+        // Duplicates resolve to the first match
+        assert_eq!(tx.input_by_utxo_ref(&first).and_then(|i| i.value), Some(1));
+        assert_eq!(tx.input_by_utxo_ref(&second).and_then(|i| i.value), Some(2));
+        assert!(tx.input_by_utxo_ref(&UtxoRef { txid: [2u8; 32], vout: 0 }).is_none());
+        assert_eq!(tx.output_by_index(0).map(|o| o.value), Some(1_000));
+        assert_eq!(tx.output_by_index(1).map(|o| o.value), Some(2_000));
+        assert!(tx.output_by_index(2).is_none());
+        
+        tx.output_by_index_mut(0).unwrap().value = 1_500;
+        assert_eq!(tx.outputs[0].value, 1_500);
+        assert_eq!(tx.outputs[2].value, 3_000);
+        tx.input_by_utxo_ref_mut(&first).unwrap().value = None;
+        assert_eq!(tx.inputs[0].value, None);
+        assert_eq!(tx.inputs[2].value, Some(3));
+        
+        let refs: Vec<&UtxoRef> = tx.input_utxo_refs().collect();
+        assert_eq!(refs, vec![&first, &second, &first]);
+    }
+    
+    #[test]
+    fn test_transaction_app_inputs_and_outputs() {
+        let mut tx = Transaction::new([9u8; 32]);
+        for (vout, state) in [
+            (0, Some(CharmState::new().with_app("token:A", Data::U64(10)))),
+            (1, None),
+            (2, Some(CharmState::new().with_app("token:B", Data::U64(20)))),
+            (3, Some(CharmState::new().with_app("token:A", Data::U64(30)).with_app("token:B", Data::U64(5)))),
+        ] {
+            tx.add_input(TxInput::new(UtxoRef { txid: [1u8; 32], vout }, state));
+        }
+        for (index, state) in [
+            (0, Some(CharmState::new().with_app("token:B", Data::U64(25)))),
+            (1, Some(CharmState::new().with_app("token:A", Data::U64(40)))),
+            (2, None),
+        ] {
+            tx.add_output(TxOutput { charm_state: state, ..p2wpkh_output(index, 1_000) });
+        }
+        
+        let inputs: Vec<(u32, &Data)> = tx.app_inputs("token:A").map(|(i, data)| (i.utxo_ref.vout, data)).collect();
+        assert_eq!(inputs, vec![(0, &Data::U64(10)), (3, &Data::U64(30))]);
+        let outputs: Vec<(u32, &Data)> = tx.app_outputs("token:A").map(|(o, data)| (o.index, data)).collect();
+        assert_eq!(outputs, vec![(1, &Data::U64(40))]);
+        
+        assert_eq!(tx.app_inputs("token:B").count(), 2);
+        assert_eq!(tx.app_output_count("token:B"), 1);
+        assert_eq!(tx.app_inputs("token:C").count(), 0);
+        assert_eq!(tx.app_outputs("token:C").count(), 0);
+    }
+    
+    #[test]
+    fn test_transaction_charm_apps() {
+        let mut tx = Transaction::new([9u8; 32]);
+        tx.add_output(p2wpkh_output(0, 1_000));
+        assert!(tx.charm_apps().is_empty());
+        assert!(!tx.has_charm("token:A"));
+        
+        // The same app on both sides is listed once
+        let state = |tags: &[&str]| {
+            tags.iter().fold(CharmState::new(), |state, tag| state.with_app(*tag, Data::U64(1)))
+        };
+        tx.add_input(TxInput::new(
+            UtxoRef { txid: [1u8; 32], vout: 0 },
+            Some(state(&["token:A"])),
+        ));
+        tx.outputs[0].charm_state = Some(state(&["token:A"]));
+        assert_eq!(tx.charm_apps(), BTreeSet::from(["token:A".to_string()]));
+        
+        tx.add_output(TxOutput { charm_state: Some(state(&["nft:B", "escrow:C"])), ..p2wpkh_output(1, 1_000) });
+        assert_eq!(
+            tx.charm_apps(),
+            BTreeSet::from(["escrow:C".to_string(), "nft:B".to_string(), "token:A".to_string()])
+        );
+        assert!(tx.has_charm("token:A"));
+        assert!(tx.has_charm("escrow:C"));
+        assert!(!tx.has_charm("token:B"));
+    }
+    
+    fn p2wpkh_output(index: u32, value: u64) -> TxOutput {
+        TxOutput {
+            index,
+            value,
+            script_pubkey: [vec![0x00, 0x14], vec![0x42; 20]].concat(),
+            charm_state: None,
+        }
+    }
+    
+    #[test]
+    fn test_full_validate_reports_every_problem() {
+        let config = VerificationConfig::default();
+        let mut tx = Transaction::new([9u8; 32]);
+        tx.add_input(TxInput {
+            value: Some(10_000),
+            ..TxInput::new(UtxoRef { txid: [1u8; 32], vout: 0 }, None)
+        });
+        tx.add_output(p2wpkh_output(0, 1_000));
+        tx.add_output(TxOutput {
+            index: 1,
+            value: 0,
+            script_pubkey: vec![0x6a, 0x00],
+            charm_state: None,
+        });
+        assert_eq!(tx.full_validate(&config), Ok(()));
+        
+        // Self-reference, duplicate output index and dust at once
+        tx.add_input(TxInput::new(UtxoRef { txid: [9u8; 32], vout: 1 }, None));
+        tx.add_output(TxOutput {
+            charm_state: Some(CharmState::new().with_app("token:A", Data::U64(1))),
+            ..p2wpkh_output(0, 100)
+        });
+        
+        assert_eq!(tx.full_validate(&config), Err(vec![
+            TransactionError::DuplicateOutputIndex { first: 0, duplicate: 2, index: 0 },
+            TransactionError::SelfReference { input: 1 },
+            TransactionError::DustOutput { output: 2, value: 100, dust_limit: DUST_LIMIT },
+        ]));
+        
+        // Both entry points apply the same rule
+        assert!(!tx.validate_dust(config.dust_limit));
+        tx.outputs[2].charm_state = None;
+        assert!(tx.validate_dust(config.dust_limit));
+        assert_eq!(tx.full_validate(&config).unwrap_err().len(), 2);
+    }
+    
+    #[test]
+    fn test_full_validate_inputs_and_scripts() {
+        let config = VerificationConfig { dust_limit: 0, max_script_len: 4 };
+        let utxo = UtxoRef { txid: [1u8; 32], vout: 0 };
+        let mut tx = Transaction::new([9u8; 32]);
+        for _ in 0..2 {
+            tx.add_input(TxInput::new(utxo.clone(), None));
+        }
+        tx.add_output(TxOutput { index: 0, value: 1, script_pubkey: vec![], charm_state: None });
+        tx.add_output(p2wpkh_output(1, 1));
+        
+        let errors = tx.full_validate(&config).unwrap_err();
+        assert_eq!(errors, vec![
+            TransactionError::DuplicateInput { first: 0, duplicate: 1 },
+            TransactionError::InvalidScript { output: 0, reason: "empty script" },
+            TransactionError::InvalidScript { output: 1, reason: "script too long" },
+        ]);
+        assert_eq!(errors[0].to_string(), "inputs[1] spends the same UTXO as inputs[0]");
+    }
+    
+    #[test]
+    fn test_transaction_hash_independent_of_insertion_order() {
+        let forwards = CharmState::new()
+            .with_app("token:A", Data::U64(1))
+            .with_app("nft:B", Data::Bytes(vec![2; 32]));
+        let backwards = CharmState::new()
+            .with_app("nft:B", Data::Bytes(vec![2; 32]))
+            .with_app("token:A", Data::U64(1));
+        
+        let tx = |state: CharmState| {
+            let mut tx = Transaction::new([9u8; 32]);
+            tx.add_input(TxInput {
+                value: Some(1_000),
+                ..TxInput::new(UtxoRef { txid: [1u8; 32], vout: 0 }, Some(state.clone()))
+            });
+            tx.add_output(TxOutput {
+                index: 0,
+                value: 546,
+                script_pubkey: vec![0x00, 0x14],
+                charm_state: Some(state),
+            });
+            tx
+        };
+        let a = tx(forwards);
+        let b = tx(backwards);
+        assert_eq!(a.hash(), b.hash());
+        
+        // Every field is covered
+        let mut c = a.clone();
+        c.outputs[0].value += 1;
+        assert_ne!(a.hash(), c.hash());
+        assert_ne!(a.hash(), a.clone().with_locktime(1).hash());
+    }
+    
+    #[test]
+    fn test_spell_hash() {
+        let spell = |first: &str, second: &str| {
+            NormalizedSpell::builder(1)
+                .input(UtxoRef { txid: [1u8; 32], vout: 0 }, None)
+                .output(0, None)
+                .build()
+                .with_nonce(first, 1)
+                .with_nonce(second, 1)
+                .with_genesis(first)
+                .with_genesis(second)
+        };
+        let a = spell("token:A", "nft:B");
+        assert_eq!(a.hash(), spell("nft:B", "token:A").hash());
+        assert_eq!(a.hash(), Sha256::digest(a.to_canonical_bytes()).as_slice());
+        
+        // Reordering inputs or outputs doesn't change the commitment
+        let ordered = canonical_spell(&[(1, 0), (2, 0)]);
+        let reordered = canonical_spell(&[(2, 0), (1, 0)]);
+        assert_ne!(ordered, reordered);
+        assert_eq!(ordered.hash(), reordered.hash());
+        assert_ne!(a.hash(), a.clone().with_valid_before(10).hash());
+        
+        // Pinned, so any change to the encoding is caught
+        assert_eq!(
+            hex::encode(&NormalizedSpell::new(1).hash()),
+            "61c3a516478dd4cd882644dea13a2cf1ab59f26d046ffc3dadf96d0ae5c347b1"
+        );
+        assert_ne!(NormalizedSpell::new(1).hash(), NormalizedSpell::new(2).hash());
+    }
+    
+    fn canonical_spell(ins: &[(u8, u32)]) -> NormalizedSpell {
+        let mut nested = BTreeMap::new();
+        nested.insert("ratio".to_string(), Data::I64(-150));
+        nested.insert("tags".to_string(), Data::List(vec![Data::Bool(true), Data::Empty]));
+        let state = CharmState::new()
+            .with_app("token:A", Data::U64(7))
+            .with_app("nft:B", Data::Map(nested));
+        
+        let mut builder = NormalizedSpell::builder(1);
+        for &(txid, vout) in ins {
+            builder = builder.input(UtxoRef { txid: [txid; 32], vout }, Some(state.clone()));
+        }
+        builder
+            .output(1, None)
+            .output(0, Some(state))
+            .build()
+            .with_nonce("token:A", 3)
+            .with_genesis("nft:B")
+            .with_valid_before(900_000)
+    }
+    
+    #[test]
+    fn test_spell_canonical_bytes_ignore_order() {
+        let a = canonical_spell(&[(2, 0), (1, 5), (1, 0)]);
+        let b = canonical_spell(&[(1, 0), (2, 0), (1, 5)]);
+        assert_ne!(a, b);
+        assert_eq!(a.to_canonical_bytes(), b.to_canonical_bytes());
+        
+        // Decoding yields the sorted spell
+        let decoded = NormalizedSpell::from_canonical_bytes(&a.to_canonical_bytes()).unwrap();
+        let vouts: Vec<_> = decoded.ins.iter().map(|input| (input.utxo_ref.txid[0], input.utxo_ref.vout)).collect();
+        assert_eq!(vouts, vec![(1, 0), (1, 5), (2, 0)]);
+        assert_eq!(decoded.outs.iter().map(|output| output.index).collect::<Vec<_>>(), vec![0, 1]);
+        assert_eq!(decoded.to_canonical_bytes(), a.to_canonical_bytes());
+        assert_eq!(decoded.nonces, a.nonces);
+        assert_eq!(decoded.genesis, a.genesis);
+        assert_eq!(decoded.valid_before, a.valid_before);
+    }
+    
+    #[test]
+    fn test_spell_canonical_bytes_detect_any_mutation() {
+        let bytes = canonical_spell(&[(1, 0), (2, 0)]).to_canonical_bytes();
+        let original = NormalizedSpell::from_canonical_bytes(&bytes).unwrap();
+        
+        // Flipping any single byte never decodes back to the same spell
+        for i in 0..bytes.len() {
+            let mut mutated = bytes.clone();
+            mutated[i] ^= 0x01;
+            assert_ne!(NormalizedSpell::from_canonical_bytes(&mutated).ok(), Some(original.clone()), "byte {}", i);
+        }
+    }
+    
+    #[test]
+    fn test_spell_canonical_bytes_reject_malformed() {
+        let bytes = canonical_spell(&[(1, 0), (2, 0)]).to_canonical_bytes();
+        
+        assert_eq!(
+            NormalizedSpell::from_canonical_bytes(&bytes[..bytes.len() - 1]),
+            Err(CanonicalDecodeError::UnexpectedEnd)
+        );
+        assert_eq!(
+            NormalizedSpell::from_canonical_bytes(&[bytes.as_slice(), &[0]].concat()),
+            Err(CanonicalDecodeError::TrailingBytes)
+        );
+        
+        // Unsorted inputs are not canonical, even though `encode` accepts them
+        let unsorted = canonical_spell(&[(2, 0), (1, 0)]);
+        assert_eq!(
+            NormalizedSpell::from_canonical_bytes(&canonical::encode(&unsorted)),
+            Err(CanonicalDecodeError::NonCanonical)
+        );
+        
+        // A huge length prefix fails without allocating
+        let mut huge = 1u32.to_le_bytes().to_vec();
+        huge.extend_from_slice(&u64::MAX.to_le_bytes());
+        assert_eq!(NormalizedSpell::from_canonical_bytes(&huge), Err(CanonicalDecodeError::UnexpectedEnd));
+    }
+    
+    #[test]
+    fn test_spell_builder() {
+        let token = CharmState::new().with_app("token:USD", Data::U64(100));
+        let nft = CharmState::new().with_app("nft:ART", Data::Bytes(vec![1; 32]));
+        let first = UtxoRef { txid: [1u8; 32], vout: 0 };
+        let second = UtxoRef { txid: [2u8; 32], vout: 3 };
+        
+        let built = NormalizedSpell::builder(2)
+            .input(first.clone(), Some(token.clone()))
+            .input(second.clone(), Some(nft.clone()))
+            .output(0, Some(nft.clone()))
+            .output(1, Some(token.clone()))
+            .build();
+        
+        let mut manual = NormalizedSpell::new(2);
+        manual.ins.push(SpellInput { utxo_ref: first, charms: Some(token.clone()) });
+        manual.ins.push(SpellInput { utxo_ref: second, charms: Some(nft.clone()) });
+        manual.outs.push(SpellOutput { index: 0, charms: Some(nft) });
+        manual.outs.push(SpellOutput { index: 1, charms: Some(token) });
+        
+        assert_eq!(built, manual);
+        assert!(built.verify());
+        assert!(!NormalizedSpell::builder(2).build().verify());
+    }
+    
+    #[test]
+    fn test_spell_diagnostics() {
+        let first = UtxoRef { txid: [1u8; 32], vout: 0 };
+        let second = UtxoRef { txid: [1u8; 32], vout: 1 };
+        
+        let spell = NormalizedSpell::builder(1)
+            .input(first.clone(), None)
+            .input(second, None)
+            .output(0, None)
+            .output(1, None)
+            .build();
+        let diagnostics = spell.diagnostics();
+        assert!(diagnostics.is_valid());
+        assert_eq!(diagnostics.version, 1);
+        assert_eq!(diagnostics.input_count, 2);
+        assert_eq!(diagnostics.output_count, 2);
+        
+        let diagnostics = NormalizedSpell::new(0).diagnostics();
+        assert_eq!(diagnostics.problems, vec!["version 0", "empty inputs", "empty outputs"]);
+        assert!(!diagnostics.is_valid());
+        
+        let spell = NormalizedSpell::builder(1)
+            .input(first.clone(), None)
+            .input(first.clone(), None)
+            .output(2, None)
+            .output(0, None)
+            .output(2, None)
+            .build();
+        assert_eq!(
+            spell.diagnostics().problems,
+            vec![format!("duplicate input {}", first), "duplicate output index 2".to_string()]
+        );
+        assert!(!spell.verify());
+        assert!(!spell.verify_at(0));
+    }
+    
+    #[test]
+    fn test_spell_apps() {
+        let utxo = UtxoRef { txid: [1u8; 32], vout: 0 };
+        
+        let none = NormalizedSpell::builder(1).input(utxo.clone(), None).output(0, None).build();
+        assert!(none.apps().is_empty());
+        assert!(!none.contains_app("token:USD"));
+        
+        let token = CharmState::new().with_app("token:USD", Data::U64(100));
+        let one = NormalizedSpell::builder(1)
+            .input(utxo.clone(), Some(token.clone()))
+            .output(0, Some(token.clone()))
+            .build();
+        assert_eq!(one.apps(), BTreeSet::from(["token:USD".to_string()]));
+        assert!(one.contains_app("token:USD"));
+        assert!(!one.contains_app("nft:ART"));
+        
+        // Apps only in inputs (burned) or only in outputs count too
+        let nft = CharmState::new().with_app("nft:ART", Data::Bytes(vec![1; 32]));
+        let many = NormalizedSpell::builder(1)
+            .input(utxo, Some(token.clone().with_app("escrow:E", Data::U64(0))))
+            .output(0, Some(token))
+            .output(1, Some(nft))
+            .build();
+        assert_eq!(
+            many.apps().into_iter().collect::<Vec<_>>(),
+            vec!["escrow:E", "nft:ART", "token:USD"]
+        );
+        assert!(many.contains_app("escrow:E"));
+        assert!(many.contains_app("nft:ART"));
+    }
+    
+    #[test]
+    fn test_data_accessors() {
+        assert_eq!(Data::Bool(true).as_bool(), Some(true));
+        assert_eq!(Data::U64(1).as_bool(), None);
+        assert_eq!(Data::I64(-7).as_i64(), Some(-7));
+        assert_eq!(Data::U64(7).as_i64(), None);
+        
+        let mut map = BTreeMap::new();
+        map.insert("a".to_string(), Data::U64(1));
+        let mut data = Data::Map(map.clone());
+        assert_eq!(data.as_map(), Some(&map));
+        assert_eq!(data.as_list(), None);
+        data.as_map_mut().unwrap().insert("b".to_string(), Data::Empty);
+        assert_eq!(data.as_map().map(BTreeMap::len), Some(2));
+        
+        let mut data = Data::List(vec![Data::U64(1)]);
+        assert_eq!(data.as_list(), Some(&[Data::U64(1)][..]));
+        assert_eq!(data.as_map(), None);
+        data.as_list_mut().unwrap().push(Data::Bool(false));
+        assert_eq!(data.as_list().map(<[Data]>::len), Some(2));
+        assert_eq!(Data::Empty.as_list_mut(), None);
+        assert_eq!(Data::Empty.as_map_mut(), None);
+    }
+    
+    #[test]
+    fn test_data_as_array32() {
+        assert_eq!(Data::Bytes(vec![7; 32]).as_array32(), Some([7; 32]));
+        assert_eq!(Data::Bytes(vec![7; 31]).as_array32(), None);
+        assert_eq!(Data::Bytes(vec![7; 33]).as_array32(), None);
+        assert_eq!(Data::String("7".repeat(32)).as_array32(), None);
+        assert_eq!(Data::U64(7).as_array32(), None);
+    }
+    
+    #[test]
+    fn test_data_try_as_u64() {
+        assert_eq!(Data::U64(42).try_as_u64(), Ok(42));
+        
+        let err = Data::String("42".to_string()).try_as_u64().unwrap_err();
+        assert_eq!(err, DataTypeError { expected: "U64", actual: "String" });
+        assert_eq!(err.to_string(), "expected U64 data, found String");
+        assert_eq!(Data::I64(42).try_as_u64().unwrap_err().actual, "I64");
+    }
+    
+    #[test]
+    fn test_data_try_merge() {
+        assert_eq!(Data::U64(2).try_merge(Data::U64(3)), Ok(Data::U64(5)));
+        assert_eq!(Data::I64(-2).try_merge(Data::I64(3)), Ok(Data::I64(1)));
+        assert_eq!(Data::Bytes(vec![1]).try_merge(Data::Bytes(vec![2, 3])), Ok(Data::Bytes(vec![1, 2, 3])));
+        assert_eq!(
+            Data::List(vec![Data::U64(1)]).try_merge(Data::List(vec![Data::Empty])),
+            Ok(Data::List(vec![Data::U64(1), Data::Empty]))
+        );
+        
+        let map = |entries: &[(&str, u64)]| {
+            Data::Map(entries.iter().map(|&(key, value)| (key.to_string(), Data::U64(value))).collect())
+        };
+        assert_eq!(map(&[("a", 1)]).try_merge(map(&[("b", 2)])), Ok(map(&[("a", 1), ("b", 2)])));
+        assert_eq!(
+            map(&[("a", 1)]).try_merge(map(&[("a", 2)])),
+            Err(DataMergeError::KeyCollision("a".to_string()))
+        );
+    }
+    
+    #[test]
+    fn test_data_try_merge_failures() {
+        assert_eq!(Data::U64(u64::MAX).try_merge(Data::U64(1)), Err(DataMergeError::Overflow));
+        assert_eq!(Data::I64(i64::MIN).try_merge(Data::I64(-1)), Err(DataMergeError::Overflow));
+        assert_eq!(Data::I64(i64::MAX).try_merge(Data::I64(1)), Err(DataMergeError::Overflow));
+        
+        assert_eq!(
+            Data::U64(1).try_merge(Data::I64(1)),
+            Err(DataMergeError::TypeMismatch { left: "U64", right: "I64" })
+        );
+        assert_eq!(
+            Data::String("a".to_string()).try_merge(Data::String("b".to_string())),
+            Err(DataMergeError::TypeMismatch { left: "String", right: "String" })
+        );
+        assert!(Data::Empty.try_merge(Data::Empty).is_err());
+        assert!(Data::Bool(true).try_merge(Data::Bool(false)).is_err());
+    }
+    
+    #[test]
+    fn test_data_ordering() {
+        let mut values = vec![
+            Data::Map(BTreeMap::new()),
+            Data::List(vec![Data::U64(2)]),
+            Data::String("b".to_string()),
+            Data::Bytes(vec![1, 2]),
+            Data::I64(-5),
+            Data::U64(7),
+            Data::Bool(true),
+            Data::Empty,
+            Data::List(vec![Data::U64(1), Data::U64(9)]),
+            Data::U64(3),
+            Data::Bool(false),
+            Data::Bytes(vec![1]),
+            Data::String("a".to_string()),
+            Data::I64(4),
+        ];
+        values.sort();
+        
+        assert_eq!(values, vec![
+            Data::Empty,
+            Data::Bool(false),
+            Data::Bool(true),
+            Data::U64(3),
+            Data::U64(7),
+            Data::I64(-5),
+            Data::I64(4),
+            Data::Bytes(vec![1]),
+            Data::Bytes(vec![1, 2]),
+            Data::String("a".to_string()),
+            Data::String("b".to_string()),
+            Data::List(vec![Data::U64(1), Data::U64(9)]),
+            Data::List(vec![Data::U64(2)]),
+            Data::Map(BTreeMap::new()),
+        ]);
+        
+        // Sorting is stable under repetition and equal values compare equal
+        let mut resorted = values.clone();
+        resorted.reverse();
+        resorted.sort();
+        assert_eq!(resorted, values);
+        assert_eq!(Data::U64(3).cmp(&Data::U64(3)), std::cmp::Ordering::Equal);
+    }
+    
+    #[test]
+    fn test_data_ordering_is_lexicographic() {
+        use std::cmp::Ordering;
+        
+        let list = |items: &[u64]| Data::List(items.iter().copied().map(Data::U64).collect());
+        assert!(list(&[1, 2]) < list(&[1, 3]));
+        assert!(list(&[1]) < list(&[1, 0]));
+        assert!(list(&[]) < list(&[0]));
+        assert!(list(&[9]) > list(&[1, 9, 9]));
+        
+        let map = |entries: &[(&str, u64)]| {
+            Data::Map(entries.iter().map(|(k, v)| (k.to_string(), Data::U64(*v))).collect())
+        };
+        // Keys are compared before values
+        assert!(map(&[("a", 9)]) < map(&[("b", 0)]));
+        assert!(map(&[("a", 1)]) < map(&[("a", 2)]));
+        assert!(map(&[("a", 1)]) < map(&[("a", 1), ("b", 0)]));
+        
+        // Equal nested values compare `Equal` however they were built
+        let built_forwards = map(&[("a", 1), ("b", 2)]);
+        let built_backwards = map(&[("b", 2), ("a", 1)]);
+        assert_eq!(built_forwards.cmp(&built_backwards), Ordering::Equal);
+        assert_eq!(
+            Data::List(vec![built_forwards.clone()]).cmp(&Data::List(vec![built_backwards])),
+            Ordering::Equal
+        );
+        assert_eq!(built_forwards.partial_cmp(&built_forwards.clone()), Some(Ordering::Equal));
+    }
+    
+    #[test]
+    fn test_f64_equality() {
+        use std::cmp::Ordering;
+        
+        // Eq and Ord are exact
+        assert_ne!(Data::F64(0.1 + 0.2), Data::F64(0.3));
+        assert_ne!(Data::F64(0.0), Data::F64(-0.0));
+        assert_eq!(Data::F64(f64::NAN), Data::F64(f64::NAN));
+        assert_eq!(Data::F64(f64::INFINITY), Data::F64(f64::INFINITY));
+        assert_ne!(Data::F64(f64::INFINITY), Data::F64(f64::MAX));
+        
+        // approx_eq tolerates F64_EPSILON, at any depth
+        assert!(Data::F64(0.1 + 0.2).approx_eq(&Data::F64(0.3)));
+        assert!(Data::F64(1.0).approx_eq(&Data::F64(1.0 + F64_EPSILON)));
+        assert!(!Data::F64(1.0).approx_eq(&Data::F64(1.0 + F64_EPSILON * 2.0)));
+        assert!(Data::F64(0.0).approx_eq(&Data::F64(-0.0)));
+        assert!(Data::F64(f64::NAN).approx_eq(&Data::F64(-f64::NAN)));
+        let mut map = BTreeMap::new();
+        map.insert("p".to_string(), Data::List(vec![Data::F64(0.3), Data::U64(1)]));
+        let mut close = map.clone();
+        close.insert("p".to_string(), Data::List(vec![Data::F64(0.1 + 0.2), Data::U64(1)]));
+        assert!(Data::Map(map.clone()).approx_eq(&Data::Map(close)));
+        let mut other = map.clone();
+        other.insert("p".to_string(), Data::List(vec![Data::F64(0.3), Data::U64(2)]));
+        assert!(!Data::Map(map).approx_eq(&Data::Map(other)));
+        
+        // Never equal to an integer holding the same number
+        assert_ne!(Data::F64(1.0), Data::U64(1));
+        assert_eq!(Data::F64(0.5).as_f64(), Some(0.5));
+        assert_eq!(Data::U64(1).as_f64(), None);
+        
+        // Floats sort after every other variant, then by value
+        assert!(Data::F64(-1.0) < Data::F64(2.5));
+        assert!(Data::Map(BTreeMap::new()) < Data::F64(f64::NEG_INFINITY));
+        assert_eq!(Data::F64(0.3).cmp(&Data::F64(0.1 + 0.2)), Ordering::Less);
+        
+        // Ordering is transitive, and hashing tells apart values Eq does
+        let mut sorted = vec![Data::F64(1.0 + F64_EPSILON), Data::F64(1.0), Data::F64(1.0 - F64_EPSILON)];
+        sorted.sort();
+        assert_eq!(sorted, vec![Data::F64(1.0 - F64_EPSILON), Data::F64(1.0), Data::F64(1.0 + F64_EPSILON)]);
+        let set: std::collections::HashSet<Data> = [0.3, 0.1 + 0.2, 0.3].map(Data::F64).into_iter().collect();
+        assert_eq!(set.len(), 2);
+        
+        assert_eq!(Data::F64(1.0).to_string(), "1.0");
+        assert_eq!(Data::List(vec![Data::F64(-0.25)]).to_string(), "[-0.25]");
+    }
+    
+    #[test]
+    fn test_f64_serialization() {
+        let price = Data::F64(1.5);
+        
+        // Canonical: variant 8, then IEEE 754 big-endian bytes
+        assert_eq!(canonical::encode(&price), vec![8, 0x3f, 0xf8, 0, 0, 0, 0, 0, 0]);
+        
+        let mut state = CharmState::new();
+        state.apps.insert("oracle:BTCUSD".to_string(), Data::F64(67_123.45));
+        let spell = NormalizedSpell::builder(1)
+            .input(UtxoRef { txid: [1u8; 32], vout: 0 }, None)
+            .output(0, Some(state))
+            .build();
+        let decoded = NormalizedSpell::from_canonical_bytes(&spell.to_canonical_bytes()).unwrap();
+        assert_eq!(decoded, spell);
+        assert_eq!(decoded.outs[0].charms.as_ref().unwrap().get("oracle:BTCUSD"), Some(&Data::F64(67_123.45)));
+        
+        #[cfg(feature = "cbor")]
+        {
+            for value in [1.5, -0.1, 1e300, f64::INFINITY] {
+                assert_eq!(Data::from_cbor(&Data::F64(value).to_cbor()), Ok(Data::F64(value)));
+            }
+            // Written at the narrowest exact width: half-precision 1.5
+            assert_eq!(price.to_cbor(), vec![0xf9, 0x3e, 0x00]);
+        }
+        
+        #[cfg(feature = "json")]
+        {
+            assert_eq!(price.to_json_value(), serde_json::json!({ "type": "F64", "value": 1.5 }));
+            assert_eq!(Data::from_json_value(&price.to_json_value()), Ok(price.clone()));
+        }
+        
+        #[cfg(feature = "serde")]
+        {
+            use bincode::Options;
+            
+            let options = bincode::DefaultOptions::new().with_fixint_encoding();
+            let bytes = options.serialize(&price).unwrap();
+            assert_eq!(bytes.len(), price.size_hint());
+            assert_eq!(options.deserialize::<Data>(&bytes).unwrap(), price);
+        }
+    }
+    
+    #[test]
+    fn test_f64_spell_warning() {
+        let mut state = CharmState::new();
+        state.apps.insert("token:A".to_string(), Data::U64(5));
+        let spell = NormalizedSpell::builder(1)
+            .input(UtxoRef { txid: [1u8; 32], vout: 0 }, Some(state.clone()))
+            .output(0, Some(state.clone()))
+            .build();
+        assert_eq!(spell.f64_warning(), None);
+        
+        let mut feed = BTreeMap::new();
+        feed.insert("price".to_string(), Data::F64(0.97));
+        state.apps.insert("oracle:USD".to_string(), Data::List(vec![Data::Map(feed)]));
+        let spell = NormalizedSpell::builder(1)
+            .input(UtxoRef { txid: [1u8; 32], vout: 0 }, None)
+            .output(0, Some(state))
+            .build();
+        assert_eq!(
+            spell.f64_warning().as_deref(),
+            Some("spell state for oracle:USD holds Data::F64, which is not deterministic in ZK proofs")
+        );
+        // Hashing is unaffected, the warning is for the host to surface
+        assert_eq!(spell.hash(), spell.clone().hash());
+    }
+    
+    #[test]
+    fn test_data_as_collection_key() {
+        let mut a = BTreeMap::new();
+        a.insert("x".to_string(), Data::U64(1));
+        let mut b = BTreeMap::new();
+        b.insert("x".to_string(), Data::U64(2));
+        assert!(Data::Map(a.clone()) < Data::Map(b));
+        
+        let mut set = std::collections::HashSet::new();
+        set.insert(Data::Map(a.clone()));
+        set.insert(Data::Map(a));
+        set.insert(Data::Bytes(vec![1]));
+        assert_eq!(set.len(), 2);
+        
+        let mut index = BTreeMap::new();
+        index.insert(Data::Bytes(vec![1]), "nft");
+        index.insert(Data::U64(1), "token");
+        assert_eq!(index.keys().next(), Some(&Data::U64(1)));
+    }
+    
+    #[test]
+    fn test_data_display() {
+        assert_eq!(Data::Empty.to_string(), "null");
+        assert_eq!(Data::Bool(true).to_string(), "true");
+        assert_eq!(Data::U64(42).to_string(), "42");
+        assert_eq!(Data::I64(-42).to_string(), "-42");
+        assert_eq!(Data::Bytes(vec![0x0a, 0xff]).to_string(), "0x0aff");
+        assert_eq!(Data::String("say \"hi\"".to_string()).to_string(), r#""say \"hi\"""#);
+        assert_eq!(Data::List(vec![]).to_string(), "[]");
+        assert_eq!(Data::Map(BTreeMap::new()).to_string(), "{}");
+        
+        let mut inner = BTreeMap::new();
+        inner.insert("id".to_string(), Data::Bytes(vec![1, 2]));
+        inner.insert("name".to_string(), Data::String("art".to_string()));
+        let nested = Data::List(vec![Data::U64(1), Data::Map(inner), Data::List(vec![Data::Empty])]);
+        assert_eq!(nested.to_string(), r#"[1, {id: 0x0102, name: "art"}, [null]]"#);
+    }
+    
+    #[test]
+    fn test_app_typed_params() {
+        let mut params = BTreeMap::new();
+        params.insert("decimals".to_string(), Data::U64(8));
+        params.insert("creator".to_string(), Data::Bytes(vec![2; 33]));
+        params.insert("name".to_string(), Data::String("USD".to_string()));
+        let app = App::with_params("token:USD", [0u8; 32], Data::Map(params));
+        
+        assert_eq!(app.param_u64("decimals"), Some(8));
+        assert_eq!(app.param_bytes("creator"), Some(&[2u8; 33][..]));
+        assert_eq!(app.param("name"), Some(&Data::String("USD".to_string())));
+        // Wrong type or missing key
+        assert_eq!(app.param_u64("creator"), None);
+        assert_eq!(app.param_bytes("decimals"), None);
+        assert_eq!(app.param_u64("supply"), None);
+        
+        let app = App::new("token:USD", [0u8; 32]);
+        assert_eq!(app.params, Data::Empty);
+        assert_eq!(app.param("decimals"), None);
+        assert_eq!(app.param_u64("decimals"), None);
+        assert_eq!(app.param_bytes("creator"), None);
+    }
+    
+    #[test]
+    fn test_app_and_charm_state_display() {
+        let app = App::new("token:USD", [0xab; 32]);
+        assert_eq!(app.to_string(), format!("token:USD (vk {})", "ab".repeat(32)));
+        let app = App::with_params("token:USD", [0xab; 32], Data::U64(1));
+        assert_eq!(app.to_string(), format!("token:USD (vk {}) 1", "ab".repeat(32)));
+        
+        let state = CharmState::new()
+            .with_app("token:USD", Data::U64(100))
+            .with_app("nft:ART", Data::Bytes(vec![9]));
+        assert_eq!(state.to_string(), "{nft:ART: 0x09, token:USD: 100}");
+        assert_eq!(CharmState::new().to_string(), "{}");
+    }
+    
+    #[test]
+    fn test_data_is_empty_and_len() {
+        let mut map = BTreeMap::new();
+        assert!(Data::Empty.is_empty());
+        assert!(Data::List(vec![]).is_empty());
+        assert!(Data::Map(map.clone()).is_empty());
+        assert!(!Data::Bool(false).is_empty());
+        assert!(!Data::U64(0).is_empty());
+        assert!(!Data::I64(0).is_empty());
+        assert!(!Data::List(vec![Data::Empty]).is_empty());
+        
+        assert_eq!(Data::Empty.len(), None);
+        assert_eq!(Data::Bool(true).len(), None);
+        assert_eq!(Data::U64(7).len(), None);
+        assert_eq!(Data::I64(-7).len(), None);
+        assert_eq!(Data::Bytes(vec![1, 2, 3]).len(), Some(3));
+        // Characters, not bytes
+        assert_eq!(Data::String("₿tc".to_string()).len(), Some(3));
+        assert_eq!(Data::List(vec![Data::Empty, Data::U64(1)]).len(), Some(2));
+        map.insert("a".to_string(), Data::U64(1));
+        assert_eq!(Data::Map(map.clone()).len(), Some(1));
+        assert!(!Data::Map(map).is_empty());
+    }
+    
+    #[test]
+    fn test_data_get_path() {
+        let mut collateral = BTreeMap::new();
+        collateral.insert("ratio".to_string(), Data::U64(150));
+        let mut params = BTreeMap::new();
+        params.insert("collateral".to_string(), Data::Map(collateral));
+        params.insert("tag".to_string(), Data::String("USD".to_string()));
+        let params = Data::Map(params);
+        
+        assert_eq!(params.get_path(&["collateral", "ratio"]), Some(&Data::U64(150)));
+        assert_eq!(params.get_path(&["collateral", "missing"]), None);
+        // "tag" is a string, so the path dead-ends
+        assert_eq!(params.get_path(&["tag", "ratio"]), None);
+        assert_eq!(params.get_path(&[]), Some(&params));
+        assert_eq!(Data::U64(1).get_path(&[]), Some(&Data::U64(1)));
+    }
+    
+    #[test]
+    fn test_data_depth_and_size_hint() {
+        assert_eq!(Data::U64(1).depth(), 0);
+        assert_eq!(Data::List(vec![]).depth(), 1);
+        assert_eq!(nested_list(MAX_DATA_DEPTH).depth(), MAX_DATA_DEPTH);
+        assert!(nested_list(MAX_DATA_DEPTH + 1).depth() > MAX_DATA_DEPTH);
+        
+        let mut map = BTreeMap::new();
+        map.insert("ab".to_string(), Data::Bytes(vec![0; 10]));
+        // Tag + length, then key length + key + (tag + length + bytes)
+        assert_eq!(Data::Map(map).size_hint(), 4 + 8 + (8 + 2 + (4 + 8 + 10)));
+    }
+    
+    #[test]
+    fn test_size_estimate() {
+        assert_eq!(Data::Empty.size_estimate(), 1);
+        assert_eq!(Data::Bool(true).size_estimate(), 1);
+        assert_eq!(Data::I64(-1).size_estimate(), 8);
+        assert_eq!(Data::String("abc".to_string()).size_estimate(), 4 + 3);
+        
+        let mut map = BTreeMap::new();
+        map.insert("ab".to_string(), Data::List(vec![Data::U64(1), Data::Bytes(vec![0; 10])]));
+        let data = Data::Map(map);
+        // Length, then key + key length + (length + u64 + (length + bytes))
+        assert_eq!(data.size_estimate(), 4 + (2 + 4 + (4 + 8 + (4 + 10))));
+        
+        let state = CharmState::new()
+            .with_app("token:A", Data::U64(5))
+            .with_app("nft:B", data.clone());
+        assert_eq!(state.size_estimate(), 8 + data.size_estimate());
+    }
+    
+    #[test]
+    fn test_spell_size_budget() {
+        let spell = NormalizedSpell::builder(1)
+            .input(UtxoRef { txid: [1u8; 32], vout: 0 }, None)
+            .output(0, Some(CharmState::new().with_app("token:A", Data::Bytes(vec![0; 100]))))
+            .build();
+        // Version, input UTXO + no charms, output index + charms, no expiry
+        let estimate = 4 + (36 + 1) + (4 + 104) + 1;
+        assert_eq!(spell.size_estimate(), estimate);
+        
+        assert_eq!(spell.validate_size_budget(estimate), Ok(()));
+        let err = spell.validate_size_budget(estimate - 1).unwrap_err();
+        assert_eq!(err, SizeBudgetExceeded { estimate, max_bytes: estimate - 1 });
+        assert_eq!(err.to_string(), format!("spell size estimate {} exceeds budget of {} bytes", estimate, estimate - 1));
+    }
+    
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_deserialize_max_depth() {
+        use bincode::Options;
+        
+        let options = bincode::DefaultOptions::new().with_fixint_encoding();
+        
+        let accepted = nested_list(MAX_DATA_DEPTH);
+        let bytes = options.serialize(&accepted).unwrap();
+        assert_eq!(bytes.len(), accepted.size_hint());
+        assert_eq!(options.deserialize::<Data>(&bytes).unwrap(), accepted);
+        
+        let bytes = options.serialize(&nested_list(MAX_DATA_DEPTH + 1)).unwrap();
+        let err = options.deserialize::<Data>(&bytes).unwrap_err();
+        assert!(err.to_string().contains("maximum depth"), "{}", err);
+        
+        // The limit is configurable
+        let mut deserializer = bincode::Deserializer::from_slice(&bytes, options);
+        assert!(Data::deserialize_with_max_depth(&mut deserializer, MAX_DATA_DEPTH + 1).is_ok());
+    }
+    
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_data_json_variant_names() {
+        let mut map = BTreeMap::new();
+        map.insert("k".to_string(), Data::List(vec![Data::Bool(true), Data::Empty]));
+        let data = Data::Map(map);
+        
+        let json = serde_json::to_string(&data).unwrap();
+        assert_eq!(serde_json::from_str::<Data>(&json).unwrap(), data);
+    }
+    
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_read_from_slice() {
+        use bincode::Options;
+        
+        let data = sample_spell_data("token:A", 100);
+        let bytes = bincode::DefaultOptions::new().with_fixint_encoding().serialize(&data).unwrap();
+        assert_eq!(util::read_from_slice(&bytes).unwrap(), data);
+        assert_eq!(util::read::<util::SpellData, _>(bytes.as_slice()).unwrap(), data);
+        assert_eq!(util::write(&data).unwrap(), bytes);
+        
+        let mut trailing = bytes.clone();
+        trailing.push(0);
+        assert!(util::read_from_slice(&trailing).is_err());
+    }
+    
+    #[test]
+    fn test_app_creation() {
+        let app = App::new("test-token", [0u8; 32]);
+        assert_eq!(app.tag, "test-token");
+    }
+    
+    #[test]
+    fn test_app_type() {
+        let cases = [
+            ("token:BTC", AppType::Token),
+            ("nft:PUNK", AppType::Nft),
+            ("escrow:DEAL1", AppType::Escrow),
+            ("bounty:BUG42", AppType::Bounty),
+            ("bollar:USD", AppType::Bollar),
+        ];
+        for (tag, expected) in cases {
+            let app = App::new(tag, [0u8; 32]);
+            assert_eq!(app.app_type(), expected);
+        }
+        
+        let app = App::new("token:BTC", [0u8; 32]);
+        assert_eq!(app.app_id(), Some("BTC"));
+    }
+    
+    #[test]
+    fn test_app_type_unknown() {
+        let app = App::new("vote:PROPOSAL", [0u8; 32]);
+        assert_eq!(app.app_type(), AppType::Unknown);
+        assert_eq!(app.app_id(), Some("PROPOSAL"));
+        
+        let app = App::new("token", [0u8; 32]);
+        assert_eq!(app.app_type(), AppType::Unknown);
+        assert_eq!(app.app_id(), None);
+    }
+    
+    #[test]
+    fn test_app_category() {
+        let cases = [
+            ("token:BTC", AppCategory::Token),
+            ("nft:PUNK", AppCategory::Nft),
+            ("escrow:DEAL1", AppCategory::Escrow),
+            ("bounty:BUG42", AppCategory::Bounty),
+            ("bollar:USD", AppCategory::Bollar),
+            ("vote:PROPOSAL", AppCategory::Custom("vote".to_string())),
+            ("token", AppCategory::Custom("token".to_string())),
+        ];
+        for (tag, expected) in cases {
+            assert_eq!(App::new(tag, [0u8; 32]).category(), expected);
+        }
+    }
+    
+    #[test]
+    fn test_app_validate_tag() {
+        assert_eq!(App::validate_tag("token:BTC"), Ok((AppCategory::Token, "BTC")));
+        assert_eq!(App::validate_tag("bounty:bug-42"), Ok((AppCategory::Bounty, "bug-42")));
+        
+        let max = format!("nft:{}", "a".repeat(MAX_TAG_LEN - 4));
+        assert!(App::validate_tag(&max).is_ok());
+        assert_eq!(App::validate_tag(&format!("{}a", max)), Err(TagValidationError::TooLong));
+        
+        assert_eq!(App::validate_tag("BTC"), Err(TagValidationError::MissingColon));
+        assert_eq!(
+            App::validate_tag("vote:PROPOSAL"),
+            Err(TagValidationError::UnknownCategory("vote".to_string()))
+        );
+        assert_eq!(App::validate_tag("token:"), Err(TagValidationError::InvalidName(String::new())));
+        assert_eq!(App::validate_tag("token:my coin"), Err(TagValidationError::InvalidName("my coin".to_string())));
+        assert_eq!(App::validate_tag("token::BTC"), Err(TagValidationError::InvalidName(":BTC".to_string())));
+        assert_eq!(App::validate_tag("token:BTC_1"), Err(TagValidationError::InvalidName("BTC_1".to_string())));
+    }
+    
+    #[test]
+    fn test_app_max_outputs() {
+        assert_eq!(App::new("escrow:DEAL1", [0u8; 32]).max_outputs(), Some(2));
+        assert_eq!(App::new("token:BTC", [0u8; 32]).max_outputs(), Some(64));
+        assert_eq!(App::new("vote:PROPOSAL", [0u8; 32]).max_outputs(), None);
+        
+        let mut params = BTreeMap::new();
+        params.insert("max_outputs".to_string(), Data::U64(5));
+        let app = App::with_params("escrow:DEAL1", [0u8; 32], Data::Map(params));
+        assert_eq!(app.max_outputs(), Some(5));
+    }
+    
+    #[test]
+    fn test_charm_state() {
+        let state = CharmState::new()
+            .with_app("token", Data::U64(1000));
+        
+        assert!(state.get("token").is_some());
+        assert_eq!(state.get("token").unwrap().as_u64(), Some(1000));
+    }
+    
+    #[test]
+    fn test_charm_state_entries() {
+        let mut state = CharmState::new()
+            .with_app("token:B", Data::U64(2))
+            .with_app("nft:A", Data::Bytes(vec![1]));
+        
+        assert!(state.contains_key("token:B"));
+        assert!(!state.contains_key("token:C"));
+        assert_eq!(state.keys().collect::<Vec<_>>(), vec!["nft:A", "token:B"]);
+        assert_eq!(state.values().collect::<Vec<_>>(), vec![&Data::Bytes(vec![1]), &Data::U64(2)]);
+        
+        assert_eq!(state.remove("token:B"), Some(Data::U64(2)));
+        assert_eq!(state.remove("token:B"), None);
+        assert!(!state.contains_key("token:B"));
+        assert_eq!(state.keys().collect::<Vec<_>>(), vec!["nft:A"]);
+    }
+    
+    #[test]
+    fn test_charm_state_semantic_eq() {
+        let empty = CharmState::new();
+        let a_empty = CharmState::new().with_app("a", Data::Empty);
+        let a_one = CharmState::new().with_app("a", Data::U64(1));
+        
+        assert_ne!(a_empty, empty);
+        assert!(a_empty.semantic_eq(&empty));
+        assert!(empty.semantic_eq(&a_empty));
+        assert!(!a_empty.semantic_eq(&a_one));
+        assert!(!a_one.semantic_eq(&empty));
+        assert!(a_one.clone().with_app("b", Data::Empty).semantic_eq(&a_one));
+    }
+    
+    #[test]
+    fn test_charm_state_diff_added_only() {
+        let before = CharmState::new().with_app("token:A", Data::U64(1));
+        let after = before.clone().with_app("nft:B", Data::Bytes(vec![1]));
+        
+        let diff = CharmState::diff(&before, &after);
+        assert_eq!(diff.added.get("nft:B"), Some(&&Data::Bytes(vec![1])));
+        assert!(diff.removed.is_empty() && diff.changed.is_empty());
+    }
+    
+    #[test]
+    fn test_charm_state_diff_removed_only() {
+        let after = CharmState::new().with_app("token:A", Data::U64(1));
+        let before = after.clone().with_app("nft:B", Data::Bytes(vec![1]));
+        
+        let diff = CharmState::diff(&before, &after);
+        assert_eq!(diff.removed.get("nft:B"), Some(&&Data::Bytes(vec![1])));
+        assert!(diff.added.is_empty() && diff.changed.is_empty());
+    }
+    
+    #[test]
+    fn test_charm_state_diff_changed_only() {
+        let before = CharmState::new().with_app("token:A", Data::U64(1));
+        let after = CharmState::new().with_app("token:A", Data::U64(2));
+        
+        let diff = CharmState::diff(&before, &after);
+        assert_eq!(diff.changed.get("token:A"), Some(&(&Data::U64(1), &Data::U64(2))));
+        assert!(diff.added.is_empty() && diff.removed.is_empty());
+        
+        assert!(CharmState::diff(&before, &before).is_empty());
+    }
+    
+    #[test]
+    fn test_charm_state_diff_mixed() {
+        let before = CharmState::new()
+            .with_app("token:A", Data::U64(100))
+            .with_app("escrow:E", Data::U64(1))
+            .with_app("nft:OLD", Data::Bytes(vec![1]));
+        let after = CharmState::new()
+            .with_app("token:A", Data::U64(100))
+            .with_app("escrow:E", Data::U64(2))
+            .with_app("nft:NEW", Data::Bytes(vec![2]));
+        
+        let diff = CharmState::diff(&before, &after);
+        assert_eq!(diff.added.keys().collect::<Vec<_>>(), ["nft:NEW"]);
+        assert_eq!(diff.removed.keys().collect::<Vec<_>>(), ["nft:OLD"]);
+        assert_eq!(diff.changed.keys().collect::<Vec<_>>(), ["escrow:E"]);
+    }
+    
+    #[test]
+    fn test_spell_genesis_authorization() {
+        let mut spell = NormalizedSpell::new(1);
+        spell.ins.push(SpellInput {
+            utxo_ref: UtxoRef { txid: [1u8; 32], vout: 0 },
+            charms: Some(CharmState::new().with_app("token:A", Data::U64(10))),
+        });
+        spell.outs.push(SpellOutput {
+            index: 0,
+            charms: Some(
+                CharmState::new()
+                    .with_app("token:A", Data::U64(10))
+                    .with_app("nft:NEW", Data::Bytes(vec![1]))
+            ),
+        });
+        
+        assert_eq!(spell.unauthorized_new_apps(), ["nft:NEW"]);
+        
+        // Declaring genesis alone authorizes nothing
+        let spell = spell.with_genesis("nft:NEW");
+        assert_eq!(spell.unauthorized_new_apps(), ["nft:NEW"]);
+        
+        // The tag must derive from a spent input
+        let tag = spell.ins[0].utxo_ref.genesis_tag("nft");
+        let mut spell = spell.with_genesis(tag.as_str());
+        spell.outs[0].charms = Some(CharmState::new().with_app(tag.as_str(), Data::Bytes(vec![1])));
+        assert!(spell.is_genesis_authorized(&tag));
+        assert!(spell.unauthorized_new_apps().is_empty());
+        
+        // ...not from a UTXO the spell doesn't spend
+        let elsewhere = UtxoRef { txid: [2u8; 32], vout: 0 }.genesis_tag("nft");
+        let spell = spell.with_genesis(elsewhere.as_str());
+        assert!(!spell.is_genesis_authorized(&elsewhere));
+    }
+    
+    #[test]
+    fn test_spell_verification() {
+        let mut spell = NormalizedSpell::new(1);
+        spell.ins.push(SpellInput {
+            utxo_ref: UtxoRef { txid: [0u8; 32], vout: 0 },
+            charms: None,
+        });
+        spell.outs.push(SpellOutput {
+            index: 0,
+            charms: None,
+        });
+        
+        assert!(spell.verify());
+    }
+    
+    fn minimal_spell() -> NormalizedSpell {
+        NormalizedSpell::builder(1)
+            .input(UtxoRef { txid: [0u8; 32], vout: 0 }, None)
+            .output(0, None)
+            .build()
+    }
+    
+    /// A transaction spending and creating exactly what `spell` describes
+    fn spell_tx(spell: NormalizedSpell) -> Transaction {
+        let mut tx = Transaction::new([0u8; 32]);
+        for spell_in in &spell.ins {
+            tx.add_input(TxInput {
+                script_pubkey: Vec::new(),
+                ..TxInput::new(spell_in.utxo_ref.clone(), None)
+            });
+        }
+        for spell_out in &spell.outs {
+            tx.add_output(TxOutput {
+                index: spell_out.index,
+                value: 546,
+                script_pubkey: Vec::new(),
+                charm_state: None,
+            });
+        }
+        tx.spell = Some(spell);
+        tx
+    }
+    
+    #[test]
+    fn test_compute_txid() {
+        // Coinbase of the genesis block, shared by mainnet and testnet3
+        let script_sig = hex::decode(concat!(
+            "04ffff001d0104455468652054696d65732030332f4a616e2f32303039204368",
+            "616e63656c6c6f72206f6e206272696e6b206f66207365636f6e64206261696c",
+            "6f757420666f722062616e6b73",
+        )).unwrap();
+        let script_pubkey = hex::decode(concat!(
+            "4104678afdb0fe5548271967f1a67130b7105cd6a828e03909a67962e0ea1f61",
+            "deb649f6bc3f4cef38c4f35504e51ec112de5c384df7ba0b8d578a4c702b6bf1",
+            "1d5fac",
+        )).unwrap();
+        let mut tx = Transaction::new([0u8; 32]).with_version(1).with_locktime(0);
+        tx.add_input(TxInput {
+            script_sig,
+            ..TxInput::new(UtxoRef { txid: [0u8; 32], vout: 0xffff_ffff }, None)
+        });
+        tx.add_output(TxOutput {
+            index: 0,
+            value: 5_000_000_000,
+            script_pubkey,
+            charm_state: None,
+        });
+        
+        let txid = tx.compute_txid();
+        assert_eq!(
+            UtxoRef { txid, vout: 0 }.to_string(),
+            "4a5e1e4baab89f3a32518a88c31bc87f618f76673e2cc77ab2127b7afdeda33b:0"
+        );
+        
+        // A missing locktime serializes as 0, while the version is committed to
+        tx.locktime = None;
+        assert_eq!(tx.compute_txid(), txid);
+        assert_ne!(tx.clone().with_version(2).compute_txid(), txid);
+    }
+    
+    #[test]
+    fn test_spell_matches_tx() {
+        let tx = spell_tx(minimal_spell());
+        assert!(tx.spell_matches_tx());
+        assert!(tx.verify_spell());
+        
+        // Spell spends a UTXO the transaction doesn't
+        let mut other = tx.clone();
+        other.inputs[0].utxo_ref = UtxoRef { txid: [1u8; 32], vout: 0 };
+        assert!(!other.spell_matches_tx());
+        assert!(!other.verify_spell());
+        
+        // Transaction spends a UTXO the spell doesn't mention
+        let mut extra = tx.clone();
+        extra.add_input(TxInput {
+            script_pubkey: Vec::new(),
+            ..TxInput::new(UtxoRef { txid: [2u8; 32], vout: 1 }, None)
+        });
+        assert!(!extra.spell_matches_tx());
+        
+        // Spell output with no transaction output behind it
+        let mut missing = tx.clone();
+        missing.outputs[0].index = 1;
+        assert!(!missing.spell_matches_tx());
+        
+        // No spell, nothing to match
+        assert!(Transaction::new([0u8; 32]).spell_matches_tx());
+    }
+    
+    #[test]
+    fn test_spell_expiry() {
+        const N: u32 = 840_000;
+        let spell = minimal_spell().with_valid_before(N + 1);
+        
+        assert!(spell.verify_at(N));
+        assert!(!spell.verify_at(N + 1));
+        
+        let unbounded = minimal_spell();
+        assert!(unbounded.verify_at(N));
+        assert!(unbounded.verify_at(u32::MAX));
+        
+        // A locktime proves nothing about expiry, so it is not consulted
+        let mut tx = spell_tx(spell).with_locktime(N + 1);
+        assert!(tx.verify_spell());
+        tx.locktime = Some(N);
+        assert!(tx.verify_spell());
+    }
+    
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_spell_valid_before_defaults_when_absent() {
+        let spell = minimal_spell();
+        let mut json = serde_json::to_value(&spell).unwrap();
+        json.as_object_mut().unwrap().remove("valid_before");
+        
+        let decoded: NormalizedSpell = serde_json::from_value(json).unwrap();
+        assert_eq!(decoded, spell);
+    }
+    
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_transaction_json_without_new_fields() {
+        let mut tx = Transaction::new([1u8; 32]);
+        tx.inputs.push(TxInput::new(UtxoRef { txid: [2u8; 32], vout: 0 }, None));
+        
+        // The shape written before versions and input scripts were modeled
+        let mut json = serde_json::to_value(&tx).unwrap();
+        let fields = json.as_object_mut().unwrap();
+        fields.remove("version");
+        fields.remove("locktime");
+        let input = fields["inputs"][0].as_object_mut().unwrap();
+        for field in ["value", "script_pubkey", "script_sig", "sequence"] {
+            input.remove(field);
+        }
+        
+        let decoded: Transaction = serde_json::from_value(json).unwrap();
+        assert_eq!(decoded.version, DEFAULT_TX_VERSION);
+        assert_eq!(decoded, tx);
+    }
+    
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_transaction_spell_round_trip() {
+        use bincode::Options;
+        
+        let options = bincode::DefaultOptions::new().with_fixint_encoding();
+        let without = Transaction::new([1u8; 32]);
+        let with = spell_tx(minimal_spell());
+        
+        for tx in [&without, &with] {
+            let bytes = options.serialize(tx).unwrap();
+            assert_eq!(&options.deserialize::<Transaction>(&bytes).unwrap(), tx);
+            
+            let json = serde_json::to_string(tx).unwrap();
+            assert_eq!(&serde_json::from_str::<Transaction>(&json).unwrap(), tx);
+        }
+        
+        // No spell costs a single tag byte
+        assert_eq!(options.serialize(&without.spell).unwrap(), vec![0]);
+    }
+    
+    fn order_schema() -> DataSchema {
+        let mut buyer = BTreeMap::new();
+        buyer.insert("pubkey".to_string(), DataSchema::Bytes { max_len: 33 });
+        buyer.insert("name".to_string(), DataSchema::Str { max_len: 8 });
+        
+        let mut fields = BTreeMap::new();
+        fields.insert("amount".to_string(), DataSchema::U64);
+        fields.insert("buyer".to_string(), DataSchema::Map(buyer));
+        fields.insert("tags".to_string(), DataSchema::List {
+            item: Box::new(DataSchema::Str { max_len: 8 }),
+            max_len: 2,
+        });
+        DataSchema::Map(fields)
+    }
+    
+    fn order_state(name: &str, tags: &[&str]) -> Data {
+        let mut buyer = BTreeMap::new();
+        buyer.insert("pubkey".to_string(), Data::Bytes(vec![2; 33]));
+        buyer.insert("name".to_string(), Data::String(name.to_string()));
+        
+        let mut map = BTreeMap::new();
+        map.insert("amount".to_string(), Data::U64(10));
+        map.insert("buyer".to_string(), Data::Map(buyer));
+        map.insert(
+            "tags".to_string(),
+            Data::List(tags.iter().map(|t| Data::String(t.to_string())).collect()),
+        );
+        Data::Map(map)
+    }
+    
+    #[test]
+    fn test_schema_nested_map() {
+        let schema = order_schema();
+        assert_eq!(schema.validate(&order_state("alice", &["a"])), Ok(()));
+        
+        let Data::Map(mut map) = order_state("alice", &[]) else { unreachable!() };
+        if let Some(Data::Map(buyer)) = map.get_mut("buyer") {
+            buyer.insert("pubkey".to_string(), Data::U64(1));
+        }
+        assert_eq!(
+            schema.validate(&Data::Map(map.clone())),
+            Err(SchemaViolation::TypeMismatch {
+                path: "$.buyer.pubkey".to_string(),
+                expected: "Bytes",
+                found: "U64",
+            })
+        );
+        
+        map.remove("amount");
+        assert_eq!(
+            schema.validate(&Data::Map(map)),
+            Err(SchemaViolation::MissingKey { path: "$".to_string(), key: "amount".to_string() })
+        );
+    }
+    
+    #[test]
+    fn test_schema_length_violations() {
+        let schema = order_schema();
+        
+        assert_eq!(
+            schema.validate(&order_state("bartholomew", &[])),
+            Err(SchemaViolation::TooLong { path: "$.buyer.name".to_string(), len: 11, max_len: 8 })
+        );
+        assert_eq!(
+            schema.validate(&order_state("bob", &["a", "b", "c"])),
+            Err(SchemaViolation::TooLong { path: "$.tags".to_string(), len: 3, max_len: 2 })
+        );
+        assert_eq!(
+            schema.validate(&order_state("bob", &["a", "too-long-tag"])),
+            Err(SchemaViolation::TooLong { path: "$.tags[1]".to_string(), len: 12, max_len: 8 })
+        );
+    }
+    
+    #[test]
+    fn test_charm_state_validate_for_app() {
+        let app = App::new("escrow:ORDER", [0u8; 32]).with_schema(order_schema());
+        
+        let valid = CharmState::new().with_app("escrow:ORDER", order_state("carol", &[]));
+        assert!(valid.validate_for_app(&app).is_ok());
+        
+        let invalid = CharmState::new().with_app("escrow:ORDER", Data::U64(1));
+        assert!(invalid.validate_for_app(&app).is_err());
+        
+        // No schema, or no state for the app: nothing to enforce
+        assert!(invalid.validate_for_app(&App::new("escrow:ORDER", [0u8; 32])).is_ok());
+        assert!(CharmState::new().validate_for_app(&app).is_ok());
+    }
+    
+    #[test]
+    fn test_per_app_nonces_advance_independently() {
+        let mut last_seen = BTreeMap::new();
+        last_seen.insert("token:A".to_string(), 1);
+        last_seen.insert("nft:B".to_string(), 5);
+        
+        let spell = NormalizedSpell::new(1)
+            .with_nonce("token:A", 2)
+            .with_nonce("nft:B", 6);
+        assert!(spell.verify_nonces(&last_seen));
+        
+        // The same counter value is fine for a different app
+        let spell = NormalizedSpell::new(1)
+            .with_nonce("token:A", 6)
+            .with_nonce("nft:B", 6);
+        assert!(spell.verify_nonces(&last_seen));
+    }
+    
+    #[test]
+    fn test_replayed_app_nonce_rejected() {
+        let mut last_seen = BTreeMap::new();
+        last_seen.insert("token:A".to_string(), 1);
+        last_seen.insert("nft:B".to_string(), 5);
+        
+        let spell = NormalizedSpell::new(1)
+            .with_nonce("token:A", 1)
+            .with_nonce("nft:B", 6);
+        assert!(!spell.verify_nonces(&last_seen));
+    }
+    
+    fn fee_tx(input_values: &[Option<u64>], output_values: &[u64]) -> Transaction {
+        let mut tx = Transaction::new([0u8; 32]);
+        for (vout, value) in input_values.iter().enumerate() {
+            tx.add_input(TxInput {
+                value: *value,
+                ..TxInput::new(UtxoRef { txid: [1u8; 32], vout: vout as u32 }, None)
+            });
+        }
+        for (index, value) in output_values.iter().enumerate() {
+            tx.add_output(TxOutput {
+                index: index as u32,
+                value: *value,
+                script_pubkey: vec![],
+                charm_state: None,
+            });
+        }
+        tx
+    }
+    
+    #[test]
+    fn test_transaction_fee() {
+        assert_eq!(fee_tx(&[Some(10_000)], &[10_000]).fee(), Some(0));
+        assert_eq!(fee_tx(&[Some(6_000), Some(4_000)], &[9_000]).fee(), Some(1_000));
+    }
+    
+    #[test]
+    fn test_transaction_fee_with_input_scripts() {
+        let p2wpkh = [vec![0x00, 0x14], vec![0xab; 20]].concat();
+        let mut tx = Transaction::new([0u8; 32]);
+        tx.add_input(TxInput {
+            value: Some(50_000),
+            script_pubkey: p2wpkh.clone(),
+            ..TxInput::new(
+                UtxoRef { txid: [1u8; 32], vout: 0 },
+                Some(CharmState::new().with_app("token:ABC", Data::U64(10))),
+            )
+        });
+        tx.add_output(TxOutput {
+            index: 0,
+            value: 48_500,
+            script_pubkey: p2wpkh.clone(),
+            charm_state: Some(CharmState::new().with_app("token:ABC", Data::U64(10))),
+        });
+        assert_eq!(tx.fee(), Some(1_500));
+        assert_eq!(tx.inputs[0].script_pubkey, p2wpkh);
+    }
+    
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_tx_input_script_defaults_when_absent() {
+        let input = TxInput {
+            value: Some(1_000),
+            ..TxInput::new(UtxoRef { txid: [1u8; 32], vout: 0 }, None)
+        };
+        let mut json = serde_json::to_value(&input).unwrap();
+        json.as_object_mut().unwrap().remove("script_pubkey");
+        
+        let decoded: TxInput = serde_json::from_value(json).unwrap();
+        assert_eq!(decoded, input);
+    }
+    
+    #[test]
+    fn test_transaction_fee_unknown_or_underflow() {
+        // Missing input value
+        assert_eq!(fee_tx(&[Some(10_000), None], &[5_000]).fee(), None);
+        // Outputs exceed inputs
+        assert_eq!(fee_tx(&[Some(1_000)], &[1_001]).fee(), None);
+    }
+    
+    #[test]
+    fn test_transaction_validate_dust() {
+        let mut tx = fee_tx(&[Some(10_000)], &[100, 1_000]);
+        // Below-dust outputs without charm state are ignored
+        assert!(tx.validate_dust(DUST_LIMIT));
+        
+        let state = CharmState::new().with_app("token:ABC", Data::U64(1));
+        tx.outputs[1].charm_state = Some(state.clone());
+        assert!(tx.validate_dust(DUST_LIMIT));
+        
+        tx.outputs[0].charm_state = Some(state);
+        assert!(!tx.validate_dust(DUST_LIMIT));
+        assert!(tx.outputs[0].is_dust(DUST_LIMIT));
+        assert!(tx.validate_dust(100));
+    }
+    
+    #[test]
+    fn test_transaction_validate_fee() {
+        assert!(fee_tx(&[Some(10_000)], &[9_000]).validate_fee());
+        assert!(fee_tx(&[Some(10_000)], &[10_000]).validate_fee());
+        // Outputs exceed inputs
+        assert!(!fee_tx(&[Some(6_000), Some(4_000)], &[10_001]).validate_fee());
+        // Missing input value
+        assert!(!fee_tx(&[Some(10_000), None], &[1_000]).validate_fee());
+    }
+    
+    #[test]
+    fn test_hex_decode_32() {
+        let valid = "0a".repeat(32);
+        assert_eq!(hex::decode_32(&valid), Ok([0x0a; 32]));
+        assert_eq!(hex::decode_32(&valid.to_uppercase()), Ok([0x0a; 32]));
+        
+        assert_eq!(
+            hex::decode_32(&valid[..63]),
+            Err(hex::HexError::InvalidLength { expected: 64, actual: 63 })
+        );
+        assert_eq!(
+            hex::decode_32(&format!("{}xy", &valid[..62])),
+            Err(hex::HexError::InvalidCharacter)
+        );
+    }
+    
+    #[test]
+    fn test_data_wasm_hex() {
+        assert_eq!(Data::Bytes(vec![]).to_wasm_hex(), Some(String::new()));
+        assert_eq!(Data::from_wasm_hex(""), Ok(Data::Bytes(vec![])));
+        
+        let data = Data::Bytes(vec![0x00, 0xab, 0xff]);
+        assert_eq!(data.to_wasm_hex().as_deref(), Some("00abff"));
+        assert_eq!(Data::from_wasm_hex("00abff"), Ok(data.clone()));
+        assert_eq!(Data::from_wasm_hex("00ABFF"), Ok(data));
+        
+        assert_eq!(Data::from_wasm_hex("abc"), Err(hex::HexError::OddLength(3)));
+        assert_eq!(Data::from_wasm_hex("0g"), Err(hex::HexError::InvalidCharacter));
+        assert_eq!(Data::U64(1).to_wasm_hex(), None);
+    }
+    
+    #[test]
+    fn test_utxo_ref_round_trip() {
+        let mut txid = [0u8; 32];
+        txid[0] = 0xab;
+        txid[31] = 0x01;
+        
+        for vout in [0, 1, 42, u32::MAX] {
+            let utxo = UtxoRef { txid, vout };
+            let s = utxo.to_string();
+            assert_eq!(s.parse::<UtxoRef>(), Ok(utxo));
+        }
+        
+        let utxo = UtxoRef { txid, vout: 7 };
+        assert_eq!(
+            utxo.to_string(),
+            format!("ab{}01:7", "00".repeat(30))
+        );
+    }
+    
+    #[test]
+    fn test_utxo_ref_parse_errors() {
+        let txid = "11".repeat(32);
+        
+        assert_eq!(
+            txid.parse::<UtxoRef>(),
+            Err(UtxoRefParseError::MissingSeparator)
+        );
+        assert_eq!(
+            format!("{}:0", &txid[..62]).parse::<UtxoRef>(),
+            Err(UtxoRefParseError::InvalidTxidLength(62))
+        );
+        assert_eq!(
+            format!("{}zz:0", &txid[..62]).parse::<UtxoRef>(),
+            Err(UtxoRefParseError::InvalidTxidHex)
+        );
+        assert_eq!(
+            format!("{}:abc", txid).parse::<UtxoRef>(),
+            Err(UtxoRefParseError::InvalidVout("abc".to_string()))
+        );
+        assert_eq!(
+            format!("{}:4294967296", txid).parse::<UtxoRef>(),
+            Err(UtxoRefParseError::InvalidVout("4294967296".to_string()))
+        );
+    }
+    
+    #[test]
+    fn test_escrow_state_codes() {
+        use escrow::EscrowState;
+        
+        for code in [0, 1, 2, 3, 4, 100, 142] {
+            let state = EscrowState::from_code(code).unwrap();
+            assert_eq!(state.code(), code);
+        }
+        assert_eq!(EscrowState::from_code(5), None);
+        assert_eq!(EscrowState::from_code(100 + u32::MAX as u64 + 1), None);
+        assert_eq!(EscrowState::MilestoneCompleted(3).to_string(), "MilestoneCompleted(3)");
     }
 }
@@ -0,0 +1,212 @@
+//! Frozen encodings of known values
+//! 
+//! Each vector is the exact bytes a value encodes to, as hex. They guard the
+//! formats other parties depend on: the canonical spell encoding and hashes,
+//! and (with the `serde` feature) the framed bincode the spell checker reads.
+//! A failing vector means the format changed; update it only together with a
+//! deliberate, versioned format change.
+
+use charms_data::*;
+use std::collections::BTreeMap;
+
+fn assert_vector(actual: &[u8], expected: &str) {
+    assert_eq!(hex::encode(actual), expected);
+}
+
+/// One value of every `Data` variant, keyed by variant name
+fn every_variant() -> Vec<(&'static str, Data)> {
+    let mut map = BTreeMap::new();
+    map.insert("a".to_string(), Data::U64(1));
+    map.insert("b".to_string(), Data::Bool(false));
+    vec![
+        ("empty", Data::Empty),
+        ("bool", Data::Bool(true)),
+        ("u64", Data::U64(0x0102_0304_0506_0708)),
+        ("i64", Data::I64(-2)),
+        ("bytes", Data::Bytes(vec![0xde, 0xad, 0xbe, 0xef])),
+        ("string", Data::String("charm".to_string())),
+        ("list", Data::List(vec![Data::U64(7), Data::Empty])),
+        ("map", Data::Map(map)),
+        ("f64", Data::F64(-2.5)),
+    ]
+}
+
+fn sample_spell() -> NormalizedSpell {
+    let mut state = CharmState::new();
+    for (name, data) in every_variant() {
+        state.apps.insert(name.to_string(), data);
+    }
+    let mut spell = NormalizedSpell::new(2)
+        .with_nonce("token:A", 9)
+        .with_genesis("nft:B")
+        .with_valid_before(850_000);
+    spell.ins = vec![
+        SpellInput { utxo_ref: UtxoRef { txid: [0x11; 32], vout: 1 }, charms: None },
+        SpellInput { utxo_ref: UtxoRef { txid: [0x22; 32], vout: 0 }, charms: Some(CharmState::new()) },
+    ];
+    spell.outs = vec![SpellOutput { index: 0, charms: Some(state) }];
+    spell
+}
+
+fn sample_transaction() -> Transaction {
+    let mut tx = Transaction::new([0xaa; 32]).with_locktime(840_000);
+    tx.add_input(TxInput {
+        value: Some(10_000),
+        script_pubkey: vec![0x51],
+        ..TxInput::new(
+            UtxoRef { txid: [0xbb; 32], vout: 3 },
+            Some(CharmState::new().with_app("token:A", Data::U64(100))),
+        )
+    });
+    tx.add_output(TxOutput {
+        index: 0,
+        value: 546,
+        script_pubkey: vec![0x00, 0x14],
+        charm_state: None,
+    });
+    tx
+}
+
+#[test]
+fn test_canonical_empty_spell() {
+    let spell = NormalizedSpell::new(1);
+    assert_vector(&spell.to_canonical_bytes(), concat!(
+        "0100000000000000000000000000000000000000000000000000000000000000",
+        "0000000000",
+    ));
+    assert_vector(&spell.hash(), "61c3a516478dd4cd882644dea13a2cf1ab59f26d046ffc3dadf96d0ae5c347b1");
+}
+
+#[test]
+fn test_canonical_spell_with_every_data_variant() {
+    let spell = sample_spell();
+    assert_vector(&spell.to_canonical_bytes(), concat!(
+        "0200000002000000000000001111111111111111111111111111111111111111",
+        "1111111111111111111111110100000000222222222222222222222222222222",
+        "2222222222222222222222222222222222000000000100000000000000000100",
+        "000000000000000000000109000000000000000400000000000000626f6f6c01",
+        "0105000000000000006279746573040400000000000000deadbeef0500000000",
+        "000000656d70747900030000000000000066363408c004000000000000030000",
+        "000000000069363403feffffffffffffff04000000000000006c697374060200",
+        "0000000000000207000000000000000003000000000000006d61700702000000",
+        "0000000001000000000000006102010000000000000001000000000000006201",
+        "000600000000000000737472696e67050500000000000000636861726d030000",
+        "0000000000753634020807060504030201010000000000000007000000000000",
+        "00746f6b656e3a41090000000000000001000000000000000500000000000000",
+        "6e66743a420150f80c00",
+    ));
+    assert_vector(&spell.hash(), "5751a4b17ac341917d8a84d1066c2790a3684723ec523b179f62a5a239a35af4");
+}
+
+#[test]
+fn test_canonical_empty_and_populated_charm_state() {
+    let spell = |charms: Option<CharmState>| {
+        let mut spell = NormalizedSpell::new(1);
+        spell.outs.push(SpellOutput { index: 0, charms });
+        spell.to_canonical_bytes()
+    };
+    assert_vector(&spell(None), concat!(
+        "0100000000000000000000000100000000000000000000000000000000000000",
+        "00000000000000000000",
+    ));
+    assert_vector(&spell(Some(CharmState::new())), concat!(
+        "0100000000000000000000000100000000000000000000000100000000000000",
+        "000000000000000000000000000000000000",
+    ));
+    assert_vector(&spell(Some(CharmState::new().with_app("token:A", Data::U64(5)))), concat!(
+        "0100000000000000000000000100000000000000000000000101000000000000",
+        "000700000000000000746f6b656e3a4102050000000000000000000000000000",
+        "00000000000000000000",
+    ));
+}
+
+#[test]
+fn test_transaction_hash() {
+    assert_vector(&sample_transaction().hash(), "bca369af3cf0b62bf9e8620b1be31a972efad4eebf670a81d88b36efd00f2966");
+}
+
+#[cfg(feature = "serde")]
+mod framed {
+    use super::*;
+    
+    fn framed<T: serde::Serialize>(value: &T) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        util::write_framed(&mut bytes, value).unwrap();
+        bytes
+    }
+    
+    #[test]
+    fn test_framed_data_variants() {
+        let expected = [
+            ("empty", "0400000000000000"),
+            ("bool", "050000000100000001"),
+            ("u64", "0c000000020000000807060504030201"),
+            ("i64", "0c00000003000000feffffffffffffff"),
+            ("bytes", "10000000040000000400000000000000deadbeef"),
+            ("string", "11000000050000000500000000000000636861726d"),
+            ("list", "1c00000006000000020000000000000002000000070000000000000000000000"),
+            ("map", concat!(
+                "2f00000007000000020000000000000001000000000000006102000000010000",
+                "00000000000100000000000000620100000000",
+            )),
+            ("f64", "0c0000000800000000000000000004c0"),
+        ];
+        assert_eq!(every_variant().len(), expected.len());
+        for ((name, data), (expected_name, expected)) in every_variant().into_iter().zip(expected) {
+            assert_eq!(name, expected_name);
+            assert_vector(&framed(&data), expected);
+        }
+    }
+    
+    #[test]
+    fn test_framed_app() {
+        let app = App::with_params("token:A", [0x01; 32], Data::U64(21_000_000));
+        assert_vector(&framed(&app), concat!(
+            "3c0000000700000000000000746f6b656e3a4101010101010101010101010101",
+            "0101010101010101010101010101010101010102000000406f40010000000000",
+        ));
+    }
+    
+    #[test]
+    fn test_framed_transaction() {
+        // Changing this vector means bumping the wire version
+        assert_eq!(util::WIRE_VERSION, 2);
+        assert_vector(&framed(&sample_transaction()), concat!(
+            "b7000000aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa",
+            "aaaaaaaa0100000000000000bbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbb",
+            "bbbbbbbbbbbbbbbbbbbbbbbb0300000001102700000000000001000000000000",
+            "00510000000000000000ffffffff010100000000000000070000000000000074",
+            "6f6b656e3a410200000064000000000000000100000000000000000000002202",
+            "0000000000000200000000000000001400000140d10c0002000000",
+        ));
+    }
+    
+    #[test]
+    fn test_framed_spell() {
+        assert_vector(&framed(&sample_spell()), concat!(
+            "b101000002000000020000000000000011111111111111111111111111111111",
+            "1111111111111111111111111111111101000000002222222222222222222222",
+            "2222222222222222222222222222222222222222220000000001000000000000",
+            "0000010000000000000000000000010900000000000000040000000000000062",
+            "6f6f6c0100000001050000000000000062797465730400000004000000000000",
+            "00deadbeef0500000000000000656d7074790000000003000000000000006636",
+            "340800000000000000000004c0030000000000000069363403000000feffffff",
+            "ffffffff04000000000000006c69737406000000020000000000000002000000",
+            "07000000000000000000000003000000000000006d6170070000000200000000",
+            "0000000100000000000000610200000001000000000000000100000000000000",
+            "6201000000000600000000000000737472696e67050000000500000000000000",
+            "636861726d030000000000000075363402000000080706050403020101000000",
+            "000000000700000000000000746f6b656e3a4109000000000000000100000000",
+            "00000005000000000000006e66743a420150f80c00",
+        ));
+    }
+    
+    #[test]
+    fn test_framed_empty_and_populated_charm_state() {
+        assert_vector(&framed(&CharmState::new()), "080000000000000000000000");
+        assert_vector(&framed(&CharmState::new().with_app("token:A", Data::U64(5))), concat!(
+            "2300000001000000000000000700000000000000746f6b656e3a410200000005",
+            "00000000000000",
+        ));
+    }
+}
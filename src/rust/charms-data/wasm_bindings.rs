@@ -8,7 +8,7 @@ use wasm_bindgen::prelude::*;
 #[cfg(feature = "wasm")]
 use serde::{Deserialize, Serialize};
 #[cfg(feature = "wasm")]
-use std::collections::BTreeMap;
+use alloc::collections::{BTreeMap, BTreeSet};
 
 // ============================================
 // WASM-compatible Data Types
@@ -76,6 +76,7 @@ pub enum WasmData {
     String(String),
     List(Vec<WasmData>),
     Map(BTreeMap<String, WasmData>),
+    F64(f64),
 }
 
 #[cfg(feature = "wasm")]
@@ -119,6 +120,11 @@ impl WasmDataBuilder {
         Self { data: WasmData::I64(value) }
     }
     
+    #[wasm_bindgen]
+    pub fn f64_value(value: f64) -> Self {
+        Self { data: WasmData::F64(value) }
+    }
+    
     #[wasm_bindgen]
     pub fn bytes_value(hex: String) -> Self {
         Self { data: WasmData::Bytes(hex) }
@@ -161,6 +167,76 @@ impl WasmUtxoRef {
     pub fn vout(&self) -> u32 {
         self.vout
     }
+    
+    /// Format as `txid:vout`
+    #[wasm_bindgen]
+    #[allow(clippy::inherent_to_string)]
+    pub fn to_string(&self) -> String {
+        format!("{}:{}", self.txid, self.vout)
+    }
+    
+    /// Parse from `txid:vout`, validating the txid hex and vout
+    #[wasm_bindgen]
+    pub fn from_string(s: &str) -> Result<WasmUtxoRef, JsError> {
+        let utxo: crate::UtxoRef = s.parse()
+            .map_err(|e| JsError::new(&format!("Invalid UTXO reference: {}", e)))?;
+        Ok(Self {
+            txid: crate::hex::encode(&utxo.txid),
+            vout: utxo.vout,
+        })
+    }
+}
+
+#[cfg(feature = "wasm")]
+impl WasmData {
+    /// Convert to native `Data`, decoding hex-encoded bytes
+    pub fn to_data(&self) -> Result<crate::Data, String> {
+        Ok(match self {
+            WasmData::Empty => crate::Data::Empty,
+            WasmData::Bool(b) => crate::Data::Bool(*b),
+            WasmData::U64(n) => crate::Data::U64(*n),
+            WasmData::I64(n) => crate::Data::I64(*n),
+            WasmData::F64(n) => crate::Data::F64(*n),
+            WasmData::Bytes(hex) => crate::Data::from_wasm_hex(hex)
+                .map_err(|e| format!("Invalid hex bytes {}: {}", hex, e))?,
+            WasmData::String(s) => crate::Data::String(s.clone()),
+            WasmData::List(items) => crate::Data::List(
+                items.iter().map(WasmData::to_data).collect::<Result<_, _>>()?,
+            ),
+            WasmData::Map(map) => crate::Data::Map(
+                map.iter()
+                    .map(|(key, value)| Ok((key.clone(), value.to_data()?)))
+                    .collect::<Result<_, String>>()?,
+            ),
+        })
+    }
+}
+
+#[cfg(feature = "wasm")]
+impl WasmData {
+    /// Human-readable form, see `Data`'s `Display`
+    pub fn to_display_string(&self) -> Result<String, String> {
+        self.to_data().map(|data| data.to_string())
+    }
+}
+
+#[cfg(feature = "wasm")]
+impl From<&crate::Data> for WasmData {
+    fn from(data: &crate::Data) -> Self {
+        match data {
+            crate::Data::Empty => WasmData::Empty,
+            crate::Data::Bool(b) => WasmData::Bool(*b),
+            crate::Data::U64(n) => WasmData::U64(*n),
+            crate::Data::I64(n) => WasmData::I64(*n),
+            crate::Data::F64(n) => WasmData::F64(*n),
+            crate::Data::Bytes(bytes) => WasmData::Bytes(crate::hex::encode(bytes)),
+            crate::Data::String(s) => WasmData::String(s.clone()),
+            crate::Data::List(items) => WasmData::List(items.iter().map(WasmData::from).collect()),
+            crate::Data::Map(map) => WasmData::Map(
+                map.iter().map(|(key, value)| (key.clone(), WasmData::from(value))).collect(),
+            ),
+        }
+    }
 }
 
 #[cfg(feature = "wasm")]
@@ -169,6 +245,62 @@ pub struct WasmCharmState {
     pub apps: BTreeMap<String, WasmData>,
 }
 
+#[cfg(feature = "wasm")]
+impl WasmCharmState {
+    /// Convert to native `CharmState`
+    pub fn to_charm_state(&self) -> Result<crate::CharmState, String> {
+        let mut state = crate::CharmState::new();
+        for (tag, data) in &self.apps {
+            state.apps.insert(tag.clone(), data.to_data()?);
+        }
+        Ok(state)
+    }
+}
+
+/// JSON form of `CharmStateDiff`
+#[cfg(feature = "wasm")]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WasmCharmStateDiff {
+    pub added: BTreeMap<String, WasmData>,
+    pub removed: BTreeMap<String, WasmData>,
+    pub changed: BTreeMap<String, WasmDataChange>,
+}
+
+#[cfg(feature = "wasm")]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WasmDataChange {
+    pub before: WasmData,
+    pub after: WasmData,
+}
+
+#[cfg(feature = "wasm")]
+impl WasmCharmStateDiff {
+    /// Diff two `WasmCharmState` JSON documents (see `CharmState::diff`)
+    pub fn from_json(before_json: &str, after_json: &str) -> Result<Self, String> {
+        let parse = |json: &str| -> Result<crate::CharmState, String> {
+            serde_json::from_str::<WasmCharmState>(json)
+                .map_err(|e| format!("Invalid charm state: {}", e))?
+                .to_charm_state()
+        };
+        let before = parse(before_json)?;
+        let after = parse(after_json)?;
+        let diff = crate::CharmState::diff(&before, &after);
+        
+        let convert = |map: BTreeMap<String, &crate::Data>| -> BTreeMap<String, WasmData> {
+            map.into_iter().map(|(tag, data)| (tag, WasmData::from(data))).collect()
+        };
+        Ok(Self {
+            added: convert(diff.added),
+            removed: convert(diff.removed),
+            changed: diff.changed.into_iter()
+                .map(|(tag, (before, after))| {
+                    (tag, WasmDataChange { before: before.into(), after: after.into() })
+                })
+                .collect(),
+        })
+    }
+}
+
 #[cfg(feature = "wasm")]
 #[wasm_bindgen]
 pub struct WasmCharmStateBuilder {
@@ -201,6 +333,12 @@ impl WasmCharmStateBuilder {
         self
     }
     
+    #[wasm_bindgen]
+    pub fn remove_app(mut self, tag: String) -> Self {
+        self.apps.remove(&tag);
+        self
+    }
+    
     #[wasm_bindgen]
     pub fn to_json(&self) -> Result<String, JsError> {
         let state = WasmCharmState { apps: self.apps.clone() };
@@ -208,6 +346,18 @@ impl WasmCharmStateBuilder {
             .map_err(|e| JsError::new(&format!("Serialization error: {}", e)))
     }
     
+    /// Diff two charm state JSON documents into `{ added, removed, changed }`
+    #[wasm_bindgen]
+    pub fn diff_json(before_json: &str, after_json: &str) -> Result<JsValue, JsError> {
+        let diff = WasmCharmStateDiff::from_json(before_json, after_json)
+            .map_err(|e| JsError::new(&e))?;
+        serde_wasm_bindgen::to_value(&diff)
+            .map_err(|e| JsError::new(&format!("Serialization error: {}", e)))
+    }
+}
+
+#[cfg(feature = "wasm")]
+impl WasmCharmStateBuilder {
     pub fn build(&self) -> WasmCharmState {
         WasmCharmState { apps: self.apps.clone() }
     }
@@ -217,9 +367,32 @@ impl WasmCharmStateBuilder {
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct WasmTxInput {
     pub utxo_ref: WasmUtxoRef,
+    #[serde(default)]
+    pub value: Option<u64>, // satoshis, if known
+    #[serde(default)]
+    pub script_pubkey: String, // hex of the spent output's script, if known
+    #[serde(default)]
+    pub script_sig: String, // hex, empty for segwit spends
+    #[serde(default = "sequence_final")]
+    pub sequence: u32,
     pub charm_state: Option<WasmCharmState>,
 }
 
+#[cfg(feature = "wasm")]
+fn sequence_final() -> u32 {
+    crate::SEQUENCE_FINAL
+}
+
+#[cfg(feature = "wasm")]
+fn tx_version() -> u32 {
+    crate::DEFAULT_TX_VERSION
+}
+
+#[cfg(feature = "wasm")]
+fn parse_spell(spell_json: &str) -> Result<WasmNormalizedSpell, String> {
+    serde_json::from_str(spell_json).map_err(|e| format!("Failed to parse spell: {}", e))
+}
+
 #[cfg(feature = "wasm")]
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct WasmTxOutput {
@@ -235,6 +408,56 @@ pub struct WasmTransaction {
     pub txid: String,
     pub inputs: Vec<WasmTxInput>,
     pub outputs: Vec<WasmTxOutput>,
+    #[serde(default = "tx_version")]
+    pub version: u32,
+    #[serde(default)]
+    pub locktime: Option<u32>,
+    /// Left out of the JSON entirely when the transaction has no spell
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub spell: Option<WasmNormalizedSpell>,
+}
+
+#[cfg(feature = "wasm")]
+impl WasmTransaction {
+    /// Miner fee implied by input and output values (see `Transaction::fee`)
+    pub fn fee(&self) -> Option<u64> {
+        let input_sum = self.inputs.iter()
+            .try_fold(0u64, |acc, input| acc.checked_add(input.value?))?;
+        let output_sum = self.outputs.iter()
+            .try_fold(0u64, |acc, output| acc.checked_add(output.value))?;
+        
+        input_sum.checked_sub(output_sum)
+    }
+    
+    /// Convert to a native `Transaction`, failing on the first invalid hex
+    pub fn to_transaction(&self) -> Result<crate::Transaction, String> {
+        let decode = |hex: &str| crate::hex::try_decode(hex).map_err(|e| format!("Invalid hex {}: {}", hex, e));
+        let decode_32 = |hex: &str| crate::hex::decode_32(hex).map_err(|e| format!("Invalid txid {}: {}", hex, e));
+        
+        let mut tx = crate::Transaction::new(decode_32(&self.txid)?).with_version(self.version);
+        tx.locktime = self.locktime;
+        for input in &self.inputs {
+            let utxo_ref = crate::UtxoRef { txid: decode_32(&input.utxo_ref.txid)?, vout: input.utxo_ref.vout };
+            let charm_state = input.charm_state.as_ref().map(WasmCharmState::to_charm_state).transpose()?;
+            tx.add_input(crate::TxInput {
+                value: input.value,
+                script_pubkey: decode(&input.script_pubkey)?,
+                script_sig: decode(&input.script_sig)?,
+                sequence: input.sequence,
+                ..crate::TxInput::new(utxo_ref, charm_state)
+            });
+        }
+        for output in &self.outputs {
+            tx.add_output(crate::TxOutput {
+                index: output.index,
+                value: output.value,
+                script_pubkey: decode(&output.script_pubkey)?,
+                charm_state: output.charm_state.as_ref().map(WasmCharmState::to_charm_state).transpose()?,
+            });
+        }
+        tx.spell = self.spell.as_ref().map(WasmNormalizedSpell::to_spell).transpose()?;
+        Ok(tx)
+    }
 }
 
 #[cfg(feature = "wasm")]
@@ -243,6 +466,9 @@ pub struct WasmTransactionBuilder {
     txid: String,
     inputs: Vec<WasmTxInput>,
     outputs: Vec<WasmTxOutput>,
+    version: u32,
+    locktime: Option<u32>,
+    spell: Option<WasmNormalizedSpell>,
 }
 
 #[cfg(feature = "wasm")]
@@ -254,6 +480,9 @@ impl WasmTransactionBuilder {
             txid,
             inputs: Vec::new(),
             outputs: Vec::new(),
+            version: crate::DEFAULT_TX_VERSION,
+            locktime: None,
+            spell: None,
         }
     }
     
@@ -265,11 +494,71 @@ impl WasmTransactionBuilder {
         
         self.inputs.push(WasmTxInput {
             utxo_ref: WasmUtxoRef { txid, vout },
+            value: None,
+            script_pubkey: String::new(),
+            script_sig: String::new(),
+            sequence: crate::SEQUENCE_FINAL,
             charm_state,
         });
         self
     }
     
+    /// Add an input whose satoshi value is known, so the fee can be computed
+    #[wasm_bindgen]
+    pub fn add_input_with_value(mut self, txid: String, vout: u32, value: u64, charm_state_json: Option<String>) -> Self {
+        let charm_state = charm_state_json.and_then(|json| {
+            serde_json::from_str::<WasmCharmState>(&json).ok()
+        });
+        
+        self.inputs.push(WasmTxInput {
+            utxo_ref: WasmUtxoRef { txid, vout },
+            value: Some(value),
+            script_pubkey: String::new(),
+            script_sig: String::new(),
+            sequence: crate::SEQUENCE_FINAL,
+            charm_state,
+        });
+        self
+    }
+    
+    /// Set the unlocking script (hex) and sequence of the input at `position`,
+    /// which `compute_txid` commits to
+    #[wasm_bindgen]
+    pub fn set_input_unlock(mut self, position: usize, script_sig: String, sequence: u32) -> Self {
+        if let Some(input) = self.inputs.get_mut(position) {
+            input.script_sig = script_sig;
+            input.sequence = sequence;
+        }
+        self
+    }
+    
+    /// Set the script (hex) of the output spent by the input at `position`
+    #[wasm_bindgen]
+    pub fn set_input_script_pubkey(mut self, position: usize, script_pubkey: String) -> Self {
+        if let Some(input) = self.inputs.get_mut(position) {
+            input.script_pubkey = script_pubkey;
+        }
+        self
+    }
+    
+    #[wasm_bindgen]
+    pub fn with_version(mut self, version: u32) -> Self {
+        self.version = version;
+        self
+    }
+    
+    #[wasm_bindgen]
+    pub fn with_locktime(mut self, locktime: u32) -> Self {
+        self.locktime = Some(locktime);
+        self
+    }
+    
+    /// Bitcoin txid of the transaction as built, see `Transaction::compute_txid`
+    #[wasm_bindgen]
+    pub fn compute_txid(&self) -> Result<String, JsError> {
+        self.txid_hex().map_err(|e| JsError::new(&e))
+    }
+    
     #[wasm_bindgen]
     pub fn add_output(mut self, index: u32, value: u64, script_pubkey: String, charm_state_json: Option<String>) -> Self {
         let charm_state = charm_state_json.and_then(|json| {
@@ -285,26 +574,112 @@ impl WasmTransactionBuilder {
         self
     }
     
+    /// Attach a spell, failing if `spell_json` doesn't parse
     #[wasm_bindgen]
-    pub fn to_json(&self) -> Result<String, JsError> {
-        let tx = WasmTransaction {
-            txid: self.txid.clone(),
-            inputs: self.inputs.clone(),
-            outputs: self.outputs.clone(),
-        };
-        serde_json::to_string(&tx)
+    pub fn with_spell(mut self, spell_json: String) -> Result<WasmTransactionBuilder, JsError> {
+        self.spell = Some(parse_spell(&spell_json).map_err(|e| JsError::new(&e))?);
+        Ok(self)
+    }
+    
+    /// Load an existing transaction's JSON for further modification
+    /// 
+    /// A `vout` or output `index` outside the `u32` range fails to parse.
+    #[wasm_bindgen]
+    pub fn from_json(json: &str) -> Result<WasmTransactionBuilder, JsError> {
+        let tx: WasmTransaction = serde_json::from_str(json)
+            .map_err(|e| JsError::new(&format!("Failed to parse transaction: {}", e)))?;
+        Ok(Self {
+            txid: tx.txid,
+            inputs: tx.inputs,
+            outputs: tx.outputs,
+            version: tx.version,
+            locktime: tx.locktime,
+            spell: tx.spell,
+        })
+    }
+    
+    /// Report the transaction's structural validity, see `WasmTxValidation`
+    #[wasm_bindgen]
+    pub fn validate(&self) -> Result<JsValue, JsError> {
+        serde_wasm_bindgen::to_value(&self.validation())
             .map_err(|e| JsError::new(&format!("Serialization error: {}", e)))
     }
     
+    #[wasm_bindgen]
+    pub fn fee(&self) -> Option<u64> {
+        self.build().fee()
+    }
+    
+    #[wasm_bindgen]
+    pub fn to_json(&self) -> Result<String, JsError> {
+        serde_json::to_string(&self.build())
+            .map_err(|e| JsError::new(&format!("Serialization error: {}", e)))
+    }
+}
+
+#[cfg(feature = "wasm")]
+impl WasmTransactionBuilder {
     pub fn build(&self) -> WasmTransaction {
         WasmTransaction {
             txid: self.txid.clone(),
             inputs: self.inputs.clone(),
             outputs: self.outputs.clone(),
+            version: self.version,
+            locktime: self.locktime,
+            spell: self.spell.clone(),
+        }
+    }
+    
+    fn txid_hex(&self) -> Result<String, String> {
+        Ok(crate::hex::encode(&self.build().to_transaction()?.compute_txid()))
+    }
+    
+    /// Check every txid is 32 bytes of hex and output indices are unique
+    pub fn validation(&self) -> WasmTxValidation {
+        let is_txid = |txid: &str| crate::hex::decode_32(txid).is_ok();
+        
+        let mut invalid_txids = Vec::new();
+        if !is_txid(&self.txid) {
+            invalid_txids.push("txid".to_string());
+        }
+        for (i, input) in self.inputs.iter().enumerate() {
+            if !is_txid(&input.utxo_ref.txid) {
+                invalid_txids.push(format!("inputs[{}].utxo_ref.txid", i));
+            }
+        }
+        
+        let mut seen = BTreeSet::new();
+        let duplicate_output_indices: Vec<u32> = self.outputs.iter()
+            .filter(|output| !seen.insert(output.index))
+            .map(|output| output.index)
+            .collect();
+        
+        WasmTxValidation {
+            valid: invalid_txids.is_empty() && duplicate_output_indices.is_empty(),
+            txids_valid: invalid_txids.is_empty(),
+            // Guaranteed by the `u32` field type; out-of-range JSON fails to parse
+            vouts_in_range: true,
+            output_indices_unique: duplicate_output_indices.is_empty(),
+            invalid_txids,
+            duplicate_output_indices,
         }
     }
 }
 
+/// Structural validity of a transaction, from `WasmTransactionBuilder::validate`
+#[cfg(feature = "wasm")]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct WasmTxValidation {
+    pub valid: bool,
+    pub txids_valid: bool,
+    pub vouts_in_range: bool,
+    pub output_indices_unique: bool,
+    /// Locations of malformed txids, e.g. `inputs[0].utxo_ref.txid`
+    pub invalid_txids: Vec<String>,
+    /// Output indices used more than once
+    pub duplicate_output_indices: Vec<u32>,
+}
+
 // ============================================
 // Spell Types for WASM
 // ============================================
@@ -329,6 +704,56 @@ pub struct WasmNormalizedSpell {
     pub version: u32,
     pub ins: Vec<WasmSpellInput>,
     pub outs: Vec<WasmSpellOutput>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub nonces: BTreeMap<String, u64>,
+    #[serde(default, skip_serializing_if = "BTreeSet::is_empty")]
+    pub genesis: BTreeSet<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub valid_before: Option<u32>,
+}
+
+#[cfg(feature = "wasm")]
+impl WasmNormalizedSpell {
+    /// Convert to native `NormalizedSpell`, field for field
+    pub fn to_spell(&self) -> Result<crate::NormalizedSpell, String> {
+        let mut builder = crate::NormalizedSpell::builder(self.version);
+        for input in &self.ins {
+            let txid = crate::hex::decode_32(&input.utxo_ref.txid)
+                .map_err(|e| format!("Invalid txid {}: {}", input.utxo_ref.txid, e))?;
+            let charms = input.charms.as_ref().map(WasmCharmState::to_charm_state).transpose()?;
+            builder = builder.input(crate::UtxoRef { txid, vout: input.utxo_ref.vout }, charms);
+        }
+        for output in &self.outs {
+            let charms = output.charms.as_ref().map(WasmCharmState::to_charm_state).transpose()?;
+            builder = builder.output(output.index, charms);
+        }
+        let mut spell = builder.build();
+        spell.nonces = self.nonces.clone();
+        spell.genesis = self.genesis.clone();
+        spell.valid_before = self.valid_before;
+        Ok(spell)
+    }
+    
+    /// Commitment hash used for proof verification, as lowercase hex
+    /// 
+    /// This is `NormalizedSpell::hash`, the sha256 of the spell's canonical
+    /// bytes, so it doesn't depend on input or output order.
+    pub fn commitment(&self) -> Result<String, String> {
+        Ok(crate::hex::encode(&self.to_spell()?.hash()))
+    }
+    
+    /// Check whether spell JSON hashes to a hex commitment (either case)
+    pub fn verify_commitment(spell_json: &str, commitment_hex: &str) -> Result<bool, JsError> {
+        Self::commitment_matches(spell_json, commitment_hex).map_err(|e| JsError::new(&e))
+    }
+    
+    fn commitment_matches(spell_json: &str, commitment_hex: &str) -> Result<bool, String> {
+        let spell: WasmNormalizedSpell = serde_json::from_str(spell_json)
+            .map_err(|e| format!("Invalid spell: {}", e))?;
+        let commitment = crate::hex::decode_32(commitment_hex)
+            .map_err(|e| format!("Invalid commitment: {}", e))?;
+        Ok(spell.to_spell()?.hash() == commitment)
+    }
 }
 
 #[cfg(feature = "wasm")]
@@ -337,6 +762,9 @@ pub struct WasmSpellBuilder {
     version: u32,
     ins: Vec<WasmSpellInput>,
     outs: Vec<WasmSpellOutput>,
+    nonces: BTreeMap<String, u64>,
+    genesis: BTreeSet<String>,
+    valid_before: Option<u32>,
 }
 
 #[cfg(feature = "wasm")]
@@ -348,6 +776,9 @@ impl WasmSpellBuilder {
             version,
             ins: Vec::new(),
             outs: Vec::new(),
+            nonces: BTreeMap::new(),
+            genesis: BTreeSet::new(),
+            valid_before: None,
         }
     }
     
@@ -377,23 +808,56 @@ impl WasmSpellBuilder {
         self
     }
     
+    #[wasm_bindgen]
+    pub fn with_nonce(mut self, tag: String, nonce: u64) -> Self {
+        self.nonces.insert(tag, nonce);
+        self
+    }
+    
+    #[wasm_bindgen]
+    pub fn with_genesis(mut self, tag: String) -> Self {
+        self.genesis.insert(tag);
+        self
+    }
+    
+    #[wasm_bindgen]
+    pub fn with_valid_before(mut self, height: u32) -> Self {
+        self.valid_before = Some(height);
+        self
+    }
+    
     #[wasm_bindgen]
     pub fn to_json(&self) -> Result<String, JsError> {
-        let spell = WasmNormalizedSpell {
-            version: self.version,
-            ins: self.ins.clone(),
-            outs: self.outs.clone(),
-        };
-        serde_json::to_string(&spell)
+        serde_json::to_string(&self.build())
             .map_err(|e| JsError::new(&format!("Serialization error: {}", e)))
     }
     
+    /// Commitment hash of the spell as 64 lowercase hex characters
+    #[wasm_bindgen]
+    pub fn compute_commitment(&self) -> Result<String, JsError> {
+        self.build().commitment().map_err(|e| JsError::new(&e))
+    }
+    
     #[wasm_bindgen]
     pub fn verify(&self) -> bool {
         self.version > 0 && !self.ins.is_empty() && !self.outs.is_empty()
     }
 }
 
+#[cfg(feature = "wasm")]
+impl WasmSpellBuilder {
+    pub fn build(&self) -> WasmNormalizedSpell {
+        WasmNormalizedSpell {
+            version: self.version,
+            ins: self.ins.clone(),
+            outs: self.outs.clone(),
+            nonces: self.nonces.clone(),
+            genesis: self.genesis.clone(),
+            valid_before: self.valid_before,
+        }
+    }
+}
+
 // ============================================
 // WASM Entry Points
 // ============================================
@@ -432,6 +896,15 @@ pub fn create_bytes_data(hex: &str) -> String {
     format!(r#"{{"type":"Bytes","value":"{}"}}"#, hex)
 }
 
+/// Format WasmData JSON in a human-readable, JSON-like form for debugging
+#[cfg(feature = "wasm")]
+#[wasm_bindgen]
+pub fn data_to_display_string(json: &str) -> Result<String, JsError> {
+    let data: WasmData = serde_json::from_str(json)
+        .map_err(|e| JsError::new(&format!("Invalid data: {}", e)))?;
+    data.to_display_string().map_err(|e| JsError::new(&e))
+}
+
 /// Parse and validate charm state JSON
 #[cfg(feature = "wasm")]
 #[wasm_bindgen]
@@ -452,6 +925,13 @@ pub fn validate_transaction(json: &str) -> Result<bool, JsError> {
     }
 }
 
+/// Check spell JSON against a hex commitment, see `WasmNormalizedSpell::verify_commitment`
+#[cfg(feature = "wasm")]
+#[wasm_bindgen]
+pub fn verify_spell_commitment(spell_json: &str, commitment_hex: &str) -> Result<bool, JsError> {
+    WasmNormalizedSpell::verify_commitment(spell_json, commitment_hex)
+}
+
 /// Parse and validate spell JSON
 #[cfg(feature = "wasm")]
 #[wasm_bindgen]
@@ -471,3 +951,246 @@ pub fn validate_spell(json: &str) -> Result<JsValue, JsError> {
     serde_wasm_bindgen::to_value(&result)
         .map_err(|e| JsError::new(&format!("Serialization error: {}", e)))
 }
+
+#[cfg(all(test, feature = "wasm"))]
+mod tests {
+    use super::*;
+    
+    #[test]
+    fn test_wasm_data_display_string() {
+        let data = WasmData::List(vec![WasmData::Bytes("0aff".to_string()), WasmData::Empty]);
+        assert_eq!(data.to_display_string(), Ok("[0x0aff, null]".to_string()));
+        assert!(WasmData::Bytes("xyz".to_string()).to_display_string().is_err());
+    }
+    
+    #[test]
+    fn test_wasm_spell_commitment_matches_native() {
+        let mut state = crate::CharmState::new();
+        state.apps.insert("token:A".to_string(), crate::Data::U64(5));
+        let spell = crate::NormalizedSpell::builder(1)
+            .input(crate::UtxoRef { txid: [0x11; 32], vout: 3 }, Some(state))
+            .output(0, None)
+            .build();
+        let expected = crate::hex::encode(&spell.hash());
+        
+        let builder = WasmSpellBuilder::new(1)
+            .add_input("11".repeat(32), 3, Some(r#"{"apps":{"token:A":{"type":"U64","value":5}}}"#.to_string()))
+            .add_output(0, None);
+        assert_eq!(builder.compute_commitment().ok(), Some(expected.clone()));
+        assert_eq!(expected.len(), 64);
+        
+        let json = builder.to_json().unwrap();
+        assert_eq!(WasmNormalizedSpell::commitment_matches(&json, &expected), Ok(true));
+        assert_eq!(WasmNormalizedSpell::commitment_matches(&json, &expected.to_uppercase()), Ok(true));
+        assert_eq!(WasmNormalizedSpell::commitment_matches(&json, &"00".repeat(32)), Ok(false));
+        assert!(WasmNormalizedSpell::commitment_matches(&json, "abc").is_err());
+        
+        let bad_txid = WasmSpellBuilder::new(1).add_input("xyz".to_string(), 0, None).build();
+        assert!(bad_txid.commitment().is_err());
+    }
+    
+    #[test]
+    fn test_wasm_commitment_matches_native() {
+        let builder = WasmSpellBuilder::new(1)
+            .add_input("22".repeat(32), 0, None)
+            .add_input("11".repeat(32), 1, None)
+            .add_output(1, None)
+            .add_output(0, None)
+            .with_nonce("token:A".to_string(), 3)
+            .with_genesis("nft:B".to_string())
+            .with_valid_before(900_000);
+        let native = builder.build().to_spell().unwrap();
+        assert_eq!(native.nonces.get("token:A"), Some(&3));
+        assert!(native.genesis.contains("nft:B"));
+        assert_eq!(native.valid_before, Some(900_000));
+        assert_eq!(builder.compute_commitment().ok(), Some(crate::hex::encode(&native.hash())));
+        
+        // Every field is committed to, and survives the JSON round trip
+        let json = builder.to_json().unwrap();
+        let reloaded: WasmNormalizedSpell = serde_json::from_str(&json).unwrap();
+        assert_eq!(reloaded.to_spell().unwrap(), native);
+        let without_nonce = WasmSpellBuilder::new(1)
+            .add_input("22".repeat(32), 0, None)
+            .add_input("11".repeat(32), 1, None)
+            .add_output(1, None)
+            .add_output(0, None)
+            .with_genesis("nft:B".to_string())
+            .with_valid_before(900_000);
+        assert_ne!(without_nonce.build().commitment(), builder.build().commitment());
+        
+        // Input and output order doesn't change the commitment
+        let reordered = WasmSpellBuilder::new(1)
+            .add_input("11".repeat(32), 1, None)
+            .add_input("22".repeat(32), 0, None)
+            .add_output(0, None)
+            .add_output(1, None)
+            .with_nonce("token:A".to_string(), 3)
+            .with_genesis("nft:B".to_string())
+            .with_valid_before(900_000);
+        assert_eq!(reordered.build().commitment(), builder.build().commitment());
+    }
+    
+    #[test]
+    fn test_wasm_transaction_fee() {
+        let builder = WasmTransactionBuilder::new("00".repeat(32))
+            .add_input_with_value("11".repeat(32), 0, 10_000, None)
+            .add_output(0, 9_500, "0014".to_string(), None);
+        assert_eq!(builder.fee(), Some(500));
+        
+        let builder = builder.add_input("22".repeat(32), 0, None);
+        assert_eq!(builder.fee(), None);
+    }
+    
+    #[test]
+    fn test_wasm_transaction_to_transaction() {
+        let builder = WasmTransactionBuilder::new("00".repeat(32))
+            .add_input_with_value("11".repeat(32), 3, 10_000, None)
+            .set_input_unlock(0, "51".to_string(), 0xffff_fffd)
+            .set_input_script_pubkey(0, "0014".to_string() + &"cd".repeat(20))
+            .add_output(0, 9_500, "0014".to_string(), None)
+            .with_version(1)
+            .with_locktime(800_000);
+        let tx = builder.build().to_transaction().unwrap();
+        assert_eq!(tx.version, 1);
+        assert_eq!(tx.locktime, Some(800_000));
+        assert_eq!(tx.inputs[0].value, Some(10_000));
+        assert_eq!(tx.inputs[0].script_sig, vec![0x51]);
+        assert_eq!(tx.inputs[0].sequence, 0xffff_fffd);
+        assert_eq!(tx.inputs[0].script_pubkey.len(), 22);
+        assert_eq!(tx.outputs[0].script_pubkey, vec![0x00, 0x14]);
+        assert_eq!(builder.txid_hex(), Ok(crate::hex::encode(&tx.compute_txid())));
+        
+        // Older JSON without the new fields reads back with final sequences and the default version
+        let json = r#"{"txid":"00","inputs":[{"utxo_ref":{"txid":"11","vout":0},"value":null,"charm_state":null}],"outputs":[]}"#;
+        let old: WasmTransaction = serde_json::from_str(json).unwrap();
+        assert_eq!(old.version, crate::DEFAULT_TX_VERSION);
+        assert_eq!(old.inputs[0].sequence, crate::SEQUENCE_FINAL);
+        assert!(old.to_transaction().is_err());
+        
+        let bad_script = builder.set_input_unlock(0, "zz".to_string(), 0).build();
+        assert!(bad_script.to_transaction().is_err());
+    }
+    
+    #[test]
+    fn test_wasm_transaction_builder_json_round_trip() {
+        let builder = WasmTransactionBuilder::new("00".repeat(32))
+            .add_input_with_value("11".repeat(32), 3, 10_000, Some(r#"{"apps":{"token:A":{"type":"U64","value":5}}}"#.to_string()))
+            .add_output(0, 9_500, "0014".to_string(), None);
+        let built = builder.build();
+        
+        let json = builder.to_json().unwrap();
+        let reloaded = WasmTransactionBuilder::from_json(&json).unwrap();
+        assert_eq!(
+            serde_json::to_value(reloaded.build()).unwrap(),
+            serde_json::to_value(&built).unwrap()
+        );
+        
+        // The reloaded builder can keep going
+        let extended = reloaded.add_output(1, 400, "0014".to_string(), None).build();
+        assert_eq!(extended.outputs.len(), 2);
+        assert_eq!(extended.fee(), Some(100));
+    }
+    
+    #[test]
+    fn test_wasm_transaction_spell_round_trip() {
+        let builder = WasmTransactionBuilder::new("00".repeat(32))
+            .add_input("11".repeat(32), 0, None)
+            .add_output(0, 546, "0014".to_string(), None);
+        
+        // Without a spell the field is left out, and absent reads back as none
+        let json = builder.to_json().unwrap();
+        assert!(!json.contains("spell"), "{}", json);
+        assert!(WasmTransactionBuilder::from_json(&json).unwrap().build().spell.is_none());
+        
+        let spell_json = WasmSpellBuilder::new(1)
+            .add_input("11".repeat(32), 0, None)
+            .add_output(0, None)
+            .to_json()
+            .unwrap();
+        let with_spell = builder.with_spell(spell_json).unwrap().build();
+        let json = serde_json::to_string(&with_spell).unwrap();
+        let reloaded = WasmTransactionBuilder::from_json(&json).unwrap().build();
+        assert_eq!(
+            serde_json::to_value(&reloaded).unwrap(),
+            serde_json::to_value(&with_spell).unwrap()
+        );
+        assert_eq!(reloaded.spell.unwrap().ins.len(), 1);
+        
+        // Malformed spell JSON is an error rather than silently dropping the spell
+        assert!(parse_spell("{not json").is_err());
+        assert!(parse_spell(r#"{"version":1}"#).is_err());
+    }
+    
+    #[test]
+    fn test_wasm_transaction_builder_validation() {
+        let builder = WasmTransactionBuilder::new("00".repeat(32))
+            .add_input("11".repeat(32), 0, None)
+            .add_output(0, 546, "0014".to_string(), None);
+        assert!(builder.validation().valid);
+        
+        let report = WasmTransactionBuilder::new("xyz".to_string())
+            .add_input("11".repeat(32), 0, None)
+            .add_input("11".repeat(31), 1, None)
+            .add_output(0, 546, "0014".to_string(), None)
+            .add_output(0, 546, "0014".to_string(), None)
+            .validation();
+        assert!(!report.valid);
+        assert!(!report.txids_valid);
+        assert!(report.vouts_in_range);
+        assert!(!report.output_indices_unique);
+        assert_eq!(report.invalid_txids, vec!["txid", "inputs[1].utxo_ref.txid"]);
+        assert_eq!(report.duplicate_output_indices, vec![0]);
+    }
+    
+    #[test]
+    fn test_wasm_utxo_ref_string_round_trip() {
+        let txid = "ab".repeat(32);
+        let utxo = WasmUtxoRef::new(txid.clone(), u32::MAX);
+        
+        let parsed = WasmUtxoRef::from_string(&utxo.to_string()).unwrap();
+        assert_eq!(parsed.txid(), txid);
+        assert_eq!(parsed.vout(), u32::MAX);
+        
+        // Uppercase hex is normalized to lowercase
+        let parsed = WasmUtxoRef::from_string(&format!("{}:0", "AB".repeat(32))).unwrap();
+        assert_eq!(parsed.txid(), txid);
+    }
+    
+    #[test]
+    fn test_wasm_charm_state_builder_remove_app() {
+        let state = WasmCharmStateBuilder::new()
+            .with_app_u64("token:A".to_string(), 100)
+            .with_app_bytes("nft:B".to_string(), "01".to_string())
+            .remove_app("token:A".to_string())
+            .remove_app("token:MISSING".to_string())
+            .build();
+        
+        assert_eq!(state.apps.keys().collect::<Vec<_>>(), vec!["nft:B"]);
+    }
+    
+    #[test]
+    fn test_wasm_charm_state_diff() {
+        let before = WasmCharmStateBuilder::new()
+            .with_app_u64("token:A".to_string(), 100)
+            .with_app_u64("escrow:E".to_string(), 1)
+            .with_app_bytes("nft:OLD".to_string(), "01".to_string())
+            .to_json()
+            .unwrap();
+        let after = WasmCharmStateBuilder::new()
+            .with_app_u64("token:A".to_string(), 100)
+            .with_app_u64("escrow:E".to_string(), 2)
+            .with_app_bytes("nft:NEW".to_string(), "02".to_string())
+            .to_json()
+            .unwrap();
+        
+        let diff = WasmCharmStateDiff::from_json(&before, &after).unwrap();
+        assert!(matches!(diff.added.get("nft:NEW"), Some(WasmData::Bytes(hex)) if hex == "02"));
+        assert!(matches!(diff.removed.get("nft:OLD"), Some(WasmData::Bytes(hex)) if hex == "01"));
+        let change = &diff.changed["escrow:E"];
+        assert!(matches!((&change.before, &change.after), (WasmData::U64(1), WasmData::U64(2))));
+        assert!(!diff.changed.contains_key("token:A"));
+        
+        let bad = r#"{"apps":{"nft:X":{"type":"Bytes","value":"zz"}}}"#;
+        assert!(WasmCharmStateDiff::from_json(bad, &after).is_err());
+    }
+}
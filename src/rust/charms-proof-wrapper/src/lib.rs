@@ -3,6 +3,8 @@
 //! This module provides proof verification wrapper for the Charms spell checker
 //! inside the SP1 zkVM environment.
 
+use charms_data::Data;
+use std::collections::BTreeMap;
 use sp1_primitives::io::sha256_hash;
 use sp1_zkvm::lib::verify::verify_sp1_proof;
 
@@ -10,17 +12,168 @@ pub const SPELL_CHECKER_VK: [u32; 8] = [
     1137430973, 2011028408, 625211435, 1988224886, 433288175, 1277294349, 746782103, 737580122,
 ];
 
+/// Spell checker vks the wrapper accepts proofs for
+///
+/// Listing a new vk here alongside the old one lets the protocol upgrade
+/// without a flag day.
+pub const REGISTERED_SPELL_VKS: &[[u32; 8]] = &[SPELL_CHECKER_VK];
+
+/// Vks still accepted for past blocks but not to be used for new proofs
+///
+/// A wrapper proof over one of these says so in `WrapperOutput::deprecated_vk`,
+/// so verifiers can hold it to blocks from before the deprecation.
+pub const DEPRECATED_SPELL_VKS: &[[u32; 8]] = &[];
+
+/// Whether proofs against `vk` are accepted, deprecated or not
+pub fn is_registered_vk(vk: &[u32; 8]) -> bool {
+    is_listed_vk(vk, REGISTERED_SPELL_VKS, DEPRECATED_SPELL_VKS)
+}
+
+/// Whether `vk` is in either the `registered` or `deprecated` list
+fn is_listed_vk(vk: &[u32; 8], registered: &[[u32; 8]], deprecated: &[[u32; 8]]) -> bool {
+    registered.contains(vk) || deprecated.contains(vk)
+}
+
+/// Whether `vk` is only accepted for past blocks
+pub fn is_deprecated_vk(vk: &[u32; 8]) -> bool {
+    is_listed_deprecated_vk(vk, DEPRECATED_SPELL_VKS)
+}
+
+/// Whether `vk` is in the `deprecated` list
+fn is_listed_deprecated_vk(vk: &[u32; 8], deprecated: &[[u32; 8]]) -> bool {
+    deprecated.contains(vk)
+}
+
+/// Public output of the wrapper proof
+///
+/// Committed as a canonical CBOR map, so fields can be added later without
+/// breaking readers that ignore them.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WrapperOutput {
+    /// Spell checker vk the wrapped proof was verified against
+    pub spell_checker_vk: [u32; 8],
+    /// Public values committed by the spell checker
+    pub committed_data: Vec<u8>,
+    /// Whether `spell_checker_vk` is in `DEPRECATED_SPELL_VKS`
+    pub deprecated_vk: bool,
+}
+
+impl WrapperOutput {
+    /// Encode as a CBOR map of `"spell_checker_vk"` (32 little-endian bytes),
+    /// `"committed_data"` and `"deprecated_vk"`
+    pub fn to_cbor(&self) -> Vec<u8> {
+        let vk = self.spell_checker_vk.iter().flat_map(|word| word.to_le_bytes()).collect();
+        let mut map = BTreeMap::new();
+        map.insert("spell_checker_vk".to_string(), Data::Bytes(vk));
+        map.insert("committed_data".to_string(), Data::Bytes(self.committed_data.clone()));
+        map.insert("deprecated_vk".to_string(), Data::Bool(self.deprecated_vk));
+        Data::Map(map).to_cbor()
+    }
+
+    /// Decode `to_cbor` output
+    pub fn from_cbor(bytes: &[u8]) -> Option<Self> {
+        let Data::Map(map) = Data::from_cbor(bytes).ok()? else {
+            return None;
+        };
+        let vk = map.get("spell_checker_vk")?.as_bytes()?;
+        Some(Self {
+            spell_checker_vk: parse_vk(vk).filter(|_| !vk.is_empty())?,
+            committed_data: map.get("committed_data")?.as_bytes()?.to_vec(),
+            deprecated_vk: match map.get("deprecated_vk") {
+                Some(flag) => flag.as_bool()?,
+                None => false,
+            },
+        })
+    }
+}
+
+/// Reads the committed data, then the spell checker vk as a second vec
+///
+/// An empty vk vec falls back to [`SPELL_CHECKER_VK`]. The data and the vk
+/// it was verified against are committed together as a [`WrapperOutput`],
+/// so the wrapper proof always shows which spell checker it vouches for.
 pub fn main() {
     let input_vec = sp1_zkvm::io::read_vec();
-    verify_proof(&SPELL_CHECKER_VK, &input_vec);
-    sp1_zkvm::io::commit_slice(&input_vec);
+    let vk_vec = sp1_zkvm::io::read_vec();
+    let Some(vk) = parse_vk(&vk_vec) else {
+        panic!("spell checker vk must be empty or 32 bytes")
+    };
+    verify_proof(&vk, &input_vec);
+    let output = WrapperOutput {
+        spell_checker_vk: vk,
+        committed_data: input_vec,
+        deprecated_vk: is_deprecated_vk(&vk),
+    };
+    sp1_zkvm::io::commit_slice(&output.to_cbor());
 }
 
-fn verify_proof(vk: &[u32; 8], committed_data: &[u8]) {
-    let Ok(pv) = sha256_hash(committed_data).try_into() else {
+/// Parse a vk from 8 little-endian `u32` words
+///
+/// Empty input yields [`SPELL_CHECKER_VK`]; any length other than 0 or 32
+/// bytes yields `None`.
+pub fn parse_vk(bytes: &[u8]) -> Option<[u32; 8]> {
+    if bytes.is_empty() {
+        return Some(SPELL_CHECKER_VK);
+    }
+    if bytes.len() != 32 {
+        return None;
+    }
+    let mut vk = [0u32; 8];
+    for (word, chunk) in vk.iter_mut().zip(bytes.chunks_exact(4)) {
+        *word = u32::from_le_bytes(chunk.try_into().ok()?);
+    }
+    Some(vk)
+}
+
+/// Why the wrapper refuses to verify a spell checker proof
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PrecheckError {
+    /// The vk is in neither `REGISTERED_SPELL_VKS` nor `DEPRECATED_SPELL_VKS`
+    UnregisteredVk,
+    /// The committed data isn't canonical CBOR, as `SpellOutput::to_cbor` writes
+    NonCanonical,
+}
+
+impl std::fmt::Display for PrecheckError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PrecheckError::UnregisteredVk => write!(f, "spell checker vk is not registered"),
+            PrecheckError::NonCanonical => write!(f, "committed data is not canonical CBOR"),
+        }
+    }
+}
+
+impl std::error::Error for PrecheckError {}
+
+/// Checks made before verifying the proof, returning the public values digest
+///
+/// The spell checker commits its `SpellOutput` as canonical CBOR, so this
+/// is the only encoding accepted.
+pub fn precheck(vk: &[u32; 8], committed_data: &[u8]) -> Result<[u8; 32], PrecheckError> {
+    if !is_registered_vk(vk) {
+        return Err(PrecheckError::UnregisteredVk);
+    }
+    let canonical = canonicalize(committed_data).ok_or(PrecheckError::NonCanonical)?;
+    let Ok(pv) = sha256_hash(&canonical).try_into() else {
         unreachable!()
     };
-    verify_sp1_proof(vk, &pv);
+    Ok(pv)
+}
+
+fn verify_proof(vk: &[u32; 8], committed_data: &[u8]) {
+    match precheck(vk, committed_data) {
+        Ok(pv) => verify_sp1_proof(vk, &pv),
+        Err(err) => panic!("{}", err),
+    }
+}
+
+/// Decode and re-encode `committed_data` as canonical CBOR
+///
+/// Returns `None` unless the input decodes and was already canonical, so
+/// the proof can only ever commit to one encoding of the same data.
+pub fn canonicalize(committed_data: &[u8]) -> Option<Vec<u8>> {
+    let canonical = Data::from_cbor(committed_data).ok()?.to_cbor();
+    (canonical == committed_data).then_some(canonical)
 }
 
 #[cfg(test)]
@@ -28,6 +181,9 @@ mod test {
     use super::*;
     use sp1_sdk::{HashableKey, Prover, ProverClient};
 
+    /// Stand-in deprecated vk, passed to `is_listed_vk` as a deprecated list
+    const DEPRECATED_VK: [u32; 8] = [1, 2, 3, 4, 5, 6, 7, 8];
+
     /// RISC-V binary compiled from `charms-spell-checker`.
     pub const SPELL_CHECKER_BINARY: &[u8] = include_bytes!("../../src/bin/charms-spell-checker");
 
@@ -37,4 +193,124 @@ mod test {
         let (_, vk) = client.setup(SPELL_CHECKER_BINARY);
         assert_eq!(SPELL_CHECKER_VK, vk.hash_u32());
     }
+
+    #[test]
+    fn test_wrong_vk_does_not_match_binary() {
+        let client = ProverClient::builder().cpu().build();
+        let (_, vk) = client.setup(SPELL_CHECKER_BINARY);
+
+        let mut wrong = SPELL_CHECKER_VK;
+        wrong[0] ^= 1;
+        let bytes: Vec<u8> = wrong.iter().flat_map(|word| word.to_le_bytes()).collect();
+        let parsed = parse_vk(&bytes).unwrap();
+        assert_eq!(parsed, wrong);
+        assert_ne!(parsed, vk.hash_u32());
+        assert_eq!(precheck(&parsed, &Data::U64(7).to_cbor()), Err(PrecheckError::UnregisteredVk));
+    }
+
+    #[test]
+    #[should_panic(expected = "spell checker vk is not registered")]
+    fn test_verify_proof_rejects_wrong_vk() {
+        let mut wrong = SPELL_CHECKER_VK;
+        wrong[0] ^= 1;
+        let bytes: Vec<u8> = wrong.iter().flat_map(|word| word.to_le_bytes()).collect();
+        verify_proof(&parse_vk(&bytes).unwrap(), &Data::U64(7).to_cbor());
+    }
+
+    #[test]
+    fn test_registered_vks() {
+        assert!(is_registered_vk(&SPELL_CHECKER_VK));
+        assert!(!is_deprecated_vk(&SPELL_CHECKER_VK));
+
+        let mut unknown = SPELL_CHECKER_VK;
+        unknown[0] ^= 1;
+        assert!(!is_registered_vk(&unknown));
+        assert!(!is_deprecated_vk(&unknown));
+    }
+
+    #[test]
+    fn test_is_listed_vk() {
+        let deprecated = [DEPRECATED_VK];
+        assert!(is_listed_vk(&SPELL_CHECKER_VK, REGISTERED_SPELL_VKS, &deprecated));
+        assert!(is_listed_vk(&DEPRECATED_VK, REGISTERED_SPELL_VKS, &deprecated));
+        assert!(!is_listed_vk(&DEPRECATED_VK, REGISTERED_SPELL_VKS, &[]));
+        assert!(!is_listed_vk(&DEPRECATED_VK, &[], &[]));
+
+        assert!(is_listed_deprecated_vk(&DEPRECATED_VK, &deprecated));
+        assert!(!is_listed_deprecated_vk(&SPELL_CHECKER_VK, &deprecated));
+        assert!(!is_listed_deprecated_vk(&DEPRECATED_VK, &[]));
+    }
+
+    #[test]
+    fn test_parse_vk() {
+        assert_eq!(parse_vk(&[]), Some(SPELL_CHECKER_VK));
+
+        let bytes: Vec<u8> = SPELL_CHECKER_VK.iter().flat_map(|word| word.to_le_bytes()).collect();
+        assert_eq!(parse_vk(&bytes), Some(SPELL_CHECKER_VK));
+
+        assert_eq!(parse_vk(&bytes[..31]), None);
+        assert_eq!(parse_vk(&[0u8; 33]), None);
+    }
+
+    #[test]
+    fn test_wrapper_output_round_trip() {
+        let output = WrapperOutput {
+            spell_checker_vk: SPELL_CHECKER_VK,
+            committed_data: Data::U64(7).to_cbor(),
+            deprecated_vk: false,
+        };
+        let bytes = output.to_cbor();
+        assert_eq!(canonicalize(&bytes), Some(bytes.clone()));
+        assert_eq!(WrapperOutput::from_cbor(&bytes), Some(output.clone()));
+
+        // The flag round-trips, and outputs from before it existed read as not deprecated
+        let deprecated = WrapperOutput { spell_checker_vk: DEPRECATED_VK, deprecated_vk: true, ..output.clone() };
+        assert_eq!(WrapperOutput::from_cbor(&deprecated.to_cbor()), Some(deprecated));
+        let Data::Map(mut map) = Data::from_cbor(&bytes).unwrap() else { unreachable!() };
+        map.remove("deprecated_vk");
+        assert_eq!(WrapperOutput::from_cbor(&Data::Map(map).to_cbor()), Some(output));
+
+        assert_eq!(WrapperOutput::from_cbor(&Data::U64(7).to_cbor()), None);
+    }
+
+    #[test]
+    fn test_precheck_accepts_spell_checker_output() {
+        use charms_data::{NormalizedSpell, UtxoRef};
+        use charms_spell_checker::SpellOutput;
+
+        let spell = NormalizedSpell::builder(1)
+            .input(UtxoRef { txid: [1u8; 32], vout: 0 }, None)
+            .output(0, None)
+            .build()
+            .with_nonce("token:USD", 1);
+        let committed = SpellOutput::batch_to_cbor(&[SpellOutput::new("vk".to_string(), spell)]);
+
+        let pv = precheck(&SPELL_CHECKER_VK, &committed).unwrap();
+        assert_eq!(pv.to_vec(), sha256_hash(&committed));
+
+        let mut trailing = committed.clone();
+        trailing.push(0);
+        assert_eq!(precheck(&SPELL_CHECKER_VK, &trailing), Err(PrecheckError::NonCanonical));
+    }
+
+    #[test]
+    fn test_canonicalize_accepts_canonical() {
+        let data = Data::List(vec![Data::U64(1000), Data::String("spell".to_string())]);
+        let bytes = data.to_cbor();
+        assert_eq!(canonicalize(&bytes), Some(bytes));
+    }
+
+    #[test]
+    fn test_canonicalize_rejects_non_canonical() {
+        // 1 encoded with a needless one-byte length argument
+        assert_eq!(canonicalize(&[0x18, 0x01]), None);
+
+        // {"b": 1, "a": 2}: keys out of order
+        assert_eq!(canonicalize(&[0xa2, 0x61, b'b', 0x01, 0x61, b'a', 0x02]), None);
+
+        // Indefinite-length list [1]
+        assert_eq!(canonicalize(&[0x9f, 0x01, 0xff]), None);
+
+        assert_eq!(canonicalize(&[0xff]), None);
+    }
 }
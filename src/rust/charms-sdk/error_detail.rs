@@ -0,0 +1,96 @@
+//! Structured spell rejections shared by the WASM bindings
+//! 
+//! Both the SDK's bindings and charmix report errors as
+//! `WasmSpellErrorDetail`, so JavaScript callers see one shape whichever
+//! module checked the spell.
+
+use serde::{Deserialize, Serialize};
+
+use crate::data::{hex, CheckError};
+
+/// Machine-readable reason a spell was rejected
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct WasmSpellErrorDetail {
+    /// Stable identifier such as `"conservation_violated"`
+    pub code: String,
+    /// The field or app at fault, if any
+    pub field: Option<String>,
+    /// What the checker required
+    pub expected: Option<serde_json::Value>,
+    /// What the spell had instead
+    pub actual: Option<serde_json::Value>,
+    /// Human-readable description
+    pub message: String,
+}
+
+impl WasmSpellErrorDetail {
+    /// Create an error with just a code and message
+    pub fn new(code: &str, message: impl Into<String>) -> Self {
+        Self {
+            code: code.to_string(),
+            field: None,
+            expected: None,
+            actual: None,
+            message: message.into(),
+        }
+    }
+    
+    /// Name the field at fault
+    pub fn field(mut self, field: impl Into<String>) -> Self {
+        self.field = Some(field.into());
+        self
+    }
+    
+    /// Record the expected value
+    pub fn expected(mut self, expected: impl Into<serde_json::Value>) -> Self {
+        self.expected = Some(expected.into());
+        self
+    }
+    
+    /// Record the actual value
+    pub fn actual(mut self, actual: impl Into<serde_json::Value>) -> Self {
+        self.actual = Some(actual.into());
+        self
+    }
+}
+
+/// Describe a native checker error
+impl From<&CheckError> for WasmSpellErrorDetail {
+    fn from(e: &CheckError) -> Self {
+        let message = e.to_string();
+        match e {
+            CheckError::ConservationViolation { input, output } => {
+                Self::new("conservation_violated", message).expected(*input).actual(*output)
+            }
+            CheckError::InvalidTransition => Self::new("invalid_transition", message),
+            CheckError::DuplicateNft(id) => {
+                Self::new("duplicate_nft", message).field("outputs").actual(hex::encode(id))
+            }
+            CheckError::MissingAuthorization => Self::new("missing_authorization", message).field("x"),
+            CheckError::InvalidMint => Self::new("invalid_mint", message),
+            CheckError::Overflow => Self::new("overflow", message),
+            CheckError::TooManyOutputs { count, max } => {
+                Self::new("too_many_outputs", message).field("outputs").expected(*max).actual(*count)
+            }
+            CheckError::MissingField(field) => Self::new("missing_field", message).field(*field),
+            CheckError::UndeliveredAsset(tag) => Self::new("undelivered_asset", message).field(tag.clone()),
+            CheckError::RoyaltyUnpaid { required, paid } => {
+                Self::new("royalty_unpaid", message).expected(*required).actual(*paid)
+            }
+            CheckError::TimeoutNotReached { height, timeout } => {
+                Self::new("timeout_not_reached", message).field("locktime").expected(*timeout).actual(*height)
+            }
+            CheckError::AmountMismatch { expected, actual } => {
+                Self::new("amount_mismatch", message).expected(*expected).actual(*actual)
+            }
+            CheckError::InvalidBurn => Self::new("invalid_burn", message),
+            CheckError::SupplyCapExceeded { minted, max_supply } => {
+                Self::new("supply_cap_exceeded", message).expected(*max_supply).actual(*minted)
+            }
+            CheckError::Undercollateralized { required, collateral } => {
+                Self::new("insufficient_collateral", message).expected(*required).actual(*collateral)
+            }
+            CheckError::InvalidJson(_) => Self::new("invalid_json", message),
+        }
+    }
+}
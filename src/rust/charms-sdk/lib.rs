@@ -6,10 +6,15 @@
 //! ## Features
 //! - `std`: Standard library support (default)
 //! - `serde`: Serialization support
+//! - `json`: `WasmSpellErrorDetail`, without the WASM exports
 //! - `wasm`: WebAssembly bindings for browser use
 
 pub use charms_data as data;
 
+// Structured errors, shared with other crates' WASM bindings
+#[cfg(feature = "json")]
+pub mod error_detail;
+
 // WASM bindings module
 #[cfg(feature = "wasm")]
 pub mod wasm_bindings;
@@ -26,18 +31,14 @@ pub mod prelude {
 /// Main macro for defining spell checker entry points
 /// 
 /// # Example
-/// ```rust
-/// charms_sdk::main! {
-///     ($path:path) => {
-///         fn main() {
-///             use charms_sdk::data::{App, Data, Transaction};
-///             let (app, tx, x, w): (App, Transaction, Data, Data) =
-///                 charms_sdk::data::util::read(std::io::stdin())
-///                     .expect("should deserialize (app, tx, x, w)");
-///             assert!($path(&app, &tx, &x, &w));
-///         }
-///     };
+/// ```no_run
+/// use charms_sdk::data::{App, Data, Transaction};
+/// 
+/// fn check(app: &App, tx: &Transaction, x: &Data, w: &Data) -> bool {
+///     true
 /// }
+/// 
+/// charms_sdk::main!(check);
 /// ```
 #[macro_export]
 macro_rules! main {
@@ -60,14 +61,14 @@ pub mod util {
     use std::io::Read;
     
     /// Read and deserialize spell data from a reader
-    pub fn read<R: Read>(reader: R) -> Result<(App, Transaction, Data, Data), std::io::Error> {
+    pub fn read<R: Read>(_reader: R) -> Result<(App, Transaction, Data, Data), std::io::Error> {
         // In a real implementation, this would use serde/bincode to deserialize
         // For now, return a placeholder
         todo!("Implement deserialization from reader")
     }
     
     /// Serialize spell data to bytes
-    pub fn serialize(app: &App, tx: &Transaction, x: &Data, w: &Data) -> Vec<u8> {
+    pub fn serialize(_app: &App, _tx: &Transaction, _x: &Data, _w: &Data) -> Vec<u8> {
         // Serialize the tuple for transmission
         todo!("Implement serialization")
     }
@@ -9,6 +9,14 @@ use wasm_bindgen::prelude::*;
 use serde::{Deserialize, Serialize};
 #[cfg(feature = "wasm")]
 use std::collections::BTreeMap;
+#[cfg(feature = "wasm")]
+use crate::data::hex;
+#[cfg(feature = "wasm")]
+use crate::data::escrow::{self, EscrowState};
+#[cfg(feature = "wasm")]
+use crate::data::AppType;
+#[cfg(feature = "wasm")]
+pub use crate::error_detail::WasmSpellErrorDetail;
 
 // ============================================
 // WASM-compatible Data Types
@@ -23,6 +31,15 @@ pub struct WasmApp {
     pub params: Option<WasmData>,
 }
 
+#[cfg(feature = "wasm")]
+impl WasmApp {
+    /// Decode `vk_hash`, which must be exactly 64 hex characters
+    pub fn vk_hash_bytes(&self) -> Result<[u8; 32], JsError> {
+        hex::decode_32(&self.vk_hash)
+            .map_err(|e| JsError::new(&format!("Invalid vk_hash: {}", e)))
+    }
+}
+
 #[cfg(feature = "wasm")]
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(tag = "type", content = "value")]
@@ -35,6 +52,31 @@ pub enum WasmData {
     String(String),
     List(Vec<WasmData>),
     Map(BTreeMap<String, WasmData>),
+    F64(f64),
+}
+
+#[cfg(feature = "wasm")]
+impl WasmData {
+    /// Convert to native `Data`, or `None` if any `Bytes` value is invalid hex
+    pub fn to_data(&self) -> Option<crate::data::Data> {
+        use crate::data::Data;
+        
+        Some(match self {
+            WasmData::Empty => Data::Empty,
+            WasmData::Bool(b) => Data::Bool(*b),
+            WasmData::U64(n) => Data::U64(*n),
+            WasmData::I64(n) => Data::I64(*n),
+            WasmData::F64(n) => Data::F64(*n),
+            WasmData::Bytes(s) => Data::from_wasm_hex(s).ok()?,
+            WasmData::String(s) => Data::String(s.clone()),
+            WasmData::List(items) => Data::List(items.iter().map(WasmData::to_data).collect::<Option<_>>()?),
+            WasmData::Map(map) => Data::Map(
+                map.iter()
+                    .map(|(key, value)| Some((key.clone(), value.to_data()?)))
+                    .collect::<Option<_>>()?,
+            ),
+        })
+    }
 }
 
 #[cfg(feature = "wasm")]
@@ -51,12 +93,33 @@ pub struct WasmUtxoRef {
     pub vout: u32,
 }
 
+#[cfg(feature = "wasm")]
+impl WasmUtxoRef {
+    /// Decode `txid`, which must be exactly 64 hex characters
+    pub fn txid_bytes(&self) -> Result<[u8; 32], JsError> {
+        hex::decode_32(&self.txid)
+            .map_err(|e| JsError::new(&format!("Invalid txid: {}", e)))
+    }
+}
+
 #[cfg(feature = "wasm")]
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct WasmCharmState {
     pub apps: BTreeMap<String, WasmData>,
 }
 
+#[cfg(feature = "wasm")]
+impl WasmCharmState {
+    /// Convert to a native `CharmState`, or `None` if any hex value is invalid
+    pub fn to_charm_state(&self) -> Option<crate::data::CharmState> {
+        let mut state = crate::data::CharmState::new();
+        for (tag, data) in &self.apps {
+            state.apps.insert(tag.clone(), data.to_data()?);
+        }
+        Some(state)
+    }
+}
+
 #[cfg(feature = "wasm")]
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct WasmTxInput {
@@ -81,6 +144,37 @@ pub struct WasmTransaction {
     pub outputs: Vec<WasmTxOutput>,
 }
 
+#[cfg(feature = "wasm")]
+impl WasmTransaction {
+    /// Convert to a native `Transaction`, or `None` if any hex field is invalid
+    pub fn to_transaction(&self) -> Option<crate::data::Transaction> {
+        use crate::data::{Transaction, TxInput, TxOutput, UtxoRef};
+        
+        let mut tx = Transaction::new(hex::decode_32(&self.txid).ok()?);
+        for input in &self.inputs {
+            tx.add_input(TxInput::new(
+                UtxoRef {
+                    txid: hex::decode_32(&input.utxo_ref.txid).ok()?,
+                    vout: input.utxo_ref.vout,
+                },
+                match &input.charm_state { Some(state) => Some(state.to_charm_state()?), None => None, },
+            ));
+        }
+        for output in &self.outputs {
+            tx.add_output(TxOutput {
+                index: output.index,
+                value: output.value,
+                script_pubkey: hex::decode(&output.script_pubkey)?,
+                charm_state: match &output.charm_state {
+                    Some(state) => Some(state.to_charm_state()?),
+                    None => None,
+                },
+            });
+        }
+        Some(tx)
+    }
+}
+
 #[cfg(feature = "wasm")]
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct WasmSpellInput {
@@ -119,7 +213,14 @@ pub struct WasmCheckResult {
     pub current_state: Option<String>,
     pub next_state: Option<String>,
     pub state_transition_valid: Option<bool>,
-    pub errors: Vec<String>,
+    pub errors: Vec<WasmSpellErrorDetail>,
+    /// The `message` of each entry in `errors`
+    pub error_messages: Vec<String>,
+}
+
+#[cfg(feature = "wasm")]
+fn error_messages(errors: &[WasmSpellErrorDetail]) -> Vec<String> {
+    errors.iter().map(|e| e.message.clone()).collect()
 }
 
 // ============================================
@@ -141,6 +242,7 @@ pub fn check_spell(app_json: &str, tx_json: &str, x_json: &str, w_json: &str) ->
         .map_err(|e| JsError::new(&format!("Failed to parse app: {}", e)))?;
     let tx: WasmTransaction = serde_json::from_str(tx_json)
         .map_err(|e| JsError::new(&format!("Failed to parse tx: {}", e)))?;
+    validate_hex_fields(&app, &tx)?;
     let x: WasmData = serde_json::from_str(x_json).unwrap_or(WasmData::Empty);
     let w: WasmData = serde_json::from_str(w_json).unwrap_or(WasmData::Empty);
     
@@ -158,6 +260,7 @@ pub fn check_token(app_json: &str, tx_json: &str, x_json: &str) -> Result<JsValu
         .map_err(|e| JsError::new(&format!("Failed to parse app: {}", e)))?;
     let tx: WasmTransaction = serde_json::from_str(tx_json)
         .map_err(|e| JsError::new(&format!("Failed to parse tx: {}", e)))?;
+    validate_hex_fields(&app, &tx)?;
     let x: WasmData = serde_json::from_str(x_json).unwrap_or(WasmData::Empty);
     
     let result = check_token_internal(&app, &tx, &x);
@@ -166,16 +269,40 @@ pub fn check_token(app_json: &str, tx_json: &str, x_json: &str) -> Result<JsValu
         .map_err(|e| JsError::new(&format!("Failed to serialize result: {}", e)))
 }
 
+/// Check an NFT spell
+#[cfg(feature = "wasm")]
+#[wasm_bindgen]
+pub fn check_nft(app_json: &str, tx_json: &str, x_json: &str) -> Result<JsValue, JsError> {
+    let app: WasmApp = serde_json::from_str(app_json)
+        .map_err(|e| JsError::new(&format!("Failed to parse app: {}", e)))?;
+    let tx: WasmTransaction = serde_json::from_str(tx_json)
+        .map_err(|e| JsError::new(&format!("Failed to parse tx: {}", e)))?;
+    validate_hex_fields(&app, &tx)?;
+    let x: WasmData = serde_json::from_str(x_json)
+        .map_err(|e| JsError::new(&format!("Failed to parse x: {}", e)))?;
+    
+    let result = check_nft_internal(&app, &tx, &x);
+    
+    serde_wasm_bindgen::to_value(&result)
+        .map_err(|e| JsError::new(&format!("Failed to serialize result: {}", e)))
+}
+
 /// Check an escrow spell
+/// 
+/// Takes `x_json` like charmix's `check_escrow`, so both modules export the
+/// same signature.
 #[cfg(feature = "wasm")]
 #[wasm_bindgen]
-pub fn check_escrow(app_json: &str, tx_json: &str) -> Result<JsValue, JsError> {
+pub fn check_escrow(app_json: &str, tx_json: &str, x_json: &str) -> Result<JsValue, JsError> {
     let app: WasmApp = serde_json::from_str(app_json)
         .map_err(|e| JsError::new(&format!("Failed to parse app: {}", e)))?;
     let tx: WasmTransaction = serde_json::from_str(tx_json)
         .map_err(|e| JsError::new(&format!("Failed to parse tx: {}", e)))?;
+    validate_hex_fields(&app, &tx)?;
+    let x: WasmData = serde_json::from_str(x_json)
+        .map_err(|e| JsError::new(&format!("Failed to parse x: {}", e)))?;
     
-    let result = check_escrow_internal(&app, &tx);
+    let result = check_escrow_internal(&app, &tx, &x);
     
     serde_wasm_bindgen::to_value(&result)
         .map_err(|e| JsError::new(&format!("Failed to serialize result: {}", e)))
@@ -270,6 +397,16 @@ pub fn get_version() -> String {
 // Internal Check Functions
 // ============================================
 
+/// Reject an app or transaction whose hex-encoded hashes are malformed
+#[cfg(feature = "wasm")]
+fn validate_hex_fields(app: &WasmApp, tx: &WasmTransaction) -> Result<(), JsError> {
+    app.vk_hash_bytes()?;
+    for input in &tx.inputs {
+        input.utxo_ref.txid_bytes()?;
+    }
+    Ok(())
+}
+
 #[cfg(feature = "wasm")]
 fn get_state_data(state: &Option<WasmCharmState>, app_tag: &str) -> Option<WasmData> {
     state.as_ref()?.apps.get(app_tag).cloned()
@@ -284,25 +421,28 @@ fn data_as_u64(data: &Option<WasmData>) -> Option<u64> {
 }
 
 #[cfg(feature = "wasm")]
-fn check_spell_internal(app: &WasmApp, tx: &WasmTransaction, x: &WasmData, w: &WasmData) -> WasmCheckResult {
-    if app.tag.starts_with("token:") {
-        check_token_internal(app, tx, x)
-    } else if app.tag.starts_with("nft:") {
-        check_nft_internal(app, tx, x)
-    } else if app.tag.starts_with("escrow:") {
-        check_escrow_internal(app, tx)
-    } else {
-        WasmCheckResult {
-            valid: false,
-            spell_type: "unknown".to_string(),
-            input_sum: None,
-            output_sum: None,
-            is_mint: None,
-            is_burn: None,
-            current_state: None,
-            next_state: None,
-            state_transition_valid: None,
-            errors: vec![format!("Unknown app type: {}", app.tag)],
+fn check_spell_internal(app: &WasmApp, tx: &WasmTransaction, x: &WasmData, _w: &WasmData) -> WasmCheckResult {
+    match AppType::from_tag(&app.tag) {
+        AppType::Token => check_token_internal(app, tx, x),
+        AppType::Nft => check_nft_internal(app, tx, x),
+        AppType::Escrow => check_escrow_internal(app, tx, x),
+        _ => {
+            let unknown = WasmSpellErrorDetail::new("unknown_app_type", format!("Unknown app type: {}", app.tag))
+                .field("tag")
+                .actual(app.tag.clone());
+            WasmCheckResult {
+                valid: false,
+                spell_type: "unknown".to_string(),
+                input_sum: None,
+                output_sum: None,
+                is_mint: None,
+                is_burn: None,
+                current_state: None,
+                next_state: None,
+                state_transition_valid: None,
+                errors: vec![unknown.clone()],
+                error_messages: vec![unknown.message],
+            }
         }
     }
 }
@@ -330,12 +470,19 @@ fn check_token_internal(app: &WasmApp, tx: &WasmTransaction, x: &WasmData) -> Wa
     
     // Check conservation
     if input_sum != output_sum {
-        errors.push(format!("Token conservation failed: input={} != output={}", input_sum, output_sum));
+        errors.push(
+            WasmSpellErrorDetail::new(
+                "conservation_violated",
+                format!("Token conservation failed: input={} != output={}", input_sum, output_sum),
+            )
+            .expected(input_sum)
+            .actual(output_sum),
+        );
     }
     
     // Check authorization
     if matches!(x, WasmData::Bytes(s) if s.is_empty()) {
-        errors.push("Empty authorization data".to_string());
+        errors.push(WasmSpellErrorDetail::new("missing_authorization", "Empty authorization data").field("x"));
     }
     
     let is_mint = input_sum == 0 && output_sum > 0;
@@ -351,6 +498,7 @@ fn check_token_internal(app: &WasmApp, tx: &WasmTransaction, x: &WasmData) -> Wa
         current_state: None,
         next_state: None,
         state_transition_valid: None,
+        error_messages: error_messages(&errors),
         errors,
     }
 }
@@ -386,7 +534,11 @@ fn check_nft_internal(app: &WasmApp, tx: &WasmTransaction, x: &WasmData) -> Wasm
     let mut seen = std::collections::HashSet::new();
     for nft in &output_nfts {
         if !seen.insert(nft.clone()) {
-            errors.push(format!("Duplicate NFT in outputs: {}", nft));
+            errors.push(
+                WasmSpellErrorDetail::new("duplicate_nft", format!("Duplicate NFT in outputs: {}", nft))
+                    .field("outputs")
+                    .actual(nft.clone()),
+            );
         }
     }
     
@@ -394,7 +546,11 @@ fn check_nft_internal(app: &WasmApp, tx: &WasmTransaction, x: &WasmData) -> Wasm
     for nft in &output_nfts {
         if !input_nfts.contains(nft) {
             if matches!(x, WasmData::Empty) {
-                errors.push(format!("NFT mint without authorization: {}", nft));
+                errors.push(
+                    WasmSpellErrorDetail::new("missing_authorization", format!("NFT mint without authorization: {}", nft))
+                        .field("x")
+                        .actual(nft.clone()),
+                );
             }
         }
     }
@@ -409,56 +565,78 @@ fn check_nft_internal(app: &WasmApp, tx: &WasmTransaction, x: &WasmData) -> Wasm
         current_state: None,
         next_state: None,
         state_transition_valid: None,
+        error_messages: error_messages(&errors),
         errors,
     }
 }
 
+/// Check an escrow spell with the state and amount rules shared with the
+/// native checker in `charms_data::escrow`
+/// 
+/// Escrow states are read as the native checker reads them: from funding on,
+/// each must be a map recording the funded `"amount"`. The signatures in `x`
+/// are only verified by charmix's `check_escrow`.
 #[cfg(feature = "wasm")]
-fn check_escrow_internal(app: &WasmApp, tx: &WasmTransaction) -> WasmCheckResult {
+fn check_escrow_internal(app: &WasmApp, tx: &WasmTransaction, _x: &WasmData) -> WasmCheckResult {
     let mut errors = Vec::new();
     let app_tag = &app.tag;
     
-    let state_names = ["Created", "Funded", "Released", "Disputed", "Refunded"];
+    let Some(native_tx) = tx.to_transaction() else {
+        let invalid = WasmSpellErrorDetail::new("invalid_hex", "Invalid hex in transaction");
+        return WasmCheckResult {
+            valid: false,
+            spell_type: "escrow".to_string(),
+            input_sum: None,
+            output_sum: None,
+            is_mint: None,
+            is_burn: None,
+            current_state: None,
+            next_state: None,
+            state_transition_valid: None,
+            errors: vec![invalid.clone()],
+            error_messages: vec![invalid.message],
+        };
+    };
     
     // Get current state
-    let current_state: Option<u64> = tx.inputs.iter()
+    let current_data = native_tx.inputs.iter()
         .find_map(|input| {
-            let state = get_state_data(&input.charm_state, app_tag);
-            data_as_u64(&state)
+            input.charm_state.as_ref()?
+                .get(app_tag)
+                .filter(|data| escrow::parse_state(data).is_some())
         });
+    let current_state = current_data.and_then(escrow::parse_state);
     
     // Get next state
-    let next_state: Option<u64> = tx.outputs.iter()
-        .find_map(|output| {
-            let state = get_state_data(&output.charm_state, app_tag);
-            data_as_u64(&state)
-        });
+    let next_state: Option<EscrowState> = native_tx.outputs.iter()
+        .find_map(|output| output.charm_state.as_ref()?.get(app_tag).and_then(escrow::parse_state));
     
     let current_name = current_state
-        .and_then(|s| state_names.get(s as usize))
         .map(|s| s.to_string())
         .unwrap_or_else(|| "None".to_string());
     
     let next_name = next_state
-        .and_then(|s| state_names.get(s as usize))
         .map(|s| s.to_string())
         .unwrap_or_else(|| "None".to_string());
     
-    // Valid transitions
-    let valid_transitions: Vec<(Option<u64>, Option<u64>)> = vec![
-        (None, Some(0)),       // -> Created
-        (Some(0), Some(1)),    // Created -> Funded
-        (Some(1), Some(2)),    // Funded -> Released
-        (Some(1), Some(3)),    // Funded -> Disputed
-        (Some(3), Some(4)),    // Disputed -> Refunded
-        (Some(3), Some(2)),    // Disputed -> Released
-    ];
-    
-    let is_valid = valid_transitions.iter()
-        .any(|(from, to)| *from == current_state && *to == next_state);
+    // Same transition table as the native checker (charms-data escrow module)
+    let is_valid = escrow::is_valid_transition(current_state, next_state);
     
     if !is_valid {
-        errors.push(format!("Invalid escrow transition: {} -> {}", current_name, next_name));
+        errors.push(
+            WasmSpellErrorDetail::new(
+                "invalid_transition",
+                format!("Invalid escrow transition: {} -> {}", current_name, next_name),
+            )
+            .field("state")
+            .actual(format!("{} -> {}", current_name, next_name)),
+        );
+    } else if let Some(next_state) = next_state {
+        // Milestone schedules live in the native checker's params, so only a
+        // dispute's recorded remainder is known here
+        if let Err(e) = escrow::check_amount_preserved(app_tag, &native_tx, current_data, next_state, None) {
+            errors.push(WasmSpellErrorDetail::from(&e));
+        }
     }
     
     WasmCheckResult {
@@ -471,6 +649,7 @@ fn check_escrow_internal(app: &WasmApp, tx: &WasmTransaction) -> WasmCheckResult
         current_state: Some(current_name),
         next_state: Some(next_name),
         state_transition_valid: Some(is_valid),
+        error_messages: error_messages(&errors),
         errors,
     }
 }
@@ -484,3 +663,171 @@ pub fn check_spell_native(app: &crate::data::App, tx: &crate::data::Transaction,
     // Native implementation delegates to the actual Rust logic
     true
 }
+
+#[cfg(all(test, feature = "wasm"))]
+mod tests {
+    use super::*;
+    
+    /// Escrow state with raw state `code`, as a map recording a funded amount
+    /// of 100,000 sats or as a bare code
+    fn escrow_state(code: u64, map: bool) -> crate::data::Data {
+        use crate::data::Data;
+        
+        if !map {
+            return Data::U64(code);
+        }
+        let mut escrow = BTreeMap::new();
+        escrow.insert("state".to_string(), Data::U64(code));
+        escrow.insert("amount".to_string(), Data::U64(100_000));
+        Data::Map(escrow)
+    }
+    
+    /// Wrap `data` as the charm state of `tag`
+    fn wasm_charm_state(tag: &str, data: crate::data::Data) -> Option<WasmCharmState> {
+        let mut apps = BTreeMap::new();
+        apps.insert(tag.to_string(), wasm_data(data));
+        Some(WasmCharmState { apps })
+    }
+    
+    fn wasm_data(data: crate::data::Data) -> WasmData {
+        use crate::data::Data;
+        
+        match data {
+            Data::U64(n) => WasmData::U64(n),
+            Data::Map(map) => WasmData::Map(map.into_iter().map(|(key, value)| (key, wasm_data(value))).collect()),
+            other => panic!("no escrow state holds {:?}", other),
+        }
+    }
+    
+    /// Build the same escrow transition as a native and a WASM transaction,
+    /// locking `value` sats in the output
+    fn escrow_pair(
+        tag: &str,
+        current: Option<crate::data::Data>,
+        next: crate::data::Data,
+        value: u64,
+    ) -> (crate::data::Transaction, WasmTransaction) {
+        use crate::data::{CharmState, Transaction, TxInput, TxOutput, UtxoRef};
+        
+        let mut native = Transaction::new([0u8; 32]);
+        let mut wasm = WasmTransaction { txid: "0".repeat(64), inputs: Vec::new(), outputs: Vec::new() };
+        
+        if let Some(current) = current {
+            native.inputs.push(TxInput::new(
+                UtxoRef { txid: [0u8; 32], vout: 0 },
+                Some(CharmState::new().with_app(tag, current.clone())),
+            ));
+            wasm.inputs.push(WasmTxInput {
+                utxo_ref: WasmUtxoRef { txid: "0".repeat(64), vout: 0 },
+                charm_state: wasm_charm_state(tag, current),
+            });
+        }
+        
+        native.outputs.push(TxOutput {
+            index: 0,
+            value,
+            script_pubkey: vec![],
+            charm_state: Some(CharmState::new().with_app(tag, next.clone())),
+        });
+        wasm.outputs.push(WasmTxOutput {
+            index: 0,
+            value,
+            script_pubkey: String::new(),
+            charm_state: wasm_charm_state(tag, next),
+        });
+        
+        (native, wasm)
+    }
+    
+    #[test]
+    fn test_escrow_matches_native() {
+        use crate::data::{App, Data};
+        
+        let tag = "escrow:CROSS";
+        let app = App::new(tag, [0u8; 32]);
+        let wasm_app = WasmApp { tag: tag.to_string(), vk_hash: "0".repeat(64), params: None };
+        
+        let codes = [0, 1, 2, 3, 4, 5, 100, 101, 102];
+        let currents = std::iter::once(None).chain(codes.iter().map(|c| Some(*c)));
+        
+        for current in currents {
+            for next in codes {
+                // Map and bare states, with the amount kept or short by a sat
+                for (map, value) in [(true, 100_000), (true, 99_999), (false, 100_000)] {
+                    let (native_tx, wasm_tx) = escrow_pair(
+                        tag,
+                        current.map(|code| escrow_state(code, map)),
+                        escrow_state(next, map),
+                        value,
+                    );
+                    let native = charmix::escrow::check(&app, &native_tx, &Data::Empty, &Data::Empty);
+                    let wasm = check_escrow_internal(&wasm_app, &wasm_tx, &WasmData::Empty);
+                    assert_eq!(
+                        native, wasm.valid,
+                        "native and WASM disagree on {:?} -> {} (map: {}, value: {})", current, next, map, value
+                    );
+                }
+            }
+        }
+        
+        // Funding with a bare state records no amount
+        let (_, wasm_tx) = escrow_pair(tag, Some(escrow_state(0, false)), escrow_state(1, false), 100_000);
+        let result = check_escrow_internal(&wasm_app, &wasm_tx, &WasmData::Empty);
+        assert_eq!(result.state_transition_valid, Some(true));
+        assert!(!result.valid);
+    }
+    
+    fn nft_output(index: u32, tag: &str, id: &str) -> WasmTxOutput {
+        let mut apps = BTreeMap::new();
+        apps.insert(tag.to_string(), WasmData::Bytes(id.to_string()));
+        WasmTxOutput {
+            index,
+            value: 546,
+            script_pubkey: "0014".to_string(),
+            charm_state: Some(WasmCharmState { apps }),
+        }
+    }
+    
+    #[test]
+    fn test_check_nft_duplicate_invalid() {
+        let tag = "nft:ART";
+        let id = "ab".repeat(32);
+        let app = WasmApp {
+            tag: tag.to_string(),
+            vk_hash: "0".repeat(64),
+            params: None,
+        };
+        let mut apps = BTreeMap::new();
+        apps.insert(tag.to_string(), WasmData::Bytes(id.clone()));
+        let tx = WasmTransaction {
+            txid: "0".repeat(64),
+            inputs: vec![WasmTxInput {
+                utxo_ref: WasmUtxoRef { txid: "1".repeat(64), vout: 0 },
+                charm_state: Some(WasmCharmState { apps }),
+            }],
+            outputs: vec![nft_output(0, tag, &id), nft_output(1, tag, &id)],
+        };
+        
+        // Parse the same JSON the `check_nft` export receives
+        let app: WasmApp = serde_json::from_str(&serde_json::to_string(&app).unwrap()).unwrap();
+        let tx: WasmTransaction = serde_json::from_str(&serde_json::to_string(&tx).unwrap()).unwrap();
+        let x: WasmData = serde_json::from_str(r#"{"type":"Empty"}"#).unwrap();
+        
+        let result = check_nft_internal(&app, &tx, &x);
+        assert!(!result.valid);
+        assert_eq!(result.spell_type, "nft");
+        assert!(result.errors.iter().any(|e| e.code == "duplicate_nft"));
+        assert!(result.error_messages.iter().any(|e| e.starts_with("Duplicate NFT")));
+        
+        // JS sees the code on each structured error
+        let json = serde_json::to_value(&result).unwrap();
+        assert_eq!(json["errors"][0]["code"], "duplicate_nft");
+        assert_eq!(json["errors"][0]["field"], "outputs");
+        assert_eq!(json["errors"][0]["actual"], id.as_str());
+        
+        // The same transfer without the duplicate is fine
+        let mut single = tx.clone();
+        single.outputs.truncate(1);
+        assert!(check_nft_internal(&app, &single, &x).valid);
+    }
+}
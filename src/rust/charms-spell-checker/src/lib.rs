@@ -3,46 +3,674 @@
 //! This module provides the main entry point for spell verification
 //! inside the SP1 zkVM environment.
 
-use charms_client::{NormalizedSpell, SpellProverInput, is_correct};
-use charms_data::util;
+use charms_client::{NormalizedSpell, SpellProverInput};
+use charms_data::{util, Data, Transaction};
+use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+use std::collections::BTreeMap;
+use std::io::Read;
+use std::ops::RangeInclusive;
+
+/// Lowest spell protocol version this checker accepts.
+pub const MIN_SPELL_VERSION: u32 = 1;
+/// Highest spell protocol version this checker accepts.
+pub const MAX_SPELL_VERSION: u32 = 1;
+/// Spell protocol versions this checker understands; `run` rejects any other.
+pub const SUPPORTED_SPELL_VERSIONS: RangeInclusive<u32> = MIN_SPELL_VERSION..=MAX_SPELL_VERSION;
+
+/// Structural problems detected in a `SpellProverInput` before `is_correct` runs.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ValidationError {
+    /// No previous transactions were supplied
+    EmptyPrevTxs,
+    /// A spell input spends a UTXO not found among the `prev_txs` outputs
+    UnknownSpellInput { txid: [u8; 32], vout: u32 },
+    /// The spell checker verification key is empty
+    EmptySpellVk,
+    /// The spell version is outside `MIN_SPELL_VERSION..=MAX_SPELL_VERSION`
+    UnsupportedVersion(u32),
+    /// An app appears only in outputs without being authorized as genesis
+    UnauthorizedGenesis(String),
+    /// A beamed source UTXO is given for a spell input that does not exist
+    UnknownBeamedInput(usize),
+}
+
+impl std::fmt::Display for ValidationError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ValidationError::EmptyPrevTxs => write!(f, "prev_txs is empty"),
+            ValidationError::UnknownSpellInput { vout, .. } => {
+                write!(f, "spell input (vout {}) not found in prev_txs", vout)
+            }
+            ValidationError::EmptySpellVk => write!(f, "self_spell_vk is empty"),
+            ValidationError::UnsupportedVersion(v) => write!(f, "unsupported spell version: {}", v),
+            ValidationError::UnauthorizedGenesis(tag) => {
+                write!(f, "app {} created without genesis authorization", tag)
+            }
+            ValidationError::UnknownBeamedInput(index) => {
+                write!(f, "beamed source given for input {}, which the spell lacks", index)
+            }
+        }
+    }
+}
+
+impl std::error::Error for ValidationError {}
+
+/// Check the structural preconditions of a prover input.
+///
+/// This runs before `is_correct` so malformed inputs fail with a
+/// `ValidationError` naming the problem rather than deep inside the zkVM.
+/// `main` commits the serialized error in place of the spell outputs, which
+/// `charms-proof-wrapper` then rejects.
+pub fn validate(input: &SpellProverInput) -> Result<(), ValidationError> {
+    validate_parts(&input.self_spell_vk, &input.prev_txs, &input.spell)?;
+    validate_beamed_sources(&input.spell, input.tx_ins_beamed_source_utxos.keys().copied())
+}
+
+/// Check every beamed source UTXO belongs to an input of `spell`.
+///
+/// `beamed_inputs` are the input indices `tx_ins_beamed_source_utxos` is keyed by.
+pub fn validate_beamed_sources(
+    spell: &NormalizedSpell,
+    beamed_inputs: impl IntoIterator<Item = usize>,
+) -> Result<(), ValidationError> {
+    match beamed_inputs.into_iter().find(|&index| index >= spell.ins.len()) {
+        Some(index) => Err(ValidationError::UnknownBeamedInput(index)),
+        None => Ok(()),
+    }
+}
+
+fn validate_parts(
+    self_spell_vk: &str,
+    prev_txs: &[Transaction],
+    spell: &NormalizedSpell,
+) -> Result<(), ValidationError> {
+    if prev_txs.is_empty() {
+        return Err(ValidationError::EmptyPrevTxs);
+    }
+
+    for input in &spell.ins {
+        let utxo = &input.utxo_ref;
+        let found = prev_txs.iter()
+            .any(|tx| tx.txid == utxo.txid && tx.output_by_index(utxo.vout).is_some());
+        if !found {
+            return Err(ValidationError::UnknownSpellInput {
+                txid: utxo.txid,
+                vout: utxo.vout,
+            });
+        }
+    }
+
+    if self_spell_vk.is_empty() {
+        return Err(ValidationError::EmptySpellVk);
+    }
+
+    if !SUPPORTED_SPELL_VERSIONS.contains(&spell.version) {
+        return Err(ValidationError::UnsupportedVersion(spell.version));
+    }
+
+    // New app state may only appear for genesis apps derived from a spent input
+    if let Some(tag) = spell.unauthorized_new_apps().first() {
+        return Err(ValidationError::UnauthorizedGenesis(tag.to_string()));
+    }
+
+    Ok(())
+}
+
+/// Public output committed for a correct spell.
+///
+/// Defined here rather than in `charms-client`, which this tree does not include.
+/// Committed as canonical CBOR (`to_cbor`), the encoding the proof wrapper
+/// checks before verifying a spell checker proof.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SpellOutput {
+    /// Verification key of the spell checker that proved the spell
+    pub spell_vk: String,
+    /// The spell proven correct
+    pub spell: NormalizedSpell,
+    /// `spell.hash()`, the sha256 of its canonical bytes, so verifiers can
+    /// match the spell without re-hashing it
+    pub commitment: [u8; 32],
+    /// `spell.version`, readable before decoding the spell itself
+    pub version: u32,
+}
+
+impl SpellOutput {
+    /// Output for `spell` proven by the checker with key `spell_vk`.
+    pub fn new(spell_vk: String, spell: NormalizedSpell) -> Self {
+        Self {
+            commitment: spell.hash(),
+            version: spell.version,
+            spell_vk,
+            spell,
+        }
+    }
+
+    /// Encode as a canonical CBOR map of `"spell_vk"`, `"spell"` (the spell's
+    /// canonical bytes), `"commitment"` and `"version"`
+    pub fn to_cbor(&self) -> Vec<u8> {
+        self.to_data().to_cbor()
+    }
+
+    /// Encode the outputs of a batch, in order, as a canonical CBOR list of
+    /// `to_cbor` maps. This is what `main` commits.
+    pub fn batch_to_cbor(outputs: &[SpellOutput]) -> Vec<u8> {
+        Data::List(outputs.iter().map(SpellOutput::to_data).collect()).to_cbor()
+    }
+
+    /// Decode `to_cbor` output, checking the commitment and version match the spell
+    pub fn from_cbor(bytes: &[u8]) -> Option<Self> {
+        Self::from_data(&Data::from_cbor(bytes).ok()?)
+    }
+
+    /// Decode `batch_to_cbor` output
+    pub fn batch_from_cbor(bytes: &[u8]) -> Option<Vec<Self>> {
+        let Data::List(items) = Data::from_cbor(bytes).ok()? else {
+            return None;
+        };
+        items.iter().map(Self::from_data).collect()
+    }
+
+    fn to_data(&self) -> Data {
+        let mut map = BTreeMap::new();
+        map.insert("spell_vk".to_string(), Data::String(self.spell_vk.clone()));
+        map.insert("spell".to_string(), Data::Bytes(self.spell.to_canonical_bytes()));
+        map.insert("commitment".to_string(), Data::Bytes(self.commitment.to_vec()));
+        map.insert("version".to_string(), Data::U64(self.version as u64));
+        Data::Map(map)
+    }
+
+    fn from_data(data: &Data) -> Option<Self> {
+        let Data::Map(map) = data else {
+            return None;
+        };
+        let spell = NormalizedSpell::from_canonical_bytes(map.get("spell")?.as_bytes()?).ok()?;
+        let output = Self::new(map.get("spell_vk")?.as_str()?.to_string(), spell);
+        let commitment = map.get("commitment")?.as_bytes()?;
+        let version = map.get("version")?.as_u64()?;
+        (commitment == output.commitment && version == output.version as u64).then_some(output)
+    }
+}
+
+/// Why `run` rejected a spell.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum SpellError {
+    /// The spell version is outside `SUPPORTED_SPELL_VERSIONS`
+    UnsupportedVersion(u32),
+    /// A beamed source UTXO does not belong to a spell input
+    InvalidBeamedSources(ValidationError),
+    /// An app nonce does not advance past the last committed one
+    NonceReplay,
+    /// `is_correct` rejected the spell, naming the check that failed
+    Incorrect(String),
+}
+
+impl std::fmt::Display for SpellError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SpellError::UnsupportedVersion(v) => write!(f, "Unsupported spell version {}", v),
+            SpellError::InvalidBeamedSources(err) => write!(f, "Invalid beamed source UTXOs: {}", err),
+            SpellError::NonceReplay => write!(f, "Spell replays an app nonce"),
+            SpellError::Incorrect(reason) => write!(f, "Spell is not correct: {}", reason),
+        }
+    }
+}
+
+impl std::error::Error for SpellError {}
+
+/// Frame magic number opening every zstd-compressed input.
+pub const ZSTD_MAGIC: [u8; 4] = [0x28, 0xb5, 0x2f, 0xfd];
+/// zstd level used by `to_compressed`.
+pub const ZSTD_LEVEL: i32 = 3;
+/// Largest decompressed input accepted, so a small frame can't exhaust guest memory.
+pub const MAX_DECOMPRESSED_LEN: usize = util::MAX_FRAME_LEN;
+
+/// Failure to recover prover inputs from `to_compressed` output.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DecompressError {
+    /// The zstd frame is corrupt or truncated
+    Zstd(String),
+    /// The frame decompresses to more than `MAX_DECOMPRESSED_LEN` bytes
+    TooLarge,
+    /// The decompressed bytes are not serialized `SpellProverInput`s
+    Decode(String),
+}
+
+impl std::fmt::Display for DecompressError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DecompressError::Zstd(msg) => write!(f, "invalid zstd input: {}", msg),
+            DecompressError::TooLarge => {
+                write!(f, "input decompresses to more than {} bytes", MAX_DECOMPRESSED_LEN)
+            }
+            DecompressError::Decode(msg) => write!(f, "invalid spell prover input: {}", msg),
+        }
+    }
+}
+
+impl std::error::Error for DecompressError {}
+
+/// Serialize and zstd-compress a batch of prover inputs, as `main` reads them.
+///
+/// `prev_txs` carries whole previous transactions, which compress well, so
+/// this keeps large inputs within the zkVM I/O budget. Host only.
+#[cfg(not(target_os = "zkvm"))]
+pub fn to_compressed(inputs: &[SpellProverInput]) -> Vec<u8> {
+    let bytes = util::write(&inputs).unwrap();
+    zstd::encode_all(bytes.as_slice(), ZSTD_LEVEL).expect("compressing in memory cannot fail")
+}
+
+/// Inverse of `to_compressed`.
+pub fn from_compressed(bytes: &[u8]) -> Result<Vec<SpellProverInput>, DecompressError> {
+    let bytes = decompress_if_zstd(bytes)?;
+    util::read(bytes.as_ref()).map_err(|e| DecompressError::Decode(e.to_string()))
+}
+
+/// Decompress `bytes` if they start with `ZSTD_MAGIC`, else pass them through.
+///
+/// Uses the pure-Rust `ruzstd` decoder so it runs in the zkVM, and stops
+/// once the output passes `MAX_DECOMPRESSED_LEN`.
+pub fn decompress_if_zstd(bytes: &[u8]) -> Result<Cow<'_, [u8]>, DecompressError> {
+    if !bytes.starts_with(&ZSTD_MAGIC) {
+        return Ok(Cow::Borrowed(bytes));
+    }
+    let decoder = ruzstd::decoding::StreamingDecoder::new(bytes)
+        .map_err(|e| DecompressError::Zstd(e.to_string()))?;
+    let mut output = Vec::new();
+    decoder
+        .take(MAX_DECOMPRESSED_LEN as u64 + 1)
+        .read_to_end(&mut output)
+        .map_err(|e| DecompressError::Zstd(e.to_string()))?;
+    if output.len() > MAX_DECOMPRESSED_LEN {
+        return Err(DecompressError::TooLarge);
+    }
+    Ok(Cow::Owned(output))
+}
 
 pub fn main() {
-    // Read an input to the program.
+    // Read a batch of inputs to the program, compressed or not. A single
+    // spell is a batch of one.
     let input_vec = sp1_zkvm::io::read_vec();
-    let input: SpellProverInput = util::read(input_vec.as_slice()).unwrap();
+    let input_bytes = decompress_if_zstd(&input_vec).unwrap();
+    let inputs: Vec<SpellProverInput> = util::read(input_bytes.as_ref()).unwrap();
+
+    // Reject malformed inputs up front, committing the reason instead of
+    // panicking deep inside `is_correct`.
+    if let Some(err) = inputs.iter().find_map(|input| validate(input).err()) {
+        let error_vec = util::write(&err).unwrap();
+        sp1_zkvm::io::commit_slice(error_vec.as_slice());
+        return;
+    }
 
-    let output = run(input);
+    // Incorrect or conflicting inputs panic, so no proof is produced.
+    let outputs = run_batch(inputs);
 
     // Commit to the public values of the program.
-    let output_vec = util::write(&output).unwrap();
-    sp1_zkvm::io::commit_slice(output_vec.as_slice());
+    sp1_zkvm::io::commit_slice(&SpellOutput::batch_to_cbor(&outputs));
 }
 
-pub fn run(input: SpellProverInput) -> (String, NormalizedSpell) {
-    let SpellProverInput {
-        self_spell_vk,
-        prev_txs,
-        spell,
-        tx_ins_beamed_source_utxos,
-        app_input,
-    } = input;
+/// Check the spell in `input` is correct, naming the first check that fails.
+///
+/// `charms_client::is_correct` only answers yes or no, so well-formedness and
+/// prev-tx linkage are checked here first and a `false` from it is reported
+/// as the app proof check.
+pub fn is_correct(input: &SpellProverInput) -> Result<(), String> {
+    let diagnostics = input.spell.diagnostics();
+    if !diagnostics.is_valid() {
+        return Err(format!("spell well-formedness: {}", diagnostics.problems.join(", ")));
+    }
+
+    check_prev_tx_linkage(&input.spell, &input.prev_txs)
+        .map_err(|reason| format!("prev-tx linkage: {}", reason))?;
+
+    if !charms_client::is_correct(
+        &input.spell,
+        &input.prev_txs,
+        input.app_input.clone(),
+        &input.self_spell_vk,
+        &input.tx_ins_beamed_source_utxos,
+    ) {
+        return Err("app proof: apps did not accept the spell".to_string());
+    }
+
+    Ok(())
+}
+
+/// Check every spell input spends a `prev_txs` output holding the charms it claims.
+fn check_prev_tx_linkage(spell: &NormalizedSpell, prev_txs: &[Transaction]) -> Result<(), String> {
+    for input in &spell.ins {
+        let utxo = &input.utxo_ref;
+        let Some(output) = prev_txs.iter()
+            .filter(|tx| tx.txid == utxo.txid)
+            .find_map(|tx| tx.output_by_index(utxo.vout))
+        else {
+            return Err(format!("input {} not found in prev_txs", utxo));
+        };
+        if let Some(charms) = &input.charms {
+            if output.charm_state.as_ref() != Some(charms) {
+                return Err(format!("input {} charms differ from the spent output", utxo));
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Verify one spell, returning its public output or why it is incorrect.
+pub fn run(input: SpellProverInput) -> Result<SpellOutput, SpellError> {
+    let spell = &input.spell;
+
+    // A spell from a newer protocol must not be judged by this version's rules.
+    if !SUPPORTED_SPELL_VERSIONS.contains(&spell.version) {
+        return Err(SpellError::UnsupportedVersion(spell.version));
+    }
+
+    validate_beamed_sources(spell, input.tx_ins_beamed_source_utxos.keys().copied())
+        .map_err(SpellError::InvalidBeamedSources)?;
+
+    // Each app's replay nonce must advance past the latest one committed
+    // by a previous spell for that same app.
+    let mut last_nonces = BTreeMap::new();
+    for prev_spell in input.prev_txs.iter().filter_map(|tx| tx.spell.as_ref()) {
+        for (tag, nonce) in &prev_spell.nonces {
+            let last = last_nonces.entry(tag.clone()).or_insert(*nonce);
+            *last = (*last).max(*nonce);
+        }
+    }
+    if !spell.verify_nonces(&last_nonces) {
+        return Err(SpellError::NonceReplay);
+    }
 
     // Check the spell that we're proving is correct.
-    assert!(is_correct(
-        &spell,
-        &prev_txs,
-        app_input,
-        &self_spell_vk,
-        &tx_ins_beamed_source_utxos,
-    ));
+    is_correct(&input).map_err(SpellError::Incorrect)?;
 
     eprintln!("Spell is correct!");
 
-    (self_spell_vk, spell)
+    Ok(SpellOutput::new(input.self_spell_vk, input.spell))
+}
+
+/// Verify a batch of spells in one zkVM invocation.
+///
+/// Every input is validated before any spell is checked, and no two spells
+/// in the batch may spend the same UTXO. The spells are then verified in
+/// order. Panics naming the index of the first input that fails.
+pub fn run_batch(inputs: Vec<SpellProverInput>) -> Vec<SpellOutput> {
+    let mut spent_by = BTreeMap::new();
+    for (index, input) in inputs.iter().enumerate() {
+        if let Err(err) = validate(input) {
+            panic!("batch input {} is invalid: {}", index, err);
+        }
+        for spell_input in &input.spell.ins {
+            let utxo = &spell_input.utxo_ref;
+            if let Some(first) = spent_by.insert((utxo.txid, utxo.vout), index) {
+                if first != index {
+                    panic!(
+                        "batch input {} spends {}, already spent by batch input {}",
+                        index, utxo, first
+                    );
+                }
+            }
+        }
+    }
+
+    inputs
+        .into_iter()
+        .enumerate()
+        .map(|(index, input)| {
+            run(input).unwrap_or_else(|err| panic!("batch input {} failed: {}", index, err))
+        })
+        .collect()
 }
 
 #[cfg(test)]
 mod test {
+    use super::*;
+    use charms_data::{CharmState, Data, SpellInput, TxOutput, UtxoRef};
+
+    fn prev_tx(txid: [u8; 32], vout: u32) -> Transaction {
+        let mut tx = Transaction::new(txid);
+        tx.add_output(TxOutput {
+            index: vout,
+            value: 546,
+            script_pubkey: vec![],
+            charm_state: None,
+        });
+        tx
+    }
+
+    fn spell_spending(txid: [u8; 32], vout: u32) -> NormalizedSpell {
+        let mut spell = NormalizedSpell::new(1);
+        spell.ins.push(SpellInput {
+            utxo_ref: UtxoRef { txid, vout },
+            charms: None,
+        });
+        spell.outs.push(charms_data::SpellOutput { index: 0, charms: None });
+        spell
+    }
+
     #[test]
     fn dummy() {}
+
+    #[test]
+    fn test_compression_round_trip() {
+        let prev_txs: Vec<Transaction> = (0..10u8)
+            .map(|i| {
+                let mut tx = prev_tx([i; 32], 0);
+                tx.outputs[0].charm_state = Some(
+                    CharmState::new().with_app("token:USD", Data::U64(1_000 * i as u64)),
+                );
+                tx.outputs[0].script_pubkey = vec![0x00, 0x14, i, i, i, i];
+                tx
+            })
+            .collect();
+        let inputs = vec![SpellProverInput { prev_txs, ..prover_input("vk", [0u8; 32]) }];
+        let raw = util::write(&inputs).unwrap();
+
+        let compressed = to_compressed(&inputs);
+        assert!(compressed.starts_with(&ZSTD_MAGIC));
+        assert!(compressed.len() < raw.len() / 2, "{} -> {}", raw.len(), compressed.len());
+
+        // Compare serialized forms, which cover every field
+        let round_trip = from_compressed(&compressed).unwrap();
+        assert_eq!(round_trip.len(), 1);
+        assert_eq!(round_trip[0].prev_txs, inputs[0].prev_txs);
+        assert_eq!(util::write(&round_trip).unwrap(), raw);
+
+        // Uncompressed input passes through untouched
+        assert_eq!(decompress_if_zstd(&raw).unwrap().as_ref(), raw.as_slice());
+        assert_eq!(util::write(&from_compressed(&raw).unwrap()).unwrap(), raw);
+    }
+
+    #[test]
+    fn test_decompress_rejects_corrupt_frame() {
+        let mut bytes = ZSTD_MAGIC.to_vec();
+        bytes.extend_from_slice(&[0xff; 8]);
+        assert!(matches!(decompress_if_zstd(&bytes), Err(DecompressError::Zstd(_))));
+        assert!(matches!(from_compressed(&bytes), Err(DecompressError::Zstd(_))));
+    }
+
+    #[test]
+    fn test_decompress_bounds_output() {
+        let bomb = zstd::encode_all(vec![0u8; MAX_DECOMPRESSED_LEN + 1].as_slice(), ZSTD_LEVEL).unwrap();
+        assert!(bomb.len() < 4096, "{}", bomb.len());
+        assert_eq!(decompress_if_zstd(&bomb), Err(DecompressError::TooLarge));
+
+        let limit = zstd::encode_all(vec![0u8; MAX_DECOMPRESSED_LEN].as_slice(), ZSTD_LEVEL).unwrap();
+        assert_eq!(decompress_if_zstd(&limit).unwrap().len(), MAX_DECOMPRESSED_LEN);
+    }
+
+    #[test]
+    fn test_validate_ok() {
+        let spell = spell_spending([1u8; 32], 0);
+        assert_eq!(validate_parts("vk", &[prev_tx([1u8; 32], 0)], &spell), Ok(()));
+    }
+
+    #[test]
+    fn test_validate_rejects_malformed() {
+        let spell = spell_spending([1u8; 32], 0);
+        assert_eq!(
+            validate_parts("vk", &[], &spell),
+            Err(ValidationError::EmptyPrevTxs)
+        );
+        assert_eq!(
+            validate_parts("vk", &[prev_tx([1u8; 32], 1)], &spell),
+            Err(ValidationError::UnknownSpellInput { txid: [1u8; 32], vout: 0 })
+        );
+        assert_eq!(
+            validate_parts("", &[prev_tx([1u8; 32], 0)], &spell),
+            Err(ValidationError::EmptySpellVk)
+        );
+
+        let mut future = spell.clone();
+        future.version = MAX_SPELL_VERSION + 1;
+        assert_eq!(
+            validate_parts("vk", &[prev_tx([1u8; 32], 0)], &future),
+            Err(ValidationError::UnsupportedVersion(MAX_SPELL_VERSION + 1))
+        );
+    }
+
+    #[test]
+    fn test_validate_beamed_sources() {
+        let spell = spell_spending([1u8; 32], 0);
+        let source = UtxoRef { txid: [9u8; 32], vout: 0 };
+
+        let beamed = BTreeMap::from([(0usize, source.clone())]);
+        assert_eq!(validate_beamed_sources(&spell, beamed.keys().copied()), Ok(()));
+        assert_eq!(validate_beamed_sources(&spell, []), Ok(()));
+
+        // The spell has a single input, so index 1 does not exist
+        let beamed = BTreeMap::from([(0usize, source.clone()), (1usize, source)]);
+        assert_eq!(
+            validate_beamed_sources(&spell, beamed.keys().copied()),
+            Err(ValidationError::UnknownBeamedInput(1))
+        );
+    }
+
+    fn prover_input(self_spell_vk: &str, txid: [u8; 32]) -> SpellProverInput {
+        SpellProverInput {
+            self_spell_vk: self_spell_vk.to_string(),
+            prev_txs: vec![prev_tx(txid, 0)],
+            spell: spell_spending(txid, 0),
+            tx_ins_beamed_source_utxos: BTreeMap::new(),
+            app_input: None,
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "batch input 1 is invalid: self_spell_vk is empty")]
+    fn test_run_batch_names_failing_index() {
+        run_batch(vec![
+            prover_input("vk", [1u8; 32]),
+            prover_input("", [2u8; 32]),
+            prover_input("vk", [3u8; 32]),
+        ]);
+    }
+
+    #[test]
+    fn test_run_rejects_unsupported_version() {
+        let mut input = prover_input("vk", [1u8; 32]);
+        input.spell.version = 999;
+        assert_eq!(run(input), Err(SpellError::UnsupportedVersion(999)));
+    }
+
+    #[test]
+    fn test_spell_output_fields() {
+        let spell = spell_spending([1u8; 32], 0).with_nonce("token:USD", 3);
+        let output = SpellOutput::new("vk".to_string(), spell.clone());
+
+        assert_eq!(output.spell_vk, "vk");
+        assert_eq!(output.version, 1);
+        assert_eq!(output.commitment, spell.hash());
+        assert_eq!(output.spell, spell);
+
+        let bytes = output.to_cbor();
+        assert_eq!(Data::from_cbor(&bytes).unwrap().to_cbor(), bytes);
+        assert_eq!(SpellOutput::from_cbor(&bytes), Some(output.clone()));
+
+        // A commitment that doesn't match the spell is rejected
+        let mut forged = output;
+        forged.commitment = [0u8; 32];
+        assert_eq!(SpellOutput::from_cbor(&forged.to_cbor()), None);
+    }
+
+    #[test]
+    fn test_run_rejects_unknown_beamed_source() {
+        let mut input = prover_input("vk", [1u8; 32]);
+        let source = UtxoRef { txid: [9u8; 32], vout: 0 };
+        input.tx_ins_beamed_source_utxos.insert(1, source);
+        assert_eq!(
+            run(input),
+            Err(SpellError::InvalidBeamedSources(ValidationError::UnknownBeamedInput(1)))
+        );
+    }
+
+    #[test]
+    fn test_run_names_well_formedness_failure() {
+        let mut input = prover_input("vk", [1u8; 32]);
+        input.spell.outs.clear();
+        assert_eq!(
+            is_correct(&input),
+            Err("spell well-formedness: empty outputs".to_string())
+        );
+        assert_eq!(
+            run(input),
+            Err(SpellError::Incorrect("spell well-formedness: empty outputs".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_is_correct_names_prev_tx_linkage_failure() {
+        let mut input = prover_input("vk", [1u8; 32]);
+        input.spell.ins[0].charms = Some(CharmState::new().with_app("token:USD", Data::U64(1_000)));
+
+        let err = is_correct(&input).unwrap_err();
+        assert!(err.starts_with("prev-tx linkage: "), "{}", err);
+    }
+
+    #[test]
+    fn test_run_batch_empty() {
+        assert!(run_batch(vec![]).is_empty());
+    }
+
+    #[test]
+    #[should_panic(expected = "batch input 2 spends")]
+    fn test_run_batch_rejects_double_spend() {
+        let mut conflicting = prover_input("vk", [3u8; 32]);
+        conflicting.prev_txs.push(prev_tx([1u8; 32], 0));
+        conflicting.spell.ins[0].utxo_ref = UtxoRef { txid: [1u8; 32], vout: 0 };
+        run_batch(vec![
+            prover_input("vk", [1u8; 32]),
+            prover_input("vk", [2u8; 32]),
+            conflicting,
+        ]);
+    }
+
+    #[test]
+    fn test_batch_output_round_trip() {
+        let outputs: Vec<SpellOutput> = (1..=2u8)
+            .map(|i| SpellOutput::new("vk".to_string(), spell_spending([i; 32], 0)))
+            .collect();
+        let bytes = SpellOutput::batch_to_cbor(&outputs);
+        assert_eq!(Data::from_cbor(&bytes).unwrap().to_cbor(), bytes);
+        assert_eq!(SpellOutput::batch_from_cbor(&bytes), Some(outputs.clone()));
+        assert_eq!(SpellOutput::batch_from_cbor(&outputs[0].to_cbor()), None);
+    }
+
+    #[test]
+    fn test_validate_rejects_unauthorized_genesis() {
+        let prev = [prev_tx([1u8; 32], 0)];
+        let mut spell = spell_spending([1u8; 32], 0);
+        spell.outs[0].charms = Some(CharmState::new().with_app("token:NEW", Data::U64(1_000)));
+
+        // Declaring genesis for an arbitrary tag is not enough
+        let spell = spell.with_genesis("token:NEW");
+        assert_eq!(
+            validate_parts("vk", &prev, &spell),
+            Err(ValidationError::UnauthorizedGenesis("token:NEW".to_string()))
+        );
+
+        let tag = spell.ins[0].utxo_ref.genesis_tag("token");
+        let mut spell = spell.with_genesis(tag.as_str());
+        spell.outs[0].charms = Some(CharmState::new().with_app(tag.as_str(), Data::U64(1_000)));
+        assert_eq!(validate_parts("vk", &prev, &spell), Ok(()));
+    }
 }